@@ -0,0 +1,1417 @@
+//! The subcommands. Each returns a report that prints as text or serializes to JSON.
+
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::{bail, Context as _, Result};
+use blackbox_client::{
+    accounts::{
+        BalanceReveal, BlackboxAccount, EmergencyExport, InitBlackboxParams, MappingAccount,
+        OpKind, OpQueueAccount, OpRecord, OpStatus, ParameterChange, PauseFlags, PendingChange,
+        ProgramConfig, SolvencyAttestation,
+    },
+    arcium::committed_circuit_hash,
+    cover_traffic::TransferEncryptor,
+    encryption::{open_claim, KEY_DERIVATION_MESSAGE},
+    instructions::{self, Computation},
+    pda, Blackbox, Circuit, PROGRAM_ID,
+};
+use rand::Rng;
+use serde::Serialize;
+use solana_client::{
+    rpc_config::RpcProgramAccountsConfig,
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address;
+
+use crate::context::Context;
+
+/// How long `balance` waits for the MXE to answer its reveals
+const REVEAL_TIMEOUT: Duration = Duration::from_secs(120);
+/// How long `prove-solvency` waits for the MXE to answer
+const SOLVENCY_TIMEOUT: Duration = Duration::from_secs(120);
+/// How long `export-balances` waits for the MXE to export each page
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Serialize)]
+pub struct Init {
+    pub blackbox: String,
+    pub vault: String,
+    pub mapping_account: String,
+    pub shard_capacity: u16,
+    pub circuit_version: u8,
+    pub signature: String,
+}
+
+impl fmt::Display for Init {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Blackbox:        {}", self.blackbox)?;
+        writeln!(f, "Vault:           {}", self.vault)?;
+        writeln!(f, "Mapping account: {}", self.mapping_account)?;
+        writeln!(f, "Shard capacity:  {} balances", self.shard_capacity)?;
+        writeln!(f, "Circuit version: {}", self.circuit_version)?;
+        write!(f, "Signature:       {}", self.signature)
+    }
+}
+
+/// Creates the blackbox of `mint` with `params` and its first mapping account; the signer must
+/// be the program authority. `shard_capacity` defaults to the program config's maximum.
+pub fn init(
+    ctx: &Context,
+    mint: Pubkey,
+    shard_capacity: Option<u16>,
+    mut params: InitBlackboxParams,
+) -> Result<Init> {
+    let config = ctx.account(&pda::program_config(), ProgramConfig::decode)?;
+    let authority = ctx.pubkey();
+    if config.authority != authority {
+        bail!(
+            "{authority} is not the program authority, {} is",
+            config.authority
+        );
+    }
+    params.shard_capacity = shard_capacity.unwrap_or(config.max_shard_capacity);
+    if params.shard_capacity == 0 || params.shard_capacity > config.max_shard_capacity {
+        bail!(
+            "shards hold between 1 and {} balances",
+            config.max_shard_capacity
+        );
+    }
+    if params.fees.deposit_bps.max(params.fees.withdraw_bps) > config.max_fee_bps {
+        bail!("fees are capped at {} bps", config.max_fee_bps);
+    }
+
+    let blackbox = ctx.blackbox(mint)?;
+    params.cluster_offset = blackbox.arcium.cluster_offset;
+    let signature = ctx.send(vec![
+        (
+            "init_blackbox",
+            blackbox.init_blackbox(&authority, &authority, &params),
+        ),
+        (
+            "initialize_mapping_account",
+            blackbox.initialize_mapping_account(&authority, 0),
+        ),
+    ])?;
+    Ok(Init {
+        blackbox: blackbox.address.to_string(),
+        vault: blackbox.vault.to_string(),
+        mapping_account: blackbox.mapping_account(0).to_string(),
+        shard_capacity: params.shard_capacity,
+        circuit_version: params.circuit_version,
+        signature: signature.to_string(),
+    })
+}
+
+#[derive(Serialize)]
+pub struct CompDef {
+    pub circuit: &'static str,
+    pub comp_def: String,
+    /// `None` if the definition was already registered
+    pub signature: Option<String>,
+    /// Hash of the compiled circuit the definition commits to; the program only queues
+    /// computations when it's the one it was built with
+    pub circuit_hash: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RegisterCompDefs {
+    pub comp_defs: Vec<CompDef>,
+}
+
+impl fmt::Display for RegisterCompDefs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, comp_def) in self.comp_defs.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            match &comp_def.signature {
+                Some(signature) => write!(f, "{:<16} registered ({signature})", comp_def.circuit)?,
+                None => write!(f, "{:<16} already registered", comp_def.circuit)?,
+            }
+            match &comp_def.circuit_hash {
+                Some(hash) => write!(f, ", circuit {hash}")?,
+                None => write!(f, ", no off-chain circuit yet")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Registers the computation definitions of `circuits`, or of every circuit if empty.
+pub fn register_comp_defs(ctx: &Context, circuits: Vec<Circuit>) -> Result<RegisterCompDefs> {
+    let circuits = if circuits.is_empty() {
+        Circuit::ALL.to_vec()
+    } else {
+        circuits
+    };
+    // Computation definitions belong to the program, not to the blackbox of a mint
+    let blackbox = ctx.blackbox(Pubkey::default())?;
+    let payer = ctx.pubkey();
+
+    let mut comp_defs = Vec::new();
+    for circuit in circuits {
+        let signature = if ctx.account_data(&circuit.comp_def())?.is_some() {
+            None
+        } else {
+            let name = circuit.init_comp_def_instruction();
+            let instruction = blackbox.init_comp_def(&payer, circuit);
+            Some(ctx.send(vec![(name.as_str(), instruction)])?.to_string())
+        };
+        let circuit_hash = ctx
+            .account_data(&circuit.comp_def())?
+            .as_deref()
+            .and_then(committed_circuit_hash);
+        comp_defs.push(CompDef {
+            circuit: circuit.name(),
+            comp_def: circuit.comp_def().to_string(),
+            signature,
+            circuit_hash: circuit_hash.map(hex::encode),
+        });
+    }
+    Ok(RegisterCompDefs { comp_defs })
+}
+
+#[derive(Serialize)]
+pub struct AddShard {
+    pub index: u8,
+    pub mapping_account: String,
+    pub op_queue: Option<String>,
+    pub signature: String,
+}
+
+impl fmt::Display for AddShard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Shard:           {}", self.index)?;
+        writeln!(f, "Mapping account: {}", self.mapping_account)?;
+        if let Some(op_queue) = &self.op_queue {
+            writeln!(f, "Operation queue: {op_queue}")?;
+        }
+        write!(f, "Signature:       {}", self.signature)
+    }
+}
+
+/// Adds the next mapping account to the blackbox of `mint`, optionally with its queue.
+pub fn add_shard(ctx: &Context, mint: Pubkey, with_op_queue: bool) -> Result<AddShard> {
+    let blackbox = ctx.blackbox(mint)?;
+    let index = ctx.blackbox_account(&blackbox)?.mapping_account_count;
+    let mapping_account = blackbox.mapping_account(index);
+    let payer = ctx.pubkey();
+
+    let mut instructions = vec![(
+        "initialize_mapping_account",
+        blackbox.initialize_mapping_account(&payer, index),
+    )];
+    if with_op_queue {
+        instructions.push((
+            "init_op_queue",
+            blackbox.init_op_queue(&payer, &mapping_account),
+        ));
+    }
+    let signature = ctx.send(instructions)?;
+
+    Ok(AddShard {
+        index,
+        mapping_account: mapping_account.to_string(),
+        op_queue: with_op_queue.then(|| pda::op_queue(&mapping_account).to_string()),
+        signature: signature.to_string(),
+    })
+}
+
+#[derive(Serialize)]
+pub struct InitConfig {
+    pub config: String,
+    pub authority: String,
+    pub signature: String,
+}
+
+impl fmt::Display for InitConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Program config: {}", self.config)?;
+        writeln!(f, "Authority:      {}", self.authority)?;
+        write!(f, "Signature:      {}", self.signature)
+    }
+}
+
+/// Creates the program config with the signer, the program's upgrade authority, as its
+/// authority.
+pub fn init_config(ctx: &Context) -> Result<InitConfig> {
+    let authority = ctx.pubkey();
+    let signature = ctx.send(vec![(
+        "init_program_config",
+        instructions::init_program_config(&authority),
+    )])?;
+    Ok(InitConfig {
+        config: pda::program_config().to_string(),
+        authority: authority.to_string(),
+        signature: signature.to_string(),
+    })
+}
+
+#[derive(Serialize)]
+pub struct Pause {
+    /// `None` for every blackbox
+    pub blackbox: Option<String>,
+    pub deposit: bool,
+    pub transfer: bool,
+    pub withdraw: bool,
+    pub signature: String,
+}
+
+impl fmt::Display for Pause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let paused = paused_names(&PauseFlags {
+            deposit: self.deposit,
+            transfer: self.transfer,
+            withdraw: self.withdraw,
+        });
+        match &self.blackbox {
+            Some(blackbox) => writeln!(f, "Paused on {blackbox}: {paused}")?,
+            None => writeln!(f, "Paused everywhere: {paused}")?,
+        }
+        write!(f, "Signature: {}", self.signature)
+    }
+}
+
+/// Pauses operations on the blackbox of `mint`, or on every blackbox if `None`, on top of
+/// those already paused. Resuming is a queued change.
+pub fn pause(ctx: &Context, mint: Option<Pubkey>, paused: PauseFlags) -> Result<Pause> {
+    let blackbox = mint.map(|mint| pda::blackbox(&mint));
+    let instruction = instructions::pause(&ctx.pubkey(), blackbox.as_ref(), paused);
+    let signature = ctx.send(vec![("pause", instruction)])?;
+    Ok(Pause {
+        blackbox: blackbox.map(|blackbox| blackbox.to_string()),
+        deposit: paused.deposit,
+        transfer: paused.transfer,
+        withdraw: paused.withdraw,
+        signature: signature.to_string(),
+    })
+}
+
+fn paused_names(paused: &PauseFlags) -> String {
+    let names: Vec<_> = [
+        ("deposit", paused.deposit),
+        ("transfer", paused.transfer),
+        ("withdraw", paused.withdraw),
+    ]
+    .into_iter()
+    .filter_map(|(name, paused)| paused.then_some(name))
+    .collect();
+    if names.is_empty() {
+        "nothing".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+#[derive(Serialize)]
+pub struct ProposeAuthority {
+    pub authority: String,
+    /// `None` if the proposal was withdrawn
+    pub proposed: Option<String>,
+    pub signature: String,
+}
+
+impl fmt::Display for ProposeAuthority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Authority: {}", self.authority)?;
+        match &self.proposed {
+            Some(proposed) => writeln!(f, "Proposed:  {proposed}, who takes over by accepting")?,
+            None => writeln!(f, "Proposed:  nobody")?,
+        }
+        write!(f, "Signature: {}", self.signature)
+    }
+}
+
+/// Proposes `new_authority` as the program authority, or withdraws the proposal if `None`; the
+/// signer must be the current authority.
+pub fn propose_authority(ctx: &Context, new_authority: Option<Pubkey>) -> Result<ProposeAuthority> {
+    let config = ctx.account(&pda::program_config(), ProgramConfig::decode)?;
+    let authority = ctx.pubkey();
+    if config.authority != authority {
+        bail!(
+            "{authority} is not the program authority, {} is",
+            config.authority
+        );
+    }
+    let instruction = instructions::propose_authority(&authority, new_authority.as_ref());
+    let signature = ctx.send(vec![("propose_authority", instruction)])?;
+    Ok(ProposeAuthority {
+        authority: authority.to_string(),
+        proposed: new_authority.map(|new_authority| new_authority.to_string()),
+        signature: signature.to_string(),
+    })
+}
+
+#[derive(Serialize)]
+pub struct AcceptAuthority {
+    pub previous: String,
+    pub authority: String,
+    pub signature: String,
+}
+
+impl fmt::Display for AcceptAuthority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Previous authority: {}", self.previous)?;
+        writeln!(f, "Authority:          {}", self.authority)?;
+        write!(f, "Signature:          {}", self.signature)
+    }
+}
+
+/// Takes over the program config; the signer must be the proposed authority.
+pub fn accept_authority(ctx: &Context) -> Result<AcceptAuthority> {
+    let config = ctx.account(&pda::program_config(), ProgramConfig::decode)?;
+    let new_authority = ctx.pubkey();
+    if config.pending_authority != Some(new_authority) {
+        bail!("{new_authority} is not the proposed authority");
+    }
+    let instruction = instructions::accept_authority(&new_authority);
+    let signature = ctx.send(vec![("accept_authority", instruction)])?;
+    Ok(AcceptAuthority {
+        previous: config.authority.to_string(),
+        authority: new_authority.to_string(),
+        signature: signature.to_string(),
+    })
+}
+
+#[derive(Serialize)]
+pub struct ChangeQueued {
+    pub id: u64,
+    /// `None` for the program config
+    pub blackbox: Option<String>,
+    pub change: String,
+    pub executable_slot: u64,
+    pub signature: String,
+}
+
+impl fmt::Display for ChangeQueued {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Queued change {}: {}", self.id, self.change)?;
+        match &self.blackbox {
+            Some(blackbox) => writeln!(f, "Applies to:    {blackbox}")?,
+            None => writeln!(f, "Applies to:    program config")?,
+        }
+        writeln!(f, "Executable at: slot {}", self.executable_slot)?;
+        write!(f, "Signature:     {}", self.signature)
+    }
+}
+
+/// Queues `change` to the blackbox of `mint`, or to the program config if `None`. Anyone can
+/// execute it once the program's timelock has passed.
+pub fn queue_change(
+    ctx: &Context,
+    mint: Option<Pubkey>,
+    change: ParameterChange,
+) -> Result<ChangeQueued> {
+    let config = ctx.account(&pda::program_config(), ProgramConfig::decode)?;
+    let blackbox = mint.map(|mint| pda::blackbox(&mint));
+    if let ParameterChange::Fees(fees) = change {
+        if fees.deposit_bps.max(fees.withdraw_bps) > config.max_fee_bps {
+            bail!("fees are capped at {} bps", config.max_fee_bps);
+        }
+    }
+    let payer = ctx.pubkey();
+    let id = config.next_change_id;
+    let instruction = instructions::queue_change(&payer, &payer, id, blackbox.as_ref(), change);
+    let signature = ctx.send(vec![("queue_change", instruction)])?;
+    let pending = ctx.account(&pda::pending_change(id), PendingChange::decode)?;
+    Ok(ChangeQueued {
+        id,
+        blackbox: blackbox.map(|blackbox| blackbox.to_string()),
+        change: describe_change(&change),
+        executable_slot: pending.executable_slot,
+        signature: signature.to_string(),
+    })
+}
+
+fn describe_change(change: &ParameterChange) -> String {
+    let max = |max: u64| match max {
+        0 => "unlimited".to_string(),
+        max => max.to_string(),
+    };
+    match change {
+        ParameterChange::MaxFee(max_fee_bps) => format!("cap fees at {max_fee_bps} bps"),
+        ParameterChange::Fees(fees) => format!(
+            "charge {} bps on deposits and {} bps on withdrawals",
+            fees.deposit_bps, fees.withdraw_bps
+        ),
+        ParameterChange::Limits(limits) => format!(
+            "deposits {} to {}, withdrawals {} to {}, {} out per epoch",
+            limits.min_deposit,
+            max(limits.max_deposit),
+            limits.min_withdraw,
+            max(limits.max_withdraw),
+            max(limits.epoch_outflow_cap)
+        ),
+        ParameterChange::Paused(paused) => format!("pause {}", paused_names(paused)),
+        ParameterChange::Timelock(slots) => format!("delay changes by {slots} slots"),
+        ParameterChange::Emergency => "enter emergency mode for good".to_string(),
+        ParameterChange::CircuitVersion(circuit_version) => {
+            format!("move to circuit version {circuit_version}")
+        }
+        ParameterChange::MaxShardCapacity(max_shard_capacity) => {
+            format!("cap new shards at {max_shard_capacity} balances")
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ChangeResolved {
+    pub id: u64,
+    /// `executed` or `cancelled`
+    pub outcome: &'static str,
+    pub signature: String,
+}
+
+impl fmt::Display for ChangeResolved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Change {} {}", self.id, self.outcome)?;
+        write!(f, "Signature: {}", self.signature)
+    }
+}
+
+/// Applies queued change `id`; fails while it's still timelocked.
+pub fn execute_change(ctx: &Context, id: u64) -> Result<ChangeResolved> {
+    let pending = ctx.account(&pda::pending_change(id), PendingChange::decode)?;
+    let slot = ctx.rpc.get_slot()?;
+    if slot < pending.executable_slot {
+        bail!(
+            "change {id} is timelocked until slot {}, {} slots from now",
+            pending.executable_slot,
+            pending.executable_slot - slot
+        );
+    }
+    let instruction = instructions::execute_change(&pending);
+    let signature = ctx.send(vec![("execute_change", instruction)])?;
+    Ok(ChangeResolved {
+        id,
+        outcome: "executed",
+        signature: signature.to_string(),
+    })
+}
+
+/// Drops queued change `id`; the signer must be the program authority.
+pub fn cancel_change(ctx: &Context, id: u64) -> Result<ChangeResolved> {
+    let pending = ctx.account(&pda::pending_change(id), PendingChange::decode)?;
+    let instruction = instructions::cancel_change(&ctx.pubkey(), &pending);
+    let signature = ctx.send(vec![("cancel_change", instruction)])?;
+    Ok(ChangeResolved {
+        id,
+        outcome: "cancelled",
+        signature: signature.to_string(),
+    })
+}
+
+#[derive(Serialize)]
+pub struct CollectFees {
+    pub treasury: String,
+    pub destination: String,
+    pub signature: String,
+}
+
+impl fmt::Display for CollectFees {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Collected {} into {}", self.treasury, self.destination)?;
+        write!(f, "Signature: {}", self.signature)
+    }
+}
+
+/// Moves the fees collected by the blackbox of `mint` to `to`, by default the signer's
+/// associated token account.
+pub fn collect_fees(ctx: &Context, mint: Pubkey, to: Option<Pubkey>) -> Result<CollectFees> {
+    let blackbox = ctx.blackbox(mint)?;
+    let authority = ctx.pubkey();
+    let to = to.unwrap_or_else(|| get_associated_token_address(&authority, &mint));
+    let instruction = blackbox.collect_fees(&authority, &to);
+    let signature = ctx.send(vec![("collect_fees", instruction)])?;
+    Ok(CollectFees {
+        treasury: blackbox.treasury.to_string(),
+        destination: to.to_string(),
+        signature: signature.to_string(),
+    })
+}
+
+/// A deposit, transfer or withdrawal handed to the program
+#[derive(Serialize)]
+pub struct Submitted {
+    pub operation: &'static str,
+    pub mapping_account: String,
+    /// Offset of the queued computation; `None` if the operation went to the operation queue
+    pub computation_offset: Option<u64>,
+    pub signature: String,
+}
+
+impl fmt::Display for Submitted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.computation_offset {
+            Some(offset) => writeln!(f, "Queued {} as computation {offset}", self.operation)?,
+            None => writeln!(f, "Added {} to the operation queue", self.operation)?,
+        }
+        writeln!(f, "Mapping account: {}", self.mapping_account)?;
+        write!(f, "Signature:       {}", self.signature)
+    }
+}
+
+/// Where a command's operation goes
+pub struct Target {
+    pub mint: Pubkey,
+    pub shard: u8,
+    /// Append to the shard's operation queue instead of queuing a computation
+    pub queue: bool,
+}
+
+impl Target {
+    fn mapping_account(&self, blackbox: &Blackbox) -> Pubkey {
+        blackbox.mapping_account(self.shard)
+    }
+}
+
+/// A fresh computation on `mapping_account`. Stale results are re-queued if the mapping
+/// account has an operation queue.
+fn computation(ctx: &Context, mapping_account: Pubkey) -> Result<Computation> {
+    Ok(Computation {
+        payer: ctx.pubkey(),
+        mapping_account,
+        computation_offset: rand::thread_rng().gen(),
+        use_op_queue: ctx
+            .account_data(&pda::op_queue(&mapping_account))?
+            .is_some(),
+        circuit_version: ctx
+            .account(&mapping_account, MappingAccount::decode)?
+            .circuit_version,
+    })
+}
+
+fn submitted(
+    operation: &'static str,
+    mapping_account: Pubkey,
+    computation: Option<Computation>,
+    signature: impl ToString,
+) -> Submitted {
+    Submitted {
+        operation,
+        mapping_account: mapping_account.to_string(),
+        computation_offset: computation.map(|computation| computation.computation_offset),
+        signature: signature.to_string(),
+    }
+}
+
+/// Deposits `amount` from `from`, by default the signer's associated token account. Blackboxes
+/// with a deposit approver are left to clients that can gather its signature.
+pub fn deposit(
+    ctx: &Context,
+    target: Target,
+    amount: u64,
+    from: Option<Pubkey>,
+) -> Result<Submitted> {
+    let blackbox = ctx.blackbox(target.mint)?;
+    let mapping_account = target.mapping_account(&blackbox);
+    let user = ctx.pubkey();
+    let account = ctx.account(&blackbox.address, BlackboxAccount::decode)?;
+    if let Some(approver) = account.policy.deposit_approver {
+        bail!("deposits into this blackbox need {approver} to co-sign");
+    }
+    let from = from.unwrap_or_else(|| get_associated_token_address(&user, &target.mint));
+
+    if target.queue {
+        let client_key = ctx.encryption_key.public_key();
+        let instruction =
+            blackbox.enqueue_deposit(&mapping_account, &user, None, &from, amount, &client_key);
+        let signature = ctx.send(vec![("enqueue_deposit", instruction)])?;
+        return Ok(submitted("deposit", mapping_account, None, signature));
+    }
+
+    let computation = computation(ctx, mapping_account)?;
+    let instruction = blackbox.deposit(&computation, &user, None, &from, amount);
+    let signature = ctx.send(vec![("deposit", instruction)])?;
+    Ok(submitted(
+        "deposit",
+        mapping_account,
+        Some(computation),
+        signature,
+    ))
+}
+
+/// Transfers `amount` to `to`, with both encrypted for the MXE under a fresh nonce. A `noop`
+/// transfer is cover traffic and moves nothing.
+pub fn transfer(
+    ctx: &Context,
+    target: Target,
+    to: Pubkey,
+    amount: u64,
+    noop: bool,
+) -> Result<Submitted> {
+    let blackbox = ctx.blackbox(target.mint)?;
+    let mapping_account = target.mapping_account(&blackbox);
+    let sender = ctx.pubkey();
+    let transfer = ctx
+        .cipher(&blackbox)?
+        .encrypt_transfer(&to, amount, noop, rand::thread_rng().gen())
+        .with_context(|| format!("encrypting recipient {to}"))?;
+
+    let client_key = ctx.encryption_key.public_key();
+
+    if target.queue {
+        let instruction =
+            blackbox.enqueue_transfer(&sender, &mapping_account, &sender, &transfer, &client_key);
+        let signature = ctx.send(vec![("enqueue_transfer", instruction)])?;
+        return Ok(submitted("transfer", mapping_account, None, signature));
+    }
+
+    let computation = computation(ctx, mapping_account)?;
+    let instruction = blackbox.transfer(&computation, &sender, &transfer, &client_key);
+    let signature = ctx.send(vec![("transfer", instruction)])?;
+    Ok(submitted(
+        "transfer",
+        mapping_account,
+        Some(computation),
+        signature,
+    ))
+}
+
+/// Withdraws `amount`, or the whole balance if `None`, to `to`, by default the signer's
+/// associated token account.
+pub fn withdraw(
+    ctx: &Context,
+    target: Target,
+    amount: Option<u64>,
+    to: Option<Pubkey>,
+) -> Result<Submitted> {
+    let blackbox = ctx.blackbox(target.mint)?;
+    let mapping_account = target.mapping_account(&blackbox);
+    let user = ctx.pubkey();
+    let to = to.unwrap_or_else(|| get_associated_token_address(&user, &target.mint));
+
+    let Some(amount) = amount else {
+        if target.queue {
+            bail!("withdrawing the whole balance can't go through the operation queue");
+        }
+        let computation = computation(ctx, mapping_account)?;
+        let instruction = blackbox.withdraw_all(&computation, &user, &to);
+        let signature = ctx.send(vec![("withdraw_all", instruction)])?;
+        return Ok(submitted(
+            "withdrawal",
+            mapping_account,
+            Some(computation),
+            signature,
+        ));
+    };
+
+    if target.queue {
+        let client_key = ctx.encryption_key.public_key();
+        let instruction =
+            blackbox.enqueue_withdraw(&mapping_account, &user, &to, amount, &client_key);
+        let signature = ctx.send(vec![("enqueue_withdraw", instruction)])?;
+        return Ok(submitted("withdrawal", mapping_account, None, signature));
+    }
+
+    let computation = computation(ctx, mapping_account)?;
+    let instruction = blackbox.withdraw(&computation, &user, &to, amount);
+    let signature = ctx.send(vec![("withdraw", instruction)])?;
+    Ok(submitted(
+        "withdrawal",
+        mapping_account,
+        Some(computation),
+        signature,
+    ))
+}
+
+/// A shard's balance as revealed by the MXE
+#[derive(Serialize)]
+pub struct ShardBalance {
+    pub shard: u8,
+    pub mapping_account: String,
+    pub mapping_version: u64,
+    pub balance: u64,
+}
+
+#[derive(Serialize)]
+pub struct Balance {
+    pub owner: String,
+    pub client_key: String,
+    pub shards: Vec<ShardBalance>,
+    /// Sum over `shards`
+    pub total: u64,
+}
+
+impl fmt::Display for Balance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Owner:      {}", self.owner)?;
+        write!(f, "Client key: {}", self.client_key)?;
+        for shard in &self.shards {
+            write!(
+                f,
+                "\nShard {} (version {}): {}",
+                shard.shard, shard.mapping_version, shard.balance
+            )?;
+        }
+        write!(f, "\nTotal: {}", self.total)
+    }
+}
+
+/// Has the MXE re-encrypt the signer's balance in `shard`, or in every shard, for the
+/// encryption key, waits for the results and decrypts them.
+pub fn balance(ctx: &Context, mint: Pubkey, shard: Option<u8>) -> Result<Balance> {
+    let blackbox = ctx.blackbox(mint)?;
+    let cipher = ctx.cipher(&blackbox)?;
+    let client_key = ctx.encryption_key.public_key();
+    let shards = match shard {
+        Some(shard) => vec![shard],
+        None => (0..ctx.blackbox_account(&blackbox)?.mapping_account_count).collect(),
+    };
+    let owner = ctx.pubkey();
+
+    // Queue every reveal first so the MXE computes them concurrently
+    let mut reveals = Vec::new();
+    for shard in shards {
+        let computation = Computation {
+            payer: owner,
+            mapping_account: blackbox.mapping_account(shard),
+            computation_offset: rand::thread_rng().gen(),
+            use_op_queue: false,
+            circuit_version: 1,
+        };
+        let nonce = rand::thread_rng().gen();
+        let instruction = blackbox.reveal_balance(&computation, &owner, &client_key, nonce);
+        ctx.send(vec![("reveal_balance", instruction)])?;
+        reveals.push((
+            shard,
+            pda::balance_reveal(&blackbox.address, computation.computation_offset),
+        ));
+    }
+
+    let mut balances = Vec::new();
+    for (shard, address) in reveals {
+        let (reveal, enc_balance) = ctx.wait_for(REVEAL_TIMEOUT, || {
+            let reveal = ctx.account(&address, BalanceReveal::decode)?;
+            Ok(reveal.enc_balance.map(|enc_balance| (reveal, enc_balance)))
+        })?;
+        let balance = cipher
+            .decrypt_u64(&enc_balance, reveal.nonce)
+            .with_context(|| format!("decrypting the balance in shard {shard}"))?;
+        balances.push(ShardBalance {
+            shard,
+            mapping_account: reveal.mapping_account.to_string(),
+            mapping_version: reveal.mapping_version,
+            balance,
+        });
+    }
+
+    Ok(Balance {
+        owner: owner.to_string(),
+        client_key: hex::encode(client_key),
+        total: balances.iter().map(|shard| shard.balance).sum(),
+        shards: balances,
+    })
+}
+
+#[derive(Serialize)]
+pub struct Solvency {
+    pub attestation: String,
+    pub vault_amount: u64,
+    pub shards: u8,
+    pub solvent: bool,
+    pub requested_slot: u64,
+    pub attested_slot: u64,
+    pub signature: String,
+}
+
+impl fmt::Display for Solvency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = if self.solvent {
+            "covers"
+        } else {
+            "does NOT cover"
+        };
+        writeln!(
+            f,
+            "Vault of {} {verdict} the balances of {} shard(s) at slot {}",
+            self.vault_amount, self.shards, self.requested_slot
+        )?;
+        writeln!(
+            f,
+            "Attestation: {} (slot {})",
+            self.attestation, self.attested_slot
+        )?;
+        write!(f, "Signature:   {}", self.signature)
+    }
+}
+
+/// Has the MXE check that the vault of the blackbox of `mint` covers every balance in its
+/// shards, and waits for the attestation. Blackboxes with more shards than one computation
+/// sums are proven a range at a time, each queued once the previous one has landed.
+pub fn prove_solvency(ctx: &Context, mint: Pubkey) -> Result<Solvency> {
+    let blackbox = ctx.blackbox(mint)?;
+    let shards = ctx.blackbox_account(&blackbox)?.mapping_account_count;
+    let attestation_offset = rand::thread_rng().gen();
+    let instruction = blackbox.prove_solvency(&ctx.pubkey(), attestation_offset, shards);
+    let signature = ctx.send(vec![("prove_solvency", instruction)])?;
+
+    let address = pda::solvency_attestation(&blackbox.address, attestation_offset);
+    let (attestation, solvent) = loop {
+        let attestation = ctx.wait_for(SOLVENCY_TIMEOUT, || {
+            let attestation = ctx.account(&address, SolvencyAttestation::decode)?;
+            Ok(attestation.queued_range.is_none().then_some(attestation))
+        })?;
+        if let Some(solvent) = attestation.solvent {
+            break (attestation, solvent);
+        }
+        let instruction = blackbox.continue_solvency_proof(
+            &ctx.pubkey(),
+            attestation_offset,
+            rand::thread_rng().gen(),
+            attestation.summed_shards,
+            attestation.shards,
+        );
+        ctx.send(vec![("continue_solvency_proof", instruction)])?;
+    };
+    Ok(Solvency {
+        attestation: address.to_string(),
+        vault_amount: attestation.vault_amount,
+        shards: attestation.shards,
+        solvent,
+        requested_slot: attestation.requested_slot,
+        attested_slot: attestation.attested_slot,
+        signature: signature.to_string(),
+    })
+}
+
+/// Pages exported from one shard
+#[derive(Serialize)]
+pub struct ExportedShard {
+    pub shard: u8,
+    pub mapping_account: String,
+    pub mapping_version: u64,
+    pub pages: u8,
+    pub entries: usize,
+}
+
+#[derive(Serialize)]
+pub struct ExportBalances {
+    pub shards: Vec<ExportedShard>,
+    /// Signatures of the exports this run queued; pages exported before are skipped
+    pub signatures: Vec<String>,
+}
+
+impl fmt::Display for ExportBalances {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines = Vec::new();
+        for shard in &self.shards {
+            lines.push(format!(
+                "Shard {} (version {}): {} balance(s) in {} page(s)",
+                shard.shard, shard.mapping_version, shard.entries, shard.pages
+            ));
+        }
+        lines.push(format!("Queued {} export(s)", self.signatures.len()));
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Has the MXE export every balance of the blackbox of `mint`, which must be in emergency
+/// mode, page by page for their owners. Pages already exported at a shard's current version
+/// are skipped, so an interrupted run can be repeated.
+pub fn export_balances(ctx: &Context, mint: Pubkey) -> Result<ExportBalances> {
+    let blackbox = ctx.blackbox(mint)?;
+    let account = ctx.blackbox_account(&blackbox)?;
+    if !account.emergency {
+        bail!("blackbox for mint {mint} isn't in emergency mode");
+    }
+
+    let mut shards = Vec::new();
+    let mut signatures = Vec::new();
+    for shard in 0..account.mapping_account_count {
+        let mapping_account = blackbox.mapping_account(shard);
+        let version = ctx
+            .account(&mapping_account, MappingAccount::decode)?
+            .version;
+        let mut exported = ExportedShard {
+            shard,
+            mapping_account: mapping_account.to_string(),
+            mapping_version: version,
+            pages: 0,
+            entries: 0,
+        };
+        for page in 0..=u8::MAX {
+            let address = pda::emergency_export(&mapping_account, version, page);
+            if ctx.account_data(&address)?.is_none() {
+                let instruction = blackbox.export_balances(
+                    &ctx.pubkey(),
+                    rand::thread_rng().gen(),
+                    &mapping_account,
+                    version,
+                    page,
+                );
+                let signature = ctx.send(vec![("export_balances", instruction)])?;
+                signatures.push(signature.to_string());
+            }
+            let export = ctx.wait_for(EXPORT_TIMEOUT, || {
+                let export = ctx.account(&address, EmergencyExport::decode)?;
+                Ok(export.exported.then_some(export))
+            })?;
+            exported.pages += 1;
+            exported.entries += export.entries.len();
+            if !export.more {
+                break;
+            }
+        }
+        shards.push(exported);
+    }
+    Ok(ExportBalances { shards, signatures })
+}
+
+/// A balance the signer claimed in emergency mode
+#[derive(Serialize)]
+pub struct ClaimedBalance {
+    pub shard: u8,
+    pub export: String,
+    pub amount: u64,
+    pub signature: String,
+}
+
+#[derive(Serialize)]
+pub struct ClaimEmergency {
+    pub owner: String,
+    pub destination: String,
+    pub claims: Vec<ClaimedBalance>,
+    /// Sum over `claims`
+    pub total: u64,
+}
+
+impl fmt::Display for ClaimEmergency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.claims.is_empty() {
+            return write!(f, "Nothing to claim for {}", self.owner);
+        }
+        for claim in &self.claims {
+            writeln!(
+                f,
+                "Shard {}: claimed {} ({})",
+                claim.shard, claim.amount, claim.signature
+            )?;
+        }
+        write!(f, "Paid {} into {}", self.total, self.destination)
+    }
+}
+
+/// Finds the signer's balances in the exports of the blackbox of `mint`, decrypts them with
+/// the signer's own ed25519 key and claims each into `to`, by default the signer's associated
+/// token account. Balances already claimed are skipped.
+pub fn claim_emergency(ctx: &Context, mint: Pubkey, to: Option<Pubkey>) -> Result<ClaimEmergency> {
+    let blackbox = ctx.blackbox(mint)?;
+    let account = ctx.blackbox_account(&blackbox)?;
+    if !account.emergency {
+        bail!("blackbox for mint {mint} isn't in emergency mode");
+    }
+    let cipher = ctx.claim_cipher(&blackbox)?;
+    let owner = ctx.pubkey();
+    let to = to.unwrap_or_else(|| get_associated_token_address(&owner, &mint));
+
+    let mut claims = Vec::new();
+    for shard in 0..account.mapping_account_count {
+        let mapping_account = blackbox.mapping_account(shard);
+        if ctx
+            .account_data(&pda::claimed_balance(&mapping_account, &owner))?
+            .is_some()
+        {
+            continue;
+        }
+        let version = ctx
+            .account(&mapping_account, MappingAccount::decode)?
+            .version;
+        for page in 0..=u8::MAX {
+            let address = pda::emergency_export(&mapping_account, version, page);
+            let Some(data) = ctx.account_data(&address)? else {
+                bail!("shard {shard} isn't exported yet; run export-balances first");
+            };
+            let export = EmergencyExport::decode(&data)?;
+            if !export.exported {
+                bail!("shard {shard} isn't exported yet; run export-balances first");
+            }
+            if let Some(claim) = open_claim(&cipher, &owner, &export) {
+                let instruction = blackbox.claim_emergency(
+                    &owner,
+                    &owner,
+                    &to,
+                    &export,
+                    claim.entry,
+                    claim.balance,
+                    claim.salt,
+                );
+                let signature = ctx.send(vec![("claim_emergency", instruction)])?;
+                claims.push(ClaimedBalance {
+                    shard,
+                    export: address.to_string(),
+                    amount: claim.balance,
+                    signature: signature.to_string(),
+                });
+                break;
+            }
+            if !export.more {
+                break;
+            }
+        }
+    }
+
+    Ok(ClaimEmergency {
+        owner: owner.to_string(),
+        destination: to.to_string(),
+        total: claims.iter().map(|claim| claim.amount).sum(),
+        claims,
+    })
+}
+
+/// A deposit of the signer's that emergency mode left uncredited, refunded to its source
+#[derive(Serialize)]
+pub struct RefundedDeposit {
+    pub mapping_account: String,
+    /// Offset of the deposit's computation; `None` for deposits refunded from the queue
+    pub computation_offset: Option<u64>,
+    pub source: String,
+    pub amount: u64,
+    pub signature: String,
+}
+
+#[derive(Serialize)]
+pub struct RefundDeposits {
+    pub owner: String,
+    pub refunds: Vec<RefundedDeposit>,
+    /// Sum over `refunds`
+    pub total: u64,
+}
+
+impl fmt::Display for RefundDeposits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.refunds.is_empty() {
+            return write!(f, "No deposits to refund for {}", self.owner);
+        }
+        for refund in &self.refunds {
+            writeln!(
+                f,
+                "Refunded {} into {} ({})",
+                refund.amount, refund.source, refund.signature
+            )?;
+        }
+        write!(f, "Refunded {} in total", self.total)
+    }
+}
+
+/// Refunds the signer's deposits that the blackbox of `mint`, in emergency mode, left
+/// uncredited: those whose computation never landed, and those still queued or in flight.
+/// Each goes back to the token account it was made from.
+pub fn refund_deposits(ctx: &Context, mint: Pubkey) -> Result<RefundDeposits> {
+    let blackbox = ctx.blackbox(mint)?;
+    let account = ctx.blackbox_account(&blackbox)?;
+    if !account.emergency {
+        bail!("blackbox for mint {mint} isn't in emergency mode");
+    }
+    let owner = ctx.pubkey();
+
+    let mut refunds = Vec::new();
+    for (_, record) in op_records(ctx, &owner)? {
+        let refundable = matches!(
+            record.status,
+            OpStatus::Pending | OpStatus::Dropped | OpStatus::Stale
+        );
+        let deposit = matches!(&record.requeue, Some(op) if op.kind == OpKind::Deposit);
+        if record.blackbox != blackbox.address || !refundable || !deposit {
+            continue;
+        }
+        let instruction = blackbox.refund_deposit(record.computation_offset, &record.destination);
+        let signature = ctx.send(vec![("refund_deposit", instruction)])?;
+        refunds.push(RefundedDeposit {
+            mapping_account: record.mapping_account.to_string(),
+            computation_offset: Some(record.computation_offset),
+            source: record.destination.to_string(),
+            amount: record.amount,
+            signature: signature.to_string(),
+        });
+    }
+
+    for shard in 0..account.mapping_account_count {
+        let mapping_account = blackbox.mapping_account(shard);
+        let Some(data) = ctx.account_data(&pda::op_queue(&mapping_account))? else {
+            continue;
+        };
+        let op_queue = OpQueueAccount::decode(&data)?;
+        let deposits: Vec<_> = op_queue
+            .in_flight
+            .iter()
+            .chain(&op_queue.pending)
+            .filter(|op| op.owner == owner && op.kind == OpKind::Deposit)
+            .collect();
+        if deposits.is_empty() {
+            continue;
+        }
+        let mut sources: Vec<_> = deposits.iter().map(|op| op.destination).collect();
+        sources.sort();
+        sources.dedup();
+        let instruction = blackbox.refund_queued_deposits(&mapping_account, &sources);
+        let signature = ctx.send(vec![("refund_queued_deposits", instruction)])?;
+        refunds.extend(deposits.iter().map(|op| RefundedDeposit {
+            mapping_account: mapping_account.to_string(),
+            computation_offset: None,
+            source: op.destination.to_string(),
+            amount: op.amount,
+            signature: signature.to_string(),
+        }));
+    }
+
+    Ok(RefundDeposits {
+        owner: owner.to_string(),
+        total: refunds.iter().map(|refund| refund.amount).sum(),
+        refunds,
+    })
+}
+
+/// A shard whose migration to the next circuit version was queued
+#[derive(Serialize)]
+pub struct MigratedShard {
+    pub shard: u8,
+    pub mapping_account: String,
+    /// Circuit version the shard is on once the migration lands
+    pub circuit_version: u8,
+    pub computation_offset: u64,
+    pub signature: String,
+}
+
+#[derive(Serialize)]
+pub struct MigrateShards {
+    /// The blackbox's circuit version
+    pub circuit_version: u8,
+    pub shards: Vec<MigratedShard>,
+}
+
+impl fmt::Display for MigrateShards {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.shards.is_empty() {
+            return write!(
+                f,
+                "Every shard is on circuit version {}",
+                self.circuit_version
+            );
+        }
+        let lines: Vec<String> = self
+            .shards
+            .iter()
+            .map(|shard| {
+                format!(
+                    "Shard {}: migrating to version {} as computation {} ({})",
+                    shard.shard, shard.circuit_version, shard.computation_offset, shard.signature
+                )
+            })
+            .collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Queues the migration of every shard of the blackbox of `mint` that's behind the blackbox's
+/// circuit version; the signer must be the program authority. Each migration moves a shard one
+/// version ahead, so a shard further behind needs another run once it has landed.
+pub fn migrate_shards(ctx: &Context, mint: Pubkey) -> Result<MigrateShards> {
+    let blackbox = ctx.blackbox(mint)?;
+    let account = ctx.blackbox_account(&blackbox)?;
+    if account.emergency {
+        bail!("blackbox for mint {mint} is in emergency mode");
+    }
+    let authority = ctx.pubkey();
+
+    let mut shards = Vec::new();
+    for shard in 0..account.mapping_account_count {
+        let mapping_account = blackbox.mapping_account(shard);
+        let circuit_version = ctx
+            .account(&mapping_account, MappingAccount::decode)?
+            .circuit_version;
+        if circuit_version >= account.circuit_version {
+            continue;
+        }
+        let computation_offset = rand::thread_rng().gen();
+        let instruction = blackbox
+            .migrate_mapping(
+                &authority,
+                &authority,
+                computation_offset,
+                &mapping_account,
+                circuit_version,
+            )
+            .with_context(|| format!("no circuit migrates out of version {circuit_version}"))?;
+        let signature = ctx.send(vec![("migrate_mapping", instruction)])?;
+        shards.push(MigratedShard {
+            shard,
+            mapping_account: mapping_account.to_string(),
+            circuit_version: circuit_version + 1,
+            computation_offset,
+            signature: signature.to_string(),
+        });
+    }
+    Ok(MigrateShards {
+        circuit_version: account.circuit_version,
+        shards,
+    })
+}
+
+/// An operation of the signer's waiting in an operation queue
+#[derive(Serialize)]
+pub struct QueuedOperation {
+    pub mapping_account: String,
+    pub kind: &'static str,
+    /// Plaintext amount; `None` for transfers, whose amount is encrypted
+    pub amount: Option<u64>,
+    /// Handed to the MXE by a flush whose callback hasn't arrived
+    pub in_flight: bool,
+}
+
+/// A computation of the signer's whose result hasn't been applied
+#[derive(Serialize)]
+pub struct UnresolvedComputation {
+    pub op_record: String,
+    pub mapping_account: String,
+    pub computation_offset: u64,
+    pub status: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct Pending {
+    pub queued: Vec<QueuedOperation>,
+    pub computations: Vec<UnresolvedComputation>,
+}
+
+impl fmt::Display for Pending {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.queued.is_empty() && self.computations.is_empty() {
+            return write!(f, "No pending operations");
+        }
+        let mut lines = Vec::new();
+        for op in &self.queued {
+            let amount = op
+                .amount
+                .map_or("encrypted".to_string(), |amount| amount.to_string());
+            let state = if op.in_flight { "in flight" } else { "queued" };
+            lines.push(format!(
+                "{:<9} {:<8} {amount:>20}  {}",
+                state, op.kind, op.mapping_account
+            ));
+        }
+        for computation in &self.computations {
+            lines.push(format!(
+                "{:<9} offset {}  {}",
+                computation.status, computation.computation_offset, computation.mapping_account
+            ));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+fn kind_name(kind: OpKind) -> &'static str {
+    match kind {
+        OpKind::Deposit => "deposit",
+        OpKind::Transfer => "transfer",
+        OpKind::Withdraw => "withdraw",
+    }
+}
+
+/// Lists the signer's queued operations and unresolved computations in the blackbox of `mint`.
+pub fn pending(ctx: &Context, mint: Pubkey) -> Result<Pending> {
+    let blackbox = ctx.blackbox(mint)?;
+    let shards = ctx.blackbox_account(&blackbox)?.mapping_account_count;
+    let owner = ctx.pubkey();
+
+    let mut queued = Vec::new();
+    for shard in 0..shards {
+        let mapping_account = blackbox.mapping_account(shard);
+        let Some(data) = ctx.account_data(&pda::op_queue(&mapping_account))? else {
+            continue;
+        };
+        let op_queue = OpQueueAccount::decode(&data)?;
+        let in_flight = op_queue.in_flight.iter().map(|op| (op, true));
+        let pending = op_queue.pending.iter().map(|op| (op, false));
+        for (op, in_flight) in in_flight.chain(pending) {
+            if op.owner != owner {
+                continue;
+            }
+            queued.push(QueuedOperation {
+                mapping_account: mapping_account.to_string(),
+                kind: kind_name(op.kind),
+                amount: (op.kind != OpKind::Transfer).then_some(op.amount),
+                in_flight,
+            });
+        }
+    }
+
+    let mut computations = Vec::new();
+    for (address, record) in op_records(ctx, &owner)? {
+        let status = match record.status {
+            OpStatus::Pending => "pending",
+            // Stale results can still be handed to the operation queue
+            OpStatus::Stale => "stale",
+            OpStatus::Applied
+            | OpStatus::Requeued
+            | OpStatus::Dropped
+            | OpStatus::Failed
+            | OpStatus::Refunded => continue,
+        };
+        if record.blackbox != blackbox.address {
+            continue;
+        }
+        computations.push(UnresolvedComputation {
+            op_record: address.to_string(),
+            mapping_account: record.mapping_account.to_string(),
+            computation_offset: record.computation_offset,
+            status,
+        });
+    }
+    computations.sort_by_key(|computation| computation.computation_offset);
+
+    Ok(Pending {
+        queued,
+        computations,
+    })
+}
+
+/// The operation records of `owner`'s computations, across every blackbox
+fn op_records(ctx: &Context, owner: &Pubkey) -> Result<Vec<(Pubkey, OpRecord)>> {
+    let filters = vec![
+        RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, OpRecord::discriminator().to_vec())),
+        RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            OpRecord::OWNER_OFFSET,
+            owner.to_bytes().to_vec(),
+        )),
+    ];
+    let records = ctx.rpc.get_program_accounts_with_config(
+        &PROGRAM_ID,
+        RpcProgramAccountsConfig {
+            filters: Some(filters),
+            ..Default::default()
+        },
+    )?;
+    records
+        .into_iter()
+        .map(|(address, account)| Ok((address, OpRecord::decode(&account.data)?)))
+        .collect()
+}
+
+#[derive(Serialize)]
+pub struct EncryptionKey {
+    pub owner: String,
+    /// Hex of the x25519 public key balances are revealed to
+    pub public_key: String,
+    /// What the owner signs to derive the key, for deriving it in a wallet
+    pub message: String,
+}
+
+impl fmt::Display for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Owner:      {}", self.owner)?;
+        writeln!(f, "Public key: {}", self.public_key)?;
+        write!(f, "Derived by signing:\n{}", self.message)
+    }
+}
+
+/// The signer's encryption key, derived from their keypair.
+pub fn encryption_key(ctx: &Context) -> EncryptionKey {
+    EncryptionKey {
+        owner: ctx.pubkey().to_string(),
+        public_key: hex::encode(ctx.encryption_key.public_key()),
+        message: String::from_utf8_lossy(KEY_DERIVATION_MESSAGE).into_owned(),
+    }
+}
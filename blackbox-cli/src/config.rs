@@ -0,0 +1,124 @@
+//! The CLI's config file.
+//!
+//! Read from `~/.config/blackbox/config.toml` unless `--config` points elsewhere. Every field
+//! is optional, and command-line flags take precedence over it:
+//!
+//! ```toml
+//! url = "devnet"                          # RPC URL, or localnet / devnet / testnet / mainnet-beta
+//! keypair = "~/.config/solana/id.json"    # signs and pays for every transaction
+//! commitment = "confirmed"
+//!
+//! [arcium]
+//! cluster = 0                             # offset of the Arcium cluster new blackboxes use
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{bail, Context as _, Result};
+use serde::Deserialize;
+use solana_sdk::commitment_config::CommitmentConfig;
+
+const DEFAULT_URL: &str = "localnet";
+const DEFAULT_KEYPAIR: &str = "~/.config/solana/id.json";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub url: Option<String>,
+    pub keypair: Option<String>,
+    pub commitment: Option<String>,
+    #[serde(default)]
+    pub arcium: ArciumConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ArciumConfig {
+    /// Offset of the Arcium cluster computations are queued to, the one new blackboxes are
+    /// created on
+    pub cluster: Option<u32>,
+}
+
+/// Settings after merging the config file with command-line flags
+pub struct Settings {
+    pub url: String,
+    pub keypair: PathBuf,
+    pub commitment: CommitmentConfig,
+    pub arcium_cluster: Option<u32>,
+}
+
+impl Config {
+    /// Default location of the config file
+    pub fn default_path() -> Option<PathBuf> {
+        home().map(|home| home.join(".config/blackbox/config.toml"))
+    }
+
+    /// Loads `path`, or the default config file. Only an explicitly given file must exist.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match Self::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if !required && err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default())
+            }
+            Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+        };
+        toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// Applies `url`, `keypair` and `arcium_cluster` from the command line over the file's
+    /// values.
+    pub fn resolve(
+        self,
+        url: Option<String>,
+        keypair: Option<String>,
+        arcium_cluster: Option<u32>,
+    ) -> Result<Settings> {
+        let url = url.or(self.url).unwrap_or_else(|| DEFAULT_URL.to_string());
+        let keypair = keypair
+            .or(self.keypair)
+            .unwrap_or_else(|| DEFAULT_KEYPAIR.to_string());
+        let commitment = match self.commitment {
+            Some(commitment) => CommitmentConfig::from_str(&commitment)
+                .map_err(|_| anyhow::anyhow!("unknown commitment {commitment:?}"))?,
+            None => CommitmentConfig::confirmed(),
+        };
+        Ok(Settings {
+            url: rpc_url(&url)?,
+            keypair: expand_home(&keypair)?,
+            commitment,
+            arcium_cluster: arcium_cluster.or(self.arcium.cluster),
+        })
+    }
+}
+
+/// Expands the Solana CLI's cluster monikers
+fn rpc_url(url: &str) -> Result<String> {
+    Ok(match url {
+        "localnet" | "l" => "http://127.0.0.1:8899",
+        "devnet" | "d" => "https://api.devnet.solana.com",
+        "testnet" | "t" => "https://api.testnet.solana.com",
+        "mainnet-beta" | "m" => "https://api.mainnet-beta.solana.com",
+        url if url.starts_with("http://") || url.starts_with("https://") => url,
+        url => bail!("{url:?} is neither a URL nor a cluster moniker"),
+    }
+    .to_string())
+}
+
+fn expand_home(path: &str) -> Result<PathBuf> {
+    match path.strip_prefix("~/") {
+        Some(rest) => Ok(home().context("HOME is not set")?.join(rest)),
+        None => Ok(PathBuf::from(path)),
+    }
+}
+
+fn home() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
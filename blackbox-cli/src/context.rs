@@ -0,0 +1,149 @@
+//! Connection to the cluster and the keypair that signs for every command.
+
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use blackbox_client::{
+    accounts::BlackboxAccount,
+    compute_budget::with_compute_budget,
+    encryption::{ClientSecret, MxePublicKey, RescueCipher},
+    pda, Blackbox,
+};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signature},
+    signer::Signer,
+    transaction::Transaction,
+};
+
+use crate::config::Settings;
+
+/// How often to poll for a computation's callback
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Arcium cluster new blackboxes queue their computations to unless one is configured
+const DEFAULT_CLUSTER_OFFSET: u32 = 0;
+
+pub struct Context {
+    pub rpc: RpcClient,
+    /// Signs as user and pays for transactions and rent
+    pub keypair: Keypair,
+    /// Derived from `keypair`
+    pub encryption_key: ClientSecret,
+    arcium_cluster: Option<u32>,
+}
+
+impl Context {
+    pub fn new(settings: Settings) -> Result<Self> {
+        let keypair = read_keypair_file(&settings.keypair)
+            .map_err(|err| anyhow!("reading keypair {}: {err}", settings.keypair.display()))?;
+        Ok(Self {
+            rpc: RpcClient::new_with_commitment(settings.url, settings.commitment),
+            encryption_key: ClientSecret::from_keypair(&keypair),
+            keypair,
+            arcium_cluster: settings.arcium_cluster,
+        })
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+
+    /// The blackbox of `token_mint`, on the Arcium cluster it was created with, or on the
+    /// configured one if it doesn't exist yet. A configured cluster it wasn't created with is
+    /// an error, as the program would refuse every computation queued to it.
+    pub fn blackbox(&self, token_mint: Pubkey) -> Result<Blackbox> {
+        let address = pda::blackbox(&token_mint);
+        let cluster_offset = match self.account_data(&address)? {
+            Some(data) => {
+                let stored = BlackboxAccount::decode(&data)
+                    .with_context(|| format!("decoding {address}"))?
+                    .cluster_offset;
+                if let Some(configured) = self.arcium_cluster.filter(|&c| c != stored) {
+                    bail!(
+                        "the blackbox of {token_mint} is on Arcium cluster {stored}, \
+                         not {configured}"
+                    );
+                }
+                stored
+            }
+            None => self.arcium_cluster.unwrap_or(DEFAULT_CLUSTER_OFFSET),
+        };
+        Ok(Blackbox::new(token_mint, cluster_offset))
+    }
+
+    /// Cipher shared between the encryption key and the MXE `blackbox` computes on
+    pub fn cipher(&self, blackbox: &Blackbox) -> Result<RescueCipher> {
+        let mxe = self.account(&blackbox.arcium.program_mxe, MxePublicKey::decode)?;
+        Ok(self.encryption_key.cipher(&mxe))
+    }
+
+    /// Cipher the MXE encrypts exported balances with, agreed with the keypair's own ed25519
+    /// key rather than the derived encryption key
+    pub fn claim_cipher(&self, blackbox: &Blackbox) -> Result<RescueCipher> {
+        let mxe = self.account(&blackbox.arcium.program_mxe, MxePublicKey::decode)?;
+        Ok(ClientSecret::from_owner_keypair(&self.keypair).cipher(&mxe))
+    }
+
+    /// Sends `instructions`, named as in the program, with a compute budget covering them.
+    pub fn send(&self, instructions: Vec<(&str, Instruction)>) -> Result<Signature> {
+        let instructions = with_compute_budget(instructions);
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&self.pubkey()),
+            &[&self.keypair],
+            blockhash,
+        );
+        Ok(self.rpc.send_and_confirm_transaction(&transaction)?)
+    }
+
+    /// Data of the account at `address`, if it exists
+    pub fn account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>> {
+        let response = self
+            .rpc
+            .get_account_with_commitment(address, self.rpc.commitment())
+            .with_context(|| format!("fetching {address}"))?;
+        Ok(response.value.map(|account| account.data))
+    }
+
+    /// Decodes the account at `address`, which must exist.
+    pub fn account<T, E>(
+        &self,
+        address: &Pubkey,
+        decode: impl Fn(&[u8]) -> Result<T, E>,
+    ) -> Result<T>
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let data = self
+            .account_data(address)?
+            .with_context(|| format!("account {address} doesn't exist"))?;
+        decode(&data).with_context(|| format!("decoding {address}"))
+    }
+
+    pub fn blackbox_account(&self, blackbox: &Blackbox) -> Result<BlackboxAccount> {
+        self.account(&blackbox.address, BlackboxAccount::decode)
+            .with_context(|| format!("no blackbox for mint {}", blackbox.token_mint))
+    }
+
+    /// Polls until `ready` returns a value, giving up after `timeout`.
+    pub fn wait_for<T>(
+        &self,
+        timeout: Duration,
+        mut ready: impl FnMut() -> Result<Option<T>>,
+    ) -> Result<T> {
+        let start = Instant::now();
+        loop {
+            if let Some(value) = ready()? {
+                return Ok(value);
+            }
+            if start.elapsed() > timeout {
+                bail!("timed out after {}s waiting for the MXE", timeout.as_secs());
+            }
+            sleep(POLL_INTERVAL);
+        }
+    }
+}
@@ -0,0 +1,479 @@
+//! `blackbox`: command-line interface for operators and users of the blackbox program.
+//!
+//! The program authority creates a blackbox for a mint with its configuration. Operators
+//! register the program's computation definitions and add shards (mapping accounts). The
+//! program authority also pauses operations, queues parameter changes behind the timelock,
+//! including emergency mode, and proposes a successor. Users deposit, transfer, withdraw,
+//! reveal their own balance, list their pending operations and, in emergency mode, claim their
+//! exported balances. Every command prints a human-readable report, or JSON with
+//! `--output json`.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use blackbox_client::{
+    accounts::{
+        Fees, InitBlackboxParams, Limits, ModeFlags, ParameterChange, PauseFlags, PolicyHooks,
+    },
+    arcium::LATEST_CIRCUIT_VERSION,
+    Circuit,
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::commands::Target;
+use crate::config::Config;
+use crate::context::Context;
+
+mod commands;
+mod config;
+mod context;
+
+#[derive(Parser)]
+#[command(name = "blackbox", version, about)]
+struct Cli {
+    /// Config file [default: ~/.config/blackbox/config.toml]
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// RPC URL or cluster moniker (localnet, devnet, testnet, mainnet-beta)
+    #[arg(long, short = 'u', global = true)]
+    url: Option<String>,
+
+    /// Keypair that signs and pays for transactions
+    #[arg(long, short = 'k', global = true)]
+    keypair: Option<String>,
+
+    /// Offset of the Arcium cluster computations are queued to [default: the one the blackbox
+    /// was created on, or 0 for a new one]
+    #[arg(long, global = true, value_name = "OFFSET")]
+    arcium_cluster: Option<u32>,
+
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+    JsonCompact,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create the blackbox of a mint with its first shard; sign with the program authority.
+    /// Fees, limits and the circuit version can be changed later, the rest is fixed
+    Init {
+        #[arg(long)]
+        mint: Pubkey,
+        /// Balances each shard holds [default: the program config's maximum]
+        #[arg(long)]
+        shard_capacity: Option<u16>,
+        #[command(flatten)]
+        fees: FeeArgs,
+        #[command(flatten)]
+        limits: LimitsArgs,
+        /// Circuit version shards start on
+        #[arg(long, default_value_t = LATEST_CIRCUIT_VERSION)]
+        circuit_version: u8,
+        /// Key that must co-sign every deposit
+        #[arg(long)]
+        deposit_approver: Option<Pubkey>,
+        /// Only take operations through shards' operation queues; disables withdrawing the
+        /// whole balance
+        #[arg(long)]
+        queued_only: bool,
+    },
+    /// Register the program's computation definitions, skipping registered ones
+    RegisterCompDefs {
+        /// Circuit to register; repeatable. Registers every circuit if omitted
+        #[arg(long = "circuit", value_parser = parse_circuit)]
+        circuits: Vec<Circuit>,
+    },
+    /// Add the next shard (mapping account) to a blackbox
+    AddShard {
+        #[arg(long)]
+        mint: Pubkey,
+        /// Also create the shard's operation queue
+        #[arg(long)]
+        op_queue: bool,
+    },
+    /// Create the program config; sign with the program's upgrade authority
+    InitConfig,
+    /// Pause operations now, on top of those already paused
+    Pause {
+        #[command(flatten)]
+        pause: PauseArgs,
+    },
+    /// Queue new pause flags; operations not passed are resumed once it's executed
+    SetPaused {
+        #[command(flatten)]
+        pause: PauseArgs,
+    },
+    /// Propose a new program authority, who takes over by accepting
+    ProposeAuthority {
+        /// Withdraws the current proposal if omitted
+        new_authority: Option<Pubkey>,
+    },
+    /// Take over as program authority; sign with the proposed authority
+    AcceptAuthority,
+    /// Queue a new delay, in slots, before queued changes can be executed
+    SetTimelock { slots: u64 },
+    /// Queue a cap on every blackbox's fees, in basis points
+    SetMaxFee { max_fee_bps: u16 },
+    /// Queue a cap on the balances each shard of a new blackbox holds
+    SetMaxShardCapacity { max_shard_capacity: u16 },
+    /// Queue the fees a blackbox charges, in basis points of the amount moved
+    SetFees {
+        #[arg(long)]
+        mint: Pubkey,
+        #[command(flatten)]
+        fees: FeeArgs,
+    },
+    /// Queue the bounds on a blackbox's deposits and withdrawals; 0 leaves a maximum unlimited
+    SetLimits {
+        #[arg(long)]
+        mint: Pubkey,
+        #[command(flatten)]
+        limits: LimitsArgs,
+    },
+    /// Apply a queued change whose timelock has passed; anyone can
+    ExecuteChange { id: u64 },
+    /// Drop a queued change
+    CancelChange { id: u64 },
+    /// Queue emergency mode for a blackbox: every operation stops for good, and owners claim
+    /// their exported balances instead
+    EnterEmergency {
+        #[arg(long)]
+        mint: Pubkey,
+    },
+    /// Queue a newer circuit version for a blackbox; its shards keep the old circuits until
+    /// migrated
+    SetCircuitVersion {
+        #[arg(long)]
+        mint: Pubkey,
+        circuit_version: u8,
+    },
+    /// Migrate every shard of a blackbox behind its circuit version, one version ahead
+    MigrateShards {
+        #[arg(long)]
+        mint: Pubkey,
+    },
+    /// Have the MXE export every balance of a blackbox in emergency mode to its owner
+    ExportBalances {
+        #[arg(long)]
+        mint: Pubkey,
+    },
+    /// Claim your exported balances from a blackbox in emergency mode
+    ClaimEmergency {
+        #[arg(long)]
+        mint: Pubkey,
+        /// Token account to pay out to [default: your associated token account]
+        #[arg(long)]
+        to: Option<Pubkey>,
+    },
+    /// Get back your deposits that a blackbox in emergency mode left uncredited
+    RefundDeposits {
+        #[arg(long)]
+        mint: Pubkey,
+    },
+    /// Move the fees a blackbox has collected out of its treasury
+    CollectFees {
+        #[arg(long)]
+        mint: Pubkey,
+        /// Token account to pay out to [default: your associated token account]
+        #[arg(long)]
+        to: Option<Pubkey>,
+    },
+    /// Deposit tokens into a blackbox
+    Deposit {
+        #[command(flatten)]
+        target: TargetArgs,
+        amount: u64,
+        /// Token account to deposit from [default: your associated token account]
+        #[arg(long)]
+        from: Option<Pubkey>,
+    },
+    /// Transfer to a recipient; the recipient and amount are encrypted for the MXE
+    Transfer {
+        #[command(flatten)]
+        target: TargetArgs,
+        #[arg(long)]
+        to: Pubkey,
+        amount: u64,
+        /// Send cover traffic: a transfer that moves nothing
+        #[arg(long)]
+        noop: bool,
+    },
+    /// Withdraw tokens from a blackbox
+    Withdraw {
+        #[command(flatten)]
+        target: TargetArgs,
+        #[arg(required_unless_present = "all")]
+        amount: Option<u64>,
+        /// Withdraw the whole balance, without knowing it
+        #[arg(long, conflicts_with = "amount")]
+        all: bool,
+        /// Token account to pay out to [default: your associated token account]
+        #[arg(long)]
+        to: Option<Pubkey>,
+    },
+    /// Have the MXE reveal your balance to your encryption key
+    Balance {
+        #[arg(long)]
+        mint: Pubkey,
+        /// Shard to read [default: every shard]
+        #[arg(long)]
+        shard: Option<u8>,
+    },
+    /// Have the MXE attest whether a blackbox's vault covers every balance in it
+    ProveSolvency {
+        #[arg(long)]
+        mint: Pubkey,
+    },
+    /// List your queued operations and unresolved computations
+    Pending {
+        #[arg(long)]
+        mint: Pubkey,
+    },
+    /// Show the encryption key derived from your keypair
+    EncryptionKey,
+}
+
+#[derive(Args)]
+struct TargetArgs {
+    #[arg(long)]
+    mint: Pubkey,
+    /// Shard (mapping account index) to operate on
+    #[arg(long, default_value_t = 0)]
+    shard: u8,
+    /// Append to the shard's operation queue instead of queuing a computation
+    #[arg(long)]
+    queue: bool,
+}
+
+#[derive(Args)]
+struct PauseArgs {
+    /// Pause on the blackbox of this mint only [default: on every blackbox]
+    #[arg(long)]
+    mint: Option<Pubkey>,
+    #[arg(long)]
+    deposit: bool,
+    #[arg(long)]
+    transfer: bool,
+    /// Also pauses withdrawing the whole balance
+    #[arg(long)]
+    withdraw: bool,
+}
+
+#[derive(Args)]
+struct FeeArgs {
+    #[arg(long, default_value_t = 0)]
+    deposit_bps: u16,
+    /// Also charged on withdrawing the whole balance
+    #[arg(long, default_value_t = 0)]
+    withdraw_bps: u16,
+}
+
+impl FeeArgs {
+    fn fees(&self) -> Fees {
+        Fees {
+            deposit_bps: self.deposit_bps,
+            withdraw_bps: self.withdraw_bps,
+        }
+    }
+}
+
+#[derive(Args)]
+struct LimitsArgs {
+    #[arg(long, default_value_t = 0)]
+    min_deposit: u64,
+    #[arg(long, default_value_t = 0)]
+    max_deposit: u64,
+    #[arg(long, default_value_t = 0)]
+    min_withdraw: u64,
+    /// Any maximum or outflow cap disables withdrawing the whole balance
+    #[arg(long, default_value_t = 0)]
+    max_withdraw: u64,
+    /// Total that may be withdrawn per epoch, fees included
+    #[arg(long, default_value_t = 0)]
+    epoch_outflow_cap: u64,
+}
+
+impl LimitsArgs {
+    fn limits(&self) -> Limits {
+        Limits {
+            min_deposit: self.min_deposit,
+            max_deposit: self.max_deposit,
+            min_withdraw: self.min_withdraw,
+            max_withdraw: self.max_withdraw,
+            epoch_outflow_cap: self.epoch_outflow_cap,
+        }
+    }
+}
+
+impl PauseArgs {
+    fn flags(&self) -> PauseFlags {
+        PauseFlags {
+            deposit: self.deposit,
+            transfer: self.transfer,
+            withdraw: self.withdraw,
+        }
+    }
+}
+
+impl From<TargetArgs> for Target {
+    fn from(args: TargetArgs) -> Self {
+        Target {
+            mint: args.mint,
+            shard: args.shard,
+            queue: args.queue,
+        }
+    }
+}
+
+fn parse_circuit(name: &str) -> Result<Circuit, String> {
+    Circuit::ALL
+        .into_iter()
+        .find(|circuit| circuit.name() == name)
+        .ok_or_else(|| {
+            let names: Vec<_> = Circuit::ALL.iter().map(Circuit::name).collect();
+            format!("expected one of {}", names.join(", "))
+        })
+}
+
+fn print(report: impl Serialize + fmt::Display, output: OutputFormat) -> Result<()> {
+    match output {
+        OutputFormat::Text => println!("{report}"),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::JsonCompact => println!("{}", serde_json::to_string(&report)?),
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let settings =
+        Config::load(cli.config.as_deref())?.resolve(cli.url, cli.keypair, cli.arcium_cluster)?;
+    let ctx = Context::new(settings)?;
+    let output = cli.output;
+
+    match cli.command {
+        Command::Init {
+            mint,
+            shard_capacity,
+            fees,
+            limits,
+            circuit_version,
+            deposit_approver,
+            queued_only,
+        } => {
+            // The shard capacity is filled in from the program config when omitted, the
+            // cluster from the blackbox the context resolves
+            let params = InitBlackboxParams {
+                shard_capacity: 0,
+                fees: fees.fees(),
+                limits: limits.limits(),
+                circuit_version,
+                policy: PolicyHooks { deposit_approver },
+                modes: ModeFlags { queued_only },
+                cluster_offset: 0,
+            };
+            print(commands::init(&ctx, mint, shard_capacity, params)?, output)
+        }
+        Command::RegisterCompDefs { circuits } => {
+            print(commands::register_comp_defs(&ctx, circuits)?, output)
+        }
+        Command::AddShard { mint, op_queue } => {
+            print(commands::add_shard(&ctx, mint, op_queue)?, output)
+        }
+        Command::InitConfig => print(commands::init_config(&ctx)?, output),
+        Command::Pause { pause } => {
+            print(commands::pause(&ctx, pause.mint, pause.flags())?, output)
+        }
+        Command::SetPaused { pause } => {
+            let change = ParameterChange::Paused(pause.flags());
+            print(commands::queue_change(&ctx, pause.mint, change)?, output)
+        }
+        Command::ProposeAuthority { new_authority } => {
+            print(commands::propose_authority(&ctx, new_authority)?, output)
+        }
+        Command::AcceptAuthority => print(commands::accept_authority(&ctx)?, output),
+        Command::SetTimelock { slots } => {
+            let change = ParameterChange::Timelock(slots);
+            print(commands::queue_change(&ctx, None, change)?, output)
+        }
+        Command::SetMaxFee { max_fee_bps } => {
+            let change = ParameterChange::MaxFee(max_fee_bps);
+            print(commands::queue_change(&ctx, None, change)?, output)
+        }
+        Command::SetMaxShardCapacity { max_shard_capacity } => {
+            let change = ParameterChange::MaxShardCapacity(max_shard_capacity);
+            print(commands::queue_change(&ctx, None, change)?, output)
+        }
+        Command::SetFees { mint, fees } => {
+            let change = ParameterChange::Fees(fees.fees());
+            print(commands::queue_change(&ctx, Some(mint), change)?, output)
+        }
+        Command::SetLimits { mint, limits } => {
+            let change = ParameterChange::Limits(limits.limits());
+            print(commands::queue_change(&ctx, Some(mint), change)?, output)
+        }
+        Command::ExecuteChange { id } => print(commands::execute_change(&ctx, id)?, output),
+        Command::CancelChange { id } => print(commands::cancel_change(&ctx, id)?, output),
+        Command::EnterEmergency { mint } => {
+            let change = ParameterChange::Emergency;
+            print(commands::queue_change(&ctx, Some(mint), change)?, output)
+        }
+        Command::SetCircuitVersion {
+            mint,
+            circuit_version,
+        } => {
+            let change = ParameterChange::CircuitVersion(circuit_version);
+            print(commands::queue_change(&ctx, Some(mint), change)?, output)
+        }
+        Command::MigrateShards { mint } => print(commands::migrate_shards(&ctx, mint)?, output),
+        Command::ExportBalances { mint } => print(commands::export_balances(&ctx, mint)?, output),
+        Command::ClaimEmergency { mint, to } => {
+            print(commands::claim_emergency(&ctx, mint, to)?, output)
+        }
+        Command::RefundDeposits { mint } => print(commands::refund_deposits(&ctx, mint)?, output),
+        Command::CollectFees { mint, to } => print(commands::collect_fees(&ctx, mint, to)?, output),
+        Command::Deposit {
+            target,
+            amount,
+            from,
+        } => print(
+            commands::deposit(&ctx, target.into(), amount, from)?,
+            output,
+        ),
+        Command::Transfer {
+            target,
+            to,
+            amount,
+            noop,
+        } => print(
+            commands::transfer(&ctx, target.into(), to, amount, noop)?,
+            output,
+        ),
+        Command::Withdraw {
+            target,
+            amount,
+            all,
+            to,
+        } => {
+            let amount = if all { None } else { amount };
+            print(commands::withdraw(&ctx, target.into(), amount, to)?, output)
+        }
+        Command::Balance { mint, shard } => print(commands::balance(&ctx, mint, shard)?, output),
+        Command::ProveSolvency { mint } => print(commands::prove_solvency(&ctx, mint)?, output),
+        Command::Pending { mint } => print(commands::pending(&ctx, mint)?, output),
+        Command::EncryptionKey => print(commands::encryption_key(&ctx), output),
+    }
+}
@@ -0,0 +1,802 @@
+//! Decoders for the accounts of the blackbox program.
+//!
+//! Each account is its Anchor discriminator followed by its Borsh-encoded fields; trailing
+//! bytes (unused allocated space) are ignored.
+
+use std::fmt;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::account_discriminator;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The data doesn't start with the expected account's discriminator
+    WrongAccountType,
+    /// The data ended before the account did
+    TooShort,
+    /// An enum field holds a variant this version doesn't know
+    InvalidVariant(u8),
+    /// Logged event data isn't valid base64
+    InvalidEncoding,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::WrongAccountType => write!(f, "account has a different type"),
+            DecodeError::TooShort => write!(f, "account data is too short"),
+            DecodeError::InvalidVariant(variant) => write!(f, "unknown enum variant {variant}"),
+            DecodeError::InvalidEncoding => write!(f, "event data isn't valid base64"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Sequential reader over Borsh-encoded fields
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Starts after the discriminator of account type `name`.
+    fn account(data: &'a [u8], name: &str) -> Result<Self, DecodeError> {
+        Self::after_discriminator(data, account_discriminator(name))
+    }
+
+    /// Starts after `discriminator`, which `data` must begin with.
+    pub(crate) fn after_discriminator(
+        data: &'a [u8],
+        discriminator: [u8; 8],
+    ) -> Result<Self, DecodeError> {
+        match data.split_first_chunk::<8>() {
+            Some((found, rest)) if *found == discriminator => Ok(Self { data: rest }),
+            Some(_) => Err(DecodeError::WrongAccountType),
+            None => Err(DecodeError::TooShort),
+        }
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let (bytes, rest) = self
+            .data
+            .split_first_chunk::<N>()
+            .ok_or(DecodeError::TooShort)?;
+        self.data = rest;
+        Ok(*bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, DecodeError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            variant => Err(DecodeError::InvalidVariant(variant)),
+        }
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(u16::from_le_bytes(self.bytes()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn u128(&mut self) -> Result<u128, DecodeError> {
+        Ok(u128::from_le_bytes(self.bytes()?))
+    }
+
+    pub(crate) fn pubkey(&mut self) -> Result<Pubkey, DecodeError> {
+        Ok(Pubkey::new_from_array(self.bytes()?))
+    }
+
+    fn vec<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<Vec<T>, DecodeError> {
+        let len = self.u32()? as usize;
+        // Every item takes at least a byte; don't trust a length the data can't hold
+        if len > self.data.len() {
+            return Err(DecodeError::TooShort);
+        }
+        (0..len).map(|_| item(self)).collect()
+    }
+
+    pub(crate) fn option<T>(
+        &mut self,
+        item: impl FnOnce(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<Option<T>, DecodeError> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => item(self).map(Some),
+            variant => Err(DecodeError::InvalidVariant(variant)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProgramConfig {
+    pub bump: u8,
+    pub authority: Pubkey,
+    /// Operations paused on every blackbox
+    pub paused: PauseFlags,
+    /// Cap on every blackbox's fees, in basis points
+    pub max_fee_bps: u16,
+    /// Successor proposed by the authority, who takes over by accepting
+    pub pending_authority: Option<Pubkey>,
+    /// Slots a queued parameter change waits before it can be executed
+    pub timelock_slots: u64,
+    /// Id of the next queued change
+    pub next_change_id: u64,
+    /// Cap on the balance entries a new blackbox's mapping accounts have room for
+    pub max_shard_capacity: u16,
+}
+
+impl ProgramConfig {
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::account(data, "ProgramConfig")?;
+        Ok(Self {
+            bump: r.u8()?,
+            authority: r.pubkey()?,
+            paused: PauseFlags::read(&mut r)?,
+            max_fee_bps: r.u16()?,
+            pending_authority: r.option(Reader::pubkey)?,
+            timelock_slots: r.u64()?,
+            next_change_id: r.u64()?,
+            max_shard_capacity: r.u16()?,
+        })
+    }
+}
+
+/// Operations stopped by an emergency pause
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PauseFlags {
+    pub deposit: bool,
+    pub transfer: bool,
+    /// Also stops `withdraw_all`
+    pub withdraw: bool,
+}
+
+impl PauseFlags {
+    pub(crate) fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            deposit: r.bool()?,
+            transfer: r.bool()?,
+            withdraw: r.bool()?,
+        })
+    }
+
+    pub(crate) fn encode(&self) -> [u8; 3] {
+        [self.deposit.into(), self.transfer.into(), self.withdraw.into()]
+    }
+}
+
+/// Fees a blackbox charges, in basis points of the amount moved
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Fees {
+    pub deposit_bps: u16,
+    /// Also charged on `withdraw_all` payouts
+    pub withdraw_bps: u16,
+}
+
+impl Fees {
+    pub(crate) fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            deposit_bps: r.u16()?,
+            withdraw_bps: r.u16()?,
+        })
+    }
+
+    pub(crate) fn encode(&self) -> [u8; 4] {
+        let [d0, d1] = self.deposit_bps.to_le_bytes();
+        let [w0, w1] = self.withdraw_bps.to_le_bytes();
+        [d0, d1, w0, w1]
+    }
+}
+
+/// Bounds on single deposits and withdrawals, and on what may leave the vault in one epoch.
+/// A maximum or cap of 0 means no limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub min_deposit: u64,
+    pub max_deposit: u64,
+    pub min_withdraw: u64,
+    pub max_withdraw: u64,
+    /// Total withdrawals, fees included, queued per epoch
+    pub epoch_outflow_cap: u64,
+}
+
+impl Limits {
+    pub(crate) fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            min_deposit: r.u64()?,
+            max_deposit: r.u64()?,
+            min_withdraw: r.u64()?,
+            max_withdraw: r.u64()?,
+            epoch_outflow_cap: r.u64()?,
+        })
+    }
+
+    pub(crate) fn encode(&self) -> [u8; 40] {
+        let mut data = [0; 40];
+        let fields = [
+            self.min_deposit,
+            self.max_deposit,
+            self.min_withdraw,
+            self.max_withdraw,
+            self.epoch_outflow_cap,
+        ];
+        for (chunk, field) in data.chunks_exact_mut(8).zip(fields) {
+            chunk.copy_from_slice(&field.to_le_bytes());
+        }
+        data
+    }
+}
+
+/// Checks operations go through on a blackbox besides its limits
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PolicyHooks {
+    /// Key that must co-sign every deposit, direct or queued; anyone can deposit if `None`
+    pub deposit_approver: Option<Pubkey>,
+}
+
+impl PolicyHooks {
+    pub(crate) fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            deposit_approver: r.option(Reader::pubkey)?,
+        })
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        match self.deposit_approver {
+            Some(approver) => [&[1][..], approver.as_ref()].concat(),
+            None => vec![0],
+        }
+    }
+}
+
+/// How a blackbox takes operations
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ModeFlags {
+    /// Deposits, transfers and withdrawals only go through operation queues; `withdraw_all`
+    /// is disabled
+    pub queued_only: bool,
+}
+
+impl ModeFlags {
+    pub(crate) fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            queued_only: r.bool()?,
+        })
+    }
+
+    pub(crate) fn encode(&self) -> [u8; 1] {
+        [self.queued_only.into()]
+    }
+}
+
+/// Configuration a blackbox is created with, bounded by the program config
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InitBlackboxParams {
+    /// Balance entries each mapping account has room for, at most
+    /// `ProgramConfig::max_shard_capacity`
+    pub shard_capacity: u16,
+    pub fees: Fees,
+    pub limits: Limits,
+    /// Circuit version the blackbox starts on
+    pub circuit_version: u8,
+    pub policy: PolicyHooks,
+    pub modes: ModeFlags,
+    /// Offset of the Arcium cluster the blackbox's computations are queued to
+    pub cluster_offset: u32,
+}
+
+impl InitBlackboxParams {
+    pub(crate) fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            shard_capacity: r.u16()?,
+            fees: Fees::read(r)?,
+            limits: Limits::read(r)?,
+            circuit_version: r.u8()?,
+            policy: PolicyHooks::read(r)?,
+            modes: ModeFlags::read(r)?,
+            cluster_offset: r.u32()?,
+        })
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        [
+            &self.shard_capacity.to_le_bytes()[..],
+            &self.fees.encode(),
+            &self.limits.encode(),
+            &[self.circuit_version],
+            &self.policy.encode(),
+            &self.modes.encode(),
+            &self.cluster_offset.to_le_bytes(),
+        ]
+        .concat()
+    }
+}
+
+/// Withdrawals counted against `Limits::epoch_outflow_cap` in `epoch`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EpochOutflow {
+    pub epoch: u64,
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlackboxAccount {
+    pub bump: u8,
+    pub token_mint: Pubkey,
+    pub vault: Pubkey,
+    /// Token account fees are paid into
+    pub treasury: Pubkey,
+    pub mapping_account_count: u8,
+    /// Operations paused on this blackbox only, on top of `ProgramConfig::paused`
+    pub paused: PauseFlags,
+    pub fees: Fees,
+    pub limits: Limits,
+    pub outflow: EpochOutflow,
+    /// Whether the blackbox is in emergency mode, which can't be left
+    pub emergency: bool,
+    /// Circuit version new mapping accounts start on, and existing ones are migrated to
+    pub circuit_version: u8,
+    /// Balance entries each mapping account has room for
+    pub shard_capacity: u16,
+    pub policy: PolicyHooks,
+    pub modes: ModeFlags,
+    /// Offset of the Arcium cluster the blackbox's computations are queued to
+    pub cluster_offset: u32,
+}
+
+impl BlackboxAccount {
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::account(data, "BlackboxAccount")?;
+        Ok(Self {
+            bump: r.u8()?,
+            token_mint: r.pubkey()?,
+            vault: r.pubkey()?,
+            treasury: r.pubkey()?,
+            mapping_account_count: r.u8()?,
+            paused: PauseFlags::read(&mut r)?,
+            fees: Fees::read(&mut r)?,
+            limits: Limits::read(&mut r)?,
+            outflow: EpochOutflow {
+                epoch: r.u64()?,
+                amount: r.u64()?,
+            },
+            emergency: r.bool()?,
+            circuit_version: r.u8()?,
+            shard_capacity: r.u16()?,
+            policy: PolicyHooks::read(&mut r)?,
+            modes: ModeFlags::read(&mut r)?,
+            cluster_offset: r.u32()?,
+        })
+    }
+}
+
+/// A parameter update, applied by `execute_change` once the timelock has passed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParameterChange {
+    /// `ProgramConfig::max_fee_bps`
+    MaxFee(u16),
+    /// A blackbox's fees
+    Fees(Fees),
+    /// A blackbox's limits
+    Limits(Limits),
+    /// The pause flags of a blackbox, or the program-wide ones
+    Paused(PauseFlags),
+    /// `ProgramConfig::timelock_slots`
+    Timelock(u64),
+    /// Puts a blackbox in emergency mode
+    Emergency,
+    /// Moves a blackbox to a newer circuit version
+    CircuitVersion(u8),
+    /// `ProgramConfig::max_shard_capacity`
+    MaxShardCapacity(u16),
+}
+
+impl ParameterChange {
+    pub(crate) fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(match r.u8()? {
+            0 => Self::MaxFee(r.u16()?),
+            1 => Self::Fees(Fees::read(r)?),
+            2 => Self::Limits(Limits::read(r)?),
+            3 => Self::Paused(PauseFlags::read(r)?),
+            4 => Self::Timelock(r.u64()?),
+            5 => Self::Emergency,
+            6 => Self::CircuitVersion(r.u8()?),
+            7 => Self::MaxShardCapacity(r.u16()?),
+            variant => return Err(DecodeError::InvalidVariant(variant)),
+        })
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        match self {
+            Self::MaxFee(max_fee_bps) => [&[0][..], &max_fee_bps.to_le_bytes()].concat(),
+            Self::Fees(fees) => [&[1][..], &fees.encode()].concat(),
+            Self::Limits(limits) => [&[2][..], &limits.encode()].concat(),
+            Self::Paused(paused) => [&[3][..], &paused.encode()].concat(),
+            Self::Timelock(timelock_slots) => [&[4][..], &timelock_slots.to_le_bytes()].concat(),
+            Self::Emergency => vec![5],
+            Self::CircuitVersion(circuit_version) => vec![6, *circuit_version],
+            Self::MaxShardCapacity(max_shard_capacity) => {
+                [&[7][..], &max_shard_capacity.to_le_bytes()].concat()
+            }
+        }
+    }
+}
+
+/// A parameter change waiting out the timelock
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingChange {
+    pub bump: u8,
+    pub id: u64,
+    /// Blackbox the change applies to; the program config if `None`
+    pub blackbox: Option<Pubkey>,
+    pub change: ParameterChange,
+    /// First slot the change can be executed in
+    pub executable_slot: u64,
+    /// Receives the account's rent back when the change is executed or cancelled
+    pub payer: Pubkey,
+}
+
+impl PendingChange {
+    /// Leading bytes of every pending change
+    pub fn discriminator() -> [u8; 8] {
+        account_discriminator("PendingChange")
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::account(data, "PendingChange")?;
+        Ok(Self {
+            bump: r.u8()?,
+            id: r.u64()?,
+            blackbox: r.option(Reader::pubkey)?,
+            change: ParameterChange::read(&mut r)?,
+            executable_slot: r.u64()?,
+            payer: r.pubkey()?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MappingAccount {
+    pub index: u8,
+    pub token_mint: Pubkey,
+    /// Nonce the stored `Mapping` ciphertexts were encrypted under
+    pub nonce: u128,
+    /// Incremented every time a callback stores a new mapping
+    pub version: u64,
+    /// Circuit version the stored mapping was written by; see `Circuit::for_version`
+    pub circuit_version: u8,
+    /// Balance entries the account has room for
+    pub capacity: u16,
+    pub encrypted_pubkeys: Vec<[u8; 32]>,
+    pub encrypted_balances: Vec<[u8; 32]>,
+}
+
+impl MappingAccount {
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::account(data, "MappingAccount")?;
+        Ok(Self {
+            index: r.u8()?,
+            token_mint: r.pubkey()?,
+            nonce: r.u128()?,
+            version: r.u64()?,
+            circuit_version: r.u8()?,
+            capacity: r.u16()?,
+            encrypted_pubkeys: r.vec(Reader::bytes)?,
+            encrypted_balances: r.vec(Reader::bytes)?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpKind {
+    Deposit,
+    Transfer,
+    Withdraw,
+}
+
+impl OpKind {
+    pub(crate) fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        match r.u8()? {
+            0 => Ok(OpKind::Deposit),
+            1 => Ok(OpKind::Transfer),
+            2 => Ok(OpKind::Withdraw),
+            variant => Err(DecodeError::InvalidVariant(variant)),
+        }
+    }
+}
+
+/// An operation waiting in an operation queue
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueuedOp {
+    pub kind: OpKind,
+    pub owner: Pubkey,
+    /// x25519 key the operation's status is encrypted for
+    pub client_key: [u8; 32],
+    /// Encrypted `TransferArgs`, transfers only
+    pub enc_transfer: [[u8; 32]; 3],
+    pub amount: u64,
+    pub nonce: u128,
+    pub destination: Pubkey,
+    /// Epoch a withdrawal was counted towards the outflow cap in
+    pub epoch: u64,
+}
+
+impl QueuedOp {
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            kind: OpKind::read(r)?,
+            owner: r.pubkey()?,
+            client_key: r.bytes()?,
+            enc_transfer: [r.bytes()?, r.bytes()?, r.bytes()?],
+            amount: r.u64()?,
+            nonce: r.u128()?,
+            destination: r.pubkey()?,
+            epoch: r.u64()?,
+        })
+    }
+}
+
+/// Encrypted status of an operation applied by the last flushed batch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpResult {
+    pub owner: Pubkey,
+    pub enc_status: [u8; 32],
+    pub nonce: u128,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpQueueAccount {
+    pub bump: u8,
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub pending: Vec<QueuedOp>,
+    pub in_flight: Vec<QueuedOp>,
+    pub in_flight_nonce: u128,
+    pub in_flight_offset: u64,
+    /// Slot the in-flight batch was flushed at
+    pub in_flight_slot: u64,
+    pub results: Vec<OpResult>,
+}
+
+impl OpQueueAccount {
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::account(data, "OpQueueAccount")?;
+        Ok(Self {
+            bump: r.u8()?,
+            blackbox: r.pubkey()?,
+            mapping_account: r.pubkey()?,
+            pending: r.vec(QueuedOp::read)?,
+            in_flight: r.vec(QueuedOp::read)?,
+            in_flight_nonce: r.u128()?,
+            in_flight_offset: r.u64()?,
+            in_flight_slot: r.u64()?,
+            results: r.vec(|r| {
+                Ok(OpResult {
+                    owner: r.pubkey()?,
+                    enc_status: r.bytes()?,
+                    nonce: r.u128()?,
+                })
+            })?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpStatus {
+    Pending,
+    Applied,
+    Stale,
+    Requeued,
+    /// Landed after the blackbox entered emergency mode and was discarded
+    Dropped,
+    /// Had nothing to apply and left the mapping as it was
+    Failed,
+    /// Deposit left uncredited, by emergency mode or a full mapping account, and paid back to
+    /// its source
+    Refunded,
+}
+
+impl OpStatus {
+    pub(crate) fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        match r.u8()? {
+            0 => Ok(OpStatus::Pending),
+            1 => Ok(OpStatus::Applied),
+            2 => Ok(OpStatus::Stale),
+            3 => Ok(OpStatus::Requeued),
+            4 => Ok(OpStatus::Dropped),
+            5 => Ok(OpStatus::Failed),
+            6 => Ok(OpStatus::Refunded),
+            variant => Err(DecodeError::InvalidVariant(variant)),
+        }
+    }
+}
+
+/// Record of a queued computation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpRecord {
+    pub bump: u8,
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub computation_offset: u64,
+    pub mapping_version: u64,
+    pub owner: Pubkey,
+    /// Paid the record's rent, which closing it returns
+    pub payer: Pubkey,
+    pub amount: u64,
+    pub epoch: u64,
+    pub destination: Pubkey,
+    pub status: OpStatus,
+    pub requeue: Option<QueuedOp>,
+}
+
+impl OpRecord {
+    /// Offset of `owner` in the account data, for filtering program accounts by owner
+    pub const OWNER_OFFSET: usize = 8 + 1 + 32 + 32 + 8 + 8;
+
+    /// Leading bytes of every operation record
+    pub fn discriminator() -> [u8; 8] {
+        account_discriminator("OpRecord")
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::account(data, "OpRecord")?;
+        Ok(Self {
+            bump: r.u8()?,
+            blackbox: r.pubkey()?,
+            mapping_account: r.pubkey()?,
+            computation_offset: r.u64()?,
+            mapping_version: r.u64()?,
+            owner: r.pubkey()?,
+            payer: r.pubkey()?,
+            amount: r.u64()?,
+            epoch: r.u64()?,
+            destination: r.pubkey()?,
+            status: OpStatus::read(&mut r)?,
+            requeue: r.option(QueuedOp::read)?,
+        })
+    }
+}
+
+/// A balance re-encrypted for its owner's x25519 key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BalanceReveal {
+    pub bump: u8,
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub computation_offset: u64,
+    /// Mapping account version the balance was read from
+    pub mapping_version: u64,
+    pub owner: Pubkey,
+    /// x25519 public key the balance is encrypted for
+    pub client_key: [u8; 32],
+    /// Nonce the balance is encrypted under
+    pub nonce: u128,
+    /// Encrypted balance, `None` until the callback arrives
+    pub enc_balance: Option<[u8; 32]>,
+}
+
+impl BalanceReveal {
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::account(data, "BalanceReveal")?;
+        Ok(Self {
+            bump: r.u8()?,
+            blackbox: r.pubkey()?,
+            mapping_account: r.pubkey()?,
+            computation_offset: r.u64()?,
+            mapping_version: r.u64()?,
+            owner: r.pubkey()?,
+            client_key: r.bytes()?,
+            nonce: r.u128()?,
+            enc_balance: r.option(Reader::bytes)?,
+        })
+    }
+}
+
+/// Whether a blackbox's vault covered every credited balance when the proof was requested
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SolvencyAttestation {
+    pub bump: u8,
+    pub blackbox: Pubkey,
+    pub computation_offset: u64,
+    /// Vault token amount the credited total was compared against
+    pub vault_amount: u64,
+    /// Number of mapping accounts summed
+    pub shards: u8,
+    /// Shards whose balances `enc_total` already counts; the next range starts here
+    pub summed_shards: u8,
+    /// Number of shards in the range whose computation is queued, `None` when none is
+    pub queued_range: Option<u8>,
+    /// Total of the summed shards' balances, encrypted for the MXE alone
+    pub enc_total: [u8; 32],
+    pub total_nonce: u128,
+    /// Slot the proof was requested at
+    pub requested_slot: u64,
+    /// Whether the vault covered the total, `None` until the last range's callback arrives
+    pub solvent: Option<bool>,
+    /// Slot the callback recorded the result at, 0 until it arrives
+    pub attested_slot: u64,
+}
+
+impl SolvencyAttestation {
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::account(data, "SolvencyAttestation")?;
+        Ok(Self {
+            bump: r.u8()?,
+            blackbox: r.pubkey()?,
+            computation_offset: r.u64()?,
+            vault_amount: r.u64()?,
+            shards: r.u8()?,
+            summed_shards: r.u8()?,
+            queued_range: r.option(Reader::u8)?,
+            enc_total: r.bytes()?,
+            total_nonce: r.u128()?,
+            requested_slot: r.u64()?,
+            solvent: r.option(Reader::bool)?,
+            attested_slot: r.u64()?,
+        })
+    }
+}
+
+/// A balance exported from a mapping account in emergency mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExportedBalance {
+    /// Balance and salt, encrypted for the x25519 form of the owner's ed25519 key
+    pub enc_claim: [[u8; 32]; 2],
+    /// Commitment to the owner, balance and salt that `claim_emergency` checks
+    pub commitment: [u8; 32],
+}
+
+/// One page of a mapping account's balances, exported for their owners in emergency mode
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EmergencyExport {
+    pub bump: u8,
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub mapping_version: u64,
+    pub page: u8,
+    pub computation_offset: u64,
+    /// Nonce of the first entry's claim; entry `k` is encrypted under `nonce + k`
+    pub nonce: u128,
+    /// Whether the callback has stored the entries
+    pub exported: bool,
+    pub entries: Vec<ExportedBalance>,
+    /// Whether the mapping has entries past this page
+    pub more: bool,
+}
+
+impl EmergencyExport {
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::account(data, "EmergencyExport")?;
+        Ok(Self {
+            bump: r.u8()?,
+            blackbox: r.pubkey()?,
+            mapping_account: r.pubkey()?,
+            mapping_version: r.u64()?,
+            page: r.u8()?,
+            computation_offset: r.u64()?,
+            nonce: r.u128()?,
+            exported: r.bool()?,
+            entries: r.vec(|r| {
+                Ok(ExportedBalance {
+                    enc_claim: [r.bytes()?, r.bytes()?],
+                    commitment: r.bytes()?,
+                })
+            })?,
+            more: r.bool()?,
+        })
+    }
+}
@@ -0,0 +1,155 @@
+//! Arcium accounts blackbox instructions pass along when registering and queuing computations.
+
+use anchor_lang::AccountDeserialize;
+use arcium_anchor::{
+    comp_def_offset, CLOCK_PDA_SEED, CLUSTER_PDA_SEED, COMP_DEF_PDA_SEED, MEMPOOL_PDA_SEED,
+    MXE_PDA_SEED, POOL_PDA_SEED,
+};
+use arcium_client::idl::arcium::{
+    accounts::ComputationDefinitionAccount, types::CircuitSource, ID_CONST as ARCIUM_PROG_ID,
+};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::PROGRAM_ID;
+
+/// Newest version of the deposit, transfer and withdraw circuits
+pub const LATEST_CIRCUIT_VERSION: u8 = 2;
+
+/// Circuits the blackbox program queues computations for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Circuit {
+    Deposit,
+    Transfer,
+    Withdraw,
+    WithdrawAll,
+    RevealBalance,
+    ProcessBatch,
+    ProveSolvency,
+    ExportBalances,
+    DepositV2,
+    TransferV2,
+    WithdrawV2,
+    WithdrawAllV2,
+    ProcessBatchV2,
+    MigrateMappingV2,
+}
+
+impl Circuit {
+    pub const ALL: [Circuit; 14] = [
+        Circuit::Deposit,
+        Circuit::Transfer,
+        Circuit::Withdraw,
+        Circuit::WithdrawAll,
+        Circuit::RevealBalance,
+        Circuit::ProcessBatch,
+        Circuit::ProveSolvency,
+        Circuit::ExportBalances,
+        Circuit::DepositV2,
+        Circuit::TransferV2,
+        Circuit::WithdrawV2,
+        Circuit::WithdrawAllV2,
+        Circuit::ProcessBatchV2,
+        Circuit::MigrateMappingV2,
+    ];
+
+    /// Name the circuit's computation definition is registered under
+    pub fn name(&self) -> &'static str {
+        match self {
+            Circuit::Deposit => "deposit",
+            Circuit::Transfer => "transfer",
+            Circuit::Withdraw => "withdraw",
+            Circuit::WithdrawAll => "withdraw_all",
+            Circuit::RevealBalance => "reveal_balance",
+            Circuit::ProcessBatch => "process_batch",
+            Circuit::ProveSolvency => "prove_solvency",
+            Circuit::ExportBalances => "export_balances",
+            Circuit::DepositV2 => "deposit_v2",
+            Circuit::TransferV2 => "transfer_v2",
+            Circuit::WithdrawV2 => "withdraw_v2",
+            Circuit::WithdrawAllV2 => "withdraw_all_v2",
+            Circuit::ProcessBatchV2 => "process_batch_v2",
+            Circuit::MigrateMappingV2 => "migrate_mapping_v2",
+        }
+    }
+
+    /// Version of the circuit a mapping account on `circuit_version` runs. Only the circuits
+    /// that write a mapping back are versioned; the read-only ones serve every version.
+    pub fn for_version(self, circuit_version: u8) -> Circuit {
+        match (self, circuit_version) {
+            (Circuit::Deposit, 2) => Circuit::DepositV2,
+            (Circuit::Transfer, 2) => Circuit::TransferV2,
+            (Circuit::Withdraw, 2) => Circuit::WithdrawV2,
+            (Circuit::WithdrawAll, 2) => Circuit::WithdrawAllV2,
+            (Circuit::ProcessBatch, 2) => Circuit::ProcessBatchV2,
+            _ => self,
+        }
+    }
+
+    /// Circuit migrating a mapping account out of `circuit_version`, or `None` if it's the
+    /// latest
+    pub fn migration(circuit_version: u8) -> Option<Circuit> {
+        match circuit_version {
+            1 => Some(Circuit::MigrateMappingV2),
+            _ => None,
+        }
+    }
+
+    /// Address of the circuit's computation definition account
+    pub fn comp_def(&self) -> Pubkey {
+        Pubkey::find_program_address(
+            &[COMP_DEF_PDA_SEED, &comp_def_offset(self.name()).to_le_bytes()],
+            &ARCIUM_PROG_ID,
+        )
+        .0
+    }
+
+    /// Blackbox instruction registering the circuit's computation definition
+    pub fn init_comp_def_instruction(&self) -> String {
+        format!("init_{}_comp_def", self.name())
+    }
+}
+
+/// Hash of the compiled circuit a computation definition account commits to, or `None` if its
+/// circuit isn't registered off-chain, which the program refuses to queue against
+pub fn committed_circuit_hash(comp_def_data: &[u8]) -> Option<[u8; 32]> {
+    let comp_def = ComputationDefinitionAccount::try_deserialize(&mut &comp_def_data[..]).ok()?;
+    match comp_def.circuit_source {
+        CircuitSource::OffChain(source) => Some(source.hash),
+        _ => None,
+    }
+}
+
+/// Arcium accounts shared by every queue instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArciumAccounts {
+    pub program: Pubkey,
+    /// Offset of the cluster a blackbox queues its computations to, stored on the blackbox
+    pub cluster_offset: u32,
+    pub clock: Pubkey,
+    pub cluster: Pubkey,
+    pub mempool: Pubkey,
+    /// MXE account computations are queued on
+    pub mxe: Pubkey,
+    /// Blackbox's MXE account, used when registering computation definitions
+    pub program_mxe: Pubkey,
+    pub pool: Pubkey,
+}
+
+impl ArciumAccounts {
+    /// Derives the Arcium accounts from the Arcium program's seeds, with the cluster, its
+    /// mempool and staking pool those of cluster `cluster_offset`.
+    pub fn resolve(cluster_offset: u32) -> Self {
+        let pda = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &ARCIUM_PROG_ID).0;
+        let offset = cluster_offset.to_le_bytes();
+        Self {
+            program: ARCIUM_PROG_ID,
+            cluster_offset,
+            clock: pda(&[CLOCK_PDA_SEED]),
+            cluster: pda(&[CLUSTER_PDA_SEED, &offset]),
+            mempool: pda(&[MEMPOOL_PDA_SEED, &offset]),
+            mxe: pda(&[MXE_PDA_SEED]),
+            program_mxe: pda(&[MXE_PDA_SEED, PROGRAM_ID.as_ref()]),
+            pool: pda(&[POOL_PDA_SEED, &offset]),
+        }
+    }
+}
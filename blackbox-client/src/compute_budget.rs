@@ -0,0 +1,36 @@
+//! Compute-unit limits for blackbox instructions.
+//!
+//! The limits come from `compute-budgets.tsv` at the root of this crate, whose budgets the
+//! program's compute-unit profile test enforces. Requesting exactly the budget keeps the priority fee a
+//! transaction pays proportional to what it can actually use.
+
+use solana_sdk::{compute_budget::ComputeBudgetInstruction, instruction::Instruction};
+
+const BUDGETS: &str = include_str!("../compute-budgets.tsv");
+
+/// Compute units requested for transactions this library doesn't know the instructions of
+pub const DEFAULT_COMPUTE_UNIT_LIMIT: u32 = 200_000;
+
+/// Budget of the blackbox instruction or callback called `instruction`
+pub fn compute_unit_limit(instruction: &str) -> Option<u32> {
+    BUDGETS
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once('\t'))
+        .find(|(name, _)| *name == instruction)
+        .map(|(_, budget)| budget.trim().parse().expect("malformed compute budget"))
+}
+
+/// Prepends a compute-unit limit covering `instructions`, named as in the program.
+///
+/// Instructions without a budget count for `DEFAULT_COMPUTE_UNIT_LIMIT`.
+pub fn with_compute_budget(instructions: Vec<(&str, Instruction)>) -> Vec<Instruction> {
+    let limit = instructions
+        .iter()
+        .map(|(name, _)| compute_unit_limit(name).unwrap_or(DEFAULT_COMPUTE_UNIT_LIMIT))
+        .fold(0u32, u32::saturating_add);
+
+    std::iter::once(ComputeBudgetInstruction::set_compute_unit_limit(limit))
+        .chain(instructions.into_iter().map(|(_, instruction)| instruction))
+        .collect()
+}
@@ -0,0 +1,72 @@
+//! Scheduled cover traffic.
+//!
+//! A transfer whose encrypted no-op flag is set does the same MPC work as a real one but
+//! leaves every balance unchanged. Sending them at random intervals, with recipients and
+//! amounts drawn like real transfers, keeps observers from telling when a user actually moves
+//! funds.
+
+use std::time::Duration;
+
+use rand::Rng;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::encryption::EncryptionError;
+use crate::instructions::EncryptedTransfer;
+
+/// Client-side encryption of transfer arguments for the MXE
+pub trait TransferEncryptor {
+    fn encrypt_transfer(
+        &self,
+        recipient: &Pubkey,
+        amount: u64,
+        noop: bool,
+        nonce: u128,
+    ) -> Result<EncryptedTransfer, EncryptionError>;
+}
+
+/// Generates no-op transfers separated by exponentially distributed delays.
+///
+/// Exponential gaps make the cover traffic a Poisson process, so its timing carries no
+/// pattern a real transfer could stand out from.
+pub struct CoverTraffic<R> {
+    rng: R,
+    mean_interval: Duration,
+    recipients: Vec<Pubkey>,
+    max_amount: u64,
+}
+
+impl<R: Rng> CoverTraffic<R> {
+    /// `recipients` are drawn uniformly for each no-op; `max_amount` bounds the decoy amounts.
+    pub fn new(
+        rng: R,
+        mean_interval: Duration,
+        recipients: Vec<Pubkey>,
+        max_amount: u64,
+    ) -> Self {
+        assert!(!recipients.is_empty(), "cover traffic needs at least one recipient");
+        Self {
+            rng,
+            mean_interval,
+            recipients,
+            max_amount,
+        }
+    }
+
+    /// Time to wait before sending the next no-op
+    pub fn next_delay(&mut self) -> Duration {
+        // Inverse transform sampling; 1 - u is in (0, 1], so the logarithm is finite
+        let u: f64 = self.rng.gen();
+        self.mean_interval.mul_f64(-(1.0 - u).ln())
+    }
+
+    /// Encrypted arguments of the next no-op transfer
+    pub fn next_transfer(
+        &mut self,
+        encryptor: &impl TransferEncryptor,
+    ) -> Result<EncryptedTransfer, EncryptionError> {
+        let recipient = self.recipients[self.rng.gen_range(0..self.recipients.len())];
+        let amount = self.rng.gen_range(0..=self.max_amount);
+        let nonce = self.rng.gen();
+        encryptor.encrypt_transfer(&recipient, amount, true, nonce)
+    }
+}
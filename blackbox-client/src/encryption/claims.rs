@@ -0,0 +1,100 @@
+//! Balances exported in emergency mode, claimed without the MXE.
+//!
+//! The `export_balances` circuit encrypts each owner's balance and a random salt for the x25519
+//! form of the owner's ed25519 key, so only the owner's keypair opens them, with no key
+//! derivation signature registered beforehand. The x25519 secret is the clamped first half of
+//! `SHA-512(seed)`, the same scalar ed25519 signs with. Next to the ciphertexts the export
+//! stores `SHA-256(owner || balance || salt)`, which `claim_emergency` checks the claimed
+//! balance and salt against.
+//!
+//! Wallets that never export the keypair, such as hardware wallets, can't decrypt a claim
+//! themselves. Their owners re-derive the keypair offline from the wallet's recovery phrase
+//! with [`ClientSecret::from_seed_phrase`], then have the wallet sign `claim_emergency` as
+//! usual.
+
+use sha2::{Digest, Sha512};
+use solana_sdk::{
+    derivation_path::DerivationPath,
+    hash::hashv,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::{
+        keypair::{
+            generate_seed_from_seed_phrase_and_passphrase, keypair_from_seed_and_derivation_path,
+        },
+        Signer,
+    },
+};
+
+use super::{ClientSecret, EncryptionError, RescueCipher};
+use crate::accounts::EmergencyExport;
+
+/// An owner's balance in an `EmergencyExport`, and what `claim_emergency` takes to pay it out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Claim {
+    /// Index of the balance in `EmergencyExport::entries`
+    pub entry: u8,
+    pub balance: u64,
+    pub salt: u128,
+}
+
+impl ClientSecret {
+    /// The x25519 secret of `keypair`'s ed25519 key, which exported balances are encrypted for.
+    /// Unlike [`ClientSecret::from_keypair`] this needs the keypair's seed, not just a signature.
+    pub fn from_owner_keypair(keypair: &Keypair) -> Self {
+        let hash = Sha512::digest(&keypair.to_bytes()[..32]);
+        let mut scalar: [u8; 32] = hash[..32].try_into().unwrap();
+        scalar[0] &= 248;
+        scalar[31] &= 127;
+        scalar[31] |= 64;
+        Self::from_bytes(scalar)
+    }
+
+    /// [`ClientSecret::from_owner_keypair`] of the keypair a wallet derives from its BIP-39
+    /// `seed_phrase` and `passphrase` at `derivation_path`, e.g. `m/44'/501'/0'/0'`. Fails
+    /// unless that keypair is `owner`'s.
+    pub fn from_seed_phrase(
+        seed_phrase: &str,
+        passphrase: &str,
+        derivation_path: &DerivationPath,
+        owner: &Pubkey,
+    ) -> Result<Self, EncryptionError> {
+        let seed = generate_seed_from_seed_phrase_and_passphrase(seed_phrase, passphrase);
+        let keypair = keypair_from_seed_and_derivation_path(&seed, Some(derivation_path.clone()))
+            .map_err(|_| EncryptionError::WrongSeedPhrase)?;
+        if keypair.pubkey() != *owner {
+            return Err(EncryptionError::WrongSeedPhrase);
+        }
+        Ok(Self::from_owner_keypair(&keypair))
+    }
+}
+
+/// Commitment to `owner`'s exported `balance` under `salt`
+pub fn claim_commitment(owner: &Pubkey, balance: u64, salt: u128) -> [u8; 32] {
+    hashv(&[owner.as_ref(), &balance.to_le_bytes(), &salt.to_le_bytes()]).to_bytes()
+}
+
+/// Finds and decrypts `owner`'s balance in `export`. `cipher` is the owner's
+/// [`ClientSecret::from_owner_keypair`] cipher with the MXE. `None` if no entry both decrypts
+/// and opens its commitment, which includes exports the callback hasn't filled in yet.
+pub fn open_claim(
+    cipher: &RescueCipher,
+    owner: &Pubkey,
+    export: &EmergencyExport,
+) -> Option<Claim> {
+    export
+        .entries
+        .iter()
+        .enumerate()
+        .find_map(|(entry, exported)| {
+            let nonce = export.nonce.wrapping_add(entry as u128);
+            let plaintext = cipher.decrypt(&exported.enc_claim, nonce).ok()?;
+            let balance = plaintext[0].to_u64()?;
+            let salt = plaintext[1].to_u128()?;
+            (claim_commitment(owner, balance, salt) == exported.commitment).then_some(Claim {
+                entry: entry as u8,
+                balance,
+                salt,
+            })
+        })
+}
@@ -0,0 +1,47 @@
+//! Encryption keys derived from Solana keypairs, so there's no second secret to back up.
+//!
+//! The owner signs [`KEY_DERIVATION_MESSAGE`] with their ed25519 key, and the x25519 secret is
+//! `SHA3-256(KEY_DERIVATION_DOMAIN || signature)`. Ed25519 signatures are deterministic, so the
+//! same keypair always derives the same key, whether it signs here or in a wallet that can't
+//! export its keys. Both constants are part of the format: changing either changes every
+//! user's key, so a new derivation needs a new version in both.
+
+use sha3::{Digest, Sha3_256};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+};
+
+use super::{ClientSecret, EncryptionError};
+
+/// What the owner signs, shown by wallets as is
+pub const KEY_DERIVATION_MESSAGE: &[u8] = b"blackbox encryption key v1\n\n\
+Signing this message derives the key your blackbox balances are revealed to. \
+Only sign it in software you trust with your balances.";
+
+/// Prefix of the hashed signature, so the secret can't collide with another use of it
+const KEY_DERIVATION_DOMAIN: &[u8] = b"blackbox/x25519/v1";
+
+impl ClientSecret {
+    /// Derives the encryption key of `keypair`'s owner.
+    pub fn from_keypair(keypair: &Keypair) -> Self {
+        Self::from_verified_signature(&keypair.sign_message(KEY_DERIVATION_MESSAGE))
+    }
+
+    /// Derives the encryption key of `owner` from their signature of
+    /// [`KEY_DERIVATION_MESSAGE`], as made by a wallet.
+    pub fn from_signature(owner: &Pubkey, signature: &Signature) -> Result<Self, EncryptionError> {
+        if !signature.verify(owner.as_ref(), KEY_DERIVATION_MESSAGE) {
+            return Err(EncryptionError::InvalidSignature);
+        }
+        Ok(Self::from_verified_signature(signature))
+    }
+
+    fn from_verified_signature(signature: &Signature) -> Self {
+        let mut hasher = Sha3_256::new();
+        hasher.update(KEY_DERIVATION_DOMAIN);
+        hasher.update(signature.as_ref());
+        Self::from_bytes(hasher.finalize().into())
+    }
+}
@@ -0,0 +1,118 @@
+//! The base field of Curve25519, 𝔽ₚ with p = 2²⁵⁵ − 19, which Rescue runs over.
+
+use std::ops::{Add, Mul, Neg, Sub};
+use std::sync::OnceLock;
+
+use num_bigint::BigUint;
+
+fn modulus() -> &'static BigUint {
+    static P: OnceLock<BigUint> = OnceLock::new();
+    P.get_or_init(|| (BigUint::from(1u8) << 255u32) - 19u8)
+}
+
+/// An element of 𝔽ₚ, serialized as its 32-byte little-endian representative
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FieldElement(BigUint);
+
+impl FieldElement {
+    pub fn zero() -> Self {
+        Self(BigUint::default())
+    }
+
+    pub fn one() -> Self {
+        Self(BigUint::from(1u8))
+    }
+
+    /// Reads a little-endian integer, or `None` if it isn't below p.
+    pub fn from_le_bytes(bytes: &[u8; 32]) -> Option<Self> {
+        let value = BigUint::from_bytes_le(bytes);
+        (value < *modulus()).then_some(Self(value))
+    }
+
+    /// Reads a little-endian integer of any length, reduced mod p.
+    pub fn from_le_bytes_mod_order(bytes: &[u8]) -> Self {
+        Self(BigUint::from_bytes_le(bytes) % modulus())
+    }
+
+    pub fn to_le_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        let digits = self.0.to_bytes_le();
+        bytes[..digits.len()].copy_from_slice(&digits);
+        bytes
+    }
+
+    /// The element as a `u64`, if it is one
+    pub fn to_u64(&self) -> Option<u64> {
+        u64::try_from(&self.0).ok()
+    }
+
+    /// The element as a `u128`, if it is one
+    pub fn to_u128(&self) -> Option<u128> {
+        u128::try_from(&self.0).ok()
+    }
+
+    /// `self` raised to `exponent`
+    pub fn pow(&self, exponent: &BigUint) -> Self {
+        Self(self.0.modpow(exponent, modulus()))
+    }
+
+    /// Multiplicative inverse; zero maps to zero.
+    pub fn inverse(&self) -> Self {
+        self.pow(&(modulus() - 2u8))
+    }
+
+    /// p − 1, the order of the multiplicative group
+    pub(crate) fn group_order() -> BigUint {
+        modulus() - 1u8
+    }
+}
+
+impl From<u64> for FieldElement {
+    fn from(value: u64) -> Self {
+        Self(BigUint::from(value))
+    }
+}
+
+impl From<u128> for FieldElement {
+    fn from(value: u128) -> Self {
+        Self(BigUint::from(value))
+    }
+}
+
+impl From<bool> for FieldElement {
+    fn from(value: bool) -> Self {
+        Self::from(value as u64)
+    }
+}
+
+impl Add for &FieldElement {
+    type Output = FieldElement;
+
+    fn add(self, rhs: &FieldElement) -> FieldElement {
+        FieldElement((&self.0 + &rhs.0) % modulus())
+    }
+}
+
+impl Sub for &FieldElement {
+    type Output = FieldElement;
+
+    fn sub(self, rhs: &FieldElement) -> FieldElement {
+        self + &-rhs
+    }
+}
+
+impl Neg for &FieldElement {
+    type Output = FieldElement;
+
+    fn neg(self) -> FieldElement {
+        FieldElement((modulus() - &self.0) % modulus())
+    }
+}
+
+impl Mul for &FieldElement {
+    type Output = FieldElement;
+
+    fn mul(self, rhs: &FieldElement) -> FieldElement {
+        FieldElement((&self.0 * &rhs.0) % modulus())
+    }
+}
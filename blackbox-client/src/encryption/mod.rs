@@ -0,0 +1,195 @@
+//! Client-side encryption for the MXE, compatible with `@arcium-hq/arcium-sdk`.
+//!
+//! A client holds an x25519 secret and agrees one shared secret with each of the MXE's five
+//! x25519 keys, read from its `PersistentMXEAccount`. The five shared secrets, each read as a
+//! little-endian integer mod p, key a [`RescueCipher`]: Rescue over the Curve25519 base field
+//! in counter mode, where every plaintext value is one field element and every ciphertext one
+//! 32-byte block. Instructions carrying ciphertexts (`transfer`, `enqueue_transfer`) take the
+//! client's x25519 public key as `client_key`, with which the circuits agree the same secrets.
+//! A client's secret can be derived from its Solana keypair; see
+//! [`KEY_DERIVATION_MESSAGE`].
+//!
+//! A transfer's recipient, amount and no-op flag form one `TransferArgs` struct, which the
+//! circuits decrypt as a whole, so they are encrypted together at consecutive keystream
+//! positions under the transfer's nonce. A nonce must never be reused for another transfer,
+//! which the program enforces anyway.
+//!
+//! In emergency mode the MXE instead encrypts each balance for the x25519 form of its owner's
+//! ed25519 key, which [`open_claim`] decrypts with [`ClientSecret::from_owner_keypair`], or
+//! [`ClientSecret::from_seed_phrase`] for wallets that don't export their keypair.
+
+use std::fmt;
+
+use anchor_lang::AccountDeserialize;
+use arcium_client::idl::arcium::accounts::PersistentMXEAccount;
+use rand::{CryptoRng, RngCore};
+use solana_sdk::pubkey::Pubkey;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::cover_traffic::TransferEncryptor;
+use crate::instructions::EncryptedTransfer;
+
+pub use claims::{claim_commitment, open_claim, Claim};
+pub use derivation::KEY_DERIVATION_MESSAGE;
+pub use field::FieldElement;
+pub use rescue::STATE_SIZE;
+
+mod claims;
+mod derivation;
+mod field;
+mod rescue;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EncryptionError {
+    /// The value, read as a little-endian integer, isn't below p
+    NotInField,
+    /// The decrypted value doesn't fit the requested type
+    WrongType,
+    /// The account isn't a `PersistentMXEAccount`
+    InvalidMxeAccount,
+    /// The signature isn't the owner's signature of [`KEY_DERIVATION_MESSAGE`]
+    InvalidSignature,
+    /// The seed phrase doesn't derive the owner's keypair at the derivation path
+    WrongSeedPhrase,
+}
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncryptionError::NotInField => write!(f, "value is not a field element"),
+            EncryptionError::WrongType => write!(f, "decrypted value has a different type"),
+            EncryptionError::InvalidMxeAccount => write!(f, "account is not an MXE account"),
+            EncryptionError::InvalidSignature => {
+                write!(f, "signature is not the owner's key derivation signature")
+            }
+            EncryptionError::WrongSeedPhrase => {
+                write!(f, "seed phrase does not derive the owner's keypair")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+/// The MXE's x25519 public keys, one per shared-secret element
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MxePublicKey(pub [[u8; 32]; STATE_SIZE]);
+
+impl MxePublicKey {
+    /// Reads the keys from the data of the program's `PersistentMXEAccount`
+    /// (`ArciumAccounts::program_mxe`).
+    pub fn decode(mut data: &[u8]) -> Result<Self, EncryptionError> {
+        let account = PersistentMXEAccount::try_deserialize(&mut data)
+            .map_err(|_| EncryptionError::InvalidMxeAccount)?;
+        Ok(Self(account.x25519_pubkeys))
+    }
+}
+
+/// A client's x25519 secret, random or derived from a Solana keypair
+#[derive(Clone)]
+pub struct ClientSecret(StaticSecret);
+
+impl ClientSecret {
+    pub fn random(rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        let mut bytes = [0; 32];
+        rng.fill_bytes(&mut bytes);
+        Self::from_bytes(bytes)
+    }
+
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(StaticSecret::from(bytes))
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes()
+    }
+
+    /// The x25519 public key ciphertexts are sent with
+    pub fn public_key(&self) -> [u8; 32] {
+        PublicKey::from(&self.0).to_bytes()
+    }
+
+    /// The cipher shared with the MXE
+    pub fn cipher(&self, mxe: &MxePublicKey) -> RescueCipher {
+        let key = mxe.0.map(|mxe_key| {
+            let shared = self.0.diffie_hellman(&PublicKey::from(mxe_key));
+            FieldElement::from_le_bytes_mod_order(shared.as_bytes())
+        });
+        RescueCipher::new(key)
+    }
+}
+
+/// Rescue in counter mode, keyed with shared secrets
+#[derive(Clone)]
+pub struct RescueCipher(rescue::Rescue);
+
+impl RescueCipher {
+    pub fn new(key: [FieldElement; STATE_SIZE]) -> Self {
+        Self(rescue::Rescue::new(key))
+    }
+
+    /// Encrypts `plaintext` under `nonce`, one 32-byte block per element.
+    pub fn encrypt(&self, plaintext: &[FieldElement], nonce: u128) -> Vec<[u8; 32]> {
+        plaintext
+            .iter()
+            .zip(self.0.keystream(nonce, plaintext.len()))
+            .map(|(value, key)| (value + &key).to_le_bytes())
+            .collect()
+    }
+
+    pub fn decrypt(
+        &self,
+        ciphertext: &[[u8; 32]],
+        nonce: u128,
+    ) -> Result<Vec<FieldElement>, EncryptionError> {
+        ciphertext
+            .iter()
+            .zip(self.0.keystream(nonce, ciphertext.len()))
+            .map(|(block, key)| {
+                let value =
+                    FieldElement::from_le_bytes(block).ok_or(EncryptionError::NotInField)?;
+                Ok(&value - &key)
+            })
+            .collect()
+    }
+
+    /// Encrypts a `PublicKey` argument; fails for keys that aren't below p.
+    pub fn encrypt_pubkey(&self, key: &Pubkey, nonce: u128) -> Result<[u8; 32], EncryptionError> {
+        let value =
+            FieldElement::from_le_bytes(&key.to_bytes()).ok_or(EncryptionError::NotInField)?;
+        Ok(self.encrypt(&[value], nonce)[0])
+    }
+
+    pub fn encrypt_u64(&self, value: u64, nonce: u128) -> [u8; 32] {
+        self.encrypt(&[value.into()], nonce)[0]
+    }
+
+    pub fn encrypt_bool(&self, value: bool, nonce: u128) -> [u8; 32] {
+        self.encrypt(&[value.into()], nonce)[0]
+    }
+
+    /// Decrypts a `u64` result, such as a revealed balance.
+    pub fn decrypt_u64(&self, ciphertext: &[u8; 32], nonce: u128) -> Result<u64, EncryptionError> {
+        self.decrypt(&[*ciphertext], nonce)?[0]
+            .to_u64()
+            .ok_or(EncryptionError::WrongType)
+    }
+}
+
+impl TransferEncryptor for RescueCipher {
+    fn encrypt_transfer(
+        &self,
+        recipient: &Pubkey,
+        amount: u64,
+        noop: bool,
+        nonce: u128,
+    ) -> Result<EncryptedTransfer, EncryptionError> {
+        let recipient = FieldElement::from_le_bytes(&recipient.to_bytes())
+            .ok_or(EncryptionError::NotInField)?;
+        let enc_transfer = self.encrypt(&[recipient, amount.into(), noop.into()], nonce);
+        Ok(EncryptedTransfer {
+            enc_transfer: [enc_transfer[0], enc_transfer[1], enc_transfer[2]],
+            nonce,
+        })
+    }
+}
@@ -0,0 +1,183 @@
+//! The Rescue block cipher over 𝔽ₚ, used in counter mode.
+//!
+//! The state holds `STATE_SIZE` field elements, as many as there are key elements. A round
+//! raises every element to α = 5, multiplies by the MDS matrix and adds a round key, then does
+//! the same with the inverse power 1/α. The round keys come from running the same rounds over
+//! the key, with round constants in place of round keys. Round constants and the MDS matrix
+//! are derived as in the Rescue-Prime reference implementation: constants are squeezed from
+//! SHAKE256, and the MDS matrix is the systematic form of a Vandermonde matrix in the
+//! primitive element 2.
+
+use std::sync::OnceLock;
+
+use num_bigint::BigUint;
+use sha3::{
+    digest::{ExtendableOutput, Update, XofReader},
+    Shake256,
+};
+
+use super::field::FieldElement;
+
+/// Elements in the cipher's state and key
+pub const STATE_SIZE: usize = 5;
+
+/// S-box exponent: the smallest prime not dividing p − 1
+const ALPHA: u32 = 5;
+
+/// Rounds for 128-bit security with a five-element state
+const ROUNDS: usize = 10;
+
+/// Seed of the SHAKE256 stream the round constants are read from
+const CONSTANTS_SEED: &[u8] = b"encrypt everything, compute anything";
+
+/// Bytes read per round constant: one more than a field element needs, to make the bias of
+/// reducing mod p negligible
+const BYTES_PER_CONSTANT: usize = 33;
+
+type State = [FieldElement; STATE_SIZE];
+type Matrix = [[FieldElement; STATE_SIZE]; STATE_SIZE];
+
+/// 1/α, the inverse of α mod p − 1
+fn alpha_inverse() -> &'static BigUint {
+    static ALPHA_INVERSE: OnceLock<BigUint> = OnceLock::new();
+    ALPHA_INVERSE.get_or_init(|| {
+        // α·(1/α) = 1 + k(p − 1) for the k in 1..α that makes the left side divisible by α
+        let order = FieldElement::group_order();
+        (1..ALPHA)
+            .map(|k| &order * k + 1u8)
+            .find(|multiple| (multiple % ALPHA).bits() == 0)
+            .map(|multiple| multiple / ALPHA)
+            .expect("α is coprime to p − 1")
+    })
+}
+
+fn mds() -> &'static Matrix {
+    static MDS: OnceLock<Matrix> = OnceLock::new();
+    MDS.get_or_init(|| {
+        // Vandermonde matrix V[i][j] = g^(i·j) with m rows and 2m columns
+        let g = FieldElement::from(2u64);
+        let mut v: Vec<Vec<FieldElement>> = (0..STATE_SIZE)
+            .map(|i| {
+                (0..2 * STATE_SIZE)
+                    .map(|j| g.pow(&BigUint::from(i * j)))
+                    .collect()
+            })
+            .collect();
+
+        // Reduce it to [I | A]; the MDS matrix is Aᵀ
+        for col in 0..STATE_SIZE {
+            let pivot = (col..STATE_SIZE)
+                .find(|&row| v[row][col] != FieldElement::zero())
+                .expect("Vandermonde matrix has full rank");
+            v.swap(col, pivot);
+            let scale = v[col][col].inverse();
+            v[col] = v[col].iter().map(|x| x * &scale).collect();
+            for row in 0..STATE_SIZE {
+                if row != col && v[row][col] != FieldElement::zero() {
+                    let factor = v[row][col].clone();
+                    v[row] = (0..2 * STATE_SIZE)
+                        .map(|j| &v[row][j] - &(&factor * &v[col][j]))
+                        .collect();
+                }
+            }
+        }
+
+        std::array::from_fn(|i| std::array::from_fn(|j| v[j][STATE_SIZE + i].clone()))
+    })
+}
+
+fn round_constants() -> &'static [State] {
+    static CONSTANTS: OnceLock<Vec<State>> = OnceLock::new();
+    CONSTANTS.get_or_init(|| {
+        let mut shake = Shake256::default();
+        shake.update(CONSTANTS_SEED);
+        let mut reader = shake.finalize_xof();
+        (0..2 * ROUNDS + 1)
+            .map(|_| {
+                std::array::from_fn(|_| {
+                    let mut bytes = [0; BYTES_PER_CONSTANT];
+                    reader.read(&mut bytes);
+                    FieldElement::from_le_bytes_mod_order(&bytes)
+                })
+            })
+            .collect()
+    })
+}
+
+fn add(state: &State, other: &State) -> State {
+    std::array::from_fn(|i| &state[i] + &other[i])
+}
+
+fn power(state: &State, exponent: &BigUint) -> State {
+    std::array::from_fn(|i| state[i].pow(exponent))
+}
+
+fn multiply(matrix: &Matrix, state: &State) -> State {
+    std::array::from_fn(|i| {
+        (0..STATE_SIZE).fold(FieldElement::zero(), |sum, j| {
+            &sum + &(&matrix[i][j] * &state[j])
+        })
+    })
+}
+
+/// Runs every round over `state`, adding `keys[0]` first and `keys[k]` after half-round `k`.
+/// With `collect`, returns the state after each key addition.
+fn rounds(state: &State, keys: &[State], collect: bool) -> Vec<State> {
+    let alpha = BigUint::from(ALPHA);
+    let mut states = Vec::new();
+    let mut state = add(state, &keys[0]);
+    if collect {
+        states.push(state.clone());
+    }
+    for half_round in 0..2 * ROUNDS {
+        let exponent = if half_round % 2 == 0 {
+            &alpha
+        } else {
+            alpha_inverse()
+        };
+        state = add(
+            &multiply(mds(), &power(&state, exponent)),
+            &keys[half_round + 1],
+        );
+        if collect {
+            states.push(state.clone());
+        }
+    }
+    if !collect {
+        states.push(state);
+    }
+    states
+}
+
+/// Rescue keyed with `STATE_SIZE` field elements
+#[derive(Clone)]
+pub struct Rescue {
+    round_keys: Vec<State>,
+}
+
+impl Rescue {
+    pub fn new(key: State) -> Self {
+        Self {
+            round_keys: rounds(&key, round_constants(), true),
+        }
+    }
+
+    /// Encrypts one block.
+    pub fn permute(&self, block: &State) -> State {
+        rounds(block, &self.round_keys, false).pop().unwrap()
+    }
+
+    /// Counter-mode keystream for `len` elements: block `i` is the encryption of
+    /// `[nonce, i, 0, 0, 0]`.
+    pub fn keystream(&self, nonce: u128, len: usize) -> Vec<FieldElement> {
+        (0..len.div_ceil(STATE_SIZE))
+            .flat_map(|block| {
+                let mut counter: State = std::array::from_fn(|_| FieldElement::zero());
+                counter[0] = FieldElement::from(nonce);
+                counter[1] = FieldElement::from(block as u64);
+                self.permute(&counter)
+            })
+            .take(len)
+            .collect()
+    }
+}
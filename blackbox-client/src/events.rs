@@ -0,0 +1,469 @@
+//! Decoders for the events the blackbox program emits.
+//!
+//! Anchor's `emit!` logs each event as `Program data: <base64>`, the base64 of the event's
+//! discriminator followed by its Borsh-encoded fields. [`from_logs`] attributes those lines
+//! to the program that was executing when they were logged, so another program can't forge
+//! blackbox events in the same transaction.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::accounts::{
+    DecodeError, Fees, InitBlackboxParams, Limits, OpKind, OpStatus, ParameterChange, PauseFlags,
+    Reader,
+};
+use crate::{event_discriminator, PROGRAM_ID};
+
+/// Circuit a computation was queued for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComputationKind {
+    Deposit,
+    Transfer,
+    Withdraw,
+    WithdrawAll,
+    RevealBalance,
+    ProcessBatch,
+    MigrateMapping,
+}
+
+impl ComputationKind {
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        match r.u8()? {
+            0 => Ok(ComputationKind::Deposit),
+            1 => Ok(ComputationKind::Transfer),
+            2 => Ok(ComputationKind::Withdraw),
+            3 => Ok(ComputationKind::WithdrawAll),
+            4 => Ok(ComputationKind::RevealBalance),
+            5 => Ok(ComputationKind::ProcessBatch),
+            6 => Ok(ComputationKind::MigrateMapping),
+            variant => Err(DecodeError::InvalidVariant(variant)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    ProgramConfigInitialized {
+        authority: Pubkey,
+    },
+    /// `proposed` may take over the program config by accepting; `None` withdraws a proposal
+    AuthorityProposed {
+        authority: Pubkey,
+        proposed: Option<Pubkey>,
+    },
+    AuthorityChanged {
+        previous: Pubkey,
+        authority: Pubkey,
+    },
+    /// A parameter change to `blackbox`, or to the program config if `None`, that anyone can
+    /// execute from `executable_slot`
+    ChangeQueued {
+        id: u64,
+        blackbox: Option<Pubkey>,
+        change: ParameterChange,
+        executable_slot: u64,
+    },
+    /// A queued change was applied; the event for the parameter it set is emitted alongside
+    ChangeExecuted {
+        id: u64,
+    },
+    ChangeCancelled {
+        id: u64,
+    },
+    TimelockUpdated {
+        timelock_slots: u64,
+    },
+    /// New pause flags of `blackbox`, or of every blackbox if `None`
+    PauseUpdated {
+        blackbox: Option<Pubkey>,
+        paused: PauseFlags,
+    },
+    MaxFeeUpdated {
+        max_fee_bps: u16,
+    },
+    MaxShardCapacityUpdated {
+        max_shard_capacity: u16,
+    },
+    FeesUpdated {
+        blackbox: Pubkey,
+        fees: Fees,
+    },
+    LimitsUpdated {
+        blackbox: Pubkey,
+        limits: Limits,
+    },
+    /// A fee on a deposit or withdrawal paid into the blackbox's treasury
+    FeeCharged {
+        blackbox: Pubkey,
+        kind: OpKind,
+        amount: u64,
+    },
+    FeesCollected {
+        blackbox: Pubkey,
+        destination: Pubkey,
+        amount: u64,
+    },
+    BlackboxInitialized {
+        blackbox: Pubkey,
+        token_mint: Pubkey,
+        vault: Pubkey,
+        treasury: Pubkey,
+        params: InitBlackboxParams,
+    },
+    MappingAccountInitialized {
+        blackbox: Pubkey,
+        mapping_account: Pubkey,
+        index: u8,
+    },
+    OpQueueInitialized {
+        blackbox: Pubkey,
+        mapping_account: Pubkey,
+    },
+    ComputationQueued {
+        blackbox: Pubkey,
+        mapping_account: Pubkey,
+        computation_offset: u64,
+        kind: ComputationKind,
+        /// User the computation acts for; whoever flushed the queue for batches
+        owner: Pubkey,
+        /// Plaintext amount of deposits and withdrawals
+        amount: Option<u64>,
+    },
+    /// `Requeued` appends the computation's operation to the back of the mapping account's
+    /// queue, except for batches, which go back to the front.
+    ComputationResolved {
+        blackbox: Pubkey,
+        mapping_account: Pubkey,
+        computation_offset: u64,
+        status: OpStatus,
+    },
+    OperationEnqueued {
+        blackbox: Pubkey,
+        mapping_account: Pubkey,
+        kind: OpKind,
+        owner: Pubkey,
+        /// Plaintext amount of deposits and withdrawals
+        amount: Option<u64>,
+    },
+    /// The first `operations` pending operations went in flight as batch
+    /// `computation_offset`.
+    OpQueueFlushed {
+        blackbox: Pubkey,
+        mapping_account: Pubkey,
+        computation_offset: u64,
+        operations: u8,
+    },
+    /// A solvency proof of `blackbox` over its first `shards` mapping accounts was queued
+    SolvencyRequested {
+        blackbox: Pubkey,
+        attestation: Pubkey,
+        computation_offset: u64,
+        vault_amount: u64,
+        shards: u8,
+    },
+    SolvencyAttested {
+        blackbox: Pubkey,
+        attestation: Pubkey,
+        computation_offset: u64,
+        solvent: bool,
+        attested_slot: u64,
+    },
+    /// `blackbox` stopped every operation for good; owners claim their exported balances
+    EmergencyEntered {
+        blackbox: Pubkey,
+    },
+    /// Page `page` of `mapping_account` was exported, with `entries` balances
+    BalancesExported {
+        blackbox: Pubkey,
+        mapping_account: Pubkey,
+        export: Pubkey,
+        page: u8,
+        entries: u8,
+        more: bool,
+    },
+    EmergencyClaimed {
+        blackbox: Pubkey,
+        mapping_account: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+    },
+    /// A queued deposit was paid back to `destination`, its source: emergency mode left it in
+    /// the operation queue, or the mapping account had no room for its owner
+    QueuedDepositRefunded {
+        blackbox: Pubkey,
+        mapping_account: Pubkey,
+        owner: Pubkey,
+        destination: Pubkey,
+        amount: u64,
+    },
+    /// `blackbox`'s new mapping accounts start on `circuit_version`, and existing ones are
+    /// migrated to it
+    CircuitVersionUpdated {
+        blackbox: Pubkey,
+        circuit_version: u8,
+    },
+    MappingMigrated {
+        blackbox: Pubkey,
+        mapping_account: Pubkey,
+        circuit_version: u8,
+    },
+    VaultDeposited {
+        blackbox: Pubkey,
+        source: Pubkey,
+        amount: u64,
+    },
+    VaultPaidOut {
+        blackbox: Pubkey,
+        destination: Pubkey,
+        amount: u64,
+    },
+}
+
+impl Event {
+    /// Decodes an event's data, or returns `None` if it isn't a blackbox event this version
+    /// knows.
+    pub fn decode(data: &[u8]) -> Result<Option<Self>, DecodeError> {
+        let Some(discriminator) = data.first_chunk::<8>() else {
+            return Err(DecodeError::TooShort);
+        };
+        let Some(name) = Self::NAMES
+            .into_iter()
+            .find(|name| event_discriminator(name) == *discriminator)
+        else {
+            return Ok(None);
+        };
+
+        let mut r = Reader::after_discriminator(data, *discriminator)?;
+        let event = match name {
+            "ProgramConfigInitialized" => Event::ProgramConfigInitialized {
+                authority: r.pubkey()?,
+            },
+            "AuthorityProposed" => Event::AuthorityProposed {
+                authority: r.pubkey()?,
+                proposed: r.option(Reader::pubkey)?,
+            },
+            "AuthorityChanged" => Event::AuthorityChanged {
+                previous: r.pubkey()?,
+                authority: r.pubkey()?,
+            },
+            "ChangeQueued" => Event::ChangeQueued {
+                id: r.u64()?,
+                blackbox: r.option(Reader::pubkey)?,
+                change: ParameterChange::read(&mut r)?,
+                executable_slot: r.u64()?,
+            },
+            "ChangeExecuted" => Event::ChangeExecuted { id: r.u64()? },
+            "ChangeCancelled" => Event::ChangeCancelled { id: r.u64()? },
+            "TimelockUpdated" => Event::TimelockUpdated {
+                timelock_slots: r.u64()?,
+            },
+            "PauseUpdated" => Event::PauseUpdated {
+                blackbox: r.option(Reader::pubkey)?,
+                paused: PauseFlags::read(&mut r)?,
+            },
+            "MaxFeeUpdated" => Event::MaxFeeUpdated {
+                max_fee_bps: r.u16()?,
+            },
+            "MaxShardCapacityUpdated" => Event::MaxShardCapacityUpdated {
+                max_shard_capacity: r.u16()?,
+            },
+            "FeesUpdated" => Event::FeesUpdated {
+                blackbox: r.pubkey()?,
+                fees: Fees::read(&mut r)?,
+            },
+            "LimitsUpdated" => Event::LimitsUpdated {
+                blackbox: r.pubkey()?,
+                limits: Limits::read(&mut r)?,
+            },
+            "FeeCharged" => Event::FeeCharged {
+                blackbox: r.pubkey()?,
+                kind: OpKind::read(&mut r)?,
+                amount: r.u64()?,
+            },
+            "FeesCollected" => Event::FeesCollected {
+                blackbox: r.pubkey()?,
+                destination: r.pubkey()?,
+                amount: r.u64()?,
+            },
+            "BlackboxInitialized" => Event::BlackboxInitialized {
+                blackbox: r.pubkey()?,
+                token_mint: r.pubkey()?,
+                vault: r.pubkey()?,
+                treasury: r.pubkey()?,
+                params: InitBlackboxParams::read(&mut r)?,
+            },
+            "MappingAccountInitialized" => Event::MappingAccountInitialized {
+                blackbox: r.pubkey()?,
+                mapping_account: r.pubkey()?,
+                index: r.u8()?,
+            },
+            "OpQueueInitialized" => Event::OpQueueInitialized {
+                blackbox: r.pubkey()?,
+                mapping_account: r.pubkey()?,
+            },
+            "ComputationQueued" => Event::ComputationQueued {
+                blackbox: r.pubkey()?,
+                mapping_account: r.pubkey()?,
+                computation_offset: r.u64()?,
+                kind: ComputationKind::read(&mut r)?,
+                owner: r.pubkey()?,
+                amount: r.option(Reader::u64)?,
+            },
+            "ComputationResolved" => Event::ComputationResolved {
+                blackbox: r.pubkey()?,
+                mapping_account: r.pubkey()?,
+                computation_offset: r.u64()?,
+                status: OpStatus::read(&mut r)?,
+            },
+            "OperationEnqueued" => Event::OperationEnqueued {
+                blackbox: r.pubkey()?,
+                mapping_account: r.pubkey()?,
+                kind: OpKind::read(&mut r)?,
+                owner: r.pubkey()?,
+                amount: r.option(Reader::u64)?,
+            },
+            "OpQueueFlushed" => Event::OpQueueFlushed {
+                blackbox: r.pubkey()?,
+                mapping_account: r.pubkey()?,
+                computation_offset: r.u64()?,
+                operations: r.u8()?,
+            },
+            "SolvencyRequested" => Event::SolvencyRequested {
+                blackbox: r.pubkey()?,
+                attestation: r.pubkey()?,
+                computation_offset: r.u64()?,
+                vault_amount: r.u64()?,
+                shards: r.u8()?,
+            },
+            "SolvencyAttested" => Event::SolvencyAttested {
+                blackbox: r.pubkey()?,
+                attestation: r.pubkey()?,
+                computation_offset: r.u64()?,
+                solvent: r.bool()?,
+                attested_slot: r.u64()?,
+            },
+            "EmergencyEntered" => Event::EmergencyEntered {
+                blackbox: r.pubkey()?,
+            },
+            "BalancesExported" => Event::BalancesExported {
+                blackbox: r.pubkey()?,
+                mapping_account: r.pubkey()?,
+                export: r.pubkey()?,
+                page: r.u8()?,
+                entries: r.u8()?,
+                more: r.bool()?,
+            },
+            "EmergencyClaimed" => Event::EmergencyClaimed {
+                blackbox: r.pubkey()?,
+                mapping_account: r.pubkey()?,
+                owner: r.pubkey()?,
+                amount: r.u64()?,
+            },
+            "QueuedDepositRefunded" => Event::QueuedDepositRefunded {
+                blackbox: r.pubkey()?,
+                mapping_account: r.pubkey()?,
+                owner: r.pubkey()?,
+                destination: r.pubkey()?,
+                amount: r.u64()?,
+            },
+            "CircuitVersionUpdated" => Event::CircuitVersionUpdated {
+                blackbox: r.pubkey()?,
+                circuit_version: r.u8()?,
+            },
+            "MappingMigrated" => Event::MappingMigrated {
+                blackbox: r.pubkey()?,
+                mapping_account: r.pubkey()?,
+                circuit_version: r.u8()?,
+            },
+            "VaultDeposited" => Event::VaultDeposited {
+                blackbox: r.pubkey()?,
+                source: r.pubkey()?,
+                amount: r.u64()?,
+            },
+            "VaultPaidOut" => Event::VaultPaidOut {
+                blackbox: r.pubkey()?,
+                destination: r.pubkey()?,
+                amount: r.u64()?,
+            },
+            _ => unreachable!("every name in NAMES is decoded"),
+        };
+        Ok(Some(event))
+    }
+
+    const NAMES: [&'static str; 31] = [
+        "ProgramConfigInitialized",
+        "AuthorityProposed",
+        "AuthorityChanged",
+        "ChangeQueued",
+        "ChangeExecuted",
+        "ChangeCancelled",
+        "TimelockUpdated",
+        "PauseUpdated",
+        "MaxFeeUpdated",
+        "MaxShardCapacityUpdated",
+        "FeesUpdated",
+        "LimitsUpdated",
+        "FeeCharged",
+        "FeesCollected",
+        "BlackboxInitialized",
+        "MappingAccountInitialized",
+        "OpQueueInitialized",
+        "ComputationQueued",
+        "ComputationResolved",
+        "OperationEnqueued",
+        "OpQueueFlushed",
+        "SolvencyRequested",
+        "SolvencyAttested",
+        "EmergencyEntered",
+        "BalancesExported",
+        "EmergencyClaimed",
+        "QueuedDepositRefunded",
+        "CircuitVersionUpdated",
+        "MappingMigrated",
+        "VaultDeposited",
+        "VaultPaidOut",
+    ];
+}
+
+/// Events found in a transaction's logs
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoggedEvents {
+    pub events: Vec<Event>,
+    /// The runtime cut the logs short, so later events may be missing
+    pub truncated: bool,
+}
+
+/// Collects the blackbox events in a transaction's log messages, in the order they were
+/// emitted. Data logged by other programs, and events this version doesn't know, are skipped.
+pub fn from_logs<S: AsRef<str>>(logs: &[S]) -> Result<LoggedEvents, DecodeError> {
+    let program_id = PROGRAM_ID.to_string();
+    let mut stack: Vec<&str> = Vec::new();
+    let mut found = LoggedEvents::default();
+
+    for line in logs.iter().map(AsRef::as_ref) {
+        if line == "Log truncated" {
+            found.truncated = true;
+        } else if let Some(rest) = line.strip_prefix("Program data: ") {
+            if stack.last() != Some(&program_id.as_str()) {
+                continue;
+            }
+            for field in rest.split(' ') {
+                let data = STANDARD
+                    .decode(field)
+                    .map_err(|_| DecodeError::InvalidEncoding)?;
+                found.events.extend(Event::decode(&data)?);
+            }
+        } else if let Some(rest) = line.strip_prefix("Program ") {
+            let mut words = rest.split(' ');
+            match (words.next(), words.next()) {
+                (Some(program), Some("invoke")) => stack.push(program),
+                (Some(_), Some("success" | "failed:")) => {
+                    stack.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(found)
+}
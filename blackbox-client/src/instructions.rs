@@ -0,0 +1,834 @@
+//! Builders for every blackbox instruction a client sends.
+//!
+//! Callbacks are left out: the Arcium program invokes those. Account lists follow the
+//! program's `Accounts` structs exactly, and instruction data is the Anchor discriminator
+//! followed by the Borsh-encoded arguments.
+
+use solana_sdk::{
+    bpf_loader_upgradeable,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+use crate::accounts::{
+    EmergencyExport, InitBlackboxParams, ParameterChange, PauseFlags, PendingChange,
+};
+use crate::{
+    instruction_discriminator, pda, ArciumAccounts, Circuit, MAX_SOLVENCY_SHARDS, PROGRAM_ID,
+};
+
+/// Transfer arguments encrypted for the MXE under `nonce`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncryptedTransfer {
+    /// Encrypted `TransferArgs`: recipient, amount and cover-traffic flag. A transfer whose
+    /// flag decrypts to `true` changes no balance.
+    pub enc_transfer: [[u8; 32]; 3],
+    pub nonce: u128,
+}
+
+/// Where a queued computation runs and who pays for it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Computation {
+    pub payer: Pubkey,
+    pub mapping_account: Pubkey,
+    /// Identifies the computation; must be unique per blackbox
+    pub computation_offset: u64,
+    /// Pass the mapping account's operation queue, so a stale result is re-queued by the
+    /// callback instead of dropped. The queue must exist.
+    pub use_op_queue: bool,
+    /// The mapping account's `circuit_version`, which picks the circuits that write the
+    /// mapping back
+    pub circuit_version: u8,
+}
+
+/// Creates the program config; `authority` must be the program's upgrade authority.
+pub fn init_program_config(authority: &Pubkey) -> Instruction {
+    instruction(
+        "init_program_config",
+        vec![
+            AccountMeta::new(pda::program_config(), false),
+            AccountMeta::new_readonly(
+                bpf_loader_upgradeable::get_program_data_address(&PROGRAM_ID),
+                false,
+            ),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        &[],
+    )
+}
+
+/// Pauses the given operations on `blackbox`, or on every blackbox if it's `None`, on top of
+/// those already paused.
+pub fn pause(authority: &Pubkey, blackbox: Option<&Pubkey>, paused: PauseFlags) -> Instruction {
+    instruction(
+        "pause",
+        vec![
+            AccountMeta::new(pda::program_config(), false),
+            optional_account(blackbox, true),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        &[&paused.encode()],
+    )
+}
+
+/// Proposes `new_authority` as the program authority, or withdraws the proposal if `None`.
+pub fn propose_authority(authority: &Pubkey, new_authority: Option<&Pubkey>) -> Instruction {
+    let new_authority = match new_authority {
+        Some(new_authority) => [&[1][..], new_authority.as_ref()].concat(),
+        None => vec![0],
+    };
+    instruction(
+        "propose_authority",
+        vec![
+            AccountMeta::new(pda::program_config(), false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        &[&new_authority],
+    )
+}
+
+/// Takes over the program config; `new_authority` must be the proposed authority.
+pub fn accept_authority(new_authority: &Pubkey) -> Instruction {
+    instruction(
+        "accept_authority",
+        vec![
+            AccountMeta::new(pda::program_config(), false),
+            AccountMeta::new_readonly(*new_authority, true),
+        ],
+        &[],
+    )
+}
+
+/// Queues `change` to `blackbox`, or to the program config if it's `None`, as change `id`,
+/// which must be the config's `next_change_id`. `payer` gets the rent back once it's executed
+/// or cancelled.
+pub fn queue_change(
+    payer: &Pubkey,
+    authority: &Pubkey,
+    id: u64,
+    blackbox: Option<&Pubkey>,
+    change: ParameterChange,
+) -> Instruction {
+    instruction(
+        "queue_change",
+        vec![
+            AccountMeta::new(pda::program_config(), false),
+            optional_account(blackbox, false),
+            AccountMeta::new(pda::pending_change(id), false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        &[&change.encode()],
+    )
+}
+
+/// Applies `change` once its timelock has passed; anyone can send it.
+pub fn execute_change(change: &PendingChange) -> Instruction {
+    instruction(
+        "execute_change",
+        vec![
+            AccountMeta::new(pda::program_config(), false),
+            AccountMeta::new(pda::pending_change(change.id), false),
+            optional_account(change.blackbox.as_ref(), true),
+            AccountMeta::new(change.payer, false),
+        ],
+        &[],
+    )
+}
+
+/// Drops a queued change.
+pub fn cancel_change(authority: &Pubkey, change: &PendingChange) -> Instruction {
+    instruction(
+        "cancel_change",
+        vec![
+            AccountMeta::new_readonly(pda::program_config(), false),
+            AccountMeta::new(pda::pending_change(change.id), false),
+            AccountMeta::new(change.payer, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        &[],
+    )
+}
+
+/// Closes `sender`'s marker of the consumed `nonce`, returning its rent to `payer`, who paid
+/// it. The nonce can be submitted again afterwards, so it must not be reused for new ciphertexts.
+pub fn close_consumed_nonce(sender: &Pubkey, payer: &Pubkey, nonce: u128) -> Instruction {
+    instruction(
+        "close_consumed_nonce",
+        vec![
+            AccountMeta::new(pda::consumed_nonce(sender, nonce), false),
+            AccountMeta::new_readonly(*sender, true),
+            AccountMeta::new(*payer, false),
+        ],
+        &[&nonce.to_le_bytes()],
+    )
+}
+
+/// A blackbox and the accounts its instructions need
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Blackbox {
+    pub token_mint: Pubkey,
+    pub address: Pubkey,
+    pub vault: Pubkey,
+    pub treasury: Pubkey,
+    pub arcium: ArciumAccounts,
+}
+
+impl Blackbox {
+    /// The blackbox of `token_mint`, queuing its computations to Arcium cluster
+    /// `cluster_offset`, the `BlackboxAccount::cluster_offset` it was created with
+    pub fn new(token_mint: Pubkey, cluster_offset: u32) -> Self {
+        let address = pda::blackbox(&token_mint);
+        Self {
+            token_mint,
+            address,
+            vault: pda::vault(&address),
+            treasury: pda::treasury(&address),
+            arcium: ArciumAccounts::resolve(cluster_offset),
+        }
+    }
+
+    /// Mapping account number `index`
+    pub fn mapping_account(&self, index: u8) -> Pubkey {
+        pda::mapping_account(&self.token_mint, index)
+    }
+
+    /// Creates the blackbox with `params`; `authority` must be the program config's, and
+    /// `params.cluster_offset` an existing Arcium cluster.
+    pub fn init_blackbox(
+        &self,
+        authority: &Pubkey,
+        payer: &Pubkey,
+        params: &InitBlackboxParams,
+    ) -> Instruction {
+        let cluster = ArciumAccounts::resolve(params.cluster_offset).cluster;
+        instruction(
+            "init_blackbox",
+            vec![
+                AccountMeta::new_readonly(pda::program_config(), false),
+                AccountMeta::new_readonly(self.token_mint, false),
+                AccountMeta::new(self.address, false),
+                AccountMeta::new(self.vault, false),
+                AccountMeta::new(self.treasury, false),
+                AccountMeta::new_readonly(cluster, false),
+                AccountMeta::new_readonly(*authority, true),
+                AccountMeta::new(*payer, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+            ],
+            &[&params.encode()],
+        )
+    }
+
+    /// Moves every fee collected in the treasury to `destination`.
+    pub fn collect_fees(&self, authority: &Pubkey, destination: &Pubkey) -> Instruction {
+        instruction(
+            "collect_fees",
+            vec![
+                AccountMeta::new_readonly(pda::program_config(), false),
+                AccountMeta::new_readonly(self.address, false),
+                AccountMeta::new(self.treasury, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(*authority, true),
+                AccountMeta::new_readonly(spl_token::ID, false),
+            ],
+            &[],
+        )
+    }
+
+    /// Initializes mapping account number `index`, which must be the blackbox's
+    /// `mapping_account_count`.
+    pub fn initialize_mapping_account(&self, payer: &Pubkey, index: u8) -> Instruction {
+        instruction(
+            "initialize_mapping_account",
+            vec![
+                AccountMeta::new(self.address, false),
+                AccountMeta::new_readonly(pda::program_config(), false),
+                AccountMeta::new(self.mapping_account(index), false),
+                AccountMeta::new(*payer, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            &[],
+        )
+    }
+
+    pub fn init_comp_def(&self, payer: &Pubkey, circuit: Circuit) -> Instruction {
+        instruction(
+            &circuit.init_comp_def_instruction(),
+            vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new(self.arcium.program_mxe, false),
+                AccountMeta::new(circuit.comp_def(), false),
+                AccountMeta::new_readonly(self.arcium.program, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            &[],
+        )
+    }
+
+    pub fn init_op_queue(&self, payer: &Pubkey, mapping_account: &Pubkey) -> Instruction {
+        instruction(
+            "init_op_queue",
+            vec![
+                AccountMeta::new_readonly(self.address, false),
+                AccountMeta::new_readonly(*mapping_account, false),
+                AccountMeta::new(pda::op_queue(mapping_account), false),
+                AccountMeta::new(*payer, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            &[],
+        )
+    }
+
+    /// Deposits `amount` from `user_token_account`. `approver` co-signs when the blackbox has a
+    /// deposit approver.
+    pub fn deposit(
+        &self,
+        computation: &Computation,
+        user: &Pubkey,
+        approver: Option<&Pubkey>,
+        user_token_account: &Pubkey,
+        amount: u64,
+    ) -> Instruction {
+        let mut accounts = self.computation_head(computation);
+        accounts.extend([
+            AccountMeta::new(*user_token_account, false),
+            AccountMeta::new(self.vault, false),
+            AccountMeta::new(self.treasury, false),
+            AccountMeta::new_readonly(*user, true),
+            optional_signer(approver),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+        ]);
+        let circuit = Circuit::Deposit.for_version(computation.circuit_version);
+        accounts.extend(self.queue_accounts(circuit, &computation.payer));
+        instruction(
+            "deposit",
+            accounts,
+            &[&computation.computation_offset.to_le_bytes(), &amount.to_le_bytes()],
+        )
+    }
+
+    /// Transfers within the blackbox. `transfer` must be encrypted with the cipher `sender`'s
+    /// client agreed with the MXE for its x25519 key `client_key`.
+    pub fn transfer(
+        &self,
+        computation: &Computation,
+        sender: &Pubkey,
+        transfer: &EncryptedTransfer,
+        client_key: &[u8; 32],
+    ) -> Instruction {
+        let mut accounts = self.computation_head(computation);
+        accounts.extend([
+            AccountMeta::new(pda::consumed_nonce(sender, transfer.nonce), false),
+            AccountMeta::new_readonly(*sender, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ]);
+        let circuit = Circuit::Transfer.for_version(computation.circuit_version);
+        accounts.extend(self.queue_accounts(circuit, &computation.payer));
+        instruction(
+            "transfer",
+            accounts,
+            &[
+                &computation.computation_offset.to_le_bytes(),
+                transfer.enc_transfer.as_flattened(),
+                client_key,
+                &transfer.nonce.to_le_bytes(),
+            ],
+        )
+    }
+
+    /// Withdraws `amount` to `destination` if `user` owns that much.
+    pub fn withdraw(
+        &self,
+        computation: &Computation,
+        user: &Pubkey,
+        destination: &Pubkey,
+        amount: u64,
+    ) -> Instruction {
+        let mut accounts = self.computation_head(computation);
+        // The blackbox counts the withdrawal towards its epoch's outflow
+        accounts[0].is_writable = true;
+        accounts.extend([
+            AccountMeta::new(self.vault, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+        ]);
+        let circuit = Circuit::Withdraw.for_version(computation.circuit_version);
+        accounts.extend(self.queue_accounts(circuit, &computation.payer));
+        instruction(
+            "withdraw",
+            accounts,
+            &[&computation.computation_offset.to_le_bytes(), &amount.to_le_bytes()],
+        )
+    }
+
+    /// Withdraws `user`'s whole balance to `destination`. Never uses the operation queue.
+    pub fn withdraw_all(
+        &self,
+        computation: &Computation,
+        user: &Pubkey,
+        destination: &Pubkey,
+    ) -> Instruction {
+        // The blackbox counts the payout towards its epoch's outflow
+        let mut accounts = vec![
+            AccountMeta::new(self.address, false),
+            AccountMeta::new_readonly(pda::program_config(), false),
+            AccountMeta::new(computation.mapping_account, false),
+            AccountMeta::new(
+                pda::op_record(&self.address, computation.computation_offset),
+                false,
+            ),
+            AccountMeta::new(self.vault, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+        ];
+        let circuit = Circuit::WithdrawAll.for_version(computation.circuit_version);
+        accounts.extend(self.queue_accounts(circuit, &computation.payer));
+        instruction(
+            "withdraw_all",
+            accounts,
+            &[&computation.computation_offset.to_le_bytes()],
+        )
+    }
+
+    /// Re-encrypts `owner`'s balance for the x25519 key `client_key` under `nonce`, into the
+    /// `BalanceReveal` at `pda::balance_reveal`. `use_op_queue` and `circuit_version` are
+    /// ignored.
+    pub fn reveal_balance(
+        &self,
+        computation: &Computation,
+        owner: &Pubkey,
+        client_key: &[u8; 32],
+        nonce: u128,
+    ) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.address, false),
+            AccountMeta::new_readonly(pda::program_config(), false),
+            AccountMeta::new_readonly(computation.mapping_account, false),
+            AccountMeta::new(
+                pda::balance_reveal(&self.address, computation.computation_offset),
+                false,
+            ),
+            AccountMeta::new(pda::consumed_nonce(owner, nonce), false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        accounts.extend(self.queue_accounts(Circuit::RevealBalance, &computation.payer));
+        instruction(
+            "reveal_balance",
+            accounts,
+            &[
+                &computation.computation_offset.to_le_bytes(),
+                client_key,
+                &nonce.to_le_bytes(),
+            ],
+        )
+    }
+
+    /// Requests a proof that the vault covers the balances of the blackbox's `shards` mapping
+    /// accounts, into the `SolvencyAttestation` at `pda::solvency_attestation`, and queues its
+    /// first range. `shards` must be the blackbox's `mapping_account_count`.
+    pub fn prove_solvency(
+        &self,
+        payer: &Pubkey,
+        computation_offset: u64,
+        shards: u8,
+    ) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.address, false),
+            AccountMeta::new_readonly(self.vault, false),
+            AccountMeta::new(
+                pda::solvency_attestation(&self.address, computation_offset),
+                false,
+            ),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        accounts.extend(self.queue_accounts(Circuit::ProveSolvency, payer));
+        accounts.extend(self.solvency_range(0, shards));
+        instruction(
+            "prove_solvency",
+            accounts,
+            &[&computation_offset.to_le_bytes()],
+        )
+    }
+
+    /// Queues the next range of the solvency proof requested at `attestation_offset`, once the
+    /// previous one has landed. `summed_shards` and `shards` are the attestation's.
+    pub fn continue_solvency_proof(
+        &self,
+        payer: &Pubkey,
+        attestation_offset: u64,
+        computation_offset: u64,
+        summed_shards: u8,
+        shards: u8,
+    ) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.address, false),
+            AccountMeta::new(
+                pda::solvency_attestation(&self.address, attestation_offset),
+                false,
+            ),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        accounts.extend(self.queue_accounts(Circuit::ProveSolvency, payer));
+        accounts.extend(self.solvency_range(summed_shards, shards));
+        instruction(
+            "continue_solvency_proof",
+            accounts,
+            &[&computation_offset.to_le_bytes()],
+        )
+    }
+
+    /// Exports page `page` of `mapping_account`'s balances for their owners, into the
+    /// `EmergencyExport` at `pda::emergency_export`. Only accepted in emergency mode;
+    /// `mapping_version` must be the mapping account's current version.
+    pub fn export_balances(
+        &self,
+        payer: &Pubkey,
+        computation_offset: u64,
+        mapping_account: &Pubkey,
+        mapping_version: u64,
+        page: u8,
+    ) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.address, false),
+            AccountMeta::new_readonly(*mapping_account, false),
+            AccountMeta::new(
+                pda::emergency_export(mapping_account, mapping_version, page),
+                false,
+            ),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        accounts.extend(self.queue_accounts(Circuit::ExportBalances, payer));
+        instruction(
+            "export_balances",
+            accounts,
+            &[&computation_offset.to_le_bytes(), &[page]],
+        )
+    }
+
+    /// Pays `owner` the balance at `entry` of `export` into `destination`, once `balance` and
+    /// `salt` open the entry's commitment. `payer` funds the account marking the claim.
+    pub fn claim_emergency(
+        &self,
+        payer: &Pubkey,
+        owner: &Pubkey,
+        destination: &Pubkey,
+        export: &EmergencyExport,
+        entry: u8,
+        balance: u64,
+        salt: u128,
+    ) -> Instruction {
+        instruction(
+            "claim_emergency",
+            vec![
+                AccountMeta::new_readonly(self.address, false),
+                AccountMeta::new_readonly(export.mapping_account, false),
+                AccountMeta::new_readonly(
+                    pda::emergency_export(
+                        &export.mapping_account,
+                        export.mapping_version,
+                        export.page,
+                    ),
+                    false,
+                ),
+                AccountMeta::new(pda::claimed_balance(&export.mapping_account, owner), false),
+                AccountMeta::new(self.vault, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(*owner, true),
+                AccountMeta::new(*payer, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+            ],
+            &[&[entry], &balance.to_le_bytes(), &salt.to_le_bytes()],
+        )
+    }
+
+    /// Refunds the deposit at `computation_offset`, left uncredited by emergency mode, into
+    /// `source`, the token account it came from.
+    pub fn refund_deposit(&self, computation_offset: u64, source: &Pubkey) -> Instruction {
+        instruction(
+            "refund_deposit",
+            vec![
+                AccountMeta::new_readonly(self.address, false),
+                AccountMeta::new(pda::op_record(&self.address, computation_offset), false),
+                AccountMeta::new(self.vault, false),
+                AccountMeta::new(*source, false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+            ],
+            &[],
+        )
+    }
+
+    /// Refunds the deposits left in `mapping_account`'s queue by emergency mode whose source
+    /// token accounts are among `sources`.
+    pub fn refund_queued_deposits(
+        &self,
+        mapping_account: &Pubkey,
+        sources: &[Pubkey],
+    ) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.address, false),
+            AccountMeta::new(pda::op_queue(mapping_account), false),
+            AccountMeta::new(self.vault, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+        ];
+        accounts.extend(sources.iter().map(|source| AccountMeta::new(*source, false)));
+        instruction("refund_queued_deposits", accounts, &[])
+    }
+
+    /// Re-encrypts `mapping_account` for the circuit version after `circuit_version`, its
+    /// current one. Signed by the program authority, once the blackbox has moved past
+    /// `circuit_version`; `None` if that's already the latest.
+    pub fn migrate_mapping(
+        &self,
+        authority: &Pubkey,
+        payer: &Pubkey,
+        computation_offset: u64,
+        mapping_account: &Pubkey,
+        circuit_version: u8,
+    ) -> Option<Instruction> {
+        let circuit = Circuit::migration(circuit_version)?;
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.address, false),
+            AccountMeta::new_readonly(pda::program_config(), false),
+            AccountMeta::new(*mapping_account, false),
+            AccountMeta::new(pda::op_record(&self.address, computation_offset), false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        accounts.extend(self.queue_accounts(circuit, payer));
+        Some(instruction(
+            "migrate_mapping",
+            accounts,
+            &[&computation_offset.to_le_bytes()],
+        ))
+    }
+
+    /// Hands the stale computation at `computation_offset` to its mapping account's queue.
+    pub fn requeue_stale_op(
+        &self,
+        mapping_account: &Pubkey,
+        computation_offset: u64,
+    ) -> Instruction {
+        instruction(
+            "requeue_stale_op",
+            vec![
+                AccountMeta::new(pda::op_record(&self.address, computation_offset), false),
+                AccountMeta::new(pda::op_queue(mapping_account), false),
+            ],
+            &[],
+        )
+    }
+
+    /// Puts the timed-out batch at `computation_offset`, the queue's `in_flight_offset`, back
+    /// in front of the mapping account's queue.
+    pub fn requeue_stuck_batch(
+        &self,
+        mapping_account: &Pubkey,
+        computation_offset: u64,
+    ) -> Instruction {
+        instruction(
+            "requeue_stuck_batch",
+            vec![
+                AccountMeta::new(pda::op_queue(mapping_account), false),
+                AccountMeta::new(pda::op_record(&self.address, computation_offset), false),
+            ],
+            &[],
+        )
+    }
+
+    /// Closes the resolved record of the computation at `computation_offset`, returning its
+    /// rent to `payer`, who paid it.
+    pub fn close_op_record(&self, payer: &Pubkey, computation_offset: u64) -> Instruction {
+        instruction(
+            "close_op_record",
+            vec![
+                AccountMeta::new(pda::op_record(&self.address, computation_offset), false),
+                AccountMeta::new(*payer, true),
+            ],
+            &[],
+        )
+    }
+
+    /// Queues a deposit whose status is encrypted for `client_key`.
+    pub fn enqueue_deposit(
+        &self,
+        mapping_account: &Pubkey,
+        user: &Pubkey,
+        approver: Option<&Pubkey>,
+        user_token_account: &Pubkey,
+        amount: u64,
+        client_key: &[u8; 32],
+    ) -> Instruction {
+        instruction(
+            "enqueue_deposit",
+            vec![
+                AccountMeta::new_readonly(self.address, false),
+                AccountMeta::new_readonly(pda::program_config(), false),
+                AccountMeta::new(pda::op_queue(mapping_account), false),
+                AccountMeta::new(*user_token_account, false),
+                AccountMeta::new(self.vault, false),
+                AccountMeta::new(self.treasury, false),
+                AccountMeta::new_readonly(*user, true),
+                optional_signer(approver),
+                AccountMeta::new_readonly(spl_token::ID, false),
+            ],
+            &[&amount.to_le_bytes(), client_key],
+        )
+    }
+
+    /// Queues a transfer whose status is encrypted for `client_key`.
+    pub fn enqueue_transfer(
+        &self,
+        payer: &Pubkey,
+        mapping_account: &Pubkey,
+        sender: &Pubkey,
+        transfer: &EncryptedTransfer,
+        client_key: &[u8; 32],
+    ) -> Instruction {
+        instruction(
+            "enqueue_transfer",
+            vec![
+                AccountMeta::new_readonly(self.address, false),
+                AccountMeta::new_readonly(pda::program_config(), false),
+                AccountMeta::new(pda::op_queue(mapping_account), false),
+                AccountMeta::new(pda::consumed_nonce(sender, transfer.nonce), false),
+                AccountMeta::new_readonly(*sender, true),
+                AccountMeta::new(*payer, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            &[
+                transfer.enc_transfer.as_flattened(),
+                client_key,
+                &transfer.nonce.to_le_bytes(),
+            ],
+        )
+    }
+
+    /// Queues a withdrawal whose status is encrypted for `client_key`.
+    pub fn enqueue_withdraw(
+        &self,
+        mapping_account: &Pubkey,
+        user: &Pubkey,
+        destination: &Pubkey,
+        amount: u64,
+        client_key: &[u8; 32],
+    ) -> Instruction {
+        instruction(
+            "enqueue_withdraw",
+            vec![
+                AccountMeta::new(self.address, false),
+                AccountMeta::new_readonly(pda::program_config(), false),
+                AccountMeta::new(pda::op_queue(mapping_account), false),
+                AccountMeta::new_readonly(*destination, false),
+                AccountMeta::new_readonly(*user, true),
+            ],
+            &[&amount.to_le_bytes(), client_key],
+        )
+    }
+
+    /// Flushes the mapping account's operation queue. `use_op_queue` is ignored: the queue is
+    /// always passed.
+    pub fn flush_op_queue(&self, computation: &Computation) -> Instruction {
+        let mapping_account = computation.mapping_account;
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.address, false),
+            AccountMeta::new_readonly(pda::program_config(), false),
+            AccountMeta::new(mapping_account, false),
+            AccountMeta::new(pda::op_queue(&mapping_account), false),
+            AccountMeta::new(
+                pda::op_record(&self.address, computation.computation_offset),
+                false,
+            ),
+            AccountMeta::new(self.vault, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+        ];
+        let circuit = Circuit::ProcessBatch.for_version(computation.circuit_version);
+        accounts.extend(self.queue_accounts(circuit, &computation.payer));
+        instruction(
+            "flush_op_queue",
+            accounts,
+            &[&computation.computation_offset.to_le_bytes()],
+        )
+    }
+
+    /// Blackbox, program config, mapping account, operation record and optional operation
+    /// queue, which lead the accounts of deposit, transfer and withdraw.
+    fn computation_head(&self, computation: &Computation) -> Vec<AccountMeta> {
+        let op_queue = if computation.use_op_queue {
+            AccountMeta::new(pda::op_queue(&computation.mapping_account), false)
+        } else {
+            // Anchor passes the program ID for an absent optional account
+            AccountMeta::new_readonly(PROGRAM_ID, false)
+        };
+        vec![
+            AccountMeta::new_readonly(self.address, false),
+            AccountMeta::new_readonly(pda::program_config(), false),
+            AccountMeta::new(computation.mapping_account, false),
+            AccountMeta::new(
+                pda::op_record(&self.address, computation.computation_offset),
+                false,
+            ),
+            op_queue,
+        ]
+    }
+
+    /// The mapping accounts of a solvency proof's range starting at `first`, in index order
+    fn solvency_range(&self, first: u8, shards: u8) -> impl Iterator<Item = AccountMeta> + '_ {
+        (first..shards)
+            .take(MAX_SOLVENCY_SHARDS.into())
+            .map(|index| AccountMeta::new_readonly(self.mapping_account(index), false))
+    }
+
+    /// Arcium accounts closing every queue instruction, followed by the payer
+    fn queue_accounts(&self, circuit: Circuit, payer: &Pubkey) -> [AccountMeta; 8] {
+        [
+            AccountMeta::new_readonly(self.arcium.program, false),
+            AccountMeta::new_readonly(self.arcium.clock, false),
+            AccountMeta::new_readonly(self.arcium.cluster, false),
+            AccountMeta::new_readonly(circuit.comp_def(), false),
+            AccountMeta::new(self.arcium.mempool, false),
+            AccountMeta::new(self.arcium.mxe, false),
+            AccountMeta::new_readonly(self.arcium.pool, false),
+            AccountMeta::new(*payer, true),
+        ]
+    }
+}
+
+/// `account`, or the program ID Anchor takes for an absent optional account
+fn optional_account(account: Option<&Pubkey>, writable: bool) -> AccountMeta {
+    match account {
+        Some(account) if writable => AccountMeta::new(*account, false),
+        Some(account) => AccountMeta::new_readonly(*account, false),
+        None => AccountMeta::new_readonly(PROGRAM_ID, false),
+    }
+}
+
+fn optional_signer(account: Option<&Pubkey>) -> AccountMeta {
+    match account {
+        Some(account) => AccountMeta::new_readonly(*account, true),
+        None => AccountMeta::new_readonly(PROGRAM_ID, false),
+    }
+}
+
+fn instruction(name: &str, accounts: Vec<AccountMeta>, args: &[&[u8]]) -> Instruction {
+    let mut data = instruction_discriminator(name).to_vec();
+    for arg in args {
+        data.extend_from_slice(arg);
+    }
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts,
+        data,
+    }
+}
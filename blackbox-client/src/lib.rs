@@ -0,0 +1,50 @@
+//! Client library for the blackbox program.
+//!
+//! Everything here works with plain `solana-sdk` types: [`pda`] derives the program's
+//! addresses, [`arcium`] resolves the Arcium accounts computations are queued through,
+//! [`instructions`] builds every user-facing instruction, [`accounts`] decodes the program's
+//! accounts and [`events`] the events it logs. [`compute_budget`] sizes compute-budget
+//! requests from the program's measured budgets, [`encryption`] encrypts arguments for the
+//! MXE and decrypts its results, and [`cover_traffic`] schedules no-op transfers.
+
+use solana_sdk::{pubkey, pubkey::Pubkey};
+
+pub mod accounts;
+pub mod arcium;
+pub mod compute_budget;
+pub mod cover_traffic;
+pub mod encryption;
+pub mod events;
+pub mod instructions;
+pub mod pda;
+
+pub use arcium::{ArciumAccounts, Circuit};
+pub use instructions::Blackbox;
+
+/// Address the blackbox program is deployed at
+pub const PROGRAM_ID: Pubkey = pubkey!("AL25N1v4WjSXdTsszwYG7WA2Ztb6Wj5VAQBFGn8Y4pnz");
+
+/// Mapping accounts one range of a solvency proof sums, `MAX_SOLVENCY_SHARDS` in
+/// `confidential-ixs`
+pub const MAX_SOLVENCY_SHARDS: u8 = 4;
+
+/// Anchor discriminator of the instruction called `name`
+pub(crate) fn instruction_discriminator(name: &str) -> [u8; 8] {
+    discriminator("global", name)
+}
+
+/// Anchor discriminator of the account type called `name`
+pub(crate) fn account_discriminator(name: &str) -> [u8; 8] {
+    discriminator("account", name)
+}
+
+/// Anchor discriminator of the event called `name`
+pub(crate) fn event_discriminator(name: &str) -> [u8; 8] {
+    discriminator("event", name)
+}
+
+fn discriminator(namespace: &str, name: &str) -> [u8; 8] {
+    solana_sdk::hash::hash(format!("{namespace}:{name}").as_bytes()).to_bytes()[..8]
+        .try_into()
+        .unwrap()
+}
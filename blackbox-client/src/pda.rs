@@ -0,0 +1,94 @@
+//! Program-derived addresses of the blackbox program.
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::PROGRAM_ID;
+
+/// The program config, holding the authority and program-wide pause flags
+pub fn program_config() -> Pubkey {
+    Pubkey::find_program_address(&[b"config"], &PROGRAM_ID).0
+}
+
+/// Parameter change number `id`, while it waits out the timelock
+pub fn pending_change(id: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"change", &id.to_le_bytes()], &PROGRAM_ID).0
+}
+
+/// Blackbox of `token_mint`
+pub fn blackbox(token_mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"blackbox", token_mint.as_ref()], &PROGRAM_ID).0
+}
+
+/// Token account holding a blackbox's deposits
+pub fn vault(blackbox: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"vault", blackbox.as_ref()], &PROGRAM_ID).0
+}
+
+/// Token account a blackbox's fees are paid into
+pub fn treasury(blackbox: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"treasury", blackbox.as_ref()], &PROGRAM_ID).0
+}
+
+/// Mapping account number `index` of `token_mint`'s blackbox
+pub fn mapping_account(token_mint: &Pubkey, index: u8) -> Pubkey {
+    Pubkey::find_program_address(&[b"mapping", token_mint.as_ref(), &[index]], &PROGRAM_ID).0
+}
+
+/// Operation queue of a mapping account
+pub fn op_queue(mapping_account: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"op_queue", mapping_account.as_ref()], &PROGRAM_ID).0
+}
+
+/// Record of the computation queued at `computation_offset`
+pub fn op_record(blackbox: &Pubkey, computation_offset: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"op", blackbox.as_ref(), &computation_offset.to_le_bytes()],
+        &PROGRAM_ID,
+    )
+    .0
+}
+
+/// Marker of a transfer or reveal nonce `sender` has used
+pub fn consumed_nonce(sender: &Pubkey, nonce: u128) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"nonce", sender.as_ref(), &nonce.to_le_bytes()],
+        &PROGRAM_ID,
+    )
+    .0
+}
+
+/// Result of the balance reveal queued at `computation_offset`
+pub fn balance_reveal(blackbox: &Pubkey, computation_offset: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"reveal", blackbox.as_ref(), &computation_offset.to_le_bytes()],
+        &PROGRAM_ID,
+    )
+    .0
+}
+
+/// Result of the solvency proof queued at `computation_offset`
+pub fn solvency_attestation(blackbox: &Pubkey, computation_offset: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"solvency", blackbox.as_ref(), &computation_offset.to_le_bytes()],
+        &PROGRAM_ID,
+    )
+    .0
+}
+
+/// Page `page` of a mapping account's balances, exported in emergency mode at `mapping_version`
+pub fn emergency_export(mapping_account: &Pubkey, mapping_version: u64, page: u8) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"emergency", mapping_account.as_ref(), &mapping_version.to_le_bytes(), &[page]],
+        &PROGRAM_ID,
+    )
+    .0
+}
+
+/// Marks `owner`'s exported balance in a mapping account as claimed
+pub fn claimed_balance(mapping_account: &Pubkey, owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"claimed", mapping_account.as_ref(), owner.as_ref()],
+        &PROGRAM_ID,
+    )
+    .0
+}
@@ -0,0 +1,286 @@
+//! Round trips through the client-side MXE encryption, key derivation from Solana keypairs,
+//! emergency claims, and compatibility with `@arcium-hq/arcium-sdk` through the vectors
+//! `tests/vectors/generate.ts` writes.
+
+use blackbox_client::{
+    accounts::{EmergencyExport, ExportedBalance},
+    cover_traffic::TransferEncryptor,
+    encryption::{
+        claim_commitment, open_claim, Claim, ClientSecret, EncryptionError, FieldElement,
+        MxePublicKey, RescueCipher, KEY_DERIVATION_MESSAGE,
+    },
+};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use rand::{rngs::StdRng, SeedableRng};
+use solana_sdk::{
+    derivation_path::DerivationPath,
+    pubkey::Pubkey,
+    signature::{keypair_from_seed, Keypair},
+    signer::{
+        keypair::{
+            generate_seed_from_seed_phrase_and_passphrase, keypair_from_seed_and_derivation_path,
+        },
+        Signer,
+    },
+};
+
+fn random_mxe(rng: &mut StdRng) -> MxePublicKey {
+    MxePublicKey(std::array::from_fn(|_| {
+        ClientSecret::random(rng).public_key()
+    }))
+}
+
+fn cipher(seed: u64) -> RescueCipher {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mxe = random_mxe(&mut rng);
+    ClientSecret::random(&mut rng).cipher(&mxe)
+}
+
+/// A public key that fits in a field element
+fn canonical_pubkey() -> Pubkey {
+    let mut key = Pubkey::new_unique().to_bytes();
+    key[31] &= 0x7f;
+    Pubkey::new_from_array(key)
+}
+
+#[test]
+fn encryption_round_trips() {
+    let cipher = cipher(1);
+    let plaintext: Vec<FieldElement> = (0..12u64).map(|i| (i * 1_000_003).into()).collect();
+    let ciphertext = cipher.encrypt(&plaintext, 7);
+    assert_eq!(cipher.decrypt(&ciphertext, 7).unwrap(), plaintext);
+    assert_ne!(cipher.decrypt(&ciphertext, 8).unwrap(), plaintext);
+
+    let balance = cipher.encrypt_u64(u64::MAX, 9);
+    assert_eq!(cipher.decrypt_u64(&balance, 9), Ok(u64::MAX));
+}
+
+#[test]
+fn both_sides_agree_the_same_key() {
+    // With all five MXE keys from one secret, swapping the roles of client and MXE must give
+    // the same shared secrets.
+    let mut rng = StdRng::seed_from_u64(2);
+    let client = ClientSecret::random(&mut rng);
+    let mxe = ClientSecret::random(&mut rng);
+    let client_side = client.cipher(&MxePublicKey([mxe.public_key(); 5]));
+    let mxe_side = mxe.cipher(&MxePublicKey([client.public_key(); 5]));
+
+    let ciphertext = client_side.encrypt_u64(350, 3);
+    assert_eq!(mxe_side.decrypt_u64(&ciphertext, 3), Ok(350));
+}
+
+#[test]
+fn transfers_encrypt_their_arguments_as_one_struct() {
+    let cipher = cipher(3);
+    let recipient = canonical_pubkey();
+    let transfer = cipher.encrypt_transfer(&recipient, 250, true, 11).unwrap();
+
+    assert_eq!(transfer.nonce, 11);
+    let decrypted = cipher.decrypt(&transfer.enc_transfer, 11).unwrap();
+    assert_eq!(decrypted[0].to_le_bytes(), recipient.to_bytes());
+    assert_eq!(decrypted[1].to_u64(), Some(250));
+    assert_eq!(decrypted[2], FieldElement::one());
+
+    // Each argument has its own keystream position, so no two share a pad
+    assert_ne!(
+        transfer.enc_transfer[1],
+        cipher.encrypt_u64(250, 11),
+        "the amount must not be encrypted at position 0"
+    );
+}
+
+#[test]
+fn values_outside_the_field_are_rejected() {
+    let cipher = cipher(4);
+    let too_large = Pubkey::new_from_array([0xff; 32]);
+    assert_eq!(
+        cipher.encrypt_pubkey(&too_large, 1),
+        Err(EncryptionError::NotInField)
+    );
+    assert_eq!(
+        cipher.decrypt(&[[0xff; 32]], 1),
+        Err(EncryptionError::NotInField)
+    );
+
+    // A pubkey-sized plaintext isn't a u64
+    let ciphertext = cipher.encrypt_pubkey(&canonical_pubkey(), 1).unwrap();
+    assert_eq!(
+        cipher.decrypt_u64(&ciphertext, 1),
+        Err(EncryptionError::WrongType)
+    );
+}
+
+#[test]
+fn derived_keys_are_stable() {
+    // Pinned: a different key here means every user's encryption key changed
+    let keypair = keypair_from_seed(&[7; 32]).unwrap();
+    assert_eq!(
+        hex::encode(ClientSecret::from_keypair(&keypair).public_key()),
+        "2bbd972c4a74bd5ca19f9181d36cf9e40c2aa0c25a8e9f1d6b800c3d3beb3a28"
+    );
+}
+
+#[test]
+fn wallet_signatures_derive_the_keypairs_key() {
+    let keypair = Keypair::new();
+    let signature = keypair.sign_message(KEY_DERIVATION_MESSAGE);
+    let derived = ClientSecret::from_signature(&keypair.pubkey(), &signature).unwrap();
+    assert_eq!(
+        derived.to_bytes(),
+        ClientSecret::from_keypair(&keypair).to_bytes()
+    );
+    assert_ne!(
+        derived.to_bytes(),
+        ClientSecret::from_keypair(&Keypair::new()).to_bytes()
+    );
+
+    let other_message = keypair.sign_message(b"something else");
+    assert!(matches!(
+        ClientSecret::from_signature(&keypair.pubkey(), &other_message),
+        Err(EncryptionError::InvalidSignature)
+    ));
+    assert!(matches!(
+        ClientSecret::from_signature(&Pubkey::new_unique(), &signature),
+        Err(EncryptionError::InvalidSignature)
+    ));
+}
+
+#[test]
+fn owner_keys_are_the_montgomery_form_of_the_ed25519_key() {
+    for seed in 0..4u8 {
+        let keypair = keypair_from_seed(&[seed; 32]).unwrap();
+        let montgomery = CompressedEdwardsY(keypair.pubkey().to_bytes())
+            .decompress()
+            .unwrap()
+            .to_montgomery();
+        assert_eq!(
+            ClientSecret::from_owner_keypair(&keypair).public_key(),
+            montgomery.to_bytes()
+        );
+    }
+}
+
+#[test]
+fn owners_open_only_their_own_claims() {
+    let mut rng = StdRng::seed_from_u64(5);
+    let mxe = ClientSecret::random(&mut rng);
+    let mxe_keys = MxePublicKey([mxe.public_key(); 5]);
+    let (alice, bob) = (Keypair::new(), Keypair::new());
+
+    // The MXE's side of each owner's cipher, agreed with the owner's Montgomery key
+    let nonce = 1 << 100;
+    let entries = [(&alice, 450, 7u128), (&bob, 250, u128::MAX)]
+        .into_iter()
+        .enumerate()
+        .map(|(k, (owner, balance, salt))| {
+            let owner_key = ClientSecret::from_owner_keypair(owner).public_key();
+            let cipher = mxe.cipher(&MxePublicKey([owner_key; 5]));
+            let enc_claim = cipher.encrypt(&[balance.into(), salt.into()], nonce + k as u128);
+            ExportedBalance {
+                enc_claim: enc_claim.try_into().unwrap(),
+                commitment: claim_commitment(&owner.pubkey(), balance, salt),
+            }
+        })
+        .collect();
+    let export = EmergencyExport {
+        bump: 255,
+        blackbox: Pubkey::new_unique(),
+        mapping_account: Pubkey::new_unique(),
+        mapping_version: 3,
+        page: 0,
+        computation_offset: 9,
+        nonce,
+        exported: true,
+        entries,
+        more: false,
+    };
+
+    let open = |owner: &Keypair| {
+        let cipher = ClientSecret::from_owner_keypair(owner).cipher(&mxe_keys);
+        open_claim(&cipher, &owner.pubkey(), &export)
+    };
+    assert_eq!(
+        open(&bob),
+        Some(Claim {
+            entry: 1,
+            balance: 250,
+            salt: u128::MAX,
+        })
+    );
+    assert_eq!(open(&alice).map(|claim| claim.balance), Some(450));
+    assert_eq!(open(&Keypair::new()), None);
+
+    // The key derivation key can't open a claim
+    let cipher = ClientSecret::from_keypair(&alice).cipher(&mxe_keys);
+    assert_eq!(open_claim(&cipher, &alice.pubkey(), &export), None);
+}
+
+#[test]
+fn wallets_open_claims_with_their_seed_phrase() {
+    let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+                  abandon abandon about";
+    let path = DerivationPath::new_bip44(Some(0), Some(0));
+    let seed = generate_seed_from_seed_phrase_and_passphrase(phrase, "");
+    let wallet = keypair_from_seed_and_derivation_path(&seed, Some(path.clone())).unwrap();
+
+    let secret = ClientSecret::from_seed_phrase(phrase, "", &path, &wallet.pubkey()).unwrap();
+    assert_eq!(
+        secret.public_key(),
+        ClientSecret::from_owner_keypair(&wallet).public_key()
+    );
+    // Another passphrase or account derives another keypair
+    assert_eq!(
+        ClientSecret::from_seed_phrase(phrase, "wrong", &path, &wallet.pubkey()).err(),
+        Some(EncryptionError::WrongSeedPhrase)
+    );
+    let other = DerivationPath::new_bip44(Some(1), Some(0));
+    assert_eq!(
+        ClientSecret::from_seed_phrase(phrase, "", &other, &wallet.pubkey()).err(),
+        Some(EncryptionError::WrongSeedPhrase)
+    );
+}
+
+fn bytes<const N: usize>(value: &serde_json::Value) -> [u8; N] {
+    hex::decode(value.as_str().unwrap())
+        .unwrap()
+        .try_into()
+        .unwrap()
+}
+
+#[test]
+#[ignore = "needs tests/vectors/rescue.json from `yarn vectors`"]
+fn matches_the_typescript_sdk() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/vectors/rescue.json");
+    let vectors: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let mxe_keys: Vec<[u8; 32]> = vectors["mxe_public_key"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(bytes)
+        .collect();
+    let mxe = MxePublicKey(mxe_keys.try_into().unwrap());
+
+    for case in vectors["cases"].as_array().unwrap() {
+        let client = ClientSecret::from_bytes(bytes(&case["client_secret"]));
+        assert_eq!(client.public_key(), bytes::<32>(&case["client_public_key"]));
+
+        let nonce: u128 = case["nonce"].as_str().unwrap().parse().unwrap();
+        let plaintext: Vec<FieldElement> = case["plaintext"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| FieldElement::from_le_bytes(&bytes(value)).unwrap())
+            .collect();
+        let ciphertext: Vec<[u8; 32]> = case["ciphertext"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(bytes)
+            .collect();
+
+        let cipher = client.cipher(&mxe);
+        assert_eq!(cipher.encrypt(&plaintext, nonce), ciphertext);
+        assert_eq!(cipher.decrypt(&ciphertext, nonce).unwrap(), plaintext);
+    }
+}
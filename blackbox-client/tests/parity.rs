@@ -0,0 +1,1527 @@
+//! Checks the hand-written builders and decoders against the program's Anchor-generated
+//! client types, so the two can't drift apart.
+
+use anchor_lang::{AccountSerialize, Event as _, InstructionData, ToAccountMetas};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use blackbox::{events, state};
+use blackbox_client::{
+    accounts::{
+        BalanceReveal, BlackboxAccount, DecodeError, EmergencyExport, EpochOutflow,
+        ExportedBalance, Fees, InitBlackboxParams, Limits, MappingAccount, ModeFlags, OpKind,
+        OpQueueAccount, OpRecord, OpStatus, ParameterChange, PauseFlags, PendingChange,
+        PolicyHooks, ProgramConfig, SolvencyAttestation,
+    },
+    events::{self as client_events, ComputationKind, Event},
+    instructions::{self, Computation, EncryptedTransfer},
+    pda, ArciumAccounts, Blackbox, Circuit, PROGRAM_ID,
+};
+use solana_sdk::{
+    bpf_loader_upgradeable,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+fn anchor_instruction(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: blackbox::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+fn serialize(account: &impl AccountSerialize) -> Vec<u8> {
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+    data
+}
+
+struct Setup {
+    blackbox: Blackbox,
+    payer: Pubkey,
+    user: Pubkey,
+    token_account: Pubkey,
+    computation: Computation,
+}
+
+fn setup(use_op_queue: bool) -> Setup {
+    let blackbox = Blackbox::new(Pubkey::new_unique(), 7);
+    let payer = Pubkey::new_unique();
+    Setup {
+        computation: Computation {
+            payer,
+            mapping_account: blackbox.mapping_account(2),
+            computation_offset: 77,
+            use_op_queue,
+            circuit_version: 1,
+        },
+        blackbox,
+        payer,
+        user: Pubkey::new_unique(),
+        token_account: Pubkey::new_unique(),
+    }
+}
+
+#[test]
+fn program_id_matches() {
+    assert_eq!(PROGRAM_ID, blackbox::ID);
+}
+
+#[test]
+fn init_instructions_match() {
+    let Setup { blackbox, payer, user: authority, .. } = setup(false);
+
+    let approver = Pubkey::new_unique();
+    let params = InitBlackboxParams {
+        shard_capacity: 100,
+        fees: Fees { deposit_bps: 30, withdraw_bps: 200 },
+        limits: Limits {
+            min_deposit: 1,
+            max_deposit: 2,
+            min_withdraw: 3,
+            max_withdraw: 4,
+            epoch_outflow_cap: 5,
+        },
+        circuit_version: 2,
+        policy: PolicyHooks { deposit_approver: Some(approver) },
+        modes: ModeFlags { queued_only: true },
+        cluster_offset: 3,
+    };
+    assert_eq!(
+        blackbox.init_blackbox(&authority, &payer, &params),
+        anchor_instruction(
+            blackbox::accounts::InitBlackbox {
+                config: pda::program_config(),
+                token_mint: blackbox.token_mint,
+                blackbox: blackbox.address,
+                vault: blackbox.vault,
+                treasury: blackbox.treasury,
+                cluster: ArciumAccounts::resolve(3).cluster,
+                authority,
+                payer,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+            },
+            blackbox::instruction::InitBlackbox {
+                params: state::InitBlackboxParams {
+                    shard_capacity: 100,
+                    fees: state::Fees { deposit_bps: 30, withdraw_bps: 200 },
+                    limits: state::Limits {
+                        min_deposit: 1,
+                        max_deposit: 2,
+                        min_withdraw: 3,
+                        max_withdraw: 4,
+                        epoch_outflow_cap: 5,
+                    },
+                    circuit_version: 2,
+                    policy: state::PolicyHooks { deposit_approver: Some(approver) },
+                    modes: state::ModeFlags { queued_only: true },
+                    cluster_offset: 3,
+                },
+            },
+        )
+    );
+
+    assert_eq!(
+        blackbox.initialize_mapping_account(&payer, 3),
+        anchor_instruction(
+            blackbox::accounts::InitializeMappingAccount {
+                blackbox: blackbox.address,
+                config: pda::program_config(),
+                mapping_account: pda::mapping_account(&blackbox.token_mint, 3),
+                payer,
+                system_program: system_program::ID,
+            },
+            blackbox::instruction::InitializeMappingAccount {},
+        )
+    );
+
+    let mapping_account = blackbox.mapping_account(0);
+    assert_eq!(
+        blackbox.init_op_queue(&payer, &mapping_account),
+        anchor_instruction(
+            blackbox::accounts::InitOpQueue {
+                blackbox: blackbox.address,
+                mapping_account,
+                op_queue: pda::op_queue(&mapping_account),
+                payer,
+                system_program: system_program::ID,
+            },
+            blackbox::instruction::InitOpQueue {},
+        )
+    );
+
+    let deposit = blackbox.init_comp_def(&payer, Circuit::Deposit);
+    assert_eq!(
+        deposit,
+        anchor_instruction(
+            blackbox::accounts::InitDepositCompDef {
+                payer,
+                mxe_account: blackbox.arcium.program_mxe,
+                comp_def_account: Circuit::Deposit.comp_def(),
+                arcium_program: blackbox.arcium.program,
+                system_program: system_program::ID,
+            },
+            blackbox::instruction::InitDepositCompDef {},
+        )
+    );
+    assert_eq!(
+        blackbox.init_comp_def(&payer, Circuit::WithdrawAll).data,
+        blackbox::instruction::InitWithdrawAllCompDef {}.data()
+    );
+    assert_eq!(
+        blackbox.init_comp_def(&payer, Circuit::RevealBalance).data,
+        blackbox::instruction::InitRevealBalanceCompDef {}.data()
+    );
+    assert_eq!(
+        blackbox.init_comp_def(&payer, Circuit::ProcessBatch).data,
+        blackbox::instruction::InitProcessBatchCompDef {}.data()
+    );
+    assert_eq!(
+        blackbox.init_comp_def(&payer, Circuit::ProveSolvency).data,
+        blackbox::instruction::InitProveSolvencyCompDef {}.data()
+    );
+    assert_eq!(
+        blackbox.init_comp_def(&payer, Circuit::ExportBalances).data,
+        blackbox::instruction::InitExportBalancesCompDef {}.data()
+    );
+    assert_eq!(
+        blackbox.init_comp_def(&payer, Circuit::DepositV2).data,
+        blackbox::instruction::InitDepositV2CompDef {}.data()
+    );
+    assert_eq!(
+        blackbox.init_comp_def(&payer, Circuit::TransferV2).data,
+        blackbox::instruction::InitTransferV2CompDef {}.data()
+    );
+    assert_eq!(
+        blackbox.init_comp_def(&payer, Circuit::WithdrawV2).data,
+        blackbox::instruction::InitWithdrawV2CompDef {}.data()
+    );
+    assert_eq!(
+        blackbox.init_comp_def(&payer, Circuit::WithdrawAllV2).data,
+        blackbox::instruction::InitWithdrawAllV2CompDef {}.data()
+    );
+    assert_eq!(
+        blackbox.init_comp_def(&payer, Circuit::ProcessBatchV2).data,
+        blackbox::instruction::InitProcessBatchV2CompDef {}.data()
+    );
+    assert_eq!(
+        blackbox.init_comp_def(&payer, Circuit::MigrateMappingV2).data,
+        blackbox::instruction::InitMigrateMappingV2CompDef {}.data()
+    );
+}
+
+#[test]
+fn admin_instructions_match() {
+    let authority = Pubkey::new_unique();
+    let blackbox = Pubkey::new_unique();
+
+    assert_eq!(
+        instructions::init_program_config(&authority),
+        anchor_instruction(
+            blackbox::accounts::InitProgramConfig {
+                config: pda::program_config(),
+                program_data: bpf_loader_upgradeable::get_program_data_address(&blackbox::ID),
+                authority,
+                system_program: system_program::ID,
+            },
+            blackbox::instruction::InitProgramConfig {},
+        )
+    );
+
+    for target in [None, Some(blackbox)] {
+        assert_eq!(
+            instructions::pause(
+                &authority,
+                target.as_ref(),
+                PauseFlags { deposit: true, transfer: false, withdraw: true },
+            ),
+            anchor_instruction(
+                blackbox::accounts::Pause {
+                    config: pda::program_config(),
+                    blackbox: target,
+                    authority,
+                },
+                blackbox::instruction::Pause {
+                    paused: state::PauseFlags { deposit: true, transfer: false, withdraw: true },
+                },
+            )
+        );
+    }
+
+    let new_authority = Pubkey::new_unique();
+    for proposed in [None, Some(new_authority)] {
+        assert_eq!(
+            instructions::propose_authority(&authority, proposed.as_ref()),
+            anchor_instruction(
+                blackbox::accounts::ProposeAuthority {
+                    config: pda::program_config(),
+                    authority,
+                },
+                blackbox::instruction::ProposeAuthority {
+                    new_authority: proposed,
+                },
+            )
+        );
+    }
+    assert_eq!(
+        instructions::accept_authority(&new_authority),
+        anchor_instruction(
+            blackbox::accounts::AcceptAuthority {
+                config: pda::program_config(),
+                new_authority,
+            },
+            blackbox::instruction::AcceptAuthority {},
+        )
+    );
+
+    let payer = Pubkey::new_unique();
+    let limits = Limits {
+        min_deposit: 1,
+        max_deposit: 2,
+        min_withdraw: 3,
+        max_withdraw: 4,
+        epoch_outflow_cap: 5,
+    };
+    let changes = [
+        (None, ParameterChange::MaxFee(250), state::ParameterChange::MaxFee(250)),
+        (
+            Some(blackbox),
+            ParameterChange::Fees(Fees { deposit_bps: 30, withdraw_bps: 200 }),
+            state::ParameterChange::Fees(state::Fees { deposit_bps: 30, withdraw_bps: 200 }),
+        ),
+        (
+            Some(blackbox),
+            ParameterChange::Limits(limits),
+            state::ParameterChange::Limits(state::Limits {
+                min_deposit: 1,
+                max_deposit: 2,
+                min_withdraw: 3,
+                max_withdraw: 4,
+                epoch_outflow_cap: 5,
+            }),
+        ),
+        (
+            None,
+            ParameterChange::Paused(PauseFlags::default()),
+            state::ParameterChange::Paused(state::PauseFlags::default()),
+        ),
+        (None, ParameterChange::Timelock(1 << 40), state::ParameterChange::Timelock(1 << 40)),
+        (Some(blackbox), ParameterChange::Emergency, state::ParameterChange::Emergency),
+        (
+            Some(blackbox),
+            ParameterChange::CircuitVersion(2),
+            state::ParameterChange::CircuitVersion(2),
+        ),
+        (
+            None,
+            ParameterChange::MaxShardCapacity(64),
+            state::ParameterChange::MaxShardCapacity(64),
+        ),
+    ];
+    for (id, (target, change, anchor_change)) in changes.into_iter().enumerate() {
+        let id = id as u64 + 7;
+        assert_eq!(
+            instructions::queue_change(&payer, &authority, id, target.as_ref(), change),
+            anchor_instruction(
+                blackbox::accounts::QueueChange {
+                    config: pda::program_config(),
+                    blackbox: target,
+                    change: pda::pending_change(id),
+                    payer,
+                    authority,
+                    system_program: system_program::ID,
+                },
+                blackbox::instruction::QueueChange {
+                    change: anchor_change,
+                },
+            )
+        );
+
+        let pending = PendingChange {
+            bump: 255,
+            id,
+            blackbox: target,
+            change,
+            executable_slot: 1_000,
+            payer,
+        };
+        assert_eq!(
+            instructions::execute_change(&pending),
+            anchor_instruction(
+                blackbox::accounts::ExecuteChange {
+                    config: pda::program_config(),
+                    change: pda::pending_change(id),
+                    blackbox: target,
+                    payer,
+                },
+                blackbox::instruction::ExecuteChange {},
+            )
+        );
+        assert_eq!(
+            instructions::cancel_change(&authority, &pending),
+            anchor_instruction(
+                blackbox::accounts::CancelChange {
+                    config: pda::program_config(),
+                    change: pda::pending_change(id),
+                    payer,
+                    authority,
+                },
+                blackbox::instruction::CancelChange {},
+            )
+        );
+    }
+
+    let blackbox = Blackbox::new(Pubkey::new_unique(), 0);
+    let destination = Pubkey::new_unique();
+    assert_eq!(
+        blackbox.collect_fees(&authority, &destination),
+        anchor_instruction(
+            blackbox::accounts::CollectFees {
+                config: pda::program_config(),
+                blackbox: blackbox.address,
+                treasury: blackbox.treasury,
+                destination,
+                authority,
+                token_program: spl_token::ID,
+            },
+            blackbox::instruction::CollectFees {},
+        )
+    );
+}
+
+#[test]
+fn deposit_matches() {
+    for use_op_queue in [false, true] {
+        let Setup { blackbox, payer, user, token_account, computation } = setup(use_op_queue);
+        let arcium = blackbox.arcium;
+        let mapping_account = computation.mapping_account;
+        // The approver is optional; cover both
+        let approver = use_op_queue.then(Pubkey::new_unique);
+
+        assert_eq!(
+            blackbox.deposit(&computation, &user, approver.as_ref(), &token_account, 500),
+            anchor_instruction(
+                blackbox::accounts::Deposit {
+                    blackbox: blackbox.address,
+                    config: pda::program_config(),
+                    mapping_account,
+                    op_record: pda::op_record(&blackbox.address, 77),
+                    op_queue: use_op_queue.then(|| pda::op_queue(&mapping_account)),
+                    user_token_account: token_account,
+                    vault: blackbox.vault,
+                    treasury: blackbox.treasury,
+                    user,
+                    deposit_approver: approver,
+                    system_program: system_program::ID,
+                    token_program: spl_token::ID,
+                    arcium_program: arcium.program,
+                    clock: arcium.clock,
+                    cluster: arcium.cluster,
+                    comp_def: Circuit::Deposit.comp_def(),
+                    mempool: arcium.mempool,
+                    mxe: arcium.mxe,
+                    pool: arcium.pool,
+                    payer,
+                },
+                blackbox::instruction::Deposit {
+                    computation_offset: 77,
+                    amount: 500,
+                },
+            )
+        );
+    }
+}
+
+#[test]
+fn transfer_and_withdraw_match() {
+    let Setup { blackbox, payer, user, token_account, computation } = setup(true);
+    let arcium = blackbox.arcium;
+    let mapping_account = computation.mapping_account;
+    let transfer = EncryptedTransfer {
+        enc_transfer: [[1; 32], [2; 32], [3; 32]],
+        nonce: 11,
+    };
+
+    assert_eq!(
+        blackbox.transfer(&computation, &user, &transfer, &[5; 32]),
+        anchor_instruction(
+            blackbox::accounts::Transfer {
+                blackbox: blackbox.address,
+                config: pda::program_config(),
+                mapping_account,
+                op_record: pda::op_record(&blackbox.address, 77),
+                op_queue: Some(pda::op_queue(&mapping_account)),
+                consumed_nonce: pda::consumed_nonce(&user, 11),
+                sender: user,
+                system_program: system_program::ID,
+                arcium_program: arcium.program,
+                clock: arcium.clock,
+                cluster: arcium.cluster,
+                comp_def: Circuit::Transfer.comp_def(),
+                mempool: arcium.mempool,
+                mxe: arcium.mxe,
+                pool: arcium.pool,
+                payer,
+            },
+            blackbox::instruction::Transfer {
+                computation_offset: 77,
+                enc_transfer: [[1; 32], [2; 32], [3; 32]],
+                client_key: [5; 32],
+                nonce: 11,
+            },
+        )
+    );
+
+    assert_eq!(
+        blackbox.withdraw(&computation, &user, &token_account, 40),
+        anchor_instruction(
+            blackbox::accounts::Withdraw {
+                blackbox: blackbox.address,
+                config: pda::program_config(),
+                mapping_account,
+                op_record: pda::op_record(&blackbox.address, 77),
+                op_queue: Some(pda::op_queue(&mapping_account)),
+                vault: blackbox.vault,
+                destination: token_account,
+                user,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                arcium_program: arcium.program,
+                clock: arcium.clock,
+                cluster: arcium.cluster,
+                comp_def: Circuit::Withdraw.comp_def(),
+                mempool: arcium.mempool,
+                mxe: arcium.mxe,
+                pool: arcium.pool,
+                payer,
+            },
+            blackbox::instruction::Withdraw {
+                computation_offset: 77,
+                amount: 40,
+            },
+        )
+    );
+
+    assert_eq!(
+        blackbox.withdraw_all(&computation, &user, &token_account),
+        anchor_instruction(
+            blackbox::accounts::WithdrawAll {
+                blackbox: blackbox.address,
+                config: pda::program_config(),
+                mapping_account,
+                op_record: pda::op_record(&blackbox.address, 77),
+                vault: blackbox.vault,
+                destination: token_account,
+                user,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                arcium_program: arcium.program,
+                clock: arcium.clock,
+                cluster: arcium.cluster,
+                comp_def: Circuit::WithdrawAll.comp_def(),
+                mempool: arcium.mempool,
+                mxe: arcium.mxe,
+                pool: arcium.pool,
+                payer,
+            },
+            blackbox::instruction::WithdrawAll { computation_offset: 77 },
+        )
+    );
+
+    assert_eq!(
+        blackbox.reveal_balance(&computation, &user, &[5; 32], 14),
+        anchor_instruction(
+            blackbox::accounts::RevealBalance {
+                blackbox: blackbox.address,
+                config: pda::program_config(),
+                mapping_account,
+                balance_reveal: pda::balance_reveal(&blackbox.address, 77),
+                consumed_nonce: pda::consumed_nonce(&user, 14),
+                owner: user,
+                system_program: system_program::ID,
+                arcium_program: arcium.program,
+                clock: arcium.clock,
+                cluster: arcium.cluster,
+                comp_def: Circuit::RevealBalance.comp_def(),
+                mempool: arcium.mempool,
+                mxe: arcium.mxe,
+                pool: arcium.pool,
+                payer,
+            },
+            blackbox::instruction::RevealBalance {
+                computation_offset: 77,
+                client_key: [5; 32],
+                nonce: 14,
+            },
+        )
+    );
+
+    let mut prove_solvency = anchor_instruction(
+        blackbox::accounts::ProveSolvency {
+            blackbox: blackbox.address,
+            vault: blackbox.vault,
+            attestation: pda::solvency_attestation(&blackbox.address, 77),
+            system_program: system_program::ID,
+            arcium_program: arcium.program,
+            clock: arcium.clock,
+            cluster: arcium.cluster,
+            comp_def: Circuit::ProveSolvency.comp_def(),
+            mempool: arcium.mempool,
+            mxe: arcium.mxe,
+            pool: arcium.pool,
+            payer,
+        },
+        blackbox::instruction::ProveSolvency {
+            computation_offset: 77,
+        },
+    );
+    for index in 0..2 {
+        let mapping_account = blackbox.mapping_account(index);
+        prove_solvency
+            .accounts
+            .push(AccountMeta::new_readonly(mapping_account, false));
+    }
+    assert_eq!(blackbox.prove_solvency(&payer, 77, 2), prove_solvency);
+
+    // Each range takes at most `MAX_SOLVENCY_SHARDS` mapping accounts, from the first not yet
+    // summed
+    let mut continue_solvency_proof = anchor_instruction(
+        blackbox::accounts::ContinueSolvencyProof {
+            blackbox: blackbox.address,
+            attestation: pda::solvency_attestation(&blackbox.address, 77),
+            system_program: system_program::ID,
+            arcium_program: arcium.program,
+            clock: arcium.clock,
+            cluster: arcium.cluster,
+            comp_def: Circuit::ProveSolvency.comp_def(),
+            mempool: arcium.mempool,
+            mxe: arcium.mxe,
+            pool: arcium.pool,
+            payer,
+        },
+        blackbox::instruction::ContinueSolvencyProof {
+            computation_offset: 78,
+        },
+    );
+    for index in 4..8 {
+        let mapping_account = blackbox.mapping_account(index);
+        continue_solvency_proof
+            .accounts
+            .push(AccountMeta::new_readonly(mapping_account, false));
+    }
+    assert_eq!(
+        blackbox.continue_solvency_proof(&payer, 77, 78, 4, 9),
+        continue_solvency_proof
+    );
+}
+
+#[test]
+fn circuit_versions_match() {
+    let Setup { blackbox, payer, user, token_account, mut computation } = setup(false);
+    let arcium = blackbox.arcium;
+    let mapping_account = computation.mapping_account;
+    let authority = Pubkey::new_unique();
+
+    assert_eq!(
+        blackbox_client::arcium::LATEST_CIRCUIT_VERSION,
+        state::LATEST_CIRCUIT_VERSION
+    );
+
+    // The circuits that write the mapping back run the mapping account's version
+    computation.circuit_version = 2;
+    // The comp def is the fourth of the eight queue accounts closing the list
+    let comp_def = |instruction: Instruction| {
+        let accounts = instruction.accounts;
+        accounts[accounts.len() - 5].pubkey
+    };
+    assert_eq!(
+        comp_def(blackbox.deposit(&computation, &user, None, &token_account, 500)),
+        Circuit::DepositV2.comp_def()
+    );
+    let transfer = EncryptedTransfer {
+        enc_transfer: [[1; 32], [2; 32], [3; 32]],
+        nonce: 11,
+    };
+    assert_eq!(
+        comp_def(blackbox.transfer(&computation, &user, &transfer, &[5; 32])),
+        Circuit::TransferV2.comp_def()
+    );
+    assert_eq!(
+        comp_def(blackbox.withdraw(&computation, &user, &token_account, 40)),
+        Circuit::WithdrawV2.comp_def()
+    );
+    assert_eq!(
+        comp_def(blackbox.withdraw_all(&computation, &user, &token_account)),
+        Circuit::WithdrawAllV2.comp_def()
+    );
+    assert_eq!(
+        comp_def(blackbox.flush_op_queue(&computation)),
+        Circuit::ProcessBatchV2.comp_def()
+    );
+    // The read-only ones serve every version
+    assert_eq!(
+        comp_def(blackbox.reveal_balance(&computation, &user, &[5; 32], 9)),
+        Circuit::RevealBalance.comp_def()
+    );
+
+    assert_eq!(
+        blackbox.migrate_mapping(&authority, &payer, 77, &mapping_account, 1),
+        Some(anchor_instruction(
+            blackbox::accounts::MigrateMapping {
+                blackbox: blackbox.address,
+                config: pda::program_config(),
+                mapping_account,
+                op_record: pda::op_record(&blackbox.address, 77),
+                authority,
+                system_program: system_program::ID,
+                arcium_program: arcium.program,
+                clock: arcium.clock,
+                cluster: arcium.cluster,
+                comp_def: Circuit::MigrateMappingV2.comp_def(),
+                mempool: arcium.mempool,
+                mxe: arcium.mxe,
+                pool: arcium.pool,
+                payer,
+            },
+            blackbox::instruction::MigrateMapping { computation_offset: 77 },
+        ))
+    );
+    assert_eq!(
+        blackbox.migrate_mapping(&authority, &payer, 77, &mapping_account, 2),
+        None
+    );
+}
+
+#[test]
+fn emergency_instructions_match() {
+    let Setup { blackbox, payer, user, token_account, computation } = setup(false);
+    let arcium = blackbox.arcium;
+    let mapping_account = computation.mapping_account;
+    let export = pda::emergency_export(&mapping_account, 6, 1);
+
+    assert_eq!(
+        blackbox.export_balances(&payer, 77, &mapping_account, 6, 1),
+        anchor_instruction(
+            blackbox::accounts::ExportBalances {
+                blackbox: blackbox.address,
+                mapping_account,
+                export,
+                system_program: system_program::ID,
+                arcium_program: arcium.program,
+                clock: arcium.clock,
+                cluster: arcium.cluster,
+                comp_def: Circuit::ExportBalances.comp_def(),
+                mempool: arcium.mempool,
+                mxe: arcium.mxe,
+                pool: arcium.pool,
+                payer,
+            },
+            blackbox::instruction::ExportBalances {
+                computation_offset: 77,
+                page: 1,
+            },
+        )
+    );
+
+    let exported = EmergencyExport {
+        bump: 255,
+        blackbox: blackbox.address,
+        mapping_account,
+        mapping_version: 6,
+        page: 1,
+        computation_offset: 77,
+        nonce: 1 << 100,
+        exported: true,
+        entries: Vec::new(),
+        more: false,
+    };
+    assert_eq!(
+        blackbox.claim_emergency(&payer, &user, &token_account, &exported, 3, 450, u128::MAX),
+        anchor_instruction(
+            blackbox::accounts::ClaimEmergency {
+                blackbox: blackbox.address,
+                mapping_account,
+                export,
+                claimed: pda::claimed_balance(&mapping_account, &user),
+                vault: blackbox.vault,
+                destination: token_account,
+                owner: user,
+                payer,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+            },
+            blackbox::instruction::ClaimEmergency {
+                entry: 3,
+                balance: 450,
+                salt: u128::MAX,
+            },
+        )
+    );
+}
+
+#[test]
+fn queue_instructions_match() {
+    let Setup { blackbox, payer, user, token_account, computation } = setup(true);
+    let arcium = blackbox.arcium;
+    let mapping_account = computation.mapping_account;
+    let op_queue = pda::op_queue(&mapping_account);
+    let transfer = EncryptedTransfer {
+        enc_transfer: [[4; 32], [5; 32], [6; 32]],
+        nonce: 14,
+    };
+
+    assert_eq!(
+        blackbox.enqueue_deposit(
+            &mapping_account,
+            &user,
+            Some(&payer),
+            &token_account,
+            5,
+            &[7; 32]
+        ),
+        anchor_instruction(
+            blackbox::accounts::EnqueueDeposit {
+                blackbox: blackbox.address,
+                config: pda::program_config(),
+                op_queue,
+                user_token_account: token_account,
+                vault: blackbox.vault,
+                treasury: blackbox.treasury,
+                user,
+                deposit_approver: Some(payer),
+                token_program: spl_token::ID,
+            },
+            blackbox::instruction::EnqueueDeposit {
+                amount: 5,
+                client_key: [7; 32],
+            },
+        )
+    );
+    assert_eq!(
+        blackbox.enqueue_transfer(&payer, &mapping_account, &user, &transfer, &[7; 32]),
+        anchor_instruction(
+            blackbox::accounts::EnqueueTransfer {
+                blackbox: blackbox.address,
+                config: pda::program_config(),
+                op_queue,
+                consumed_nonce: pda::consumed_nonce(&user, 14),
+                user,
+                payer,
+                system_program: system_program::ID,
+            },
+            blackbox::instruction::EnqueueTransfer {
+                enc_transfer: [[4; 32], [5; 32], [6; 32]],
+                client_key: [7; 32],
+                nonce: 14,
+            },
+        )
+    );
+    assert_eq!(
+        blackbox.enqueue_withdraw(&mapping_account, &user, &token_account, 6, &[7; 32]),
+        anchor_instruction(
+            blackbox::accounts::EnqueueWithdraw {
+                blackbox: blackbox.address,
+                config: pda::program_config(),
+                op_queue,
+                destination: token_account,
+                user,
+            },
+            blackbox::instruction::EnqueueWithdraw {
+                amount: 6,
+                client_key: [7; 32],
+            },
+        )
+    );
+    assert_eq!(
+        blackbox.flush_op_queue(&computation),
+        anchor_instruction(
+            blackbox::accounts::FlushOpQueue {
+                blackbox: blackbox.address,
+                config: pda::program_config(),
+                mapping_account,
+                op_queue,
+                op_record: pda::op_record(&blackbox.address, 77),
+                vault: blackbox.vault,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                arcium_program: arcium.program,
+                clock: arcium.clock,
+                cluster: arcium.cluster,
+                comp_def: Circuit::ProcessBatch.comp_def(),
+                mempool: arcium.mempool,
+                mxe: arcium.mxe,
+                pool: arcium.pool,
+                payer,
+            },
+            blackbox::instruction::FlushOpQueue {
+                computation_offset: 77,
+            },
+        )
+    );
+    assert_eq!(
+        blackbox.requeue_stale_op(&mapping_account, 77),
+        anchor_instruction(
+            blackbox::accounts::RequeueStaleOp {
+                op_record: pda::op_record(&blackbox.address, 77),
+                op_queue,
+            },
+            blackbox::instruction::RequeueStaleOp {},
+        )
+    );
+    assert_eq!(
+        blackbox.requeue_stuck_batch(&mapping_account, 77),
+        anchor_instruction(
+            blackbox::accounts::RequeueStuckBatch {
+                op_queue,
+                op_record: pda::op_record(&blackbox.address, 77),
+            },
+            blackbox::instruction::RequeueStuckBatch {},
+        )
+    );
+    assert_eq!(
+        blackbox.refund_deposit(77, &token_account),
+        anchor_instruction(
+            blackbox::accounts::RefundDeposit {
+                blackbox: blackbox.address,
+                op_record: pda::op_record(&blackbox.address, 77),
+                vault: blackbox.vault,
+                destination: token_account,
+                token_program: spl_token::ID,
+            },
+            blackbox::instruction::RefundDeposit {},
+        )
+    );
+    let mut refund_queued_deposits = anchor_instruction(
+        blackbox::accounts::RefundQueuedDeposits {
+            blackbox: blackbox.address,
+            op_queue,
+            vault: blackbox.vault,
+            token_program: spl_token::ID,
+        },
+        blackbox::instruction::RefundQueuedDeposits {},
+    );
+    refund_queued_deposits
+        .accounts
+        .push(AccountMeta::new(token_account, false));
+    assert_eq!(
+        blackbox.refund_queued_deposits(&mapping_account, &[token_account]),
+        refund_queued_deposits
+    );
+    assert_eq!(
+        blackbox.close_op_record(&payer, 77),
+        anchor_instruction(
+            blackbox::accounts::CloseOpRecord {
+                op_record: pda::op_record(&blackbox.address, 77),
+                payer,
+            },
+            blackbox::instruction::CloseOpRecord {},
+        )
+    );
+    assert_eq!(
+        instructions::close_consumed_nonce(&user, &payer, 14),
+        anchor_instruction(
+            blackbox::accounts::CloseConsumedNonce {
+                consumed_nonce: pda::consumed_nonce(&user, 14),
+                sender: user,
+                payer,
+            },
+            blackbox::instruction::CloseConsumedNonce { nonce: 14 },
+        )
+    );
+}
+
+#[test]
+fn accounts_decode() {
+    let token_mint = Pubkey::new_unique();
+    let vault = Pubkey::new_unique();
+    let treasury = Pubkey::new_unique();
+
+    let data = serialize(&state::BlackboxAccount {
+        bump: 254,
+        token_mint,
+        vault,
+        treasury,
+        mapping_account_count: 3,
+        paused: state::PauseFlags { deposit: false, transfer: true, withdraw: false },
+        fees: state::Fees { deposit_bps: 10, withdraw_bps: 20 },
+        limits: state::Limits {
+            min_deposit: 1,
+            max_deposit: 2,
+            min_withdraw: 3,
+            max_withdraw: 4,
+            epoch_outflow_cap: 5,
+        },
+        outflow: state::EpochOutflow { epoch: 6, amount: 7 },
+        emergency: true,
+        circuit_version: 2,
+        shard_capacity: 48,
+        policy: state::PolicyHooks { deposit_approver: Some(vault) },
+        modes: state::ModeFlags { queued_only: true },
+        cluster_offset: 1 << 20,
+    });
+    assert_eq!(
+        BlackboxAccount::decode(&data).unwrap(),
+        BlackboxAccount {
+            bump: 254,
+            token_mint,
+            vault,
+            treasury,
+            mapping_account_count: 3,
+            paused: PauseFlags { deposit: false, transfer: true, withdraw: false },
+            fees: Fees { deposit_bps: 10, withdraw_bps: 20 },
+            limits: Limits {
+                min_deposit: 1,
+                max_deposit: 2,
+                min_withdraw: 3,
+                max_withdraw: 4,
+                epoch_outflow_cap: 5,
+            },
+            outflow: EpochOutflow { epoch: 6, amount: 7 },
+            emergency: true,
+            circuit_version: 2,
+            shard_capacity: 48,
+            policy: PolicyHooks { deposit_approver: Some(vault) },
+            modes: ModeFlags { queued_only: true },
+            cluster_offset: 1 << 20,
+        }
+    );
+    assert_eq!(MappingAccount::decode(&data), Err(DecodeError::WrongAccountType));
+
+    let authority = Pubkey::new_unique();
+    let data = serialize(&state::ProgramConfig {
+        bump: 253,
+        authority,
+        paused: state::PauseFlags { deposit: true, transfer: false, withdraw: false },
+        max_fee_bps: 500,
+        pending_authority: Some(token_mint),
+        timelock_slots: 1 << 40,
+        next_change_id: 9,
+        max_shard_capacity: 64,
+    });
+    assert_eq!(
+        ProgramConfig::decode(&data).unwrap(),
+        ProgramConfig {
+            bump: 253,
+            authority,
+            paused: PauseFlags { deposit: true, transfer: false, withdraw: false },
+            max_fee_bps: 500,
+            pending_authority: Some(token_mint),
+            timelock_slots: 1 << 40,
+            next_change_id: 9,
+            max_shard_capacity: 64,
+        }
+    );
+
+    let data = serialize(&state::PendingChange {
+        bump: 252,
+        id: 8,
+        blackbox: Some(vault),
+        change: state::ParameterChange::Fees(state::Fees { deposit_bps: 1, withdraw_bps: 2 }),
+        executable_slot: 1_000,
+        payer: authority,
+    });
+    assert_eq!(data[..8], PendingChange::discriminator());
+    assert_eq!(
+        PendingChange::decode(&data).unwrap(),
+        PendingChange {
+            bump: 252,
+            id: 8,
+            blackbox: Some(vault),
+            change: ParameterChange::Fees(Fees { deposit_bps: 1, withdraw_bps: 2 }),
+            executable_slot: 1_000,
+            payer: authority,
+        }
+    );
+
+    let mut data = serialize(&state::MappingAccount {
+        index: 1,
+        token_mint,
+        nonce: 1 << 100,
+        version: 7,
+        circuit_version: 2,
+        capacity: 300,
+        encrypted_pubkeys: vec![[8; 32]],
+        encrypted_balances: vec![[9; 32]],
+    });
+    // Mapping accounts are allocated larger than their contents
+    data.resize(data.len() + 64, 0);
+    let mapping = MappingAccount::decode(&data).unwrap();
+    assert_eq!(mapping.nonce, 1 << 100);
+    assert_eq!(mapping.version, 7);
+    assert_eq!(mapping.circuit_version, 2);
+    assert_eq!(mapping.capacity, 300);
+    assert_eq!(mapping.encrypted_pubkeys, vec![[8; 32]]);
+    assert_eq!(mapping.encrypted_balances, vec![[9; 32]]);
+
+    let owner = Pubkey::new_unique();
+    let payer = Pubkey::new_unique();
+    let op = state::QueuedOp {
+        kind: state::OpKind::Withdraw,
+        owner,
+        client_key: [5; 32],
+        enc_transfer: [[0; 32]; 3],
+        amount: 100,
+        nonce: 0,
+        destination: Pubkey::default(),
+        epoch: 3,
+    };
+    let data = serialize(&state::OpRecord {
+        bump: 1,
+        blackbox: Pubkey::new_unique(),
+        mapping_account: Pubkey::new_unique(),
+        computation_offset: 42,
+        mapping_version: 6,
+        owner,
+        payer,
+        amount: 100,
+        epoch: 3,
+        destination: Pubkey::default(),
+        status: state::OpStatus::Requeued,
+        requeue: Some(op),
+    });
+    assert_eq!(data[..8], OpRecord::discriminator());
+    assert_eq!(
+        data[OpRecord::OWNER_OFFSET..OpRecord::OWNER_OFFSET + 32],
+        owner.to_bytes()
+    );
+    let record = OpRecord::decode(&data).unwrap();
+    assert_eq!(record.computation_offset, 42);
+    assert_eq!(record.status, OpStatus::Requeued);
+    assert_eq!(record.payer, payer);
+    let requeue = record.requeue.unwrap();
+    assert_eq!(requeue.kind, OpKind::Withdraw);
+    assert_eq!(requeue.amount, 100);
+    assert_eq!(requeue.client_key, [5; 32]);
+    assert_eq!(requeue.epoch, 3);
+
+    assert_eq!(OpRecord::decode(&data[..data.len() - 1]), Err(DecodeError::TooShort));
+
+    let data = serialize(&state::OpQueueAccount {
+        bump: 3,
+        blackbox: Pubkey::new_unique(),
+        mapping_account: Pubkey::new_unique(),
+        pending: vec![],
+        in_flight: vec![op],
+        in_flight_nonce: 1 << 80,
+        in_flight_offset: 44,
+        in_flight_slot: 900,
+        results: vec![state::OpResult {
+            owner,
+            enc_status: [6; 32],
+            nonce: 1 << 70,
+        }],
+    });
+    let op_queue = OpQueueAccount::decode(&data).unwrap();
+    assert_eq!(op_queue.in_flight.len(), 1);
+    assert_eq!(op_queue.in_flight_offset, 44);
+    assert_eq!(op_queue.in_flight_slot, 900);
+    assert_eq!(op_queue.results[0].enc_status, [6; 32]);
+
+    let data = serialize(&state::BalanceReveal {
+        bump: 2,
+        blackbox: Pubkey::new_unique(),
+        mapping_account: Pubkey::new_unique(),
+        computation_offset: 43,
+        mapping_version: 6,
+        owner,
+        client_key: [3; 32],
+        nonce: 1 << 90,
+        enc_balance: Some([4; 32]),
+    });
+    let reveal = BalanceReveal::decode(&data).unwrap();
+    assert_eq!(reveal.owner, owner);
+    assert_eq!(reveal.client_key, [3; 32]);
+    assert_eq!(reveal.nonce, 1 << 90);
+    assert_eq!(reveal.enc_balance, Some([4; 32]));
+
+    let data = serialize(&state::SolvencyAttestation {
+        bump: 3,
+        blackbox: Pubkey::new_unique(),
+        computation_offset: 44,
+        vault_amount: 5_000,
+        shards: 6,
+        summed_shards: 4,
+        queued_range: Some(2),
+        enc_total: [6; 32],
+        total_nonce: 1 << 100,
+        requested_slot: 90,
+        solvent: Some(false),
+        attested_slot: 95,
+    });
+    let attestation = SolvencyAttestation::decode(&data).unwrap();
+    assert_eq!(attestation.vault_amount, 5_000);
+    assert_eq!(attestation.shards, 6);
+    assert_eq!(attestation.summed_shards, 4);
+    assert_eq!(attestation.queued_range, Some(2));
+    assert_eq!(attestation.enc_total, [6; 32]);
+    assert_eq!(attestation.total_nonce, 1 << 100);
+    assert_eq!(attestation.requested_slot, 90);
+    assert_eq!(attestation.solvent, Some(false));
+    assert_eq!(attestation.attested_slot, 95);
+
+    let mapping_account = Pubkey::new_unique();
+    let data = serialize(&state::EmergencyExport {
+        bump: 2,
+        blackbox: Pubkey::new_unique(),
+        mapping_account,
+        mapping_version: 12,
+        page: 1,
+        computation_offset: 45,
+        nonce: 1 << 100,
+        exported: true,
+        entries: vec![state::ExportedBalance {
+            enc_claim: [[5; 32], [6; 32]],
+            commitment: [7; 32],
+        }],
+        more: true,
+    });
+    let export = EmergencyExport::decode(&data).unwrap();
+    assert_eq!(export.mapping_account, mapping_account);
+    assert_eq!(export.mapping_version, 12);
+    assert_eq!(export.page, 1);
+    assert_eq!(export.nonce, 1 << 100);
+    assert!(export.exported && export.more);
+    assert_eq!(
+        export.entries,
+        vec![ExportedBalance {
+            enc_claim: [[5; 32], [6; 32]],
+            commitment: [7; 32],
+        }]
+    );
+}
+
+#[test]
+fn events_decode() {
+    let blackbox = Pubkey::new_unique();
+    let mapping_account = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+
+    let queued = events::ComputationQueued {
+        blackbox,
+        mapping_account,
+        computation_offset: 42,
+        kind: events::ComputationKind::Withdraw,
+        owner,
+        amount: Some(100),
+    }
+    .data();
+    assert_eq!(
+        Event::decode(&queued),
+        Ok(Some(Event::ComputationQueued {
+            blackbox,
+            mapping_account,
+            computation_offset: 42,
+            kind: ComputationKind::Withdraw,
+            owner,
+            amount: Some(100),
+        }))
+    );
+
+    let resolved = events::ComputationResolved {
+        blackbox,
+        mapping_account,
+        computation_offset: 42,
+        status: state::OpStatus::Requeued,
+    }
+    .data();
+    let enqueued = events::OperationEnqueued {
+        blackbox,
+        mapping_account,
+        kind: state::OpKind::Transfer,
+        owner,
+        amount: None,
+    }
+    .data();
+    let flushed = events::OpQueueFlushed {
+        blackbox,
+        mapping_account,
+        computation_offset: 43,
+        operations: 5,
+    }
+    .data();
+    let paid_out = events::VaultPaidOut {
+        blackbox,
+        destination: owner,
+        amount: 7,
+    }
+    .data();
+
+    for target in [None, Some(blackbox)] {
+        let paused = events::PauseUpdated {
+            blackbox: target,
+            paused: state::PauseFlags { deposit: false, transfer: false, withdraw: true },
+        }
+        .data();
+        assert_eq!(
+            Event::decode(&paused),
+            Ok(Some(Event::PauseUpdated {
+                blackbox: target,
+                paused: PauseFlags { deposit: false, transfer: false, withdraw: true },
+            }))
+        );
+    }
+
+    let fee = events::FeeCharged {
+        blackbox,
+        kind: state::OpKind::Withdraw,
+        amount: 3,
+    }
+    .data();
+    assert_eq!(
+        Event::decode(&fee),
+        Ok(Some(Event::FeeCharged {
+            blackbox,
+            kind: OpKind::Withdraw,
+            amount: 3,
+        }))
+    );
+
+    let change = events::ChangeQueued {
+        id: 3,
+        blackbox: None,
+        change: state::ParameterChange::Timelock(150),
+        executable_slot: 1_150,
+    }
+    .data();
+    assert_eq!(
+        Event::decode(&change),
+        Ok(Some(Event::ChangeQueued {
+            id: 3,
+            blackbox: None,
+            change: ParameterChange::Timelock(150),
+            executable_slot: 1_150,
+        }))
+    );
+    let proposed = events::AuthorityProposed {
+        authority: owner,
+        proposed: Some(blackbox),
+    }
+    .data();
+    assert_eq!(
+        Event::decode(&proposed),
+        Ok(Some(Event::AuthorityProposed {
+            authority: owner,
+            proposed: Some(blackbox),
+        }))
+    );
+
+    let attested = events::SolvencyAttested {
+        blackbox,
+        attestation: owner,
+        computation_offset: 44,
+        solvent: true,
+        attested_slot: 95,
+    }
+    .data();
+    assert_eq!(
+        Event::decode(&attested),
+        Ok(Some(Event::SolvencyAttested {
+            blackbox,
+            attestation: owner,
+            computation_offset: 44,
+            solvent: true,
+            attested_slot: 95,
+        }))
+    );
+    let requested = events::SolvencyRequested {
+        blackbox,
+        attestation: owner,
+        computation_offset: 44,
+        vault_amount: 5_000,
+        shards: 2,
+    }
+    .data();
+    assert_eq!(
+        Event::decode(&requested),
+        Ok(Some(Event::SolvencyRequested {
+            blackbox,
+            attestation: owner,
+            computation_offset: 44,
+            vault_amount: 5_000,
+            shards: 2,
+        }))
+    );
+
+    let exported = events::BalancesExported {
+        blackbox,
+        mapping_account,
+        export: owner,
+        page: 2,
+        entries: 8,
+        more: true,
+    }
+    .data();
+    assert_eq!(
+        Event::decode(&exported),
+        Ok(Some(Event::BalancesExported {
+            blackbox,
+            mapping_account,
+            export: owner,
+            page: 2,
+            entries: 8,
+            more: true,
+        }))
+    );
+    let claimed = events::EmergencyClaimed {
+        blackbox,
+        mapping_account,
+        owner,
+        amount: 450,
+    }
+    .data();
+    assert_eq!(
+        Event::decode(&claimed),
+        Ok(Some(Event::EmergencyClaimed {
+            blackbox,
+            mapping_account,
+            owner,
+            amount: 450,
+        }))
+    );
+    let refunded = events::QueuedDepositRefunded {
+        blackbox,
+        mapping_account,
+        owner,
+        destination: owner,
+        amount: 300,
+    }
+    .data();
+    assert_eq!(
+        Event::decode(&refunded),
+        Ok(Some(Event::QueuedDepositRefunded {
+            blackbox,
+            mapping_account,
+            owner,
+            destination: owner,
+            amount: 300,
+        }))
+    );
+    assert_eq!(
+        Event::decode(&events::EmergencyEntered { blackbox }.data()),
+        Ok(Some(Event::EmergencyEntered { blackbox }))
+    );
+
+    assert_eq!(
+        Event::decode(
+            &events::CircuitVersionUpdated {
+                blackbox,
+                circuit_version: 2,
+            }
+            .data()
+        ),
+        Ok(Some(Event::CircuitVersionUpdated {
+            blackbox,
+            circuit_version: 2,
+        }))
+    );
+    let migrated = events::MappingMigrated {
+        blackbox,
+        mapping_account,
+        circuit_version: 2,
+    }
+    .data();
+    assert_eq!(
+        Event::decode(&migrated),
+        Ok(Some(Event::MappingMigrated {
+            blackbox,
+            mapping_account,
+            circuit_version: 2,
+        }))
+    );
+
+    let initialized = events::BlackboxInitialized {
+        blackbox,
+        token_mint: mapping_account,
+        vault: owner,
+        treasury: owner,
+        params: state::InitBlackboxParams {
+            shard_capacity: 16,
+            fees: state::Fees { deposit_bps: 1, withdraw_bps: 2 },
+            limits: state::Limits::default(),
+            circuit_version: 1,
+            policy: state::PolicyHooks { deposit_approver: Some(owner) },
+            modes: state::ModeFlags { queued_only: false },
+            cluster_offset: 2,
+        },
+    }
+    .data();
+    assert_eq!(
+        Event::decode(&initialized),
+        Ok(Some(Event::BlackboxInitialized {
+            blackbox,
+            token_mint: mapping_account,
+            vault: owner,
+            treasury: owner,
+            params: InitBlackboxParams {
+                shard_capacity: 16,
+                fees: Fees { deposit_bps: 1, withdraw_bps: 2 },
+                limits: Limits::default(),
+                circuit_version: 1,
+                policy: PolicyHooks { deposit_approver: Some(owner) },
+                modes: ModeFlags { queued_only: false },
+                cluster_offset: 2,
+            },
+        }))
+    );
+    assert_eq!(
+        Event::decode(&events::MaxShardCapacityUpdated { max_shard_capacity: 32 }.data()),
+        Ok(Some(Event::MaxShardCapacityUpdated { max_shard_capacity: 32 }))
+    );
+
+    let logged = |program: Pubkey, data: &[u8]| {
+        vec![
+            format!("Program {program} invoke [1]"),
+            format!("Program data: {}", STANDARD.encode(data)),
+            format!("Program {program} success"),
+        ]
+    };
+    let mut logs = logged(PROGRAM_ID, &resolved);
+    // A CPI'd program logging blackbox-shaped data isn't the blackbox
+    logs.splice(
+        2..2,
+        logged(Pubkey::new_unique(), &paid_out)
+            .into_iter()
+            .map(|line| line.replace("[1]", "[2]")),
+    );
+    logs.extend(logged(PROGRAM_ID, &enqueued));
+    logs.extend(logged(PROGRAM_ID, &flushed));
+    logs.extend(logged(PROGRAM_ID, &[0; 16]));
+    logs.extend(logged(PROGRAM_ID, &paid_out));
+
+    let found = client_events::from_logs(&logs).unwrap();
+    assert!(!found.truncated);
+    assert_eq!(
+        found.events,
+        vec![
+            Event::ComputationResolved {
+                blackbox,
+                mapping_account,
+                computation_offset: 42,
+                status: OpStatus::Requeued,
+            },
+            Event::OperationEnqueued {
+                blackbox,
+                mapping_account,
+                kind: OpKind::Transfer,
+                owner,
+                amount: None,
+            },
+            Event::OpQueueFlushed {
+                blackbox,
+                mapping_account,
+                computation_offset: 43,
+                operations: 5,
+            },
+            Event::VaultPaidOut {
+                blackbox,
+                destination: owner,
+                amount: 7,
+            },
+        ]
+    );
+
+    assert_eq!(Event::decode(&flushed[..flushed.len() - 1]), Err(DecodeError::TooShort));
+    logs.push("Log truncated".into());
+    assert!(client_events::from_logs(&logs).unwrap().truncated);
+}
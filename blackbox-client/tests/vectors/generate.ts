@@ -0,0 +1,66 @@
+// Writes rescue.json: encryptions made by @arcium-hq/arcium-sdk, which the Rust client's
+// `tests/encryption.rs` checks it reproduces byte for byte.
+//
+//   yarn vectors
+import {
+  RescueCipher,
+  x25519GetPublicKey,
+  x25519GetSharedSecretWithMXE,
+  deserializeLE,
+} from "@arcium-hq/arcium-sdk";
+import { createHash } from "crypto";
+import * as fs from "fs";
+import * as path from "path";
+
+const hex = (bytes: Uint8Array | number[]) => Buffer.from(bytes).toString("hex");
+
+// A field element as the 32-byte little-endian block the Rust side reads
+const le = (value: bigint) => {
+  const out = new Uint8Array(32);
+  for (let i = 0; i < 32; i++) {
+    out[i] = Number((value >> BigInt(8 * i)) & BigInt(255));
+  }
+  return out;
+};
+
+// Deterministic bytes, so the vectors only change when the SDK does
+const bytes = (label: string, length: number) =>
+  new Uint8Array(
+    createHash("sha256").update(label).digest().subarray(0, length)
+  );
+
+const mxePublicKey = [0, 1, 2, 3, 4].map((i) =>
+  x25519GetPublicKey(bytes(`mxe ${i}`, 32))
+);
+
+const plaintexts: bigint[][] = [
+  [BigInt(0)],
+  [BigInt(350)],
+  [BigInt("18446744073709551615")],
+  [BigInt(1)],
+  // A recipient public key, as a little-endian integer below p
+  [deserializeLE(bytes("recipient", 31))],
+  // Crosses a keystream block
+  [1, 2, 3, 4, 5, 6, 7].map(BigInt),
+];
+
+const cases = plaintexts.map((plaintext, i) => {
+  const clientSecret = bytes(`client ${i}`, 32);
+  const nonce = bytes(`nonce ${i}`, 16);
+  const cipher = new RescueCipher(
+    x25519GetSharedSecretWithMXE(clientSecret, mxePublicKey)
+  );
+  return {
+    client_secret: hex(clientSecret),
+    client_public_key: hex(x25519GetPublicKey(clientSecret)),
+    nonce: deserializeLE(nonce).toString(),
+    plaintext: plaintext.map((value) => hex(le(value))),
+    ciphertext: cipher.encrypt(plaintext, nonce).map(hex),
+  };
+});
+
+fs.writeFileSync(
+  path.join(__dirname, "rescue.json"),
+  JSON.stringify({ mxe_public_key: mxePublicKey.map(hex), cases }, null, 2) +
+    "\n"
+);
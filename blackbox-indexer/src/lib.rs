@@ -0,0 +1,28 @@
+//! Indexer for the blackbox program.
+//!
+//! [`source`] fetches the program's transactions from an RPC node, oldest first, and
+//! [`store`] folds the events in their logs into a SQLite database: the blackboxes and their
+//! shards, tokens moving in and out of each vault and treasury, every computation's status and
+//! the contents of each operation queue. Only public state is indexed; balances and transfer
+//! amounts stay encrypted.
+//!
+//! Transactions are applied one at a time, each in a single SQLite transaction together with
+//! the cursor, so a crashed indexer resumes where it stopped.
+
+use serde::{Deserialize, Serialize};
+
+pub mod source;
+pub mod store;
+
+pub use store::Store;
+
+/// A transaction that touched the program, as fetched or recorded in a fixture
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgramTransaction {
+    pub slot: u64,
+    pub signature: String,
+    pub block_time: Option<i64>,
+    /// The transaction failed, so none of its events took effect
+    pub failed: bool,
+    pub logs: Vec<String>,
+}
@@ -0,0 +1,133 @@
+//! `blackbox-indexer`: follows the blackbox program into a SQLite database.
+//!
+//! `run` backfills from `--from-slot` on first start, then polls for new finalized
+//! transactions, resuming from the database's cursor after a restart. `record` saves a slot
+//! range of transactions, e.g. from a local validator, as a JSON fixture and `replay` applies
+//! one to a database, without an RPC node.
+
+use std::fs;
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::Duration;
+
+use anyhow::{Context as _, Result};
+use blackbox_indexer::{
+    source::{Source, Start},
+    ProgramTransaction, Store,
+};
+use clap::{Parser, Subcommand};
+
+#[derive(Parser)]
+#[command(name = "blackbox-indexer", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Index the program's transactions, then keep following new ones
+    Run {
+        #[arg(long)]
+        db: PathBuf,
+        #[arg(long, short = 'u', default_value = "http://127.0.0.1:8899")]
+        url: String,
+        /// Slot to backfill from when the database is new
+        #[arg(long, default_value_t = 0)]
+        from_slot: u64,
+        /// Seconds between polls for new transactions
+        #[arg(long, default_value_t = 10)]
+        poll_interval: u64,
+    },
+    /// Save the program's transactions from a slot on as a fixture
+    Record {
+        #[arg(long, short = 'u', default_value = "http://127.0.0.1:8899")]
+        url: String,
+        #[arg(long, default_value_t = 0)]
+        from_slot: u64,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Apply a fixture's transactions to a database
+    Replay {
+        #[arg(long)]
+        db: PathBuf,
+        fixture: PathBuf,
+    },
+}
+
+fn run(db: PathBuf, url: String, from_slot: u64, poll_interval: Duration) -> Result<()> {
+    let mut store = Store::open(&db)?;
+    let source = Source::new(url);
+    loop {
+        // RPC errors are usually transient, so they're retried on the next poll
+        if let Err(err) = sync(&mut store, &source, from_slot) {
+            eprintln!("sync failed: {err:#}");
+        }
+        sleep(poll_interval);
+    }
+}
+
+/// Applies every transaction since the cursor, or since `from_slot` if there's none yet.
+fn sync(store: &mut Store, source: &Source, from_slot: u64) -> Result<()> {
+    let start = match store.cursor()? {
+        Some(cursor) => Start::After(cursor.signature.parse()?),
+        None => Start::Slot(from_slot),
+    };
+    let signatures = source.signatures(&start)?;
+    for status in &signatures {
+        let transaction = source.transaction(status)?;
+        store.apply(&transaction)?;
+    }
+    if let Some(last) = signatures.last() {
+        eprintln!(
+            "indexed {} transactions, up to slot {}",
+            signatures.len(),
+            last.slot
+        );
+    }
+    Ok(())
+}
+
+fn record(url: String, from_slot: u64, out: PathBuf) -> Result<()> {
+    let source = Source::new(url);
+    let transactions = source
+        .signatures(&Start::Slot(from_slot))?
+        .iter()
+        .map(|status| source.transaction(status))
+        .collect::<Result<Vec<_>>>()?;
+    fs::write(&out, serde_json::to_string_pretty(&transactions)?)
+        .with_context(|| format!("writing {}", out.display()))?;
+    eprintln!("recorded {} transactions", transactions.len());
+    Ok(())
+}
+
+fn replay(db: PathBuf, fixture: PathBuf) -> Result<()> {
+    let fixture =
+        fs::read_to_string(&fixture).with_context(|| format!("reading {}", fixture.display()))?;
+    let transactions: Vec<ProgramTransaction> = serde_json::from_str(&fixture)?;
+    let mut store = Store::open(&db)?;
+    let mut applied = 0;
+    for transaction in &transactions {
+        applied += usize::from(store.apply(transaction)?);
+    }
+    eprintln!("applied {applied} of {} transactions", transactions.len());
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Run {
+            db,
+            url,
+            from_slot,
+            poll_interval,
+        } => run(db, url, from_slot, Duration::from_secs(poll_interval)),
+        Command::Record {
+            url,
+            from_slot,
+            out,
+        } => record(url, from_slot, out),
+        Command::Replay { db, fixture } => replay(db, fixture),
+    }
+}
@@ -0,0 +1,115 @@
+//! The program's transactions, fetched from an RPC node.
+
+use anyhow::{Context as _, Result};
+use blackbox_client::PROGRAM_ID;
+use solana_client::{
+    rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient},
+    rpc_config::RpcTransactionConfig,
+    rpc_response::RpcConfirmedTransactionStatusWithSignature,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Signature};
+use solana_transaction_status::{option_serializer::OptionSerializer, UiTransactionEncoding};
+
+use crate::ProgramTransaction;
+
+/// Signatures fetched per `getSignaturesForAddress` request, the most nodes allow
+const PAGE_SIZE: usize = 1000;
+
+/// Fetches finalized transactions only, so nothing indexed is rolled back.
+pub struct Source {
+    rpc: RpcClient,
+}
+
+/// Where fetching starts
+pub enum Start {
+    /// After the transaction with this signature
+    After(Signature),
+    /// At this slot
+    Slot(u64),
+}
+
+impl Source {
+    pub fn new(url: String) -> Self {
+        Self {
+            rpc: RpcClient::new_with_commitment(url, CommitmentConfig::finalized()),
+        }
+    }
+
+    /// Signatures of the program's transactions since `start`, oldest first
+    pub fn signatures(
+        &self,
+        start: &Start,
+    ) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        let until = match start {
+            Start::After(signature) => Some(*signature),
+            Start::Slot(_) => None,
+        };
+        // Pages come newest first; walk back until `until` or the start slot
+        let mut signatures = Vec::new();
+        let mut before = None;
+        loop {
+            let page = self.rpc.get_signatures_for_address_with_config(
+                &PROGRAM_ID,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until,
+                    limit: Some(PAGE_SIZE),
+                    commitment: Some(CommitmentConfig::finalized()),
+                },
+            )?;
+            let full = page.len() == PAGE_SIZE;
+            let Some(last) = page.last() else {
+                break;
+            };
+            before = Some(last.signature.parse()?);
+            let reached_start = matches!(start, Start::Slot(slot) if last.slot < *slot);
+            signatures.extend(page);
+            if !full || reached_start {
+                break;
+            }
+        }
+        if let Start::Slot(slot) = start {
+            signatures.retain(|status| status.slot >= *slot);
+        }
+        signatures.reverse();
+        Ok(signatures)
+    }
+
+    /// Fetches the transaction `status` lists. Failed transactions aren't fetched, since none
+    /// of their events took effect.
+    pub fn transaction(
+        &self,
+        status: &RpcConfirmedTransactionStatusWithSignature,
+    ) -> Result<ProgramTransaction> {
+        let mut transaction = ProgramTransaction {
+            slot: status.slot,
+            signature: status.signature.clone(),
+            block_time: status.block_time,
+            failed: status.err.is_some(),
+            logs: Vec::new(),
+        };
+        if transaction.failed {
+            return Ok(transaction);
+        }
+
+        let fetched = self
+            .rpc
+            .get_transaction_with_config(
+                &status.signature.parse()?,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Json),
+                    commitment: Some(CommitmentConfig::finalized()),
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .with_context(|| format!("fetching transaction {}", status.signature))?;
+        let meta = fetched
+            .transaction
+            .meta
+            .with_context(|| format!("transaction {} has no status", status.signature))?;
+        if let OptionSerializer::Some(logs) = meta.log_messages {
+            transaction.logs = logs;
+        }
+        Ok(transaction)
+    }
+}
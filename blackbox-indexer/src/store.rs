@@ -0,0 +1,1079 @@
+//! SQLite store the indexed state is folded into.
+//!
+//! Addresses are stored as base58 and `u64` amounts and computation offsets as decimal text,
+//! since SQLite integers are signed 64-bit. Tables:
+//!
+//! - `cursor`: the last transaction applied, where indexing resumes
+//! - `transactions`: every transaction applied, so none is applied twice
+//! - `blackboxes` and `shards`: each blackbox's mint, vault, treasury and mapping accounts,
+//!   the slot it entered emergency mode in, and the circuit versions they moved to
+//! - `vault_flows`: every token movement into (`in`) or out of (`out`) a vault
+//! - `fee_flows`: every fee charged on a `deposit` or `withdraw` into a treasury, and every
+//!   collection (`collected`) out of one
+//! - `computations`: every queued computation and its latest status
+//! - `queued_operations`: operations appended to an operation queue; `pending` ones in
+//!   `position` order, `in_flight` ones in batch `batch_offset`, `processed` ones applied by
+//!   a batch, which may still have failed inside the MXE, and `refunded` deposits paid back
+//!   after emergency mode stopped the queue, or by a batch without room for their owner
+//! - `parameter_changes`: every queued parameter change, `queued` until it's `executed` or
+//!   `cancelled`
+//! - `solvency_attestations`: every solvency proof requested, and whether the vault covered
+//!   the credited balances once its callback arrived
+//! - `emergency_exports` and `emergency_claims`: pages of balances exported in emergency mode,
+//!   and the balances their owners claimed
+//!
+//! State is only complete when indexing started from the program's first transaction: a
+//! backfill from a later slot misses what happened before it.
+
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use blackbox_client::{
+    accounts::{OpKind, OpStatus, ParameterChange},
+    events::{self, ComputationKind, Event},
+};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::ProgramTransaction;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS cursor (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    slot INTEGER NOT NULL,
+    signature TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS transactions (
+    signature TEXT PRIMARY KEY,
+    slot INTEGER NOT NULL,
+    block_time INTEGER,
+    failed INTEGER NOT NULL,
+    logs_truncated INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS blackboxes (
+    address TEXT PRIMARY KEY,
+    token_mint TEXT NOT NULL,
+    vault TEXT NOT NULL,
+    treasury TEXT NOT NULL,
+    created_slot INTEGER NOT NULL,
+    emergency_slot INTEGER,
+    circuit_version INTEGER
+);
+CREATE TABLE IF NOT EXISTS shards (
+    mapping_account TEXT PRIMARY KEY,
+    blackbox TEXT NOT NULL,
+    shard_index INTEGER NOT NULL,
+    has_op_queue INTEGER NOT NULL DEFAULT 0,
+    circuit_version INTEGER,
+    created_slot INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS vault_flows (
+    signature TEXT NOT NULL,
+    event_index INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    blackbox TEXT NOT NULL,
+    direction TEXT NOT NULL CHECK (direction IN ('in', 'out')),
+    token_account TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    PRIMARY KEY (signature, event_index)
+);
+CREATE TABLE IF NOT EXISTS fee_flows (
+    signature TEXT NOT NULL,
+    event_index INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    blackbox TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('deposit', 'withdraw', 'collected')),
+    amount TEXT NOT NULL,
+    PRIMARY KEY (signature, event_index)
+);
+CREATE TABLE IF NOT EXISTS computations (
+    blackbox TEXT NOT NULL,
+    computation_offset TEXT NOT NULL,
+    mapping_account TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    amount TEXT,
+    status TEXT NOT NULL,
+    queued_slot INTEGER NOT NULL,
+    queued_signature TEXT NOT NULL,
+    resolved_slot INTEGER,
+    resolved_signature TEXT,
+    PRIMARY KEY (blackbox, computation_offset)
+);
+CREATE TABLE IF NOT EXISTS queued_operations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    mapping_account TEXT NOT NULL,
+    state TEXT NOT NULL CHECK (state IN ('pending', 'in_flight', 'processed', 'refunded')),
+    position INTEGER NOT NULL,
+    batch_offset TEXT,
+    kind TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    amount TEXT,
+    enqueued_slot INTEGER NOT NULL,
+    enqueued_signature TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS queued_operations_by_queue
+    ON queued_operations (mapping_account, state, position);
+CREATE TABLE IF NOT EXISTS parameter_changes (
+    id TEXT PRIMARY KEY,
+    blackbox TEXT,
+    kind TEXT NOT NULL,
+    executable_slot TEXT NOT NULL,
+    state TEXT NOT NULL CHECK (state IN ('queued', 'executed', 'cancelled')),
+    queued_slot INTEGER NOT NULL,
+    queued_signature TEXT NOT NULL,
+    resolved_slot INTEGER,
+    resolved_signature TEXT
+);
+CREATE TABLE IF NOT EXISTS solvency_attestations (
+    attestation TEXT PRIMARY KEY,
+    blackbox TEXT NOT NULL,
+    computation_offset TEXT NOT NULL,
+    vault_amount TEXT NOT NULL,
+    shards INTEGER NOT NULL,
+    solvent INTEGER,
+    requested_slot INTEGER NOT NULL,
+    requested_signature TEXT NOT NULL,
+    attested_slot INTEGER,
+    attested_signature TEXT
+);
+CREATE TABLE IF NOT EXISTS emergency_exports (
+    export TEXT PRIMARY KEY,
+    blackbox TEXT NOT NULL,
+    mapping_account TEXT NOT NULL,
+    page INTEGER NOT NULL,
+    entries INTEGER NOT NULL,
+    more INTEGER NOT NULL,
+    exported_slot INTEGER NOT NULL,
+    exported_signature TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS emergency_claims (
+    mapping_account TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    blackbox TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    claimed_slot INTEGER NOT NULL,
+    claimed_signature TEXT NOT NULL,
+    PRIMARY KEY (mapping_account, owner)
+);
+";
+
+/// Where indexing resumes: the last transaction applied
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cursor {
+    pub slot: u64,
+    pub signature: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Shard {
+    pub mapping_account: Pubkey,
+    pub index: u8,
+    pub has_op_queue: bool,
+    /// Circuit version the shard runs; `None` while it's the one its blackbox was created on,
+    /// which no event carries
+    pub circuit_version: Option<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Computation {
+    pub mapping_account: Pubkey,
+    pub kind: String,
+    pub owner: Pubkey,
+    pub amount: Option<u64>,
+    pub status: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueuedOperation {
+    pub state: String,
+    pub kind: String,
+    pub owner: Pubkey,
+    pub amount: Option<u64>,
+    /// Batch the operation went in flight as
+    pub batch_offset: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueuedChange {
+    pub id: u64,
+    /// Blackbox the change applies to; the program config if `None`
+    pub blackbox: Option<Pubkey>,
+    pub kind: String,
+    pub executable_slot: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attestation {
+    pub attestation: Pubkey,
+    pub computation_offset: u64,
+    pub vault_amount: u64,
+    pub shards: u8,
+    pub solvent: bool,
+    pub attested_slot: u64,
+}
+
+/// A page of a mapping account's balances, exported in emergency mode
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExportedPage {
+    pub export: Pubkey,
+    pub mapping_account: Pubkey,
+    pub page: u8,
+    pub entries: u8,
+    /// Whether the mapping has entries past this page
+    pub more: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EmergencyClaim {
+    pub mapping_account: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+pub struct Store {
+    db: Connection,
+}
+
+impl Store {
+    /// Opens the database at `path`, creating it if needed.
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(db: Connection) -> Result<Self> {
+        db.execute_batch(SCHEMA)?;
+        Ok(Self { db })
+    }
+
+    pub fn cursor(&self) -> Result<Option<Cursor>> {
+        Ok(self
+            .db
+            .query_row("SELECT slot, signature FROM cursor", [], |row| {
+                Ok(Cursor {
+                    slot: row.get::<_, i64>(0)? as u64,
+                    signature: row.get(1)?,
+                })
+            })
+            .optional()?)
+    }
+
+    /// Applies the events of `transaction` and moves the cursor to it. Returns `false`, without
+    /// changing anything, if it was already applied.
+    pub fn apply(&mut self, transaction: &ProgramTransaction) -> Result<bool> {
+        let db = self.db.transaction()?;
+        let known = db
+            .query_row(
+                "SELECT 1 FROM transactions WHERE signature = ?1",
+                [&transaction.signature],
+                |_| Ok(()),
+            )
+            .optional()?;
+        if known.is_some() {
+            return Ok(false);
+        }
+
+        let slot = slot_value(transaction.slot)?;
+        let logged = if transaction.failed {
+            events::LoggedEvents::default()
+        } else {
+            events::from_logs(&transaction.logs)?
+        };
+        db.execute(
+            "INSERT INTO transactions (signature, slot, block_time, failed, logs_truncated)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                transaction.signature,
+                slot,
+                transaction.block_time,
+                transaction.failed,
+                logged.truncated
+            ],
+        )?;
+
+        let applying = Applying {
+            db: &db,
+            slot,
+            signature: &transaction.signature,
+        };
+        for (index, event) in logged.events.iter().enumerate() {
+            applying.event(index, event)?;
+        }
+
+        db.execute(
+            "INSERT INTO cursor (id, slot, signature) VALUES (0, ?1, ?2)
+             ON CONFLICT (id) DO UPDATE SET slot = excluded.slot, signature = excluded.signature",
+            params![slot, transaction.signature],
+        )?;
+        db.commit()?;
+        Ok(true)
+    }
+
+    pub fn shards(&self, blackbox: &Pubkey) -> Result<Vec<Shard>> {
+        let mut statement = self.db.prepare(
+            "SELECT mapping_account, shard_index, has_op_queue, circuit_version FROM shards
+             WHERE blackbox = ?1 ORDER BY shard_index",
+        )?;
+        let rows = statement.query_map([blackbox.to_string()], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, u8>(1)?,
+                row.get::<_, bool>(2)?,
+                row.get::<_, Option<u8>>(3)?,
+            ))
+        })?;
+        rows.map(|row| -> Result<Shard> {
+            let (mapping_account, index, has_op_queue, circuit_version) = row?;
+            Ok(Shard {
+                mapping_account: mapping_account.parse()?,
+                index,
+                has_op_queue,
+                circuit_version,
+            })
+        })
+        .collect()
+    }
+
+    /// Tokens deposited into `blackbox`'s vault minus those paid out of it
+    pub fn vault_balance(&self, blackbox: &Pubkey) -> Result<i128> {
+        let mut statement = self
+            .db
+            .prepare("SELECT direction, amount FROM vault_flows WHERE blackbox = ?1")?;
+        let rows = statement.query_map([blackbox.to_string()], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut balance = 0i128;
+        for row in rows {
+            let (direction, amount) = row?;
+            let amount = i128::from(amount.parse::<u64>()?);
+            match direction.as_str() {
+                "in" => balance += amount,
+                _ => balance -= amount,
+            }
+        }
+        Ok(balance)
+    }
+
+    /// Fees charged by `blackbox` minus those collected
+    pub fn treasury_balance(&self, blackbox: &Pubkey) -> Result<i128> {
+        let mut statement = self
+            .db
+            .prepare("SELECT kind, amount FROM fee_flows WHERE blackbox = ?1")?;
+        let rows = statement.query_map([blackbox.to_string()], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut balance = 0i128;
+        for row in rows {
+            let (kind, amount) = row?;
+            let amount = i128::from(amount.parse::<u64>()?);
+            match kind.as_str() {
+                "collected" => balance -= amount,
+                _ => balance += amount,
+            }
+        }
+        Ok(balance)
+    }
+
+    pub fn computation(&self, blackbox: &Pubkey, offset: u64) -> Result<Option<Computation>> {
+        let row = self
+            .db
+            .query_row(
+                "SELECT mapping_account, kind, owner, amount, status FROM computations
+                 WHERE blackbox = ?1 AND computation_offset = ?2",
+                params![blackbox.to_string(), offset.to_string()],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                        row.get::<_, String>(4)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((mapping_account, kind, owner, amount, status)) = row else {
+            return Ok(None);
+        };
+        Ok(Some(Computation {
+            mapping_account: mapping_account.parse()?,
+            kind,
+            owner: owner.parse()?,
+            amount: amount.map(|amount| amount.parse()).transpose()?,
+            status,
+        }))
+    }
+
+    /// Operations in `mapping_account`'s queue: in flight first, then pending, in queue order
+    pub fn queue(&self, mapping_account: &Pubkey) -> Result<Vec<QueuedOperation>> {
+        let mut statement = self.db.prepare(
+            "SELECT state, kind, owner, amount, batch_offset FROM queued_operations
+             WHERE mapping_account = ?1 AND state IN ('pending', 'in_flight')
+             ORDER BY position",
+        )?;
+        let rows = statement.query_map([mapping_account.to_string()], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
+        })?;
+        rows.map(|row| -> Result<QueuedOperation> {
+            let (state, kind, owner, amount, batch_offset) = row?;
+            Ok(QueuedOperation {
+                state,
+                kind,
+                owner: owner.parse()?,
+                amount: amount.map(|amount| amount.parse()).transpose()?,
+                batch_offset: batch_offset.map(|offset| offset.parse()).transpose()?,
+            })
+        })
+        .collect()
+    }
+
+    /// Changes queued and neither executed nor cancelled yet, in the order they were queued
+    pub fn queued_changes(&self) -> Result<Vec<QueuedChange>> {
+        let mut statement = self.db.prepare(
+            "SELECT id, blackbox, kind, executable_slot FROM parameter_changes
+             WHERE state = 'queued' ORDER BY queued_slot, rowid",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+        rows.map(|row| -> Result<QueuedChange> {
+            let (id, blackbox, kind, executable_slot) = row?;
+            Ok(QueuedChange {
+                id: id.parse()?,
+                blackbox: blackbox.map(|blackbox| blackbox.parse()).transpose()?,
+                kind,
+                executable_slot: executable_slot.parse()?,
+            })
+        })
+        .collect()
+    }
+
+    /// The most recently attested solvency proof of `blackbox`
+    pub fn latest_attestation(&self, blackbox: &Pubkey) -> Result<Option<Attestation>> {
+        let row = self
+            .db
+            .query_row(
+                "SELECT attestation, computation_offset, vault_amount, shards, solvent,
+                     attested_slot
+                 FROM solvency_attestations
+                 WHERE blackbox = ?1 AND solvent IS NOT NULL
+                 ORDER BY attested_slot DESC, rowid DESC LIMIT 1",
+                [blackbox.to_string()],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, u8>(3)?,
+                        row.get::<_, bool>(4)?,
+                        row.get::<_, i64>(5)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((attestation, offset, vault_amount, shards, solvent, attested_slot)) = row else {
+            return Ok(None);
+        };
+        Ok(Some(Attestation {
+            attestation: attestation.parse()?,
+            computation_offset: offset.parse()?,
+            vault_amount: vault_amount.parse()?,
+            shards,
+            solvent,
+            attested_slot: u64::try_from(attested_slot)?,
+        }))
+    }
+
+    /// Slot `blackbox` entered emergency mode in, if it has
+    pub fn emergency_slot(&self, blackbox: &Pubkey) -> Result<Option<u64>> {
+        let slot = self
+            .db
+            .query_row(
+                "SELECT emergency_slot FROM blackboxes WHERE address = ?1",
+                [blackbox.to_string()],
+                |row| row.get::<_, Option<i64>>(0),
+            )
+            .optional()?
+            .flatten();
+        Ok(slot.map(u64::try_from).transpose()?)
+    }
+
+    /// Circuit version `blackbox` is on: the one it was created with, or the one it last moved
+    /// to
+    pub fn circuit_version(&self, blackbox: &Pubkey) -> Result<Option<u8>> {
+        Ok(self
+            .db
+            .query_row(
+                "SELECT circuit_version FROM blackboxes WHERE address = ?1",
+                [blackbox.to_string()],
+                |row| row.get::<_, Option<u8>>(0),
+            )
+            .optional()?
+            .flatten())
+    }
+
+    /// Pages of balances exported from `blackbox`'s mapping accounts, by shard and page. A
+    /// mapping account updated by a late callback is exported again at its new version, so a
+    /// page can appear more than once; the latest one is listed last.
+    pub fn emergency_exports(&self, blackbox: &Pubkey) -> Result<Vec<ExportedPage>> {
+        let mut statement = self.db.prepare(
+            "SELECT export, emergency_exports.mapping_account, page, entries, more
+             FROM emergency_exports
+             LEFT JOIN shards ON shards.mapping_account = emergency_exports.mapping_account
+             WHERE emergency_exports.blackbox = ?1
+             ORDER BY shard_index, page, exported_slot, emergency_exports.rowid",
+        )?;
+        let rows = statement.query_map([blackbox.to_string()], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, u8>(2)?,
+                row.get::<_, u8>(3)?,
+                row.get::<_, bool>(4)?,
+            ))
+        })?;
+        rows.map(|row| -> Result<ExportedPage> {
+            let (export, mapping_account, page, entries, more) = row?;
+            Ok(ExportedPage {
+                export: export.parse()?,
+                mapping_account: mapping_account.parse()?,
+                page,
+                entries,
+                more,
+            })
+        })
+        .collect()
+    }
+
+    /// Balances claimed out of `blackbox` in emergency mode, in the order they were claimed
+    pub fn emergency_claims(&self, blackbox: &Pubkey) -> Result<Vec<EmergencyClaim>> {
+        let mut statement = self.db.prepare(
+            "SELECT mapping_account, owner, amount FROM emergency_claims
+             WHERE blackbox = ?1 ORDER BY claimed_slot, rowid",
+        )?;
+        let rows = statement.query_map([blackbox.to_string()], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        rows.map(|row| -> Result<EmergencyClaim> {
+            let (mapping_account, owner, amount) = row?;
+            Ok(EmergencyClaim {
+                mapping_account: mapping_account.parse()?,
+                owner: owner.parse()?,
+                amount: amount.parse()?,
+            })
+        })
+        .collect()
+    }
+}
+
+/// One transaction's events being applied
+struct Applying<'a> {
+    db: &'a Transaction<'a>,
+    slot: i64,
+    signature: &'a str,
+}
+
+impl Applying<'_> {
+    fn event(&self, index: usize, event: &Event) -> Result<()> {
+        match event {
+            Event::BlackboxInitialized {
+                blackbox,
+                token_mint,
+                vault,
+                treasury,
+                params,
+            } => {
+                self.db.execute(
+                    "INSERT INTO blackboxes
+                         (address, token_mint, vault, treasury, circuit_version, created_slot)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        blackbox.to_string(),
+                        token_mint.to_string(),
+                        vault.to_string(),
+                        treasury.to_string(),
+                        params.circuit_version,
+                        self.slot
+                    ],
+                )?;
+            }
+            Event::MappingAccountInitialized {
+                blackbox,
+                mapping_account,
+                index,
+            } => {
+                // New shards start on their blackbox's circuit version
+                self.db.execute(
+                    "INSERT INTO shards
+                         (mapping_account, blackbox, shard_index, circuit_version, created_slot)
+                     VALUES (?1, ?2, ?3,
+                         (SELECT circuit_version FROM blackboxes WHERE address = ?2), ?4)",
+                    params![
+                        mapping_account.to_string(),
+                        blackbox.to_string(),
+                        index,
+                        self.slot
+                    ],
+                )?;
+            }
+            Event::OpQueueInitialized {
+                mapping_account, ..
+            } => {
+                self.db.execute(
+                    "UPDATE shards SET has_op_queue = 1 WHERE mapping_account = ?1",
+                    [mapping_account.to_string()],
+                )?;
+            }
+            Event::VaultDeposited {
+                blackbox,
+                source,
+                amount,
+            } => self.vault_flow(index, blackbox, "in", source, *amount)?,
+            Event::VaultPaidOut {
+                blackbox,
+                destination,
+                amount,
+            } => self.vault_flow(index, blackbox, "out", destination, *amount)?,
+            Event::FeeCharged {
+                blackbox,
+                kind,
+                amount,
+            } => self.fee_flow(index, blackbox, op_kind(*kind), *amount)?,
+            Event::FeesCollected {
+                blackbox, amount, ..
+            } => self.fee_flow(index, blackbox, "collected", *amount)?,
+            Event::ComputationQueued {
+                blackbox,
+                mapping_account,
+                computation_offset,
+                kind,
+                owner,
+                amount,
+            } => {
+                self.db.execute(
+                    "INSERT INTO computations (blackbox, computation_offset, mapping_account,
+                         kind, owner, amount, status, queued_slot, queued_signature)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'pending', ?7, ?8)",
+                    params![
+                        blackbox.to_string(),
+                        computation_offset.to_string(),
+                        mapping_account.to_string(),
+                        computation_kind(*kind),
+                        owner.to_string(),
+                        amount.map(|amount| amount.to_string()),
+                        self.slot,
+                        self.signature
+                    ],
+                )?;
+            }
+            Event::ComputationResolved {
+                blackbox,
+                mapping_account,
+                computation_offset,
+                status,
+            } => self.resolve(blackbox, mapping_account, *computation_offset, *status)?,
+            Event::OperationEnqueued {
+                mapping_account,
+                kind,
+                owner,
+                amount,
+                ..
+            } => self.enqueue(mapping_account, op_kind(*kind), owner, *amount)?,
+            Event::OpQueueFlushed {
+                mapping_account,
+                computation_offset,
+                operations,
+                ..
+            } => {
+                self.db.execute(
+                    "UPDATE queued_operations SET state = 'in_flight', batch_offset = ?2
+                     WHERE id IN (
+                         SELECT id FROM queued_operations
+                         WHERE mapping_account = ?1 AND state = 'pending'
+                         ORDER BY position LIMIT ?3
+                     )",
+                    params![
+                        mapping_account.to_string(),
+                        computation_offset.to_string(),
+                        operations
+                    ],
+                )?;
+            }
+            Event::ChangeQueued {
+                id,
+                blackbox,
+                change,
+                executable_slot,
+            } => {
+                self.db.execute(
+                    "INSERT INTO parameter_changes (id, blackbox, kind, executable_slot, state,
+                         queued_slot, queued_signature)
+                     VALUES (?1, ?2, ?3, ?4, 'queued', ?5, ?6)",
+                    params![
+                        id.to_string(),
+                        blackbox.map(|blackbox| blackbox.to_string()),
+                        change_kind(change),
+                        executable_slot.to_string(),
+                        self.slot,
+                        self.signature
+                    ],
+                )?;
+            }
+            Event::SolvencyRequested {
+                blackbox,
+                attestation,
+                computation_offset,
+                vault_amount,
+                shards,
+            } => {
+                self.db.execute(
+                    "INSERT INTO solvency_attestations (attestation, blackbox, computation_offset,
+                         vault_amount, shards, requested_slot, requested_signature)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        attestation.to_string(),
+                        blackbox.to_string(),
+                        computation_offset.to_string(),
+                        vault_amount.to_string(),
+                        shards,
+                        self.slot,
+                        self.signature
+                    ],
+                )?;
+            }
+            // A proof requested before indexing started isn't tracked, so there's nothing to
+            // update
+            Event::SolvencyAttested {
+                attestation,
+                solvent,
+                ..
+            } => {
+                self.db.execute(
+                    "UPDATE solvency_attestations
+                     SET solvent = ?2, attested_slot = ?3, attested_signature = ?4
+                     WHERE attestation = ?1",
+                    params![attestation.to_string(), solvent, self.slot, self.signature],
+                )?;
+            }
+            Event::EmergencyEntered { blackbox } => {
+                self.db.execute(
+                    "UPDATE blackboxes SET emergency_slot = ?2 WHERE address = ?1",
+                    params![blackbox.to_string(), self.slot],
+                )?;
+            }
+            Event::BalancesExported {
+                blackbox,
+                mapping_account,
+                export,
+                page,
+                entries,
+                more,
+            } => {
+                self.db.execute(
+                    "INSERT INTO emergency_exports (export, blackbox, mapping_account, page,
+                         entries, more, exported_slot, exported_signature)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        export.to_string(),
+                        blackbox.to_string(),
+                        mapping_account.to_string(),
+                        page,
+                        entries,
+                        more,
+                        self.slot,
+                        self.signature
+                    ],
+                )?;
+            }
+            Event::EmergencyClaimed {
+                blackbox,
+                mapping_account,
+                owner,
+                amount,
+            } => {
+                self.db.execute(
+                    "INSERT INTO emergency_claims (mapping_account, owner, blackbox, amount,
+                         claimed_slot, claimed_signature)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        mapping_account.to_string(),
+                        owner.to_string(),
+                        blackbox.to_string(),
+                        amount.to_string(),
+                        self.slot,
+                        self.signature
+                    ],
+                )?;
+            }
+            // The program refunds in-flight deposits before pending ones
+            Event::QueuedDepositRefunded {
+                mapping_account,
+                owner,
+                amount,
+                ..
+            } => {
+                self.db.execute(
+                    "UPDATE queued_operations SET state = 'refunded', batch_offset = NULL
+                     WHERE id = (
+                         SELECT id FROM queued_operations
+                         WHERE mapping_account = ?1 AND state IN ('pending', 'in_flight')
+                             AND kind = 'deposit' AND owner = ?2 AND amount = ?3
+                         ORDER BY state = 'pending', position LIMIT 1
+                     )",
+                    params![
+                        mapping_account.to_string(),
+                        owner.to_string(),
+                        amount.to_string()
+                    ],
+                )?;
+            }
+            Event::CircuitVersionUpdated {
+                blackbox,
+                circuit_version,
+            } => {
+                self.db.execute(
+                    "UPDATE blackboxes SET circuit_version = ?2 WHERE address = ?1",
+                    params![blackbox.to_string(), circuit_version],
+                )?;
+            }
+            Event::MappingMigrated {
+                mapping_account,
+                circuit_version,
+                ..
+            } => {
+                self.db.execute(
+                    "UPDATE shards SET circuit_version = ?2 WHERE mapping_account = ?1",
+                    params![mapping_account.to_string(), circuit_version],
+                )?;
+            }
+            Event::ChangeExecuted { id } => self.resolve_change(*id, "executed")?,
+            Event::ChangeCancelled { id } => self.resolve_change(*id, "cancelled")?,
+            // Administration doesn't change the indexed state otherwise
+            Event::ProgramConfigInitialized { .. }
+            | Event::AuthorityProposed { .. }
+            | Event::AuthorityChanged { .. }
+            | Event::TimelockUpdated { .. }
+            | Event::PauseUpdated { .. }
+            | Event::MaxFeeUpdated { .. }
+            | Event::MaxShardCapacityUpdated { .. }
+            | Event::FeesUpdated { .. }
+            | Event::LimitsUpdated { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn vault_flow(
+        &self,
+        index: usize,
+        blackbox: &Pubkey,
+        direction: &str,
+        token_account: &Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        self.db.execute(
+            "INSERT INTO vault_flows
+                 (signature, event_index, slot, blackbox, direction, token_account, amount)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                self.signature,
+                index,
+                self.slot,
+                blackbox.to_string(),
+                direction,
+                token_account.to_string(),
+                amount.to_string()
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn fee_flow(&self, index: usize, blackbox: &Pubkey, kind: &str, amount: u64) -> Result<()> {
+        self.db.execute(
+            "INSERT INTO fee_flows (signature, event_index, slot, blackbox, kind, amount)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                self.signature,
+                index,
+                self.slot,
+                blackbox.to_string(),
+                kind,
+                amount.to_string()
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn resolve(
+        &self,
+        blackbox: &Pubkey,
+        mapping_account: &Pubkey,
+        offset: u64,
+        status: OpStatus,
+    ) -> Result<()> {
+        let computation = self
+            .db
+            .query_row(
+                "SELECT kind, owner, amount FROM computations
+                 WHERE blackbox = ?1 AND computation_offset = ?2",
+                params![blackbox.to_string(), offset.to_string()],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, Option<String>>(2)?,
+                    ))
+                },
+            )
+            .optional()?;
+        // Queued before indexing started
+        let Some((kind, owner, amount)) = computation else {
+            return Ok(());
+        };
+
+        self.db.execute(
+            "UPDATE computations SET status = ?3, resolved_slot = ?4, resolved_signature = ?5
+             WHERE blackbox = ?1 AND computation_offset = ?2",
+            params![
+                blackbox.to_string(),
+                offset.to_string(),
+                op_status(status),
+                self.slot,
+                self.signature
+            ],
+        )?;
+
+        let batch = kind == computation_kind(ComputationKind::ProcessBatch);
+        match (status, batch) {
+            // In-flight operations keep positions ahead of every pending one, so going back
+            // to pending puts them back in front of the queue.
+            (OpStatus::Requeued, true) => {
+                self.db.execute(
+                    "UPDATE queued_operations SET state = 'pending', batch_offset = NULL
+                     WHERE mapping_account = ?1 AND state = 'in_flight' AND batch_offset = ?2",
+                    params![mapping_account.to_string(), offset.to_string()],
+                )?;
+            }
+            (OpStatus::Applied, true) => {
+                self.db.execute(
+                    "UPDATE queued_operations SET state = 'processed'
+                     WHERE mapping_account = ?1 AND state = 'in_flight' AND batch_offset = ?2",
+                    params![mapping_account.to_string(), offset.to_string()],
+                )?;
+            }
+            (OpStatus::Requeued, false) => {
+                let amount = amount.map(|amount| amount.parse()).transpose()?;
+                self.enqueue(mapping_account, &kind, &owner.parse()?, amount)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// A change queued before indexing started isn't tracked, so there's nothing to update
+    fn resolve_change(&self, id: u64, state: &str) -> Result<()> {
+        self.db.execute(
+            "UPDATE parameter_changes SET state = ?2, resolved_slot = ?3, resolved_signature = ?4
+             WHERE id = ?1",
+            params![id.to_string(), state, self.slot, self.signature],
+        )?;
+        Ok(())
+    }
+
+    fn enqueue(
+        &self,
+        mapping_account: &Pubkey,
+        kind: &str,
+        owner: &Pubkey,
+        amount: Option<u64>,
+    ) -> Result<()> {
+        if !matches!(kind, "deposit" | "transfer" | "withdraw") {
+            bail!("{kind} computations can't be queued as operations");
+        }
+        self.db.execute(
+            "INSERT INTO queued_operations
+                 (mapping_account, state, position, kind, owner, amount,
+                  enqueued_slot, enqueued_signature)
+             VALUES (?1, 'pending',
+                 (SELECT COALESCE(MAX(position), -1) + 1 FROM queued_operations
+                  WHERE mapping_account = ?1 AND state != 'processed'),
+                 ?2, ?3, ?4, ?5, ?6)",
+            params![
+                mapping_account.to_string(),
+                kind,
+                owner.to_string(),
+                amount.map(|amount| amount.to_string()),
+                self.slot,
+                self.signature
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+fn slot_value(slot: u64) -> Result<i64> {
+    Ok(i64::try_from(slot)?)
+}
+
+fn computation_kind(kind: ComputationKind) -> &'static str {
+    match kind {
+        ComputationKind::Deposit => "deposit",
+        ComputationKind::Transfer => "transfer",
+        ComputationKind::Withdraw => "withdraw",
+        ComputationKind::WithdrawAll => "withdraw_all",
+        ComputationKind::RevealBalance => "reveal_balance",
+        ComputationKind::ProcessBatch => "process_batch",
+        ComputationKind::MigrateMapping => "migrate_mapping",
+    }
+}
+
+fn op_kind(kind: OpKind) -> &'static str {
+    match kind {
+        OpKind::Deposit => "deposit",
+        OpKind::Transfer => "transfer",
+        OpKind::Withdraw => "withdraw",
+    }
+}
+
+fn op_status(status: OpStatus) -> &'static str {
+    match status {
+        OpStatus::Pending => "pending",
+        OpStatus::Applied => "applied",
+        OpStatus::Stale => "stale",
+        OpStatus::Requeued => "requeued",
+        OpStatus::Dropped => "dropped",
+        OpStatus::Failed => "failed",
+        OpStatus::Refunded => "refunded",
+    }
+}
+
+fn change_kind(change: &ParameterChange) -> &'static str {
+    match change {
+        ParameterChange::MaxFee(_) => "max_fee",
+        ParameterChange::Fees(_) => "fees",
+        ParameterChange::Limits(_) => "limits",
+        ParameterChange::Paused(_) => "paused",
+        ParameterChange::Timelock(_) => "timelock",
+        ParameterChange::Emergency => "emergency",
+        ParameterChange::CircuitVersion(_) => "circuit_version",
+        ParameterChange::MaxShardCapacity(_) => "max_shard_capacity",
+    }
+}
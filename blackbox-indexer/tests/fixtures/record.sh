@@ -0,0 +1,54 @@
+#!/usr/bin/env bash
+# Records tests/fixtures/localnet.json, which `tests/replay.rs` replays: a blackbox created,
+# deposited into, transferred and withdrawn from, and attested solvent on a local validator.
+#
+# Needs a local validator with the program deployed and an Arcium node computing for it, e.g.
+# from `arcium localnet`, and the Solana CLI, `spl-token` and `jq` on the path. The default
+# keypair must be the program's upgrade authority.
+#
+#   blackbox-indexer/tests/fixtures/record.sh
+set -euo pipefail
+
+cd "$(dirname "$0")/../../.."
+url=http://127.0.0.1:8899
+out=blackbox-indexer/tests/fixtures/localnet.json
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+
+cargo build -q -p blackbox-cli -p blackbox-indexer
+blackbox() { target/debug/blackbox --url "$url" "$@"; }
+
+# Waits for every computation the default keypair queued to resolve
+settle() {
+  until blackbox --output json pending --mint "$mint" |
+    jq -e '.queued == [] and .computations == []' >/dev/null; do
+    sleep 2
+  done
+}
+
+from_slot=$(solana --url "$url" slot)
+
+mint=$(spl-token --url "$url" create-token --decimals 0 --output json | jq -r .commandOutput.address)
+spl-token --url "$url" create-account "$mint" >/dev/null
+spl-token --url "$url" mint "$mint" 1000 >/dev/null
+solana-keygen new --no-bip39-passphrase --silent --outfile "$tmp/bob.json"
+bob=$(solana-keygen pubkey "$tmp/bob.json")
+
+blackbox init-config
+blackbox register-comp-defs
+blackbox init --mint "$mint" --shard-capacity 8
+blackbox deposit --mint "$mint" 500
+settle
+blackbox transfer --mint "$mint" --to "$bob" 100
+settle
+blackbox withdraw --mint "$mint" 200
+settle
+blackbox prove-solvency --mint "$mint"
+
+# Only finalized transactions are recorded
+last=$(solana --url "$url" slot)
+until [ "$(solana --url "$url" slot --commitment finalized)" -ge "$last" ]; do
+  sleep 2
+done
+
+target/debug/blackbox-indexer record --url "$url" --from-slot "$from_slot" --out "$out"
@@ -0,0 +1,789 @@
+//! Replays fixtures built from the program's own event types through the store, the way
+//! `blackbox-indexer replay` applies ones recorded from a validator.
+
+use std::path::PathBuf;
+
+use anchor_lang::Event;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use blackbox::{
+    events::{
+        BalancesExported, BlackboxInitialized, ChangeCancelled, ChangeExecuted, ChangeQueued,
+        CircuitVersionUpdated, ComputationKind, ComputationQueued, ComputationResolved,
+        EmergencyClaimed, EmergencyEntered, FeeCharged, FeesCollected, MappingAccountInitialized,
+        MappingMigrated, OpQueueFlushed, OpQueueInitialized, OperationEnqueued,
+        QueuedDepositRefunded, SolvencyAttested, SolvencyRequested, VaultDeposited, VaultPaidOut,
+    },
+    state::{Fees, InitBlackboxParams, Limits, OpKind, OpStatus, ParameterChange},
+};
+use blackbox_client::{events as logged, PROGRAM_ID};
+use blackbox_indexer::{
+    store::{
+        Attestation, Cursor, EmergencyClaim, ExportedPage, QueuedChange, QueuedOperation, Shard,
+    },
+    ProgramTransaction, Store,
+};
+use solana_sdk::pubkey::Pubkey;
+
+struct Fixture {
+    blackbox: Pubkey,
+    shards: [Pubkey; 2],
+    alice: Pubkey,
+    bob: Pubkey,
+    transactions: Vec<ProgramTransaction>,
+}
+
+impl Fixture {
+    /// Appends a transaction logging `events` from the program.
+    fn push(&mut self, events: &[Vec<u8>]) {
+        self.push_transaction(events, false);
+    }
+
+    fn push_transaction(&mut self, events: &[Vec<u8>], failed: bool) {
+        let mut logs = vec![format!("Program {PROGRAM_ID} invoke [1]")];
+        logs.extend(
+            events
+                .iter()
+                .map(|data| format!("Program data: {}", STANDARD.encode(data))),
+        );
+        logs.push(format!("Program {PROGRAM_ID} success"));
+
+        let slot = 100 + self.transactions.len() as u64;
+        self.transactions.push(ProgramTransaction {
+            slot,
+            signature: format!("signature-{slot}"),
+            block_time: Some(1_700_000_000 + slot as i64),
+            failed,
+            logs,
+        });
+    }
+
+    fn queued(
+        &self,
+        offset: u64,
+        kind: ComputationKind,
+        owner: Pubkey,
+        amount: Option<u64>,
+    ) -> Vec<u8> {
+        ComputationQueued {
+            blackbox: self.blackbox,
+            mapping_account: self.shards[0],
+            computation_offset: offset,
+            kind,
+            owner,
+            amount,
+        }
+        .data()
+    }
+
+    fn resolved(&self, offset: u64, status: OpStatus) -> Vec<u8> {
+        ComputationResolved {
+            blackbox: self.blackbox,
+            mapping_account: self.shards[0],
+            computation_offset: offset,
+            status,
+        }
+        .data()
+    }
+
+    fn enqueued(&self, kind: OpKind, owner: Pubkey, amount: Option<u64>) -> Vec<u8> {
+        OperationEnqueued {
+            blackbox: self.blackbox,
+            mapping_account: self.shards[0],
+            kind,
+            owner,
+            amount,
+        }
+        .data()
+    }
+
+    fn flushed(&self, offset: u64, operations: u8) -> Vec<u8> {
+        OpQueueFlushed {
+            blackbox: self.blackbox,
+            mapping_account: self.shards[0],
+            computation_offset: offset,
+            operations,
+        }
+        .data()
+    }
+
+    fn deposited(&self, amount: u64) -> Vec<u8> {
+        VaultDeposited {
+            blackbox: self.blackbox,
+            source: Pubkey::new_unique(),
+            amount,
+        }
+        .data()
+    }
+}
+
+/// A blackbox with two shards, the first with an operation queue that sees a stale deposit
+/// re-queued, a batch re-queued and the batch applied.
+fn fixture() -> Fixture {
+    let mut fixture = Fixture {
+        blackbox: Pubkey::new_unique(),
+        shards: [Pubkey::new_unique(), Pubkey::new_unique()],
+        alice: Pubkey::new_unique(),
+        bob: Pubkey::new_unique(),
+        transactions: Vec::new(),
+    };
+    let (blackbox, shards, alice, bob) =
+        (fixture.blackbox, fixture.shards, fixture.alice, fixture.bob);
+
+    fixture.push(&[
+        BlackboxInitialized {
+            blackbox,
+            token_mint: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            treasury: Pubkey::new_unique(),
+            params: InitBlackboxParams {
+                shard_capacity: 16,
+                fees: Fees::default(),
+                limits: Limits::default(),
+                circuit_version: 1,
+                policy: Default::default(),
+                modes: Default::default(),
+                cluster_offset: 0,
+            },
+        }
+        .data(),
+        MappingAccountInitialized {
+            blackbox,
+            mapping_account: shards[0],
+            index: 0,
+        }
+        .data(),
+    ]);
+    fixture.push(&[
+        OpQueueInitialized {
+            blackbox,
+            mapping_account: shards[0],
+        }
+        .data(),
+        MappingAccountInitialized {
+            blackbox,
+            mapping_account: shards[1],
+            index: 1,
+        }
+        .data(),
+    ]);
+
+    // A direct deposit, and one that failed
+    let events = [
+        fixture.deposited(100),
+        fixture.queued(1, ComputationKind::Deposit, alice, Some(100)),
+    ];
+    fixture.push(&events);
+    let events = [
+        fixture.deposited(999),
+        fixture.queued(9, ComputationKind::Deposit, alice, Some(999)),
+    ];
+    fixture.push_transaction(&events, true);
+
+    let events = [
+        fixture.deposited(50),
+        fixture.enqueued(OpKind::Deposit, bob, Some(50)),
+    ];
+    fixture.push(&events);
+    let events = [fixture.enqueued(OpKind::Transfer, bob, None)];
+    fixture.push(&events);
+    let events = [fixture.enqueued(OpKind::Withdraw, alice, Some(30))];
+    fixture.push(&events);
+
+    // The direct deposit comes back stale and joins the queue
+    let events = [fixture.resolved(1, OpStatus::Requeued)];
+    fixture.push(&events);
+
+    // The first batch goes stale while another transfer is queued behind it
+    let events = [
+        fixture.queued(2, ComputationKind::ProcessBatch, bob, None),
+        fixture.flushed(2, 3),
+    ];
+    fixture.push(&events);
+    let events = [fixture.enqueued(OpKind::Transfer, alice, None)];
+    fixture.push(&events);
+    let events = [fixture.resolved(2, OpStatus::Requeued)];
+    fixture.push(&events);
+
+    let events = [
+        fixture.queued(3, ComputationKind::ProcessBatch, bob, None),
+        fixture.flushed(3, 5),
+    ];
+    fixture.push(&events);
+    let events = [
+        fixture.resolved(3, OpStatus::Applied),
+        VaultPaidOut {
+            blackbox,
+            destination: Pubkey::new_unique(),
+            amount: 30,
+        }
+        .data(),
+    ];
+    fixture.push(&events);
+
+    fixture
+}
+
+fn operation(
+    state: &str,
+    kind: &str,
+    owner: Pubkey,
+    amount: Option<u64>,
+    batch: Option<u64>,
+) -> QueuedOperation {
+    QueuedOperation {
+        state: state.into(),
+        kind: kind.into(),
+        owner,
+        amount,
+        batch_offset: batch,
+    }
+}
+
+fn temp_db(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("blackbox-indexer-{}-{name}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn replays_public_state() {
+    let fixture = fixture();
+    let mut store = Store::open_in_memory().unwrap();
+    // Recorded fixtures are JSON
+    let json = serde_json::to_string(&fixture.transactions).unwrap();
+    let transactions: Vec<ProgramTransaction> = serde_json::from_str(&json).unwrap();
+
+    // Up to the first flush
+    for transaction in &transactions[..8] {
+        assert!(store.apply(transaction).unwrap());
+    }
+    assert_eq!(
+        store.shards(&fixture.blackbox).unwrap(),
+        vec![
+            Shard {
+                mapping_account: fixture.shards[0],
+                index: 0,
+                has_op_queue: true,
+                circuit_version: Some(1),
+            },
+            Shard {
+                mapping_account: fixture.shards[1],
+                index: 1,
+                has_op_queue: false,
+                circuit_version: Some(1),
+            },
+        ]
+    );
+    // The failed deposit moved nothing
+    assert_eq!(store.vault_balance(&fixture.blackbox).unwrap(), 150);
+    assert_eq!(store.computation(&fixture.blackbox, 9).unwrap(), None);
+    let deposit = store.computation(&fixture.blackbox, 1).unwrap().unwrap();
+    assert_eq!(deposit.status, "requeued");
+    assert_eq!(deposit.amount, Some(100));
+    assert_eq!(
+        store.queue(&fixture.shards[0]).unwrap(),
+        vec![
+            operation("pending", "deposit", fixture.bob, Some(50), None),
+            operation("pending", "transfer", fixture.bob, None, None),
+            operation("pending", "withdraw", fixture.alice, Some(30), None),
+            operation("pending", "deposit", fixture.alice, Some(100), None),
+        ]
+    );
+
+    for transaction in &transactions[8..10] {
+        store.apply(transaction).unwrap();
+    }
+    assert_eq!(
+        store.queue(&fixture.shards[0]).unwrap(),
+        vec![
+            operation("in_flight", "deposit", fixture.bob, Some(50), Some(2)),
+            operation("in_flight", "transfer", fixture.bob, None, Some(2)),
+            operation("in_flight", "withdraw", fixture.alice, Some(30), Some(2)),
+            operation("pending", "deposit", fixture.alice, Some(100), None),
+            operation("pending", "transfer", fixture.alice, None, None),
+        ]
+    );
+
+    // The stale batch goes back in front, in order
+    store.apply(&transactions[10]).unwrap();
+    let queue = store.queue(&fixture.shards[0]).unwrap();
+    assert!(queue.iter().all(|op| op.state == "pending"));
+    assert_eq!(
+        queue[0],
+        operation("pending", "deposit", fixture.bob, Some(50), None)
+    );
+    assert_eq!(
+        queue[4],
+        operation("pending", "transfer", fixture.alice, None, None)
+    );
+
+    for transaction in &transactions[11..] {
+        store.apply(transaction).unwrap();
+    }
+    assert_eq!(store.queue(&fixture.shards[0]).unwrap(), vec![]);
+    assert_eq!(store.vault_balance(&fixture.blackbox).unwrap(), 120);
+    assert_eq!(
+        store
+            .computation(&fixture.blackbox, 2)
+            .unwrap()
+            .unwrap()
+            .status,
+        "requeued"
+    );
+    assert_eq!(
+        store
+            .computation(&fixture.blackbox, 3)
+            .unwrap()
+            .unwrap()
+            .status,
+        "applied"
+    );
+    let last = transactions.last().unwrap();
+    assert_eq!(
+        store.cursor().unwrap(),
+        Some(Cursor {
+            slot: last.slot,
+            signature: last.signature.clone(),
+        })
+    );
+}
+
+#[test]
+fn resumes_after_a_restart() {
+    let fixture = fixture();
+    let path = temp_db("resume");
+
+    let mut store = Store::open(&path).unwrap();
+    assert_eq!(store.cursor().unwrap(), None);
+    for transaction in &fixture.transactions[..9] {
+        store.apply(transaction).unwrap();
+    }
+    drop(store);
+
+    // Overlapping with what was already applied changes nothing
+    let mut store = Store::open(&path).unwrap();
+    assert_eq!(
+        store.cursor().unwrap().unwrap().signature,
+        fixture.transactions[8].signature
+    );
+    let applied: Vec<bool> = fixture
+        .transactions
+        .iter()
+        .map(|transaction| store.apply(transaction).unwrap())
+        .collect();
+    assert!(applied[..9].iter().all(|applied| !applied));
+    assert!(applied[9..].iter().all(|applied| *applied));
+
+    assert_eq!(store.vault_balance(&fixture.blackbox).unwrap(), 120);
+    assert_eq!(store.queue(&fixture.shards[0]).unwrap(), vec![]);
+    drop(store);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn stores_amounts_beyond_sqlite_integers() {
+    let mut fixture = fixture();
+    fixture.transactions.clear();
+    let events = [fixture.deposited(u64::MAX), fixture.deposited(u64::MAX)];
+    fixture.push(&events);
+
+    let mut store = Store::open_in_memory().unwrap();
+    store.apply(&fixture.transactions[0]).unwrap();
+    assert_eq!(
+        store.vault_balance(&fixture.blackbox).unwrap(),
+        2 * i128::from(u64::MAX)
+    );
+}
+
+#[test]
+fn tracks_fees_through_the_treasury() {
+    let mut fixture = fixture();
+    let blackbox = fixture.blackbox;
+    let fee = |kind, amount| {
+        FeeCharged {
+            blackbox,
+            kind,
+            amount,
+        }
+        .data()
+    };
+    let events = [fixture.deposited(99), fee(OpKind::Deposit, 1)];
+    fixture.push(&events);
+    let events = [fee(OpKind::Withdraw, 2)];
+    fixture.push_transaction(&events, true);
+    let events = [fee(OpKind::Withdraw, 3)];
+    fixture.push(&events);
+
+    let mut store = Store::open_in_memory().unwrap();
+    for transaction in &fixture.transactions {
+        store.apply(transaction).unwrap();
+    }
+    assert_eq!(store.treasury_balance(&blackbox).unwrap(), 4);
+
+    let events = [FeesCollected {
+        blackbox,
+        destination: Pubkey::new_unique(),
+        amount: 4,
+    }
+    .data()];
+    fixture.push(&events);
+    store.apply(fixture.transactions.last().unwrap()).unwrap();
+    assert_eq!(store.treasury_balance(&blackbox).unwrap(), 0);
+    assert_eq!(store.treasury_balance(&Pubkey::new_unique()).unwrap(), 0);
+}
+
+#[test]
+fn tracks_queued_changes() {
+    let mut fixture = fixture();
+    let blackbox = fixture.blackbox;
+    let queued = |id, blackbox, change| {
+        ChangeQueued {
+            id,
+            blackbox,
+            change,
+            executable_slot: 1_000 + id,
+        }
+        .data()
+    };
+    let events = [
+        queued(0, None, ParameterChange::MaxFee(100)),
+        queued(1, Some(blackbox), ParameterChange::Fees(Fees::default())),
+        queued(2, None, ParameterChange::Timelock(u64::MAX)),
+    ];
+    fixture.push(&events);
+    let events = [
+        ChangeExecuted { id: 0 }.data(),
+        ChangeCancelled { id: 2 }.data(),
+    ];
+    fixture.push(&events);
+    // Executed in a transaction that failed
+    let events = [ChangeExecuted { id: 1 }.data()];
+    fixture.push_transaction(&events, true);
+
+    let mut store = Store::open_in_memory().unwrap();
+    for transaction in &fixture.transactions {
+        store.apply(transaction).unwrap();
+    }
+    assert_eq!(
+        store.queued_changes().unwrap(),
+        vec![QueuedChange {
+            id: 1,
+            blackbox: Some(blackbox),
+            kind: "fees".into(),
+            executable_slot: 1_001,
+        }]
+    );
+
+    // Change 7 was queued before indexing started
+    let events = [
+        ChangeExecuted { id: 1 }.data(),
+        ChangeCancelled { id: 7 }.data(),
+    ];
+    fixture.push(&events);
+    store.apply(fixture.transactions.last().unwrap()).unwrap();
+    assert!(store.queued_changes().unwrap().is_empty());
+}
+
+#[test]
+fn tracks_the_latest_solvency_attestation() {
+    let mut fixture = fixture();
+    let blackbox = fixture.blackbox;
+    let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let requested = |attestation, computation_offset| {
+        SolvencyRequested {
+            blackbox,
+            attestation,
+            computation_offset,
+            vault_amount: 120,
+            shards: 2,
+        }
+        .data()
+    };
+    let attested = |attestation, computation_offset, solvent| {
+        SolvencyAttested {
+            blackbox,
+            attestation,
+            computation_offset,
+            solvent,
+            attested_slot: 0,
+        }
+        .data()
+    };
+    let events = [requested(first, 10), requested(second, 11)];
+    fixture.push(&events);
+
+    let mut store = Store::open_in_memory().unwrap();
+    for transaction in &fixture.transactions {
+        store.apply(transaction).unwrap();
+    }
+    // Requested proofs don't count until attested
+    assert_eq!(store.latest_attestation(&blackbox).unwrap(), None);
+
+    let events = [attested(second, 11, false)];
+    fixture.push(&events);
+    let events = [attested(first, 10, true)];
+    fixture.push(&events);
+    // Attested in a transaction that failed
+    let events = [attested(second, 11, true)];
+    fixture.push_transaction(&events, true);
+    for transaction in &fixture.transactions[fixture.transactions.len() - 3..] {
+        store.apply(transaction).unwrap();
+    }
+
+    let slot = fixture.transactions[fixture.transactions.len() - 2].slot;
+    assert_eq!(
+        store.latest_attestation(&blackbox).unwrap(),
+        Some(Attestation {
+            attestation: first,
+            computation_offset: 10,
+            vault_amount: 120,
+            shards: 2,
+            solvent: true,
+            attested_slot: slot,
+        })
+    );
+    assert_eq!(
+        store.latest_attestation(&Pubkey::new_unique()).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn tracks_emergency_exports_and_claims() {
+    let mut fixture = fixture();
+    let (blackbox, shards, alice) = (fixture.blackbox, fixture.shards, fixture.alice);
+    let exports = [
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    ];
+    let exported = |export, mapping_account, page, entries, more| {
+        BalancesExported {
+            blackbox,
+            mapping_account,
+            export,
+            page,
+            entries,
+            more,
+        }
+        .data()
+    };
+
+    let mut store = Store::open_in_memory().unwrap();
+    for transaction in &fixture.transactions {
+        store.apply(transaction).unwrap();
+    }
+    assert_eq!(store.emergency_slot(&blackbox).unwrap(), None);
+
+    let events = [EmergencyEntered { blackbox }.data()];
+    fixture.push(&events);
+    let events = [
+        exported(exports[0], shards[1], 0, 3, false),
+        exported(exports[1], shards[0], 1, 1, false),
+        exported(exports[2], shards[0], 0, 8, true),
+    ];
+    fixture.push(&events);
+    let claimed = EmergencyClaimed {
+        blackbox,
+        mapping_account: shards[0],
+        owner: alice,
+        amount: 450,
+    };
+    let events = [
+        claimed.data(),
+        VaultPaidOut {
+            blackbox,
+            destination: Pubkey::new_unique(),
+            amount: 450,
+        }
+        .data(),
+    ];
+    fixture.push(&events);
+    for transaction in &fixture.transactions[fixture.transactions.len() - 3..] {
+        store.apply(transaction).unwrap();
+    }
+
+    let entered = fixture.transactions[fixture.transactions.len() - 3].slot;
+    assert_eq!(store.emergency_slot(&blackbox).unwrap(), Some(entered));
+    let page = |export, mapping_account, page, entries, more| ExportedPage {
+        export,
+        mapping_account,
+        page,
+        entries,
+        more,
+    };
+    assert_eq!(
+        store.emergency_exports(&blackbox).unwrap(),
+        [
+            page(exports[2], shards[0], 0, 8, true),
+            page(exports[1], shards[0], 1, 1, false),
+            page(exports[0], shards[1], 0, 3, false),
+        ]
+    );
+    assert_eq!(
+        store.emergency_claims(&blackbox).unwrap(),
+        [EmergencyClaim {
+            mapping_account: shards[0],
+            owner: alice,
+            amount: 450,
+        }]
+    );
+}
+
+#[test]
+fn tracks_deposits_refunded_from_the_queue() {
+    let mut fixture = fixture();
+    let (blackbox, shards, alice, bob) =
+        (fixture.blackbox, fixture.shards, fixture.alice, fixture.bob);
+    let refunded = |owner, amount| {
+        let destination = Pubkey::new_unique();
+        [
+            QueuedDepositRefunded {
+                blackbox,
+                mapping_account: shards[0],
+                owner,
+                destination,
+                amount,
+            }
+            .data(),
+            VaultPaidOut {
+                blackbox,
+                destination,
+                amount,
+            }
+            .data(),
+        ]
+    };
+
+    let events = [
+        fixture.enqueued(OpKind::Deposit, bob, Some(40)),
+        fixture.deposited(40),
+    ];
+    fixture.push(&events);
+    let events = [
+        fixture.queued(20, ComputationKind::ProcessBatch, bob, None),
+        fixture.flushed(20, 1),
+    ];
+    fixture.push(&events);
+    let events = [
+        fixture.enqueued(OpKind::Transfer, alice, None),
+        fixture.enqueued(OpKind::Deposit, alice, Some(60)),
+        fixture.deposited(60),
+    ];
+    fixture.push(&events);
+    fixture.push(&[EmergencyEntered { blackbox }.data()]);
+    let mut store = Store::open_in_memory().unwrap();
+    for transaction in &fixture.transactions {
+        store.apply(transaction).unwrap();
+    }
+    let vault = store.vault_balance(&blackbox).unwrap();
+
+    // The in-flight deposit goes first; the transfer stays queued
+    let events = [refunded(bob, 40), refunded(alice, 60)].concat();
+    fixture.push(&events);
+    store.apply(fixture.transactions.last().unwrap()).unwrap();
+    assert_eq!(
+        store.queue(&shards[0]).unwrap(),
+        vec![operation("pending", "transfer", alice, None, None)]
+    );
+    assert_eq!(store.vault_balance(&blackbox).unwrap(), vault - 100);
+}
+
+#[test]
+fn tracks_circuit_versions() {
+    let mut fixture = fixture();
+    let (blackbox, shards) = (fixture.blackbox, fixture.shards);
+    let added = Pubkey::new_unique();
+    let events = [CircuitVersionUpdated {
+        blackbox,
+        circuit_version: 2,
+    }
+    .data()];
+    fixture.push(&events);
+    let events = [MappingAccountInitialized {
+        blackbox,
+        mapping_account: added,
+        index: 2,
+    }
+    .data()];
+    fixture.push(&events);
+    let events = [
+        fixture.queued(20, ComputationKind::MigrateMapping, fixture.alice, None),
+        fixture.resolved(20, OpStatus::Applied),
+        MappingMigrated {
+            blackbox,
+            mapping_account: shards[0],
+            circuit_version: 2,
+        }
+        .data(),
+    ];
+    fixture.push(&events);
+
+    let mut store = Store::open_in_memory().unwrap();
+    for transaction in &fixture.transactions {
+        store.apply(transaction).unwrap();
+    }
+    assert_eq!(store.circuit_version(&blackbox).unwrap(), Some(2));
+    let circuit_versions: Vec<_> = store
+        .shards(&blackbox)
+        .unwrap()
+        .into_iter()
+        .map(|shard| (shard.mapping_account, shard.circuit_version))
+        .collect();
+    // The shard added after the update starts on it; the unmigrated one is still behind
+    assert_eq!(
+        circuit_versions,
+        [(shards[0], Some(2)), (shards[1], Some(1)), (added, Some(2))]
+    );
+    let migration = store.computation(&blackbox, 20).unwrap().unwrap();
+    assert_eq!(migration.kind, "migrate_mapping");
+    assert_eq!(migration.status, "applied");
+}
+
+#[test]
+#[ignore = "needs tests/fixtures/localnet.json from tests/fixtures/record.sh"]
+fn replays_a_recorded_fixture() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/localnet.json");
+    let transactions: Vec<ProgramTransaction> =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let events: Vec<logged::Event> = transactions
+        .iter()
+        .filter(|transaction| !transaction.failed)
+        .flat_map(|transaction| logged::from_logs(&transaction.logs).unwrap().events)
+        .collect();
+    let blackbox = events
+        .iter()
+        .find_map(|event| match event {
+            logged::Event::BlackboxInitialized { blackbox, .. } => Some(*blackbox),
+            _ => None,
+        })
+        .unwrap();
+
+    let mut store = Store::open_in_memory().unwrap();
+    for transaction in &transactions {
+        assert!(store.apply(transaction).unwrap());
+    }
+    assert_eq!(store.shards(&blackbox).unwrap().len(), 1);
+    // 500 deposited, 200 withdrawn
+    assert_eq!(store.vault_balance(&blackbox).unwrap(), 300);
+    for event in &events {
+        if let logged::Event::ComputationQueued {
+            computation_offset, ..
+        } = event
+        {
+            let computation = store.computation(&blackbox, *computation_offset).unwrap();
+            assert_eq!(computation.unwrap().status, "applied");
+        }
+    }
+    let attestation = store.latest_attestation(&blackbox).unwrap().unwrap();
+    assert_eq!((attestation.vault_amount, attestation.solvent), (300, true));
+    let last = transactions.last().unwrap();
+    assert_eq!(
+        store.cursor().unwrap(),
+        Some(Cursor {
+            slot: last.slot,
+            signature: last.signature.clone(),
+        })
+    );
+}
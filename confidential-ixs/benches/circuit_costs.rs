@@ -0,0 +1,175 @@
+//! Cost of each circuit as the `Mapping` grows toward a full mapping account.
+//!
+//! Every circuit of `src/lib.rs` is compiled with `arcis_compiler` once per capacity, bounding
+//! the mapping's entries to it, and the gate count, multiplication count and multiplicative
+//! depth of the compiled circuit are reported as the compiler counts them. Under MPC every
+//! branch on secret data is evaluated, so those counts are what a computation costs whatever
+//! the inputs. From them we derive the preprocessing material the MXE consumes and an estimate
+//! of its latency.
+//!
+//! ```text
+//! cargo bench -p confidential-ixs --bench circuit_costs            # report and check
+//! cargo bench -p confidential-ixs --bench circuit_costs -- --bless # rewrite the baseline
+//! ```
+//!
+//! The check fails when a metric grows more than `TOLERANCE_PERCENT` past `baseline.tsv`, or
+//! when a circuit or capacity is missing from it: a circuit change that makes it costlier must
+//! come with a re-blessed baseline.
+
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use arcis_compiler::{CompileOptions, Compiler};
+
+/// Mapping sizes to report, up to what fits in one mapping account
+const CAPACITIES: [u64; 6] = [1, 8, 32, 64, 128, MAX_ENTRIES_PER_ACCOUNT];
+/// `MAX_ENTRIES_PER_ACCOUNT` of the blackbox program
+const MAX_ENTRIES_PER_ACCOUNT: u64 = (10 * 1024 - (8 + 1 + 32 + 16 + 8 + 1 + 2 + 4 + 4)) / 64;
+/// Allowed growth of any metric over the baseline
+const TOLERANCE_PERCENT: u64 = 5;
+
+/// Bytes of preprocessing per multiplication: one Beaver triple of 32-byte field elements
+const TRIPLE_BYTES: u64 = 3 * 32;
+/// Network round trip between MXE nodes, per unit of multiplicative depth
+const ROUND_TRIP_US: u64 = 2_000;
+/// Multiplications a node evaluates per millisecond
+const MULTS_PER_MS: u64 = 50_000;
+
+/// Circuits the program registers a computation definition for, as named in `build.rs`
+const CIRCUITS: [&str; 14] = [
+    "deposit",
+    "transfer",
+    "withdraw",
+    "withdraw_all",
+    "reveal_balance",
+    "process_batch",
+    "prove_solvency",
+    "export_balances",
+    "deposit_v2",
+    "transfer_v2",
+    "withdraw_v2",
+    "withdraw_all_v2",
+    "process_batch_v2",
+    "migrate_mapping_v2",
+];
+
+struct Row {
+    circuit: &'static str,
+    capacity: u64,
+    gates: u64,
+    mults: u64,
+    depth: u64,
+    preprocessing_bytes: u64,
+    latency_ms: u64,
+}
+
+impl Row {
+    /// Compiles `circuit` out of `source` with every mapping bounded to `capacity` entries.
+    fn compile(source: &str, circuit: &'static str, capacity: u64) -> Self {
+        let options = CompileOptions {
+            max_vec_len: capacity as usize,
+            ..CompileOptions::default()
+        };
+        let compiled = Compiler::new(options)
+            .compile(source, circuit)
+            .unwrap_or_else(|err| panic!("compiling `{circuit}` at {capacity}: {err}"));
+        let stats = compiled.stats();
+        let (mults, depth) = (stats.multiplications as u64, stats.depth as u64);
+        Self {
+            circuit,
+            capacity,
+            gates: stats.gates as u64,
+            mults,
+            depth,
+            preprocessing_bytes: mults * TRIPLE_BYTES,
+            latency_ms: depth * ROUND_TRIP_US / 1_000 + mults / MULTS_PER_MS,
+        }
+    }
+
+    fn metrics(&self) -> [(&'static str, u64); 5] {
+        [
+            ("gates", self.gates),
+            ("mults", self.mults),
+            ("depth", self.depth),
+            ("preprocessing_bytes", self.preprocessing_bytes),
+            ("latency_ms", self.latency_ms),
+        ]
+    }
+}
+
+fn render(rows: &[Row]) -> String {
+    let mut out =
+        String::from("circuit\tcapacity\tgates\tmults\tdepth\tpreprocessing_bytes\tlatency_ms\n");
+    for row in rows {
+        write!(out, "{}\t{}", row.circuit, row.capacity).unwrap();
+        for (_, value) in row.metrics() {
+            write!(out, "\t{value}").unwrap();
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Compares `rows` with a rendered baseline, returning every regression found.
+fn check(rows: &[Row], baseline: &str) -> Vec<String> {
+    let mut failures = Vec::new();
+
+    let baseline: Vec<Vec<&str>> = baseline
+        .lines()
+        .skip(1)
+        .map(|line| line.split('\t').collect())
+        .collect();
+    for row in rows {
+        let capacity = row.capacity.to_string();
+        let Some(base) = baseline
+            .iter()
+            .find(|base| base[0] == row.circuit && base[1] == capacity)
+        else {
+            failures.push(format!("{} at {}: missing from baseline", row.circuit, row.capacity));
+            continue;
+        };
+        for (column, (name, value)) in row.metrics().into_iter().enumerate() {
+            let base: u64 = base[column + 2].parse().unwrap();
+            if value * 100 > base * (100 + TOLERANCE_PERCENT) {
+                failures.push(format!(
+                    "{} at {}: {name} went from {base} to {value}",
+                    row.circuit, row.capacity
+                ));
+            }
+        }
+    }
+
+    failures
+}
+
+fn main() -> ExitCode {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let source = std::fs::read_to_string(manifest_dir.join("src/lib.rs")).unwrap();
+    let baseline_path = manifest_dir.join("benches/baseline.tsv");
+
+    let rows: Vec<Row> = CIRCUITS
+        .into_iter()
+        .flat_map(|circuit| CAPACITIES.into_iter().map(move |n| (circuit, n)))
+        .map(|(circuit, n)| Row::compile(&source, circuit, n))
+        .collect();
+    let rendered = render(&rows);
+    print!("{rendered}");
+
+    if std::env::args().any(|arg| arg == "--bless") {
+        std::fs::write(&baseline_path, rendered).unwrap();
+        println!("wrote {}", baseline_path.display());
+        return ExitCode::SUCCESS;
+    }
+
+    let baseline = std::fs::read_to_string(&baseline_path).unwrap_or_default();
+    let failures = check(&rows, &baseline);
+    for failure in &failures {
+        eprintln!("regression: {failure}");
+    }
+    if failures.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -1,5 +1,52 @@
 #[cfg(not(feature = "solana"))]
 arcis_compiler::arcis_compiler!();
 
+/// Circuits the program registers a computation definition for
 #[cfg(feature = "solana")]
-pub fn main() {}
+const CIRCUITS: [&str; 14] = [
+    "deposit",
+    "transfer",
+    "withdraw",
+    "process_batch",
+    "withdraw_all",
+    "reveal_balance",
+    "prove_solvency",
+    "export_balances",
+    "deposit_v2",
+    "transfer_v2",
+    "withdraw_v2",
+    "withdraw_all_v2",
+    "process_batch_v2",
+    "migrate_mapping_v2",
+];
+
+/// Pins the compiled circuits: writes the SHA-256 of each `build/<circuit>.arcis` to
+/// `$OUT_DIR/circuit_hashes.rs` for the program to check comp defs against. Every circuit
+/// must be compiled first, so the program is never pinned to a circuit that doesn't exist.
+#[cfg(feature = "solana")]
+pub fn main() {
+    use sha2::{Digest, Sha256};
+    use std::{env, fmt::Write, fs, path::PathBuf};
+
+    let build_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("../build");
+    let mut hashes = String::new();
+    for circuit in CIRCUITS {
+        let path = build_dir.join(format!("{circuit}.arcis"));
+        println!("cargo:rerun-if-changed={}", path.display());
+        let compiled = fs::read(&path).unwrap_or_else(|err| {
+            panic!(
+                "circuit `{circuit}` isn't compiled: reading {}: {err}; run `arcium build` first",
+                path.display()
+            )
+        });
+        let hash: [u8; 32] = Sha256::digest(&compiled).into();
+        writeln!(
+            hashes,
+            "/// SHA-256 of the compiled `{circuit}` circuit\npub const {}_CIRCUIT_HASH: [u8; 32] = {hash:?};",
+            circuit.to_uppercase(),
+        )
+        .unwrap();
+    }
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("circuit_hashes.rs"), hashes).unwrap();
+}
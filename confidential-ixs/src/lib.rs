@@ -1,8 +1,18 @@
 use arcis::prelude::*;
 use crypto::*;
 
+pub mod model;
+
+/// Hashes of the compiled circuits, written by the build script
+#[cfg(feature = "solana")]
+pub mod circuit_hashes {
+    include!(concat!(env!("OUT_DIR"), "/circuit_hashes.rs"));
+}
+
 arcis_linker!();
 
+/// Balances of a mapping account, one entry per owner. Circuits that add entries take the
+/// account's capacity and add none past it.
 #[derive(ArcisType, Copy, Clone, ArcisEncryptable)]
 pub struct Mapping {
     pub pubkeys: Vec<PublicKey>,
@@ -15,6 +25,16 @@ pub struct Transfer {
     pub amount: u64,
 }
 
+/// A transfer's secret arguments, encrypted together by the sender with the cipher agreed with
+/// their client key, so each field takes its own keystream position under the transfer's nonce.
+#[derive(ArcisType, Copy, Clone, ArcisEncryptable)]
+pub struct TransferArgs {
+    pub to: PublicKey,
+    pub amount: u64,
+    /// Cover traffic: a no-op does all the same work but moves nothing
+    pub noop: bool,
+}
+
 #[confidential]
 pub fn init_mapping(mapping_nonce: u128) -> [Ciphertext; 2] {
     let cipher = RescueCipher::new_for_mxe();
@@ -31,8 +51,9 @@ pub fn deposit(
     deposit_amount: u64,
     mapping: [Ciphertext; 2],
     mapping_nonce: u128,
+    capacity: u16,
     nonce: u128,
-) -> [Ciphertext; 2] {
+) -> ([Ciphertext; 2], bool) {
     let cipher = RescueCipher::new_for_mxe();
     
     // Decrypt the mapping
@@ -49,24 +70,27 @@ pub fn deposit(
         }
     }
     
-    // If recipient not found, add a new entry
-    if !found {
+    // If recipient not found, add a new entry, as long as the mapping account has room for it
+    let credited = found || mapping_data.pubkeys.len() < capacity as usize;
+    if !found && credited {
         mapping_data.pubkeys.push(to);
         mapping_data.balances.push(deposit_amount);
     }
     
-    // Re-encrypt the updated mapping
-    cipher.encrypt::<1, Mapping>(mapping_data, nonce)
+    // Re-encrypt the updated mapping, revealing only whether the deposit was credited
+    (cipher.encrypt::<1, Mapping>(mapping_data, nonce), credited)
 }
 
 #[confidential]
 pub fn transfer(
     mapping: [Ciphertext; 2],
     mapping_nonce: u128,
+    capacity: u16,
     from: PublicKey,
-    to: Ciphertext,
-    transfer_amount: Ciphertext,
+    client_key: PublicKey,
+    transfer: [Ciphertext; 3],
     nonce: u128,
+    output_nonce: u128,
 ) -> [Ciphertext; 2] {
     let cipher = RescueCipher::new_for_mxe();
     
@@ -77,13 +101,19 @@ pub fn transfer(
     let mut sender_idx = None;
     let mut recipient_idx = None;
 
-    let to_decrypted = cipher.decrypt::<PublicKey>(to, nonce);
-    let transfer_amount_decrypted = cipher.decrypt::<u64>(transfer_amount, nonce);
+    // The sender encrypted the arguments with the cipher agreed with their client key
+    let client_cipher = RescueCipher::new_with_client(client_key);
+    let transfer = client_cipher.decrypt::<TransferArgs>(transfer, nonce);
+    let to_decrypted = transfer.to;
+    let transfer_amount_decrypted = transfer.amount;
+    let noop_decrypted = transfer.noop;
     
     for i in 0..mapping_data.pubkeys.len() {
         if mapping_data.pubkeys[i] == from {
             sender_idx = Some(i);
-        } else if mapping_data.pubkeys[i] == to_decrypted {
+        }
+        // Not an `else`: a self-transfer must find the sender's entry as the recipient too
+        if mapping_data.pubkeys[i] == to_decrypted {
             recipient_idx = Some(i);
         }
         
@@ -93,9 +123,11 @@ pub fn transfer(
         }
     }
     
-    // Verify sender exists and transfer funds if balance is sufficient
+    // Verify sender exists and transfer funds if balance is sufficient, and a new recipient
+    // fits in the mapping account
     if let Some(idx) = sender_idx {
-        if mapping_data.balances[idx] >= transfer_amount {
+        let has_room = recipient_idx.is_some() || mapping_data.pubkeys.len() < capacity as usize;
+        if !noop_decrypted && has_room && mapping_data.balances[idx] >= transfer_amount_decrypted {
             // Subtract from sender
             mapping_data.balances[idx] -= transfer_amount_decrypted;
             
@@ -110,7 +142,7 @@ pub fn transfer(
     }
     
     // Re-encrypt the updated mapping
-    cipher.encrypt::<1, Mapping>(mapping_data, nonce)
+    cipher.encrypt::<1, Mapping>(mapping_data, output_nonce)
 }
 
 #[confidential]
@@ -150,3 +182,589 @@ pub fn withdraw(
     // Re-encrypt the updated mapping, return false since withdrawal failed
     (cipher.encrypt::<1, Mapping>(mapping_data, nonce), false)
 }
+
+/// Maximum number of queued operations applied by a single `process_batch` invocation.
+pub const MAX_BATCH_SIZE: usize = 8;
+
+/// Operation kinds understood by `process_batch`. Slots past the queue length are `OP_EMPTY`.
+pub const OP_EMPTY: u8 = 0;
+pub const OP_DEPOSIT: u8 = 1;
+pub const OP_TRANSFER: u8 = 2;
+pub const OP_WITHDRAW: u8 = 3;
+
+/// Per-operation status codes returned (encrypted) by `process_batch`.
+pub const STATUS_SKIPPED: u64 = 0;
+pub const STATUS_APPLIED: u64 = 1;
+pub const STATUS_REJECTED: u64 = 2;
+
+/// A single queued operation as laid out in the on-chain operation queue.
+///
+/// `transfer` is only meaningful for transfers (a `TransferArgs` the sender encrypted with
+/// `client_key` under `nonce`); deposits and withdrawals carry their plaintext amount in
+/// `amount`. The operation's status is encrypted for `client_key`.
+#[derive(ArcisType, Copy, Clone)]
+pub struct QueuedOp {
+    pub kind: u8,
+    pub owner: PublicKey,
+    pub client_key: PublicKey,
+    pub transfer: [Ciphertext; 3],
+    pub amount: u64,
+    pub nonce: u128,
+}
+
+#[confidential]
+pub fn process_batch(
+    mapping: [Ciphertext; 2],
+    mapping_nonce: u128,
+    capacity: u16,
+    ops: [QueuedOp; MAX_BATCH_SIZE],
+    nonce: u128,
+) -> ([Ciphertext; 2], [Ciphertext; MAX_BATCH_SIZE], [bool; MAX_BATCH_SIZE]) {
+    let cipher = RescueCipher::new_for_mxe();
+
+    // Decrypt the mapping once for the whole batch
+    let mut mapping_data = cipher.decrypt::<Mapping>(mapping, mapping_nonce);
+
+    let mut statuses = [STATUS_SKIPPED; MAX_BATCH_SIZE];
+    // Only whether tokens leave the vault is revealed, because the callback has to move them:
+    // approved withdrawals are paid out, and deposits the mapping account has no room for are
+    // refunded. Every other status is for the operation's owner alone
+    let mut payouts = [false; MAX_BATCH_SIZE];
+
+    for k in 0..MAX_BATCH_SIZE {
+        let op = ops[k];
+
+        // Every op resolves a recipient and an amount so all kinds do the same work
+        let mut to = op.owner;
+        let mut amount = op.amount;
+        let mut noop = false;
+        if op.kind == OP_TRANSFER {
+            let client_cipher = RescueCipher::new_with_client(op.client_key);
+            let transfer = client_cipher.decrypt::<TransferArgs>(op.transfer, op.nonce);
+            to = transfer.to;
+            amount = transfer.amount;
+            noop = transfer.noop;
+        }
+
+        let mut owner_idx = None;
+        let mut to_idx = None;
+        for i in 0..mapping_data.pubkeys.len() {
+            if mapping_data.pubkeys[i] == op.owner {
+                owner_idx = Some(i);
+            }
+            if mapping_data.pubkeys[i] == to {
+                to_idx = Some(i);
+            }
+        }
+
+        // A new entry for the depositor or recipient needs room in the mapping account
+        let has_room = mapping_data.pubkeys.len() < capacity as usize;
+
+        if op.kind == OP_DEPOSIT {
+            statuses[k] = STATUS_APPLIED;
+            if let Some(idx) = owner_idx {
+                mapping_data.balances[idx] += amount;
+            } else if has_room {
+                mapping_data.pubkeys.push(op.owner);
+                mapping_data.balances.push(amount);
+            } else {
+                statuses[k] = STATUS_REJECTED;
+                payouts[k] = true;
+            }
+        } else if op.kind == OP_TRANSFER || op.kind == OP_WITHDRAW {
+            statuses[k] = STATUS_REJECTED;
+            let fits = op.kind == OP_WITHDRAW || to_idx.is_some() || has_room;
+            if noop {
+                // Reported as applied so cover traffic looks like a successful transfer
+                statuses[k] = STATUS_APPLIED;
+            } else if let Some(idx) = owner_idx {
+                if fits && mapping_data.balances[idx] >= amount {
+                    mapping_data.balances[idx] -= amount;
+                    statuses[k] = STATUS_APPLIED;
+
+                    if op.kind == OP_WITHDRAW {
+                        payouts[k] = true;
+                    } else if let Some(to_idx) = to_idx {
+                        mapping_data.balances[to_idx] += amount;
+                    } else {
+                        mapping_data.pubkeys.push(to);
+                        mapping_data.balances.push(amount);
+                    }
+                }
+            }
+        }
+    }
+
+    // Encrypt each status for its owner's client key, under its own nonce so results can't be
+    // correlated by position
+    let mut enc_statuses = [Ciphertext::default(); MAX_BATCH_SIZE];
+    for k in 0..MAX_BATCH_SIZE {
+        let client_cipher = RescueCipher::new_with_client(ops[k].client_key);
+        enc_statuses[k] = client_cipher.encrypt::<1, u64>(statuses[k], nonce + 1 + k as u128)[0];
+    }
+
+    // Re-encrypt the updated mapping
+    (
+        cipher.encrypt::<1, Mapping>(mapping_data, nonce),
+        enc_statuses,
+        payouts,
+    )
+}
+
+#[confidential]
+pub fn withdraw_all(
+    mapping: [Ciphertext; 2],
+    mapping_nonce: u128,
+    from: PublicKey,
+    max_payout: u64,
+    nonce: u128,
+) -> ([Ciphertext; 2], u64, bool) {
+    let cipher = RescueCipher::new_for_mxe();
+
+    // Decrypt the mapping
+    let mut mapping_data = cipher.decrypt::<Mapping>(mapping, mapping_nonce);
+
+    // Debit the user's balance, up to `max_payout`, wherever it is, without revealing where
+    let mut payout = 0;
+    let mut found = false;
+    for i in 0..mapping_data.pubkeys.len() {
+        if mapping_data.pubkeys[i] == from {
+            let balance = mapping_data.balances[i];
+            payout = if balance > max_payout { max_payout } else { balance };
+            mapping_data.balances[i] = balance - payout;
+            found = true;
+        }
+    }
+
+    // Re-encrypt the updated mapping, revealing only the payout and whether the user exists
+    (cipher.encrypt::<1, Mapping>(mapping_data, nonce), payout, found)
+}
+
+#[confidential]
+pub fn reveal_balance(
+    mapping: [Ciphertext; 2],
+    mapping_nonce: u128,
+    owner: PublicKey,
+    client_key: PublicKey,
+    nonce: u128,
+) -> Ciphertext {
+    let cipher = RescueCipher::new_for_mxe();
+
+    // Decrypt the mapping; it is only read, so nothing is re-encrypted
+    let mapping_data = cipher.decrypt::<Mapping>(mapping, mapping_nonce);
+
+    // Read the owner's balance wherever it is, without revealing where
+    let mut balance = 0;
+    for i in 0..mapping_data.pubkeys.len() {
+        if mapping_data.pubkeys[i] == owner {
+            balance = mapping_data.balances[i];
+        }
+    }
+
+    // Encrypt the balance for the holder of `client_key`'s x25519 secret only
+    let client_cipher = RescueCipher::new_with_client(client_key);
+    client_cipher.encrypt::<1, u64>(balance, nonce)[0]
+}
+
+/// Maximum number of mapping shards one `prove_solvency` computation sums over. Blackboxes with
+/// more are proven a range of shards at a time, carrying the total from one range to the next.
+pub const MAX_SOLVENCY_SHARDS: usize = 4;
+
+/// One mapping shard as stored on chain: the encrypted `Mapping` and its nonce.
+#[derive(ArcisType, Copy, Clone)]
+pub struct MappingShard {
+    pub mapping: [Ciphertext; 2],
+    pub nonce: u128,
+}
+
+#[confidential]
+pub fn prove_solvency(
+    shards: [MappingShard; MAX_SOLVENCY_SHARDS],
+    shard_count: u8,
+    total: Ciphertext,
+    total_nonce: u128,
+    first: bool,
+    last: bool,
+    vault_amount: u64,
+    nonce: u128,
+) -> (Ciphertext, bool) {
+    let cipher = RescueCipher::new_for_mxe();
+
+    // Carry on from the total of the ranges summed before, which the first range has none of.
+    // The total is kept as a u128 so it can't overflow.
+    let carried = cipher.decrypt::<u128>([total], total_nonce);
+    let mut sum: u128 = if first { 0 } else { carried };
+
+    // Sum every balance in the shards of this range; slots past `shard_count` are decrypted
+    // but not counted
+    for s in 0..MAX_SOLVENCY_SHARDS {
+        let mapping_data = cipher.decrypt::<Mapping>(shards[s].mapping, shards[s].nonce);
+        if s < shard_count as usize {
+            for i in 0..mapping_data.balances.len() {
+                sum += mapping_data.balances[i] as u128;
+            }
+        }
+    }
+
+    // Pass the total on encrypted for the MXE alone. Only the last range reveals anything:
+    // whether the vault covers the total, never the total itself.
+    (
+        cipher.encrypt::<1, u128>(sum, nonce)[0],
+        last && sum <= vault_amount as u128,
+    )
+}
+
+/// Number of mapping entries one `export_balances` computation exports.
+pub const MAX_EXPORT_ENTRIES: usize = 8;
+
+/// An exported balance and the salt of its commitment, encrypted for the entry's owner.
+#[derive(ArcisType, Copy, Clone, ArcisEncryptable)]
+pub struct EmergencyClaim {
+    pub balance: u64,
+    pub salt: u128,
+}
+
+/// Commitment `claim_emergency` checks an owner's claim against: SHA-256 of the owner's key,
+/// then the balance and the salt in little-endian.
+pub fn claim_commitment(owner: PublicKey, balance: u64, salt: u128) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    hasher.update(owner.to_bytes());
+    hasher.update(balance.to_le_bytes());
+    hasher.update(salt.to_le_bytes());
+    hasher.finalize().into()
+}
+
+#[confidential]
+pub fn export_balances(
+    mapping: [Ciphertext; 2],
+    mapping_nonce: u128,
+    page: u8,
+    nonce: u128,
+) -> ([[Ciphertext; 2]; MAX_EXPORT_ENTRIES], [[u8; 32]; MAX_EXPORT_ENTRIES], bool) {
+    let cipher = RescueCipher::new_for_mxe();
+
+    // Decrypt the mapping; it is only read, so nothing is re-encrypted
+    let mapping_data = cipher.decrypt::<Mapping>(mapping, mapping_nonce);
+
+    // Slots past the end of the mapping stay zeroed
+    let mut claims = [[Ciphertext::default(); 2]; MAX_EXPORT_ENTRIES];
+    let mut commitments = [[0u8; 32]; MAX_EXPORT_ENTRIES];
+    let start = page as usize * MAX_EXPORT_ENTRIES;
+    for k in 0..MAX_EXPORT_ENTRIES {
+        let i = start + k;
+        if i < mapping_data.pubkeys.len() {
+            let owner = mapping_data.pubkeys[i];
+            let balance = mapping_data.balances[i];
+
+            // The salt keeps the balance from being guessed from its commitment
+            let salt = ArcisRNG::gen_integer_from_width(128);
+
+            // Encrypt the claim for the x25519 form of the owner's ed25519 key, the only key
+            // the mapping knows them by
+            let owner_cipher = RescueCipher::new_with_client(owner.to_x25519());
+            claims[k] = owner_cipher
+                .encrypt::<2, EmergencyClaim>(EmergencyClaim { balance, salt }, nonce + k as u128);
+            commitments[k] = claim_commitment(owner, balance, salt);
+        }
+    }
+
+    // Reveal whether entries remain past this page, so the next one can be exported
+    (
+        claims,
+        commitments,
+        start + MAX_EXPORT_ENTRIES < mapping_data.pubkeys.len(),
+    )
+}
+
+// Version 2 of the circuits that write a mapping back. Blackboxes select a version per mapping
+// account, and version 1 keeps the unversioned names above. Version 2 drops every entry whose
+// balance is zero before re-encrypting, so emptied balances and zero credits stop taking up
+// room in the mapping account; `migrate_mapping_v2` moves a version 1 mapping over. Both
+// versions share a layout, so the circuits that only read a mapping serve both.
+
+/// Drops the entries whose balance is zero, keeping the others in order.
+pub fn drop_empty_entries(mapping_data: Mapping) -> Mapping {
+    let mut kept = Mapping {
+        pubkeys: vec![],
+        balances: vec![],
+    };
+    for i in 0..mapping_data.pubkeys.len() {
+        if mapping_data.balances[i] != 0 {
+            kept.pubkeys.push(mapping_data.pubkeys[i]);
+            kept.balances.push(mapping_data.balances[i]);
+        }
+    }
+    kept
+}
+
+#[confidential]
+pub fn deposit_v2(
+    to: PublicKey,
+    deposit_amount: u64,
+    mapping: [Ciphertext; 2],
+    mapping_nonce: u128,
+    capacity: u16,
+    nonce: u128,
+) -> ([Ciphertext; 2], bool) {
+    let cipher = RescueCipher::new_for_mxe();
+
+    // Decrypt the mapping
+    let mut mapping_data = cipher.decrypt::<Mapping>(mapping, mapping_nonce);
+
+    // Credit the recipient wherever they are, without revealing where
+    let mut found = false;
+    for i in 0..mapping_data.pubkeys.len() {
+        if mapping_data.pubkeys[i] == to {
+            mapping_data.balances[i] += deposit_amount;
+            found = true;
+        }
+    }
+
+    // If recipient not found, add a new entry if the mapping account has room for it; a zero
+    // credit is dropped again below
+    let credited = found || mapping_data.pubkeys.len() < capacity as usize;
+    if !found && credited {
+        mapping_data.pubkeys.push(to);
+        mapping_data.balances.push(deposit_amount);
+    }
+
+    // Re-encrypt the updated mapping without its empty entries, revealing only whether the
+    // deposit was credited
+    (
+        cipher.encrypt::<1, Mapping>(drop_empty_entries(mapping_data), nonce),
+        credited,
+    )
+}
+
+#[confidential]
+pub fn transfer_v2(
+    mapping: [Ciphertext; 2],
+    mapping_nonce: u128,
+    capacity: u16,
+    from: PublicKey,
+    client_key: PublicKey,
+    transfer: [Ciphertext; 3],
+    nonce: u128,
+    output_nonce: u128,
+) -> [Ciphertext; 2] {
+    let cipher = RescueCipher::new_for_mxe();
+
+    // Decrypt the mapping
+    let mut mapping_data = cipher.decrypt::<Mapping>(mapping, mapping_nonce);
+
+    let client_cipher = RescueCipher::new_with_client(client_key);
+    let transfer = client_cipher.decrypt::<TransferArgs>(transfer, nonce);
+    let to_decrypted = transfer.to;
+    let transfer_amount_decrypted = transfer.amount;
+    let noop_decrypted = transfer.noop;
+
+    // Find sender and recipient indices; a self-transfer finds the same entry for both
+    let mut sender_idx = None;
+    let mut recipient_idx = None;
+    for i in 0..mapping_data.pubkeys.len() {
+        if mapping_data.pubkeys[i] == from {
+            sender_idx = Some(i);
+        }
+        if mapping_data.pubkeys[i] == to_decrypted {
+            recipient_idx = Some(i);
+        }
+    }
+
+    // Verify sender exists and transfer funds if balance is sufficient, and a new recipient
+    // fits in the mapping account
+    if let Some(idx) = sender_idx {
+        let has_room = recipient_idx.is_some() || mapping_data.pubkeys.len() < capacity as usize;
+        if !noop_decrypted && has_room && mapping_data.balances[idx] >= transfer_amount_decrypted {
+            mapping_data.balances[idx] -= transfer_amount_decrypted;
+
+            // Add to recipient if they exist, otherwise create new entry
+            if let Some(idx) = recipient_idx {
+                mapping_data.balances[idx] += transfer_amount_decrypted;
+            } else {
+                mapping_data.pubkeys.push(to_decrypted);
+                mapping_data.balances.push(transfer_amount_decrypted);
+            }
+        }
+    }
+
+    // Re-encrypt the updated mapping without its empty entries
+    cipher.encrypt::<1, Mapping>(drop_empty_entries(mapping_data), output_nonce)
+}
+
+#[confidential]
+pub fn withdraw_v2(
+    mapping: [Ciphertext; 2],
+    mapping_nonce: u128,
+    from: PublicKey,
+    withdraw_amount: u64,
+    nonce: u128,
+) -> ([Ciphertext; 2], bool) {
+    let cipher = RescueCipher::new_for_mxe();
+
+    // Decrypt the mapping
+    let mut mapping_data = cipher.decrypt::<Mapping>(mapping, mapping_nonce);
+
+    // Debit the user wherever they are, if their balance covers the withdrawal
+    let mut withdrawn = false;
+    for i in 0..mapping_data.pubkeys.len() {
+        if mapping_data.pubkeys[i] == from && mapping_data.balances[i] >= withdraw_amount {
+            mapping_data.balances[i] -= withdraw_amount;
+            withdrawn = true;
+        }
+    }
+
+    // Re-encrypt the updated mapping without its empty entries, revealing only the outcome
+    (
+        cipher.encrypt::<1, Mapping>(drop_empty_entries(mapping_data), nonce),
+        withdrawn,
+    )
+}
+
+#[confidential]
+pub fn withdraw_all_v2(
+    mapping: [Ciphertext; 2],
+    mapping_nonce: u128,
+    from: PublicKey,
+    max_payout: u64,
+    nonce: u128,
+) -> ([Ciphertext; 2], u64, bool) {
+    let cipher = RescueCipher::new_for_mxe();
+
+    // Decrypt the mapping
+    let mut mapping_data = cipher.decrypt::<Mapping>(mapping, mapping_nonce);
+
+    // Debit the user's balance, up to `max_payout`, wherever it is, without revealing where
+    let mut payout = 0;
+    let mut found = false;
+    for i in 0..mapping_data.pubkeys.len() {
+        if mapping_data.pubkeys[i] == from {
+            let balance = mapping_data.balances[i];
+            payout = if balance > max_payout { max_payout } else { balance };
+            mapping_data.balances[i] = balance - payout;
+            found = true;
+        }
+    }
+
+    // Re-encrypt the updated mapping without its empty entries, revealing only the payout and
+    // whether the user exists
+    (
+        cipher.encrypt::<1, Mapping>(drop_empty_entries(mapping_data), nonce),
+        payout,
+        found,
+    )
+}
+
+#[confidential]
+pub fn process_batch_v2(
+    mapping: [Ciphertext; 2],
+    mapping_nonce: u128,
+    capacity: u16,
+    ops: [QueuedOp; MAX_BATCH_SIZE],
+    nonce: u128,
+) -> ([Ciphertext; 2], [Ciphertext; MAX_BATCH_SIZE], [bool; MAX_BATCH_SIZE]) {
+    let cipher = RescueCipher::new_for_mxe();
+
+    // Decrypt the mapping once for the whole batch
+    let mut mapping_data = cipher.decrypt::<Mapping>(mapping, mapping_nonce);
+
+    let mut statuses = [STATUS_SKIPPED; MAX_BATCH_SIZE];
+    // Only whether tokens leave the vault is revealed, because the callback has to move them:
+    // approved withdrawals are paid out, and deposits the mapping account has no room for are
+    // refunded. Every other status is for the operation's owner alone
+    let mut payouts = [false; MAX_BATCH_SIZE];
+
+    for k in 0..MAX_BATCH_SIZE {
+        let op = ops[k];
+
+        // Every op resolves a recipient and an amount so all kinds do the same work
+        let mut to = op.owner;
+        let mut amount = op.amount;
+        let mut noop = false;
+        if op.kind == OP_TRANSFER {
+            let client_cipher = RescueCipher::new_with_client(op.client_key);
+            let transfer = client_cipher.decrypt::<TransferArgs>(op.transfer, op.nonce);
+            to = transfer.to;
+            amount = transfer.amount;
+            noop = transfer.noop;
+        }
+
+        let mut owner_idx = None;
+        let mut to_idx = None;
+        for i in 0..mapping_data.pubkeys.len() {
+            if mapping_data.pubkeys[i] == op.owner {
+                owner_idx = Some(i);
+            }
+            if mapping_data.pubkeys[i] == to {
+                to_idx = Some(i);
+            }
+        }
+
+        // A new entry for the depositor or recipient needs room in the mapping account
+        let has_room = mapping_data.pubkeys.len() < capacity as usize;
+
+        if op.kind == OP_DEPOSIT {
+            statuses[k] = STATUS_APPLIED;
+            if let Some(idx) = owner_idx {
+                mapping_data.balances[idx] += amount;
+            } else if has_room {
+                mapping_data.pubkeys.push(op.owner);
+                mapping_data.balances.push(amount);
+            } else {
+                statuses[k] = STATUS_REJECTED;
+                payouts[k] = true;
+            }
+        } else if op.kind == OP_TRANSFER || op.kind == OP_WITHDRAW {
+            statuses[k] = STATUS_REJECTED;
+            let fits = op.kind == OP_WITHDRAW || to_idx.is_some() || has_room;
+            if noop {
+                // Reported as applied so cover traffic looks like a successful transfer
+                statuses[k] = STATUS_APPLIED;
+            } else if let Some(idx) = owner_idx {
+                if fits && mapping_data.balances[idx] >= amount {
+                    mapping_data.balances[idx] -= amount;
+                    statuses[k] = STATUS_APPLIED;
+
+                    if op.kind == OP_WITHDRAW {
+                        payouts[k] = true;
+                    } else if let Some(to_idx) = to_idx {
+                        mapping_data.balances[to_idx] += amount;
+                    } else {
+                        mapping_data.pubkeys.push(to);
+                        mapping_data.balances.push(amount);
+                    }
+                }
+            }
+        }
+    }
+
+    // Encrypt each status for its owner's client key, under its own nonce so results can't be
+    // correlated by position
+    let mut enc_statuses = [Ciphertext::default(); MAX_BATCH_SIZE];
+    for k in 0..MAX_BATCH_SIZE {
+        let client_cipher = RescueCipher::new_with_client(ops[k].client_key);
+        enc_statuses[k] = client_cipher.encrypt::<1, u64>(statuses[k], nonce + 1 + k as u128)[0];
+    }
+
+    // Re-encrypt the updated mapping without its empty entries
+    (
+        cipher.encrypt::<1, Mapping>(drop_empty_entries(mapping_data), nonce),
+        enc_statuses,
+        payouts,
+    )
+}
+
+#[confidential]
+pub fn migrate_mapping_v2(
+    mapping: [Ciphertext; 2],
+    mapping_nonce: u128,
+    nonce: u128,
+) -> [Ciphertext; 2] {
+    let cipher = RescueCipher::new_for_mxe();
+
+    // A version 1 mapping has the same layout, but may hold empty entries
+    let mapping_data = cipher.decrypt::<Mapping>(mapping, mapping_nonce);
+
+    cipher.encrypt::<1, Mapping>(drop_empty_entries(mapping_data), nonce)
+}
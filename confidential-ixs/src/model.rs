@@ -0,0 +1,576 @@
+//! Plaintext reference model of the `Mapping` circuits.
+//!
+//! Every function here mirrors the `#[confidential]` circuit of the same name step for step,
+//! but runs as ordinary Rust so it can be unit-tested without an MPC cluster. Encryption goes
+//! through the [`Cipher`] trait: [`IdentityCipher`] leaves values in the clear, while a real
+//! cipher seals every [`Encodable`] value field element by field element, as the MXE does.
+
+use crate::{
+    claim_commitment, drop_empty_entries, EmergencyClaim, Mapping, TransferArgs, MAX_BATCH_SIZE,
+    MAX_EXPORT_ENTRIES, MAX_SOLVENCY_SHARDS, OP_DEPOSIT, OP_TRANSFER, OP_WITHDRAW, STATUS_APPLIED,
+    STATUS_REJECTED, STATUS_SKIPPED,
+};
+use arcis::prelude::PublicKey;
+
+/// A value a real cipher can seal: a sequence of field elements, one per field, encrypted at
+/// consecutive keystream positions under one nonce.
+pub trait Encodable: Clone {
+    /// The value's field elements, each the little-endian bytes of an integer below p
+    fn encode(&self) -> Vec<[u8; 32]>;
+
+    /// Reads a value back from the elements `encode` produced
+    fn decode(elements: &[[u8; 32]]) -> Self;
+}
+
+fn element(value: u128) -> [u8; 32] {
+    let mut element = [0; 32];
+    element[..16].copy_from_slice(&value.to_le_bytes());
+    element
+}
+
+fn integer(element: &[u8; 32]) -> u128 {
+    u128::from_le_bytes(element[..16].try_into().unwrap())
+}
+
+impl Encodable for TransferArgs {
+    fn encode(&self) -> Vec<[u8; 32]> {
+        vec![
+            self.to.to_bytes(),
+            element(self.amount.into()),
+            element(self.noop.into()),
+        ]
+    }
+
+    fn decode(elements: &[[u8; 32]]) -> Self {
+        TransferArgs {
+            to: PublicKey::from(elements[0]),
+            amount: integer(&elements[1]) as u64,
+            noop: integer(&elements[2]) != 0,
+        }
+    }
+}
+
+/// A running total, such as the one `prove_solvency` carries between ranges of shards.
+impl Encodable for u128 {
+    fn encode(&self) -> Vec<[u8; 32]> {
+        vec![element(*self)]
+    }
+
+    fn decode(elements: &[[u8; 32]]) -> Self {
+        integer(&elements[0])
+    }
+}
+
+/// A mapping's length varies, so its elements lead with the entry count.
+impl Encodable for Mapping {
+    fn encode(&self) -> Vec<[u8; 32]> {
+        let mut elements = vec![element(self.pubkeys.len() as u128)];
+        elements.extend(self.pubkeys.iter().map(|key| key.to_bytes()));
+        elements.extend(self.balances.iter().map(|&balance| element(balance.into())));
+        elements
+    }
+
+    fn decode(elements: &[[u8; 32]]) -> Self {
+        let len = integer(&elements[0]) as usize;
+        Mapping {
+            pubkeys: elements[1..=len]
+                .iter()
+                .map(|&key| PublicKey::from(key))
+                .collect(),
+            balances: elements[len + 1..]
+                .iter()
+                .map(|balance| integer(balance) as u64)
+                .collect(),
+        }
+    }
+}
+
+/// Encryption used by the model in place of the MXE's `RescueCipher`.
+pub trait Cipher {
+    /// Encrypted form of a `T`
+    type Sealed<T: Encodable>: Clone;
+
+    fn encrypt<T: Encodable>(&self, value: T, nonce: u128) -> Self::Sealed<T>;
+
+    fn decrypt<T: Encodable>(&self, sealed: &Self::Sealed<T>, nonce: u128) -> T;
+}
+
+/// Cipher that doesn't encrypt anything, for testing circuit logic in isolation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IdentityCipher;
+
+impl Cipher for IdentityCipher {
+    type Sealed<T: Encodable> = T;
+
+    fn encrypt<T: Encodable>(&self, value: T, _nonce: u128) -> T {
+        value
+    }
+
+    fn decrypt<T: Encodable>(&self, sealed: &T, _nonce: u128) -> T {
+        sealed.clone()
+    }
+}
+
+/// A queued operation with its transfer fields sealed by `C`, mirroring `QueuedOp`.
+pub struct ModelOp<C: Cipher> {
+    pub kind: u8,
+    pub owner: PublicKey,
+    pub client_key: PublicKey,
+    pub transfer: C::Sealed<TransferArgs>,
+    pub amount: u64,
+    pub nonce: u128,
+}
+
+impl<C: Cipher> Clone for ModelOp<C> {
+    fn clone(&self) -> Self {
+        Self {
+            kind: self.kind,
+            owner: self.owner,
+            client_key: self.client_key,
+            transfer: self.transfer.clone(),
+            amount: self.amount,
+            nonce: self.nonce,
+        }
+    }
+}
+
+/// Model of the `init_mapping` circuit.
+pub fn init_mapping<C: Cipher>(cipher: &C, mapping_nonce: u128) -> C::Sealed<Mapping> {
+    let mapping = Mapping {
+        pubkeys: vec![],
+        balances: vec![],
+    };
+    cipher.encrypt(mapping, mapping_nonce)
+}
+
+/// Model of the `deposit` circuit.
+pub fn deposit<C: Cipher>(
+    cipher: &C,
+    to: PublicKey,
+    deposit_amount: u64,
+    mapping: &C::Sealed<Mapping>,
+    mapping_nonce: u128,
+    capacity: u16,
+    nonce: u128,
+) -> (C::Sealed<Mapping>, bool) {
+    let mut mapping_data = cipher.decrypt(mapping, mapping_nonce);
+
+    let mut found = false;
+    for i in 0..mapping_data.pubkeys.len() {
+        if mapping_data.pubkeys[i] == to {
+            mapping_data.balances[i] += deposit_amount;
+            found = true;
+            break;
+        }
+    }
+
+    let credited = found || mapping_data.pubkeys.len() < capacity as usize;
+    if !found && credited {
+        mapping_data.pubkeys.push(to);
+        mapping_data.balances.push(deposit_amount);
+    }
+
+    (cipher.encrypt(mapping_data, nonce), credited)
+}
+
+/// Model of the `transfer` circuit.
+///
+/// The circuit decrypts `transfer` with the cipher agreed with the sender's client key, which
+/// `C` doesn't model, so it takes no client key and `C` seals `transfer` too.
+pub fn transfer<C: Cipher>(
+    cipher: &C,
+    mapping: &C::Sealed<Mapping>,
+    mapping_nonce: u128,
+    capacity: u16,
+    from: PublicKey,
+    transfer: &C::Sealed<TransferArgs>,
+    nonce: u128,
+    output_nonce: u128,
+) -> C::Sealed<Mapping> {
+    let mut mapping_data = cipher.decrypt(mapping, mapping_nonce);
+
+    let transfer = cipher.decrypt(transfer, nonce);
+    let to_decrypted = transfer.to;
+    let transfer_amount_decrypted = transfer.amount;
+    let noop_decrypted = transfer.noop;
+
+    let mut sender_idx = None;
+    let mut recipient_idx = None;
+    for i in 0..mapping_data.pubkeys.len() {
+        if mapping_data.pubkeys[i] == from {
+            sender_idx = Some(i);
+        }
+        if mapping_data.pubkeys[i] == to_decrypted {
+            recipient_idx = Some(i);
+        }
+        if sender_idx.is_some() && recipient_idx.is_some() {
+            break;
+        }
+    }
+
+    if let Some(idx) = sender_idx {
+        let has_room = recipient_idx.is_some() || mapping_data.pubkeys.len() < capacity as usize;
+        if !noop_decrypted && has_room && mapping_data.balances[idx] >= transfer_amount_decrypted {
+            mapping_data.balances[idx] -= transfer_amount_decrypted;
+
+            if let Some(idx) = recipient_idx {
+                mapping_data.balances[idx] += transfer_amount_decrypted;
+            } else {
+                mapping_data.pubkeys.push(to_decrypted);
+                mapping_data.balances.push(transfer_amount_decrypted);
+            }
+        }
+    }
+
+    cipher.encrypt(mapping_data, output_nonce)
+}
+
+/// Model of the `withdraw` circuit.
+pub fn withdraw<C: Cipher>(
+    cipher: &C,
+    mapping: &C::Sealed<Mapping>,
+    mapping_nonce: u128,
+    from: PublicKey,
+    withdraw_amount: u64,
+    nonce: u128,
+) -> (C::Sealed<Mapping>, bool) {
+    let mut mapping_data = cipher.decrypt(mapping, mapping_nonce);
+
+    let mut user_idx = None;
+    for i in 0..mapping_data.pubkeys.len() {
+        if mapping_data.pubkeys[i] == from {
+            user_idx = Some(i);
+            break;
+        }
+    }
+
+    if let Some(idx) = user_idx {
+        if mapping_data.balances[idx] >= withdraw_amount {
+            mapping_data.balances[idx] -= withdraw_amount;
+            return (cipher.encrypt(mapping_data, nonce), true);
+        }
+    }
+
+    (cipher.encrypt(mapping_data, nonce), false)
+}
+
+/// Model of the `withdraw_all` circuit.
+pub fn withdraw_all<C: Cipher>(
+    cipher: &C,
+    mapping: &C::Sealed<Mapping>,
+    mapping_nonce: u128,
+    from: PublicKey,
+    max_payout: u64,
+    nonce: u128,
+) -> (C::Sealed<Mapping>, u64, bool) {
+    let mut mapping_data = cipher.decrypt(mapping, mapping_nonce);
+
+    let mut payout = 0;
+    let mut found = false;
+    for i in 0..mapping_data.pubkeys.len() {
+        if mapping_data.pubkeys[i] == from {
+            let balance = mapping_data.balances[i];
+            payout = balance.min(max_payout);
+            mapping_data.balances[i] = balance - payout;
+            found = true;
+        }
+    }
+
+    (cipher.encrypt(mapping_data, nonce), payout, found)
+}
+
+/// Model of the `process_batch` circuit. `ops` holds at most `MAX_BATCH_SIZE` operations;
+/// missing slots are treated as empty.
+///
+/// Returns the statuses in the clear: the circuit encrypts each for its operation's client key
+/// rather than the MXE, which `C` doesn't model.
+pub fn process_batch<C: Cipher>(
+    cipher: &C,
+    mapping: &C::Sealed<Mapping>,
+    mapping_nonce: u128,
+    capacity: u16,
+    ops: &[ModelOp<C>],
+    nonce: u128,
+) -> (
+    C::Sealed<Mapping>,
+    [u64; MAX_BATCH_SIZE],
+    [bool; MAX_BATCH_SIZE],
+) {
+    assert!(ops.len() <= MAX_BATCH_SIZE, "batch holds at most {MAX_BATCH_SIZE} ops");
+    let mut mapping_data = cipher.decrypt(mapping, mapping_nonce);
+
+    let mut statuses = [STATUS_SKIPPED; MAX_BATCH_SIZE];
+    let mut payouts = [false; MAX_BATCH_SIZE];
+
+    for (k, op) in ops.iter().enumerate() {
+        let mut to = op.owner;
+        let mut amount = op.amount;
+        let mut noop = false;
+        if op.kind == OP_TRANSFER {
+            let transfer = cipher.decrypt(&op.transfer, op.nonce);
+            to = transfer.to;
+            amount = transfer.amount;
+            noop = transfer.noop;
+        }
+
+        let mut owner_idx = None;
+        let mut to_idx = None;
+        for i in 0..mapping_data.pubkeys.len() {
+            if mapping_data.pubkeys[i] == op.owner {
+                owner_idx = Some(i);
+            }
+            if mapping_data.pubkeys[i] == to {
+                to_idx = Some(i);
+            }
+        }
+
+        let has_room = mapping_data.pubkeys.len() < capacity as usize;
+
+        if op.kind == OP_DEPOSIT {
+            statuses[k] = STATUS_APPLIED;
+            if let Some(idx) = owner_idx {
+                mapping_data.balances[idx] += amount;
+            } else if has_room {
+                mapping_data.pubkeys.push(op.owner);
+                mapping_data.balances.push(amount);
+            } else {
+                statuses[k] = STATUS_REJECTED;
+                payouts[k] = true;
+            }
+        } else if op.kind == OP_TRANSFER || op.kind == OP_WITHDRAW {
+            statuses[k] = STATUS_REJECTED;
+            let fits = op.kind == OP_WITHDRAW || to_idx.is_some() || has_room;
+            if noop {
+                statuses[k] = STATUS_APPLIED;
+            } else if let Some(idx) = owner_idx {
+                if fits && mapping_data.balances[idx] >= amount {
+                    mapping_data.balances[idx] -= amount;
+                    statuses[k] = STATUS_APPLIED;
+
+                    if op.kind == OP_WITHDRAW {
+                        payouts[k] = true;
+                    } else if let Some(to_idx) = to_idx {
+                        mapping_data.balances[to_idx] += amount;
+                    } else {
+                        mapping_data.pubkeys.push(to);
+                        mapping_data.balances.push(amount);
+                    }
+                }
+            }
+        }
+    }
+
+    (cipher.encrypt(mapping_data, nonce), statuses, payouts)
+}
+
+/// Model of the `reveal_balance` circuit.
+///
+/// Returns the owner's balance in the clear: the circuit encrypts it for the client rather than
+/// the MXE, which `C` doesn't model.
+pub fn reveal_balance<C: Cipher>(
+    cipher: &C,
+    mapping: &C::Sealed<Mapping>,
+    mapping_nonce: u128,
+    owner: PublicKey,
+) -> u64 {
+    let mapping_data = cipher.decrypt(mapping, mapping_nonce);
+
+    let mut balance = 0;
+    for i in 0..mapping_data.pubkeys.len() {
+        if mapping_data.pubkeys[i] == owner {
+            balance = mapping_data.balances[i];
+        }
+    }
+
+    balance
+}
+
+/// Model of the `prove_solvency` circuit. `shards` holds the sealed mapping and nonce of at
+/// most `MAX_SOLVENCY_SHARDS` shards; missing slots aren't counted. `total` is the sealed total
+/// of the ranges summed before and its nonce, `None` for the first range.
+///
+/// Returns the total so far sealed under `nonce`, and whether the vault covers it, which is
+/// only ever true for the `last` range.
+pub fn prove_solvency<C: Cipher>(
+    cipher: &C,
+    shards: &[(C::Sealed<Mapping>, u128)],
+    total: Option<(&C::Sealed<u128>, u128)>,
+    last: bool,
+    vault_amount: u64,
+    nonce: u128,
+) -> (C::Sealed<u128>, bool) {
+    assert!(
+        shards.len() <= MAX_SOLVENCY_SHARDS,
+        "at most {MAX_SOLVENCY_SHARDS} shards are summed"
+    );
+
+    let mut sum = total.map_or(0, |(total, total_nonce)| cipher.decrypt(total, total_nonce));
+    for (mapping, mapping_nonce) in shards {
+        let mapping_data = cipher.decrypt(mapping, *mapping_nonce);
+        for i in 0..mapping_data.balances.len() {
+            sum += mapping_data.balances[i] as u128;
+        }
+    }
+
+    (
+        cipher.encrypt(sum, nonce),
+        last && sum <= vault_amount as u128,
+    )
+}
+
+/// An entry exported by `export_balances`, with its owner in the clear.
+#[derive(Clone, Copy)]
+pub struct ExportedEntry {
+    pub owner: PublicKey,
+    pub claim: EmergencyClaim,
+    pub commitment: [u8; 32],
+}
+
+/// Model of the `export_balances` circuit, with `salts` standing in for the MXE's randomness.
+///
+/// Returns each exported entry with its claim in the clear, since the circuit encrypts claims
+/// for their owners rather than the MXE, which `C` doesn't model. Entries come in mapping
+/// order and stop at the end of the mapping; the flag tells whether entries remain past the
+/// page.
+pub fn export_balances<C: Cipher>(
+    cipher: &C,
+    mapping: &C::Sealed<Mapping>,
+    mapping_nonce: u128,
+    page: u8,
+    salts: [u128; MAX_EXPORT_ENTRIES],
+) -> (Vec<ExportedEntry>, bool) {
+    let mapping_data = cipher.decrypt(mapping, mapping_nonce);
+
+    let mut entries = Vec::with_capacity(MAX_EXPORT_ENTRIES);
+    let start = page as usize * MAX_EXPORT_ENTRIES;
+    for k in 0..MAX_EXPORT_ENTRIES {
+        let i = start + k;
+        if i < mapping_data.pubkeys.len() {
+            let owner = mapping_data.pubkeys[i];
+            let balance = mapping_data.balances[i];
+            let salt = salts[k];
+            entries.push(ExportedEntry {
+                owner,
+                claim: EmergencyClaim { balance, salt },
+                commitment: claim_commitment(owner, balance, salt),
+            });
+        }
+    }
+
+    (
+        entries,
+        start + MAX_EXPORT_ENTRIES < mapping_data.pubkeys.len(),
+    )
+}
+
+/// Model of the `deposit_v2` circuit: `deposit`, then the empty entries dropped.
+pub fn deposit_v2<C: Cipher>(
+    cipher: &C,
+    to: PublicKey,
+    deposit_amount: u64,
+    mapping: &C::Sealed<Mapping>,
+    mapping_nonce: u128,
+    capacity: u16,
+    nonce: u128,
+) -> (C::Sealed<Mapping>, bool) {
+    let mapping_data = cipher.decrypt(mapping, mapping_nonce);
+    let (mapping_data, credited) =
+        deposit(&IdentityCipher, to, deposit_amount, &mapping_data, 0, capacity, 0);
+    (cipher.encrypt(drop_empty_entries(mapping_data), nonce), credited)
+}
+
+/// Model of the `transfer_v2` circuit: `transfer`, then the empty entries dropped.
+pub fn transfer_v2<C: Cipher>(
+    cipher: &C,
+    mapping: &C::Sealed<Mapping>,
+    mapping_nonce: u128,
+    capacity: u16,
+    from: PublicKey,
+    transfer: &C::Sealed<TransferArgs>,
+    nonce: u128,
+    output_nonce: u128,
+) -> C::Sealed<Mapping> {
+    let mapping_data = cipher.decrypt(mapping, mapping_nonce);
+    let mapping_data = transfer(
+        &IdentityCipher,
+        &mapping_data,
+        0,
+        capacity,
+        from,
+        &cipher.decrypt(transfer, nonce),
+        0,
+        0,
+    );
+    cipher.encrypt(drop_empty_entries(mapping_data), output_nonce)
+}
+
+/// Model of the `withdraw_v2` circuit: `withdraw`, then the empty entries dropped.
+pub fn withdraw_v2<C: Cipher>(
+    cipher: &C,
+    mapping: &C::Sealed<Mapping>,
+    mapping_nonce: u128,
+    from: PublicKey,
+    withdraw_amount: u64,
+    nonce: u128,
+) -> (C::Sealed<Mapping>, bool) {
+    let mapping_data = cipher.decrypt(mapping, mapping_nonce);
+    let (mapping_data, withdrawn) =
+        withdraw(&IdentityCipher, &mapping_data, 0, from, withdraw_amount, 0);
+    (cipher.encrypt(drop_empty_entries(mapping_data), nonce), withdrawn)
+}
+
+/// Model of the `withdraw_all_v2` circuit: `withdraw_all`, then the empty entries dropped.
+pub fn withdraw_all_v2<C: Cipher>(
+    cipher: &C,
+    mapping: &C::Sealed<Mapping>,
+    mapping_nonce: u128,
+    from: PublicKey,
+    max_payout: u64,
+    nonce: u128,
+) -> (C::Sealed<Mapping>, u64, bool) {
+    let mapping_data = cipher.decrypt(mapping, mapping_nonce);
+    let (mapping_data, payout, found) =
+        withdraw_all(&IdentityCipher, &mapping_data, 0, from, max_payout, 0);
+    (cipher.encrypt(drop_empty_entries(mapping_data), nonce), payout, found)
+}
+
+/// Model of the `process_batch_v2` circuit: `process_batch`, then the empty entries dropped.
+pub fn process_batch_v2<C: Cipher>(
+    cipher: &C,
+    mapping: &C::Sealed<Mapping>,
+    mapping_nonce: u128,
+    capacity: u16,
+    ops: &[ModelOp<C>],
+    nonce: u128,
+) -> (
+    C::Sealed<Mapping>,
+    [u64; MAX_BATCH_SIZE],
+    [bool; MAX_BATCH_SIZE],
+) {
+    let mapping_data = cipher.decrypt(mapping, mapping_nonce);
+    let ops: Vec<_> = ops
+        .iter()
+        .map(|op| ModelOp::<IdentityCipher> {
+            kind: op.kind,
+            owner: op.owner,
+            client_key: op.client_key,
+            transfer: cipher.decrypt(&op.transfer, op.nonce),
+            amount: op.amount,
+            nonce: 0,
+        })
+        .collect();
+    let (mapping_data, statuses, payouts) =
+        process_batch(&IdentityCipher, &mapping_data, 0, capacity, &ops, 0);
+    (cipher.encrypt(drop_empty_entries(mapping_data), nonce), statuses, payouts)
+}
+
+/// Model of the `migrate_mapping_v2` circuit.
+pub fn migrate_mapping_v2<C: Cipher>(
+    cipher: &C,
+    mapping: &C::Sealed<Mapping>,
+    mapping_nonce: u128,
+    nonce: u128,
+) -> C::Sealed<Mapping> {
+    let mapping_data = cipher.decrypt(mapping, mapping_nonce);
+    cipher.encrypt(drop_empty_entries(mapping_data), nonce)
+}
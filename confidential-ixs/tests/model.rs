@@ -0,0 +1,589 @@
+use std::collections::HashMap;
+
+use arcis::prelude::PublicKey;
+use blackbox_client::encryption::{FieldElement, RescueCipher};
+use confidential_ixs::model::{self, Cipher, Encodable, IdentityCipher, ModelOp};
+use confidential_ixs::{
+    claim_commitment, Mapping, TransferArgs, MAX_BATCH_SIZE, MAX_EXPORT_ENTRIES,
+    MAX_SOLVENCY_SHARDS, OP_DEPOSIT, OP_TRANSFER, OP_WITHDRAW,
+};
+use proptest::prelude::*;
+
+const USERS: u8 = 4;
+
+/// The client's Rescue cipher under a fixed key, sealing values element by element as the MXE
+/// does
+struct Rescue(RescueCipher);
+
+impl Rescue {
+    fn new() -> Self {
+        Self(RescueCipher::new(std::array::from_fn(|i| {
+            FieldElement::from(i as u64 + 1)
+        })))
+    }
+}
+
+impl Cipher for Rescue {
+    type Sealed<T: Encodable> = Vec<[u8; 32]>;
+
+    fn encrypt<T: Encodable>(&self, value: T, nonce: u128) -> Vec<[u8; 32]> {
+        let elements: Vec<_> = value
+            .encode()
+            .iter()
+            .map(|element| FieldElement::from_le_bytes(element).expect("element is below p"))
+            .collect();
+        self.0.encrypt(&elements, nonce)
+    }
+
+    fn decrypt<T: Encodable>(&self, sealed: &Vec<[u8; 32]>, nonce: u128) -> T {
+        let elements = self.0.decrypt(sealed, nonce).expect("ciphertext is below p");
+        T::decode(&elements.iter().map(FieldElement::to_le_bytes).collect::<Vec<_>>())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Step {
+    Deposit { user: u8, amount: u64 },
+    Transfer { from: u8, to: u8, amount: u64, noop: bool },
+    Withdraw { user: u8, amount: u64 },
+    WithdrawAll { user: u8, max_payout: u64 },
+}
+
+fn key(user: u8) -> PublicKey {
+    PublicKey::from([user + 1; 32])
+}
+
+fn args(to: u8, amount: u64, noop: bool) -> TransferArgs {
+    TransferArgs {
+        to: key(to),
+        amount,
+        noop,
+    }
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    let user = 0..USERS;
+    let amount = 0..1_000_000u64;
+    prop_oneof![
+        (user.clone(), amount.clone()).prop_map(|(user, amount)| Step::Deposit { user, amount }),
+        (user.clone(), user.clone(), amount.clone(), any::<bool>())
+            .prop_map(|(from, to, amount, noop)| Step::Transfer { from, to, amount, noop }),
+        (user.clone(), amount.clone()).prop_map(|(user, amount)| Step::Withdraw { user, amount }),
+        (user, prop_oneof![amount, Just(u64::MAX)])
+            .prop_map(|(user, max_payout)| Step::WithdrawAll { user, max_payout }),
+    ]
+}
+
+fn balance_of(mapping: &Mapping, user: u8) -> u64 {
+    (0..mapping.pubkeys.len())
+        .filter(|&i| mapping.pubkeys[i] == key(user))
+        .map(|i| mapping.balances[i])
+        .sum()
+}
+
+fn same_mapping(a: &Mapping, b: &Mapping) -> bool {
+    a.pubkeys.len() == b.pubkeys.len()
+        && (0..a.pubkeys.len()).all(|i| a.pubkeys[i] == b.pubkeys[i])
+        && a.balances == b.balances
+}
+
+/// A mapping sealed by `C`, re-encrypted under a fresh nonce by every step as on-chain
+struct Run<'a, C: Cipher> {
+    cipher: &'a C,
+    mapping: C::Sealed<Mapping>,
+    nonce: u128,
+    /// Entries the mapping account has room for
+    capacity: u16,
+}
+
+impl<C: Cipher> Clone for Run<'_, C> {
+    fn clone(&self) -> Self {
+        Self {
+            cipher: self.cipher,
+            mapping: self.mapping.clone(),
+            nonce: self.nonce,
+            capacity: self.capacity,
+        }
+    }
+}
+
+impl<'a, C: Cipher> Run<'a, C> {
+    fn new(cipher: &'a C) -> Self {
+        Self::with_capacity(cipher, u16::MAX)
+    }
+
+    fn with_capacity(cipher: &'a C, capacity: u16) -> Self {
+        Self {
+            cipher,
+            mapping: model::init_mapping(cipher, 0),
+            nonce: 0,
+            capacity,
+        }
+    }
+
+    fn open(&self) -> Mapping {
+        self.cipher.decrypt(&self.mapping, self.nonce)
+    }
+
+    /// Seals transfer arguments under a nonce no mapping is encrypted under.
+    fn seal(&self, transfer: TransferArgs) -> (C::Sealed<TransferArgs>, u128) {
+        let nonce = u128::MAX - self.nonce;
+        (self.cipher.encrypt(transfer, nonce), nonce)
+    }
+
+    fn deposit(&mut self, user: u8, amount: u64, version: u8) -> bool {
+        let (cipher, nonce, capacity) = (self.cipher, self.nonce, self.capacity);
+        let circuit = if version == 1 { model::deposit::<C> } else { model::deposit_v2::<C> };
+        let (mapping, credited) =
+            circuit(cipher, key(user), amount, &self.mapping, nonce, capacity, nonce + 1);
+        (self.mapping, self.nonce) = (mapping, nonce + 1);
+        credited
+    }
+
+    fn withdraw(&mut self, user: u8, amount: u64, version: u8) -> bool {
+        let (cipher, nonce) = (self.cipher, self.nonce);
+        let circuit = if version == 1 { model::withdraw::<C> } else { model::withdraw_v2::<C> };
+        let (mapping, ok) = circuit(cipher, &self.mapping, nonce, key(user), amount, nonce + 1);
+        (self.mapping, self.nonce) = (mapping, nonce + 1);
+        ok
+    }
+
+    /// Applies a step through the version 1 or 2 circuits, returning the amount paid out, or
+    /// refunded for a deposit the mapping has no room for.
+    fn apply(&mut self, step: &Step, version: u8) -> u64 {
+        let (cipher, nonce) = (self.cipher, self.nonce);
+        let (mapping, payout) = match *step {
+            Step::Deposit { user, amount } => {
+                return if self.deposit(user, amount, version) { 0 } else { amount };
+            }
+            Step::Transfer { from, to, amount, noop } => {
+                let (transfer, args_nonce) = self.seal(args(to, amount, noop));
+                let circuit =
+                    if version == 1 { model::transfer::<C> } else { model::transfer_v2::<C> };
+                let mapping = circuit(
+                    cipher,
+                    &self.mapping,
+                    nonce,
+                    self.capacity,
+                    key(from),
+                    &transfer,
+                    args_nonce,
+                    nonce + 1,
+                );
+                (mapping, 0)
+            }
+            Step::Withdraw { user, amount } => {
+                return if self.withdraw(user, amount, version) { amount } else { 0 };
+            }
+            Step::WithdrawAll { user, max_payout } => {
+                let circuit = if version == 1 {
+                    model::withdraw_all::<C>
+                } else {
+                    model::withdraw_all_v2::<C>
+                };
+                let (mapping, payout, _) = circuit(
+                    cipher,
+                    &self.mapping,
+                    nonce,
+                    key(user),
+                    max_payout,
+                    nonce + 1,
+                );
+                (mapping, payout)
+            }
+        };
+        (self.mapping, self.nonce) = (mapping, nonce + 1);
+        payout
+    }
+
+    /// Version 2 of this run's mapping, as `migrate_mapping` stores it.
+    fn migrate(&self) -> Mapping {
+        let migrated =
+            model::migrate_mapping_v2(self.cipher, &self.mapping, self.nonce, self.nonce + 1);
+        self.cipher.decrypt(&migrated, self.nonce + 1)
+    }
+}
+
+fn as_op<C: Cipher>(cipher: &C, step: &Step, nonce: u128) -> ModelOp<C> {
+    let (kind, owner, to, amount, noop) = match *step {
+        Step::Deposit { user, amount } => (OP_DEPOSIT, user, user, amount, false),
+        Step::Transfer { from, to, amount, noop } => (OP_TRANSFER, from, to, amount, noop),
+        Step::Withdraw { user, amount } => (OP_WITHDRAW, user, user, amount, false),
+        Step::WithdrawAll { .. } => unreachable!("withdraw_all has no batch op"),
+    };
+    ModelOp {
+        kind,
+        owner: key(owner),
+        client_key: key(owner),
+        transfer: cipher.encrypt(args(to, amount, noop), nonce),
+        amount: if kind == OP_TRANSFER { 0 } else { amount },
+        nonce,
+    }
+}
+
+fn balances_are_conserved<C: Cipher>(
+    cipher: &C,
+    steps: &[Step],
+    capacity: u16,
+) -> Result<(), TestCaseError> {
+    let mut run = Run::with_capacity(cipher, capacity);
+    let mut vault = 0u64;
+    let mut expected: HashMap<u8, u64> = HashMap::new();
+
+    for step in steps {
+        let payout = run.apply(step, 1);
+
+        // Plaintext oracle of what every user should own; version 1 keeps every entry it adds,
+        // so a user has one exactly when they're in `expected`
+        let has_room = |expected: &HashMap<u8, u64>, user| {
+            expected.contains_key(&user) || expected.len() < capacity as usize
+        };
+        match *step {
+            Step::Deposit { user, amount } => {
+                vault += amount;
+                if has_room(&expected, user) {
+                    *expected.entry(user).or_default() += amount;
+                } else {
+                    prop_assert_eq!(payout, amount, "deposit wasn't refunded");
+                }
+            }
+            Step::Transfer { from, to, amount, noop } => {
+                let owned = expected.get(&from).copied().unwrap_or(0);
+                let moves = !noop && expected.contains_key(&from) && has_room(&expected, to);
+                if moves && owned >= amount {
+                    *expected.get_mut(&from).unwrap() -= amount;
+                    *expected.entry(to).or_default() += amount;
+                }
+            }
+            Step::Withdraw { user, .. } | Step::WithdrawAll { user, .. } => {
+                let owned = expected.get(&user).copied().unwrap_or(0);
+                prop_assert!(payout <= owned, "paid {} out of {}", payout, owned);
+                if let Step::WithdrawAll { max_payout, .. } = *step {
+                    prop_assert_eq!(payout, owned.min(max_payout));
+                }
+                if let Some(balance) = expected.get_mut(&user) {
+                    *balance -= payout;
+                }
+            }
+        }
+        vault -= payout;
+
+        let mapping = run.open();
+        prop_assert_eq!(mapping.pubkeys.len(), mapping.balances.len());
+        prop_assert!(mapping.pubkeys.len() <= capacity as usize);
+        prop_assert_eq!(mapping.balances.iter().sum::<u64>(), vault);
+        for user in 0..USERS {
+            let entries = (0..mapping.pubkeys.len())
+                .filter(|&i| mapping.pubkeys[i] == key(user))
+                .count();
+            prop_assert!(entries <= 1, "user {} has {} entries", user, entries);
+            prop_assert_eq!(
+                balance_of(&mapping, user),
+                expected.get(&user).copied().unwrap_or(0)
+            );
+            prop_assert_eq!(
+                model::reveal_balance(cipher, &run.mapping, run.nonce, key(user)),
+                balance_of(&mapping, user)
+            );
+        }
+    }
+    Ok(())
+}
+
+fn failed_operations_leave_mapping_unchanged<C: Cipher>(
+    cipher: &C,
+    deposits: &[(u8, u64)],
+    user: u8,
+    to: u8,
+    excess: u64,
+) -> Result<(), TestCaseError> {
+    let mut run = Run::new(cipher);
+    for &(user, amount) in deposits {
+        run.apply(&Step::Deposit { user, amount }, 1);
+    }
+    let mapping = run.open();
+    let amount = balance_of(&mapping, user) + excess;
+
+    let mut failed = run.clone();
+    for _ in 0..2 {
+        prop_assert!(!failed.withdraw(user, amount, 1));
+        prop_assert!(same_mapping(&failed.open(), &mapping));
+    }
+
+    let mut failed = run;
+    let transfer = Step::Transfer { from: user, to, amount, noop: false };
+    for _ in 0..2 {
+        failed.apply(&transfer, 1);
+        prop_assert!(same_mapping(&failed.open(), &mapping));
+    }
+    Ok(())
+}
+
+fn noop_transfers_leave_mapping_unchanged<C: Cipher>(
+    cipher: &C,
+    deposits: &[(u8, u64)],
+    from: u8,
+    to: u8,
+    amount: u64,
+) -> Result<(), TestCaseError> {
+    let mut run = Run::new(cipher);
+    for &(user, amount) in deposits {
+        run.apply(&Step::Deposit { user, amount }, 1);
+    }
+    let mapping = run.open();
+
+    run.apply(&Step::Transfer { from, to, amount, noop: true }, 1);
+    prop_assert!(same_mapping(&run.open(), &mapping));
+    Ok(())
+}
+
+fn batch_matches_sequential_operations<C: Cipher>(
+    cipher: &C,
+    steps: &[Step],
+    capacity: u16,
+) -> Result<(), TestCaseError> {
+    let mut sequential = Run::with_capacity(cipher, capacity);
+    let mut payouts = Vec::new();
+    for step in steps {
+        // Refunded deposits leave the vault like paid out withdrawals
+        let paid = match *step {
+            Step::Deposit { user, amount } => !sequential.deposit(user, amount, 1),
+            Step::Withdraw { user, amount } => sequential.withdraw(user, amount, 1),
+            _ => {
+                sequential.apply(step, 1);
+                false
+            }
+        };
+        payouts.push(paid);
+    }
+
+    // Each op's arguments are sealed under a nonce of their own
+    let ops: Vec<_> = (0..)
+        .zip(steps)
+        .map(|(k, step)| as_op(cipher, step, u128::MAX - k))
+        .collect();
+    let empty = model::init_mapping(cipher, 0);
+    let (batched, _, batch_payouts) = model::process_batch(cipher, &empty, 0, capacity, &ops, 1);
+
+    prop_assert!(same_mapping(&cipher.decrypt(&batched, 1), &sequential.open()));
+    prop_assert_eq!(&batch_payouts[..steps.len()], &payouts[..]);
+
+    // Version 2 ends on the same balances, without the empty entries
+    let (batched, _, v2_payouts) = model::process_batch_v2(cipher, &empty, 0, capacity, &ops, 1);
+    prop_assert!(same_mapping(&cipher.decrypt(&batched, 1), &sequential.migrate()));
+    prop_assert_eq!(v2_payouts, batch_payouts);
+    Ok(())
+}
+
+fn solvency_carries_the_total_across_ranges<C: Cipher>(
+    cipher: &C,
+    deposits: &[Vec<(u8, u64)>],
+    vault: u64,
+) -> Result<(), TestCaseError> {
+    let mut total = 0u128;
+    let shards: Vec<_> = deposits
+        .iter()
+        .map(|shard| {
+            let mut run = Run::new(cipher);
+            for &(user, amount) in shard {
+                total += amount as u128;
+                run.apply(&Step::Deposit { user, amount }, 1);
+            }
+            (run.mapping, run.nonce)
+        })
+        .collect();
+
+    // Sum the shards a range at a time, carrying the sealed total from one to the next
+    let ranges: Vec<_> = shards.chunks(MAX_SOLVENCY_SHARDS).collect();
+    let mut carried = None;
+    let mut solvent = false;
+    for (k, range) in ranges.iter().enumerate() {
+        let last = k + 1 == ranges.len();
+        let nonce = 100 + k as u128;
+        let previous = carried.as_ref().map(|(total, nonce)| (total, *nonce));
+        let (sealed, covered) = model::prove_solvency(cipher, range, previous, last, vault, nonce);
+        // Only the last range reveals anything
+        prop_assert!(last || !covered);
+        carried = Some((sealed, nonce));
+        solvent = covered;
+    }
+    let (sealed, nonce) = carried.unwrap();
+    prop_assert_eq!(cipher.decrypt(&sealed, nonce), total);
+    prop_assert_eq!(solvent, total <= vault as u128);
+    Ok(())
+}
+
+fn export_covers_every_entry_once<C: Cipher>(
+    cipher: &C,
+    deposits: &[(u8, u64)],
+    salt: u128,
+) -> Result<(), TestCaseError> {
+    let mut run = Run::new(cipher);
+    for &(user, amount) in deposits {
+        run.apply(&Step::Deposit { user, amount }, 1);
+    }
+    let mapping = run.open();
+
+    let mut exported = Vec::new();
+    for page in 0.. {
+        let salts = std::array::from_fn(|k| salt ^ (page * MAX_EXPORT_ENTRIES + k) as u128);
+        let (entries, more) =
+            model::export_balances(cipher, &run.mapping, run.nonce, page as u8, salts);
+        prop_assert!(entries.len() == MAX_EXPORT_ENTRIES || !more);
+        exported.extend(entries);
+        if !more {
+            break;
+        }
+    }
+
+    prop_assert_eq!(exported.len(), mapping.pubkeys.len());
+    for (i, entry) in exported.iter().enumerate() {
+        prop_assert!(entry.owner == mapping.pubkeys[i]);
+        prop_assert_eq!(entry.claim.balance, mapping.balances[i]);
+        prop_assert_eq!(
+            entry.commitment,
+            claim_commitment(entry.owner, entry.claim.balance, entry.claim.salt)
+        );
+        // The commitment doesn't open to any other balance
+        prop_assert_ne!(
+            entry.commitment,
+            claim_commitment(entry.owner, entry.claim.balance + 1, entry.claim.salt)
+        );
+    }
+    Ok(())
+}
+
+fn version_2_keeps_balances_without_empty_entries<C: Cipher>(
+    cipher: &C,
+    steps: &[Step],
+) -> Result<(), TestCaseError> {
+    let mut v1 = Run::new(cipher);
+    let mut v2 = Run::new(cipher);
+    for step in steps {
+        prop_assert_eq!(v1.apply(step, 1), v2.apply(step, 2));
+
+        let (v1, v2) = (v1.open(), v2.open());
+        for user in 0..USERS {
+            prop_assert_eq!(balance_of(&v1, user), balance_of(&v2, user));
+        }
+        prop_assert!(v2.balances.iter().all(|&balance| balance != 0));
+    }
+    Ok(())
+}
+
+fn migration_drops_only_empty_entries<C: Cipher>(
+    cipher: &C,
+    steps: &[Step],
+) -> Result<(), TestCaseError> {
+    let mut run = Run::new(cipher);
+    for step in steps {
+        run.apply(step, 1);
+    }
+    let mapping = run.open();
+
+    let migrated = run.migrate();
+    let kept: Vec<usize> = (0..mapping.pubkeys.len())
+        .filter(|&i| mapping.balances[i] != 0)
+        .collect();
+    prop_assert_eq!(migrated.pubkeys.len(), kept.len());
+    for (j, &i) in kept.iter().enumerate() {
+        prop_assert!(migrated.pubkeys[j] == mapping.pubkeys[i]);
+        prop_assert_eq!(migrated.balances[j], mapping.balances[i]);
+    }
+    Ok(())
+}
+
+/// Runs every property over `$cipher`, with `$cases` cases of at most `$steps` steps each.
+macro_rules! properties {
+    ($name:ident, $cipher:expr, $cases:expr, $steps:expr) => {
+        mod $name {
+            use super::*;
+
+            proptest! {
+                #![proptest_config(ProptestConfig::with_cases($cases))]
+
+                #[test]
+                fn balances_are_conserved(
+                    steps in prop::collection::vec(step(), 1..$steps),
+                    capacity in prop_oneof![1..=USERS as u16, Just(u16::MAX)],
+                ) {
+                    super::balances_are_conserved(&$cipher, &steps, capacity)?;
+                }
+
+                #[test]
+                fn failed_operations_leave_mapping_unchanged(
+                    deposits in prop::collection::vec((0..USERS, 0..1_000_000u64), 0..8),
+                    user in 0..USERS,
+                    to in 0..USERS,
+                    excess in 1..1_000_000u64,
+                ) {
+                    super::failed_operations_leave_mapping_unchanged(
+                        &$cipher, &deposits, user, to, excess,
+                    )?;
+                }
+
+                #[test]
+                fn noop_transfers_leave_mapping_unchanged(
+                    deposits in prop::collection::vec((0..USERS, 0..1_000_000u64), 0..8),
+                    from in 0..USERS,
+                    to in 0..USERS,
+                    amount in 0..1_000_000u64,
+                ) {
+                    super::noop_transfers_leave_mapping_unchanged(
+                        &$cipher, &deposits, from, to, amount,
+                    )?;
+                }
+
+                #[test]
+                fn batch_matches_sequential_operations(
+                    steps in prop::collection::vec(step(), 0..=MAX_BATCH_SIZE)
+                        .prop_map(|steps| steps
+                            .into_iter()
+                            .filter(|step| !matches!(step, Step::WithdrawAll { .. }))
+                            .collect::<Vec<_>>()),
+                    capacity in prop_oneof![1..=USERS as u16, Just(u16::MAX)],
+                ) {
+                    super::batch_matches_sequential_operations(&$cipher, &steps, capacity)?;
+                }
+
+                #[test]
+                fn solvency_carries_the_total_across_ranges(
+                    deposits in prop::collection::vec(
+                        prop::collection::vec((0..USERS, 0..1_000_000u64), 0..8),
+                        1..=3 * MAX_SOLVENCY_SHARDS,
+                    ),
+                    vault in 0..48_000_000u64,
+                ) {
+                    super::solvency_carries_the_total_across_ranges(&$cipher, &deposits, vault)?;
+                }
+
+                #[test]
+                fn export_covers_every_entry_once(
+                    deposits in prop::collection::vec((0..32u8, 0..1_000_000u64), 0..$steps),
+                    salt in any::<u128>(),
+                ) {
+                    super::export_covers_every_entry_once(&$cipher, &deposits, salt)?;
+                }
+
+                #[test]
+                fn version_2_keeps_balances_without_empty_entries(
+                    steps in prop::collection::vec(step(), 1..$steps),
+                ) {
+                    super::version_2_keeps_balances_without_empty_entries(&$cipher, &steps)?;
+                }
+
+                #[test]
+                fn migration_drops_only_empty_entries(
+                    steps in prop::collection::vec(step(), 1..$steps),
+                ) {
+                    super::migration_drops_only_empty_entries(&$cipher, &steps)?;
+                }
+            }
+        }
+    };
+}
+
+properties!(identity, IdentityCipher, 256, 64);
+
+// Rescue is slow enough in software that it gets fewer, shorter cases
+properties!(rescue, Rescue::new(), 8, 16);
@@ -0,0 +1,365 @@
+//! Encryption used by the mock MXE at the boundary between on-chain bytes and the plaintext
+//! circuit model.
+//!
+//! [`DummyCipher`] keeps values in the clear. With the `rescue` feature, `RescueMxeCipher`
+//! encrypts them with the client's Rescue cipher instead, as the real MXE does.
+
+use std::collections::HashMap;
+
+use arcis::prelude::PublicKey;
+use confidential_ixs::{EmergencyClaim, Mapping, TransferArgs};
+use solana_sdk::signature::Keypair;
+
+#[cfg(feature = "rescue")]
+pub use rescue::RescueMxeCipher;
+
+/// How the mock MXE opens circuit inputs and seals circuit outputs.
+///
+/// Mirrors what the real MXE does with its key: encrypted `Mapping`s and user ciphertexts come
+/// in as 32-byte blocks, and results go back out the same way.
+pub trait MxeCipher {
+    fn open_mapping(&self, sealed: [[u8; 32]; 2], nonce: u128) -> Mapping;
+    fn seal_mapping(&mut self, mapping: Mapping, nonce: u128) -> [[u8; 32]; 2];
+
+    /// Decryption of a transfer's arguments, encrypted by the client holding `client_key`'s
+    /// x25519 secret
+    fn open_transfer(
+        &self,
+        sealed: [[u8; 32]; 3],
+        client_key: PublicKey,
+        nonce: u128,
+    ) -> TransferArgs;
+    fn open_u64(&self, sealed: [u8; 32], nonce: u128) -> u64;
+    /// Decryption of a running total the MXE sealed for itself
+    fn open_total(&self, sealed: [u8; 32], nonce: u128) -> u128;
+    /// Encryption of a running total for the MXE alone, as `prove_solvency` carries it from
+    /// one range of shards to the next
+    fn seal_total(&mut self, total: u128, nonce: u128) -> [u8; 32];
+    /// Encryption of a result for the client holding `client_key`'s x25519 secret
+    fn seal_u64_for_client(&mut self, value: u64, client_key: PublicKey, nonce: u128) -> [u8; 32];
+    /// Encryption of an emergency claim for the owner of the ed25519 key `owner`
+    fn seal_claim_for_owner(
+        &mut self,
+        claim: EmergencyClaim,
+        owner: PublicKey,
+        nonce: u128,
+    ) -> [[u8; 32]; 2];
+    /// Secret randomness drawn by the MXE
+    fn random_u128(&mut self) -> u128;
+
+    /// Client-side encryption of a transfer's arguments
+    fn seal_transfer(&self, transfer: TransferArgs, nonce: u128) -> [[u8; 32]; 3];
+    /// Client-side encryption of an amount
+    fn seal_amount(&self, value: u64, nonce: u128) -> [u8; 32];
+    /// x25519 key of the client `owner` encrypts with, which results for them are sealed for
+    fn client_key(&self, owner: PublicKey) -> [u8; 32];
+    /// Owner-side decryption of a claim sealed for `owner`'s ed25519 key; `None` if the claim
+    /// isn't theirs
+    fn open_claim(
+        &self,
+        sealed: [[u8; 32]; 2],
+        owner: &Keypair,
+        nonce: u128,
+    ) -> Option<EmergencyClaim>;
+}
+
+/// Cipher that encodes values in the clear and keeps mappings in a local table.
+///
+/// A `Mapping` doesn't fit in the two 32-byte blocks stored on chain, so sealing one records it
+/// under a fresh handle instead. All-zero blocks, as found in a fresh mapping account, open to
+/// an empty mapping.
+#[derive(Default)]
+pub struct DummyCipher {
+    mappings: HashMap<[[u8; 32]; 2], Mapping>,
+    next_handle: u64,
+}
+
+impl MxeCipher for DummyCipher {
+    fn open_mapping(&self, sealed: [[u8; 32]; 2], _nonce: u128) -> Mapping {
+        if sealed == [[0; 32]; 2] {
+            return Mapping {
+                pubkeys: vec![],
+                balances: vec![],
+            };
+        }
+        self.mappings
+            .get(&sealed)
+            .cloned()
+            .expect("mapping was not sealed by this cipher")
+    }
+
+    fn seal_mapping(&mut self, mapping: Mapping, nonce: u128) -> [[u8; 32]; 2] {
+        self.next_handle += 1;
+        let mut handle = [[0; 32]; 2];
+        handle[0][..8].copy_from_slice(&self.next_handle.to_le_bytes());
+        handle[1][..16].copy_from_slice(&nonce.to_le_bytes());
+        self.mappings.insert(handle, mapping);
+        handle
+    }
+
+    fn open_transfer(
+        &self,
+        sealed: [[u8; 32]; 3],
+        _client_key: PublicKey,
+        nonce: u128,
+    ) -> TransferArgs {
+        TransferArgs {
+            to: PublicKey::from(sealed[0]),
+            amount: self.open_u64(sealed[1], nonce),
+            noop: sealed[2][0] != 0,
+        }
+    }
+
+    fn open_u64(&self, sealed: [u8; 32], _nonce: u128) -> u64 {
+        u64::from_le_bytes(sealed[..8].try_into().unwrap())
+    }
+
+    fn open_total(&self, sealed: [u8; 32], _nonce: u128) -> u128 {
+        u128::from_le_bytes(sealed[..16].try_into().unwrap())
+    }
+
+    fn seal_total(&mut self, total: u128, _nonce: u128) -> [u8; 32] {
+        let mut sealed = [0; 32];
+        sealed[..16].copy_from_slice(&total.to_le_bytes());
+        sealed
+    }
+
+    fn seal_u64_for_client(&mut self, value: u64, _client_key: PublicKey, nonce: u128) -> [u8; 32] {
+        self.seal_amount(value, nonce)
+    }
+
+    fn seal_claim_for_owner(
+        &mut self,
+        claim: EmergencyClaim,
+        _owner: PublicKey,
+        nonce: u128,
+    ) -> [[u8; 32]; 2] {
+        let mut salt = [0; 32];
+        salt[..16].copy_from_slice(&claim.salt.to_le_bytes());
+        [self.seal_amount(claim.balance, nonce), salt]
+    }
+
+    fn random_u128(&mut self) -> u128 {
+        self.next_handle += 1;
+        u128::from(self.next_handle) << 64 | 0x5a17
+    }
+
+    fn seal_transfer(&self, transfer: TransferArgs, nonce: u128) -> [[u8; 32]; 3] {
+        let mut noop = [0; 32];
+        noop[0] = transfer.noop as u8;
+        [
+            transfer.to.to_bytes(),
+            self.seal_amount(transfer.amount, nonce),
+            noop,
+        ]
+    }
+
+    fn seal_amount(&self, value: u64, _nonce: u128) -> [u8; 32] {
+        let mut sealed = [0; 32];
+        sealed[..8].copy_from_slice(&value.to_le_bytes());
+        sealed
+    }
+
+    fn client_key(&self, owner: PublicKey) -> [u8; 32] {
+        owner.to_bytes()
+    }
+
+    fn open_claim(
+        &self,
+        sealed: [[u8; 32]; 2],
+        _owner: &Keypair,
+        nonce: u128,
+    ) -> Option<EmergencyClaim> {
+        Some(EmergencyClaim {
+            balance: self.open_u64(sealed[0], nonce),
+            salt: u128::from_le_bytes(sealed[1][..16].try_into().unwrap()),
+        })
+    }
+}
+
+#[cfg(feature = "rescue")]
+mod rescue {
+    use std::collections::HashMap;
+
+    use arcis::prelude::PublicKey;
+    use blackbox_client::encryption::{ClientSecret, FieldElement, MxePublicKey, RescueCipher};
+    use confidential_ixs::model::Encodable;
+    use confidential_ixs::{EmergencyClaim, Mapping, TransferArgs};
+    use curve25519_dalek::edwards::CompressedEdwardsY;
+    use solana_sdk::signature::Keypair;
+
+    use super::MxeCipher;
+
+    /// Cipher that encrypts with Rescue under x25519 shared secrets, as the real MXE does.
+    ///
+    /// The MXE and every client each hold one fixed x25519 secret, so all users share one
+    /// client key; see [`MxeCipher::client_key`]. Results sealed for another key still decrypt
+    /// only with that key's secret. Mappings are encrypted with the MXE's key, and as with
+    /// [`DummyCipher`](super::DummyCipher) kept in a local table under a handle, so opening one
+    /// under a nonce other than the one it was sealed under fails.
+    pub struct RescueMxeCipher {
+        mxe: ClientSecret,
+        client: ClientSecret,
+        mappings: HashMap<[[u8; 32]; 2], Vec<[u8; 32]>>,
+        next_handle: u64,
+    }
+
+    impl Default for RescueMxeCipher {
+        fn default() -> Self {
+            Self {
+                mxe: ClientSecret::from_bytes([1; 32]),
+                client: ClientSecret::from_bytes([2; 32]),
+                mappings: HashMap::new(),
+                next_handle: 0,
+            }
+        }
+    }
+
+    impl RescueMxeCipher {
+        /// The MXE's side of the cipher shared with the holder of `key`
+        fn shared_with(&self, key: [u8; 32]) -> RescueCipher {
+            self.mxe.cipher(&MxePublicKey([key; 5]))
+        }
+
+        /// The client's side of the cipher it shares with the MXE
+        fn client_cipher(&self) -> RescueCipher {
+            self.client
+                .cipher(&MxePublicKey([self.mxe.public_key(); 5]))
+        }
+    }
+
+    fn seal<T: Encodable>(cipher: &RescueCipher, value: &T, nonce: u128) -> Vec<[u8; 32]> {
+        let elements: Vec<_> = value
+            .encode()
+            .iter()
+            .map(|element| FieldElement::from_le_bytes(element).expect("element is below p"))
+            .collect();
+        cipher.encrypt(&elements, nonce)
+    }
+
+    fn open(cipher: &RescueCipher, sealed: &[[u8; 32]], nonce: u128) -> Vec<[u8; 32]> {
+        cipher
+            .decrypt(sealed, nonce)
+            .expect("ciphertext is below p")
+            .iter()
+            .map(FieldElement::to_le_bytes)
+            .collect()
+    }
+
+    impl MxeCipher for RescueMxeCipher {
+        fn open_mapping(&self, sealed: [[u8; 32]; 2], nonce: u128) -> Mapping {
+            if sealed == [[0; 32]; 2] {
+                return Mapping {
+                    pubkeys: vec![],
+                    balances: vec![],
+                };
+            }
+            let ciphertext = self
+                .mappings
+                .get(&sealed)
+                .expect("mapping was not sealed by this cipher");
+            let elements = open(&self.shared_with(self.mxe.public_key()), ciphertext, nonce);
+            // A mapping of n entries encodes to 2n + 1 elements, led by n
+            let len = u64::from_le_bytes(elements[0][..8].try_into().unwrap());
+            assert!(
+                elements[0][8..] == [0; 24] && len as usize * 2 + 1 == elements.len(),
+                "mapping opened under the wrong nonce"
+            );
+            Mapping::decode(&elements)
+        }
+
+        fn seal_mapping(&mut self, mapping: Mapping, nonce: u128) -> [[u8; 32]; 2] {
+            self.next_handle += 1;
+            let mut handle = [[0; 32]; 2];
+            handle[0][..8].copy_from_slice(&self.next_handle.to_le_bytes());
+            handle[1][..16].copy_from_slice(&nonce.to_le_bytes());
+            let ciphertext = seal(&self.shared_with(self.mxe.public_key()), &mapping, nonce);
+            self.mappings.insert(handle, ciphertext);
+            handle
+        }
+
+        fn open_transfer(
+            &self,
+            sealed: [[u8; 32]; 3],
+            client_key: PublicKey,
+            nonce: u128,
+        ) -> TransferArgs {
+            let cipher = self.shared_with(client_key.to_bytes());
+            TransferArgs::decode(&open(&cipher, &sealed, nonce))
+        }
+
+        fn open_u64(&self, sealed: [u8; 32], nonce: u128) -> u64 {
+            self.shared_with(self.client.public_key())
+                .decrypt_u64(&sealed, nonce)
+                .expect("ciphertext is a u64")
+        }
+
+        fn open_total(&self, sealed: [u8; 32], nonce: u128) -> u128 {
+            let cipher = self.shared_with(self.mxe.public_key());
+            u128::decode(&open(&cipher, &[sealed], nonce))
+        }
+
+        fn seal_total(&mut self, total: u128, nonce: u128) -> [u8; 32] {
+            seal(&self.shared_with(self.mxe.public_key()), &total, nonce)[0]
+        }
+
+        fn seal_u64_for_client(
+            &mut self,
+            value: u64,
+            client_key: PublicKey,
+            nonce: u128,
+        ) -> [u8; 32] {
+            self.shared_with(client_key.to_bytes())
+                .encrypt_u64(value, nonce)
+        }
+
+        fn seal_claim_for_owner(
+            &mut self,
+            claim: EmergencyClaim,
+            owner: PublicKey,
+            nonce: u128,
+        ) -> [[u8; 32]; 2] {
+            let owner_key = CompressedEdwardsY(owner.to_bytes())
+                .decompress()
+                .expect("owner is an ed25519 key")
+                .to_montgomery()
+                .to_bytes();
+            let plaintext = [claim.balance.into(), claim.salt.into()];
+            let sealed = self.shared_with(owner_key).encrypt(&plaintext, nonce);
+            [sealed[0], sealed[1]]
+        }
+
+        fn random_u128(&mut self) -> u128 {
+            self.next_handle += 1;
+            u128::from(self.next_handle) << 64 | 0x5a17
+        }
+
+        fn seal_transfer(&self, transfer: TransferArgs, nonce: u128) -> [[u8; 32]; 3] {
+            let sealed = seal(&self.client_cipher(), &transfer, nonce);
+            [sealed[0], sealed[1], sealed[2]]
+        }
+
+        fn seal_amount(&self, value: u64, nonce: u128) -> [u8; 32] {
+            self.client_cipher().encrypt_u64(value, nonce)
+        }
+
+        fn client_key(&self, _owner: PublicKey) -> [u8; 32] {
+            self.client.public_key()
+        }
+
+        fn open_claim(
+            &self,
+            sealed: [[u8; 32]; 2],
+            owner: &Keypair,
+            nonce: u128,
+        ) -> Option<EmergencyClaim> {
+            let cipher = ClientSecret::from_owner_keypair(owner)
+                .cipher(&MxePublicKey([self.mxe.public_key(); 5]));
+            let plaintext = cipher.decrypt(&sealed, nonce).ok()?;
+            Some(EmergencyClaim {
+                balance: plaintext[0].to_u64()?,
+                salt: plaintext[1].to_u128()?,
+            })
+        }
+    }
+}
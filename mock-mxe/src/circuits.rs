@@ -0,0 +1,421 @@
+//! Runs queued computations through the plaintext circuit model and formats their outputs the
+//! way the blackbox callbacks expect them.
+
+use anchor_lang::prelude::Pubkey;
+use arcis::prelude::PublicKey;
+use arcium_anchor::{comp_def_offset, COMP_DEF_PDA_SEED};
+use arcium_client::idl::arcium::{types::Argument, ID_CONST as ARCIUM_PROG_ID};
+use confidential_ixs::circuit_hashes::*;
+use confidential_ixs::model::{self, IdentityCipher, ModelOp};
+use confidential_ixs::{
+    Mapping, MAX_BATCH_SIZE, MAX_EXPORT_ENTRIES, MAX_SOLVENCY_SHARDS, OP_EMPTY,
+};
+
+use crate::cipher::MxeCipher;
+
+/// Circuits the mock MXE knows how to execute
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Circuit {
+    Deposit,
+    Transfer,
+    Withdraw,
+    WithdrawAll,
+    RevealBalance,
+    ProcessBatch,
+    ProveSolvency,
+    ExportBalances,
+    DepositV2,
+    TransferV2,
+    WithdrawV2,
+    WithdrawAllV2,
+    ProcessBatchV2,
+    MigrateMappingV2,
+}
+
+impl Circuit {
+    pub const ALL: [Circuit; 14] = [
+        Circuit::Deposit,
+        Circuit::Transfer,
+        Circuit::Withdraw,
+        Circuit::WithdrawAll,
+        Circuit::RevealBalance,
+        Circuit::ProcessBatch,
+        Circuit::ProveSolvency,
+        Circuit::ExportBalances,
+        Circuit::DepositV2,
+        Circuit::TransferV2,
+        Circuit::WithdrawV2,
+        Circuit::WithdrawAllV2,
+        Circuit::ProcessBatchV2,
+        Circuit::MigrateMappingV2,
+    ];
+
+    /// Name the circuit's computation definition is registered under
+    pub fn name(&self) -> &'static str {
+        match self {
+            Circuit::Deposit => "deposit",
+            Circuit::Transfer => "transfer",
+            Circuit::Withdraw => "withdraw",
+            Circuit::WithdrawAll => "withdraw_all",
+            Circuit::RevealBalance => "reveal_balance",
+            Circuit::ProcessBatch => "process_batch",
+            Circuit::ProveSolvency => "prove_solvency",
+            Circuit::ExportBalances => "export_balances",
+            Circuit::DepositV2 => "deposit_v2",
+            Circuit::TransferV2 => "transfer_v2",
+            Circuit::WithdrawV2 => "withdraw_v2",
+            Circuit::WithdrawAllV2 => "withdraw_all_v2",
+            Circuit::ProcessBatchV2 => "process_batch_v2",
+            Circuit::MigrateMappingV2 => "migrate_mapping_v2",
+        }
+    }
+
+    /// The version of this circuit run against a mapping account at `circuit_version`
+    pub fn for_version(self, circuit_version: u8) -> Circuit {
+        match (self, circuit_version) {
+            (Circuit::Deposit, 2) => Circuit::DepositV2,
+            (Circuit::Transfer, 2) => Circuit::TransferV2,
+            (Circuit::Withdraw, 2) => Circuit::WithdrawV2,
+            (Circuit::WithdrawAll, 2) => Circuit::WithdrawAllV2,
+            (Circuit::ProcessBatch, 2) => Circuit::ProcessBatchV2,
+            _ => self,
+        }
+    }
+
+    /// Hash of the compiled circuit the program is pinned to
+    pub fn pinned_hash(&self) -> [u8; 32] {
+        match self {
+            Circuit::Deposit => DEPOSIT_CIRCUIT_HASH,
+            Circuit::Transfer => TRANSFER_CIRCUIT_HASH,
+            Circuit::Withdraw => WITHDRAW_CIRCUIT_HASH,
+            Circuit::WithdrawAll => WITHDRAW_ALL_CIRCUIT_HASH,
+            Circuit::RevealBalance => REVEAL_BALANCE_CIRCUIT_HASH,
+            Circuit::ProcessBatch => PROCESS_BATCH_CIRCUIT_HASH,
+            Circuit::ProveSolvency => PROVE_SOLVENCY_CIRCUIT_HASH,
+            Circuit::ExportBalances => EXPORT_BALANCES_CIRCUIT_HASH,
+            Circuit::DepositV2 => DEPOSIT_V2_CIRCUIT_HASH,
+            Circuit::TransferV2 => TRANSFER_V2_CIRCUIT_HASH,
+            Circuit::WithdrawV2 => WITHDRAW_V2_CIRCUIT_HASH,
+            Circuit::WithdrawAllV2 => WITHDRAW_ALL_V2_CIRCUIT_HASH,
+            Circuit::ProcessBatchV2 => PROCESS_BATCH_V2_CIRCUIT_HASH,
+            Circuit::MigrateMappingV2 => MIGRATE_MAPPING_V2_CIRCUIT_HASH,
+        }
+    }
+
+    /// Address of the circuit's computation definition account
+    pub fn comp_def_address(&self) -> Pubkey {
+        Pubkey::find_program_address(
+            &[COMP_DEF_PDA_SEED, &comp_def_offset(self.name()).to_le_bytes()],
+            &ARCIUM_PROG_ID,
+        )
+        .0
+    }
+
+    /// Looks up the circuit whose computation definition lives at `address`
+    pub fn from_comp_def_address(address: &Pubkey) -> Option<Circuit> {
+        Circuit::ALL
+            .into_iter()
+            .find(|circuit| circuit.comp_def_address() == *address)
+    }
+
+    /// Name of the blackbox instruction receiving the circuit's output
+    pub fn callback_name(&self) -> String {
+        format!("{}_callback", self.name())
+    }
+}
+
+/// Sequential reader over a computation's arguments, in the order the program pushed them.
+struct Args<'a> {
+    args: std::slice::Iter<'a, Argument>,
+}
+
+impl<'a> Args<'a> {
+    fn next(&mut self) -> &'a Argument {
+        self.args.next().expect("circuit is missing an argument")
+    }
+
+    fn pubkey(&mut self) -> PublicKey {
+        match self.next() {
+            Argument::PlaintextPubkey(key) => PublicKey::from(key.to_bytes()),
+            _ => panic!("expected a plaintext pubkey argument"),
+        }
+    }
+
+    fn u8(&mut self) -> u8 {
+        match self.next() {
+            Argument::PlaintextU8(value) => *value,
+            _ => panic!("expected a plaintext u8 argument"),
+        }
+    }
+
+    fn bool(&mut self) -> bool {
+        match self.next() {
+            Argument::PlaintextBool(value) => *value,
+            _ => panic!("expected a plaintext bool argument"),
+        }
+    }
+
+    fn u16(&mut self) -> u16 {
+        match self.next() {
+            Argument::PlaintextU16(value) => *value,
+            _ => panic!("expected a plaintext u16 argument"),
+        }
+    }
+
+    fn u64(&mut self) -> u64 {
+        match self.next() {
+            Argument::PlaintextU64(value) => *value,
+            _ => panic!("expected a plaintext u64 argument"),
+        }
+    }
+
+    fn u128(&mut self) -> u128 {
+        match self.next() {
+            Argument::PlaintextU128(value) => *value,
+            _ => panic!("expected a plaintext u128 argument"),
+        }
+    }
+
+    fn ciphertext(&mut self) -> [u8; 32] {
+        match self.next() {
+            Argument::CipheredPubkey(ct)
+            | Argument::CipheredU64(ct)
+            | Argument::CipheredU128(ct)
+            | Argument::CipheredBool(ct) => *ct,
+            _ => panic!("expected a ciphertext argument"),
+        }
+    }
+
+    /// A transfer's encrypted `TransferArgs`
+    fn transfer(&mut self) -> [[u8; 32]; 3] {
+        [self.ciphertext(), self.ciphertext(), self.ciphertext()]
+    }
+
+    /// The encrypted mapping and its nonce, as pushed by `mapping_args`
+    fn mapping<C: MxeCipher>(&mut self, cipher: &C) -> Mapping {
+        let sealed = [self.ciphertext(), self.ciphertext()];
+        let nonce = self.u128();
+        cipher.open_mapping(sealed, nonce)
+    }
+}
+
+/// Executes `circuit` on `args`, returning the bytes handed to its callback.
+pub fn execute<C: MxeCipher>(cipher: &mut C, circuit: Circuit, args: &[Argument]) -> Vec<u8> {
+    let mut args = Args { args: args.iter() };
+    let plain = IdentityCipher;
+
+    match circuit {
+        Circuit::Deposit => {
+            let to = args.pubkey();
+            let amount = args.u64();
+            let mapping = args.mapping(cipher);
+            let capacity = args.u16();
+            let nonce = args.u128();
+
+            let (mapping, credited) = model::deposit(&plain, to, amount, &mapping, 0, capacity, 0);
+            let mut output = mapping_output(cipher, mapping, nonce);
+            output.push(credited as u8);
+            output
+        }
+        Circuit::Transfer => {
+            let mapping = args.mapping(cipher);
+            let capacity = args.u16();
+            let from = args.pubkey();
+            let client_key = args.pubkey();
+            let transfer = args.transfer();
+            let nonce = args.u128();
+            let output_nonce = args.u128();
+
+            let transfer = cipher.open_transfer(transfer, client_key, nonce);
+            let mapping = model::transfer(&plain, &mapping, 0, capacity, from, &transfer, 0, 0);
+            mapping_output(cipher, mapping, output_nonce)
+        }
+        Circuit::Withdraw => {
+            let mapping = args.mapping(cipher);
+            let from = args.pubkey();
+            let amount = args.u64();
+            let nonce = args.u128();
+
+            let (mapping, ok) = model::withdraw(&plain, &mapping, 0, from, amount, 0);
+            let mut output = mapping_output(cipher, mapping, nonce);
+            output.push(ok as u8);
+            output
+        }
+        Circuit::DepositV2 => {
+            let to = args.pubkey();
+            let amount = args.u64();
+            let mapping = args.mapping(cipher);
+            let capacity = args.u16();
+            let nonce = args.u128();
+
+            let (mapping, credited) =
+                model::deposit_v2(&plain, to, amount, &mapping, 0, capacity, 0);
+            let mut output = mapping_output(cipher, mapping, nonce);
+            output.push(credited as u8);
+            output
+        }
+        Circuit::TransferV2 => {
+            let mapping = args.mapping(cipher);
+            let capacity = args.u16();
+            let from = args.pubkey();
+            let client_key = args.pubkey();
+            let transfer = args.transfer();
+            let nonce = args.u128();
+            let output_nonce = args.u128();
+
+            let transfer = cipher.open_transfer(transfer, client_key, nonce);
+            let mapping = model::transfer_v2(&plain, &mapping, 0, capacity, from, &transfer, 0, 0);
+            mapping_output(cipher, mapping, output_nonce)
+        }
+        Circuit::WithdrawV2 => {
+            let mapping = args.mapping(cipher);
+            let from = args.pubkey();
+            let amount = args.u64();
+            let nonce = args.u128();
+
+            let (mapping, ok) = model::withdraw_v2(&plain, &mapping, 0, from, amount, 0);
+            let mut output = mapping_output(cipher, mapping, nonce);
+            output.push(ok as u8);
+            output
+        }
+        Circuit::MigrateMappingV2 => {
+            let mapping = args.mapping(cipher);
+            let nonce = args.u128();
+
+            let mapping = model::migrate_mapping_v2(&plain, &mapping, 0, 0);
+            mapping_output(cipher, mapping, nonce)
+        }
+        Circuit::WithdrawAll | Circuit::WithdrawAllV2 => {
+            let mapping = args.mapping(cipher);
+            let from = args.pubkey();
+            let max_payout = args.u64();
+            let nonce = args.u128();
+
+            let withdraw_all = if circuit == Circuit::WithdrawAll {
+                model::withdraw_all::<IdentityCipher>
+            } else {
+                model::withdraw_all_v2::<IdentityCipher>
+            };
+            let (mapping, payout, found) = withdraw_all(&plain, &mapping, 0, from, max_payout, 0);
+            let mut output = mapping_output(cipher, mapping, nonce);
+            output.extend_from_slice(&payout.to_le_bytes());
+            output.push(found as u8);
+            output
+        }
+        Circuit::RevealBalance => {
+            let mapping = args.mapping(cipher);
+            let owner = args.pubkey();
+            let client_key = args.pubkey();
+            let nonce = args.u128();
+
+            let balance = model::reveal_balance(&plain, &mapping, 0, owner);
+            cipher.seal_u64_for_client(balance, client_key, nonce).to_vec()
+        }
+        Circuit::ProcessBatch | Circuit::ProcessBatchV2 => {
+            let mapping = args.mapping(cipher);
+            let capacity = args.u16();
+            let mut ops = Vec::with_capacity(MAX_BATCH_SIZE);
+            let mut client_keys = [PublicKey::from([0; 32]); MAX_BATCH_SIZE];
+            for client_key in &mut client_keys {
+                let kind = args.u8();
+                let owner = args.pubkey();
+                *client_key = args.pubkey();
+                let transfer = args.transfer();
+                let amount = args.u64();
+                let op_nonce = args.u128();
+                if kind == OP_EMPTY {
+                    continue;
+                }
+                ops.push(ModelOp::<IdentityCipher> {
+                    kind,
+                    owner,
+                    client_key: *client_key,
+                    transfer: cipher.open_transfer(transfer, *client_key, op_nonce),
+                    amount,
+                    nonce: op_nonce,
+                });
+            }
+            let nonce = args.u128();
+
+            let process_batch = if circuit == Circuit::ProcessBatch {
+                model::process_batch::<IdentityCipher>
+            } else {
+                model::process_batch_v2::<IdentityCipher>
+            };
+            let (mapping, statuses, payouts) =
+                process_batch(&plain, &mapping, 0, capacity, &ops, 0);
+
+            // The batch callback takes its nonce from the op queue, so none is prepended
+            let mut output = Vec::new();
+            for block in cipher.seal_mapping(mapping, nonce) {
+                output.extend_from_slice(&block);
+            }
+            for (k, status) in statuses.into_iter().enumerate() {
+                let sealed =
+                    cipher.seal_u64_for_client(status, client_keys[k], nonce + 1 + k as u128);
+                output.extend_from_slice(&sealed);
+            }
+            output.extend(payouts.map(|paid| paid as u8));
+            output
+        }
+        Circuit::ProveSolvency => {
+            let shards: Vec<_> = (0..MAX_SOLVENCY_SHARDS)
+                .map(|_| (args.mapping(cipher), 0))
+                .collect();
+            let shard_count = args.u8() as usize;
+            let total = args.ciphertext();
+            let total_nonce = args.u128();
+            let first = args.bool();
+            let last = args.bool();
+            let vault_amount = args.u64();
+            let nonce = args.u128();
+
+            // The first range has no total to carry on from
+            let carried = (!first).then(|| cipher.open_total(total, total_nonce));
+            let (total, solvent) = model::prove_solvency(
+                &plain,
+                &shards[..shard_count],
+                carried.as_ref().map(|total| (total, 0)),
+                last,
+                vault_amount,
+                0,
+            );
+            let mut output = nonce.to_le_bytes().to_vec();
+            output.extend_from_slice(&cipher.seal_total(total, nonce));
+            output.push(solvent as u8);
+            output
+        }
+        Circuit::ExportBalances => {
+            let mapping = args.mapping(cipher);
+            let page = args.u8();
+            let nonce = args.u128();
+
+            let salts = std::array::from_fn(|_| cipher.random_u128());
+            let (entries, more) = model::export_balances(&plain, &mapping, 0, page, salts);
+
+            // Claims for every slot, then commitments; slots past the mapping stay zeroed
+            let mut claims = vec![0; 64 * MAX_EXPORT_ENTRIES];
+            let mut commitments = vec![0; 32 * MAX_EXPORT_ENTRIES];
+            for (k, entry) in entries.iter().enumerate() {
+                let sealed =
+                    cipher.seal_claim_for_owner(entry.claim, entry.owner, nonce + k as u128);
+                claims[k * 64..k * 64 + 32].copy_from_slice(&sealed[0]);
+                claims[k * 64 + 32..(k + 1) * 64].copy_from_slice(&sealed[1]);
+                commitments[k * 32..(k + 1) * 32].copy_from_slice(&entry.commitment);
+            }
+            let mut output = claims;
+            output.extend(commitments);
+            output.push(more as u8);
+            output
+        }
+    }
+}
+
+/// Output prefix shared by the mapping circuits: nonce, then the sealed mapping.
+fn mapping_output<C: MxeCipher>(cipher: &mut C, mapping: Mapping, nonce: u128) -> Vec<u8> {
+    let mut output = nonce.to_le_bytes().to_vec();
+    for block in cipher.seal_mapping(mapping, nonce) {
+        output.extend_from_slice(&block);
+    }
+    output
+}
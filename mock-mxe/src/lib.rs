@@ -0,0 +1,232 @@
+//! In-process stand-in for an Arcium MXE, so the blackbox program can be exercised end to end
+//! with `cargo test` instead of a live cluster.
+//!
+//! [`program_test`] builds a `ProgramTest` with blackbox, a mock Arcium program and the Arcium
+//! accounts blackbox expects. Instructions that queue computations run unchanged; the mock
+//! program records each computation, and [`MockMxe::process_pending`] executes them with the
+//! plaintext circuit model and delivers the outputs to the matching blackbox callbacks.
+
+use std::sync::{Mutex, MutexGuard};
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    instruction::{AccountMeta, Instruction},
+    sysvar,
+};
+use arcium_anchor::{
+    CLOCK_PDA_SEED, CLUSTER_PDA_SEED, MEMPOOL_PDA_SEED, MXE_PDA_SEED, POOL_PDA_SEED,
+};
+use arcium_client::idl::arcium::{
+    accounts::{ClockAccount, Cluster, Mempool, PersistentMXEAccount, StakingPoolAccount},
+    ID_CONST as ARCIUM_PROG_ID,
+};
+use blackbox::state::MappingAccount;
+use confidential_ixs::Mapping;
+use solana_program_test::{processor, BanksClient, BanksClientError, ProgramTest};
+use solana_sdk::{account::Account, signature::Keypair, signer::Signer, transaction::Transaction};
+
+pub mod cipher;
+pub mod circuits;
+pub mod processor;
+
+#[cfg(feature = "rescue")]
+pub use cipher::RescueMxeCipher;
+pub use cipher::{DummyCipher, MxeCipher};
+pub use circuits::Circuit;
+pub use processor::QueuedComputation;
+
+use processor::{sighash, EXECUTE_CALLBACK_TAG, QUEUE};
+
+/// Serializes harnesses: the mock program records computations in process-wide state.
+static HARNESS: Mutex<()> = Mutex::new(());
+
+/// Offset of the one Arcium cluster the mock sets up, with its mempool and staking pool
+pub const CLUSTER_OFFSET: u32 = 0;
+
+/// Builds a `ProgramTest` running blackbox against the mock Arcium program.
+pub fn program_test() -> ProgramTest {
+    let mut program_test = ProgramTest::new("blackbox", blackbox::ID, processor!(blackbox::entry));
+    program_test.add_program(
+        "arcium",
+        ARCIUM_PROG_ID,
+        processor!(processor::process_instruction),
+    );
+
+    add_arcium_account(&mut program_test, &[CLOCK_PDA_SEED], ClockAccount::default());
+    let offset = CLUSTER_OFFSET.to_le_bytes();
+    add_arcium_account(&mut program_test, &[CLUSTER_PDA_SEED, &offset], Cluster::default());
+    add_arcium_account(&mut program_test, &[MEMPOOL_PDA_SEED, &offset], Mempool::default());
+    add_arcium_account(
+        &mut program_test,
+        &[POOL_PDA_SEED, &offset],
+        StakingPoolAccount::default(),
+    );
+    // Queue instructions use the bare MXE seed, comp def registration the per-program one
+    for seeds in [&[MXE_PDA_SEED][..], &[MXE_PDA_SEED, blackbox::ID.as_ref()][..]] {
+        let (_, bump) = Pubkey::find_program_address(seeds, &ARCIUM_PROG_ID);
+        let mxe = PersistentMXEAccount {
+            bump,
+            ..Default::default()
+        };
+        add_arcium_account(&mut program_test, seeds, mxe);
+    }
+
+    program_test
+}
+
+fn add_arcium_account<T: AccountSerialize>(
+    program_test: &mut ProgramTest,
+    seeds: &[&[u8]],
+    account: T,
+) {
+    let (address, _) = Pubkey::find_program_address(seeds, &ARCIUM_PROG_ID);
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: ARCIUM_PROG_ID,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+}
+
+/// Outcome of delivering one computation's output to its callback
+pub struct Executed {
+    pub computation: QueuedComputation,
+    pub result: std::result::Result<(), BanksClientError>,
+    /// Compute units the callback transaction consumed, including the mock program's CPI
+    pub compute_units: Option<u64>,
+}
+
+/// Cipher of `MockMxe::new`: `RescueMxeCipher` with the `rescue` feature, else `DummyCipher`
+#[cfg(feature = "rescue")]
+pub type DefaultCipher = RescueMxeCipher;
+#[cfg(not(feature = "rescue"))]
+pub type DefaultCipher = DummyCipher;
+
+/// Executes computations queued against the mock Arcium program.
+pub struct MockMxe<C: MxeCipher = DefaultCipher> {
+    pub cipher: C,
+    _harness: MutexGuard<'static, ()>,
+}
+
+impl MockMxe<DefaultCipher> {
+    pub fn new() -> Self {
+        Self::with_cipher(DefaultCipher::default())
+    }
+}
+
+impl Default for MockMxe<DefaultCipher> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: MxeCipher> MockMxe<C> {
+    /// Creates a harness using `cipher`. Blocks while another harness is alive.
+    pub fn with_cipher(cipher: C) -> Self {
+        let harness = HARNESS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        lock_queue().clear();
+        Self {
+            cipher,
+            _harness: harness,
+        }
+    }
+
+    /// Number of computations queued and not yet executed
+    pub fn pending(&self) -> usize {
+        lock_queue().len()
+    }
+
+    /// Takes every queued computation out of the queue, so later ones execute first
+    pub fn hold(&mut self) -> Vec<QueuedComputation> {
+        std::mem::take(&mut *lock_queue())
+    }
+
+    /// Puts computations taken by [`MockMxe::hold`] back, behind those queued since
+    pub fn release(&mut self, held: Vec<QueuedComputation>) {
+        lock_queue().extend(held);
+    }
+
+    /// Executes every queued computation in order and delivers its output to its callback.
+    ///
+    /// A failing callback doesn't stop the others; its error is returned in its `Executed`.
+    pub async fn process_pending(
+        &mut self,
+        banks: &mut BanksClient,
+        payer: &Keypair,
+    ) -> std::result::Result<Vec<Executed>, BanksClientError> {
+        let queued = std::mem::take(&mut *lock_queue());
+        let mut executed = Vec::with_capacity(queued.len());
+
+        for computation in queued {
+            let output = circuits::execute(&mut self.cipher, computation.circuit, &computation.args);
+            let instruction = callback_instruction(&computation, output);
+
+            let blockhash = banks.get_latest_blockhash().await?;
+            let transaction = Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[payer],
+                blockhash,
+            );
+            let processed = banks.process_transaction_with_metadata(transaction).await?;
+            executed.push(Executed {
+                computation,
+                result: processed.result.map_err(BanksClientError::from),
+                compute_units: processed
+                    .metadata
+                    .map(|metadata| metadata.compute_units_consumed),
+            });
+        }
+
+        Ok(executed)
+    }
+
+    /// Decrypts the mapping stored in a mapping account
+    pub fn mapping(&self, mapping_account: &MappingAccount) -> Mapping {
+        let first = |blocks: &Vec<[u8; 32]>| blocks.first().copied().unwrap_or_default();
+        self.cipher.open_mapping(
+            [
+                first(&mapping_account.encrypted_pubkeys),
+                first(&mapping_account.encrypted_balances),
+            ],
+            mapping_account.nonce,
+        )
+    }
+}
+
+fn lock_queue() -> MutexGuard<'static, Vec<QueuedComputation>> {
+    QUEUE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Instruction asking the mock Arcium program to invoke a computation's callback.
+fn callback_instruction(computation: &QueuedComputation, output: Vec<u8>) -> Instruction {
+    let mut data = EXECUTE_CALLBACK_TAG.to_vec();
+    data.extend_from_slice(&sighash(&computation.circuit.callback_name()));
+    output.serialize(&mut data).unwrap();
+
+    // Callbacks start with the Arcium program and the instructions sysvar
+    let mut accounts = vec![
+        AccountMeta::new_readonly(blackbox::ID, false),
+        AccountMeta::new_readonly(ARCIUM_PROG_ID, false),
+        AccountMeta::new_readonly(sysvar::instructions::ID, false),
+    ];
+    accounts.extend(computation.callback_accounts.iter().map(|account| {
+        if account.is_writable {
+            AccountMeta::new(account.pubkey, false)
+        } else {
+            AccountMeta::new_readonly(account.pubkey, false)
+        }
+    }));
+
+    Instruction {
+        program_id: ARCIUM_PROG_ID,
+        accounts,
+        data,
+    }
+}
@@ -0,0 +1,195 @@
+//! Native stand-in for the Arcium program.
+//!
+//! Registered at the Arcium program ID, it accepts the CPIs blackbox makes while registering
+//! computation definitions and queuing computations. Queued computations are recorded for the
+//! harness to execute; the harness then calls back into blackbox through this program, so the
+//! callbacks see the Arcium program as their caller just like on a real cluster.
+
+use std::sync::Mutex;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    entrypoint::ProgramResult,
+    hash::hash,
+    instruction::{AccountMeta, Instruction},
+    program::{invoke, invoke_signed},
+    system_instruction, system_program,
+};
+use arcium_anchor::{comp_def_offset, COMP_DEF_PDA_SEED};
+use arcium_client::idl::arcium::{
+    accounts::ComputationDefinitionAccount,
+    types::{
+        Argument, CallbackAccount, CircuitSource, ComputationDefinitionMeta, OnChainCircuitSource,
+    },
+};
+
+use crate::circuits::Circuit;
+
+/// Tag of the instruction the harness sends to deliver a computation's output
+pub const EXECUTE_CALLBACK_TAG: [u8; 8] = *b"mockmxe!";
+
+/// Computations queued since the harness last drained them
+pub(crate) static QUEUE: Mutex<Vec<QueuedComputation>> = Mutex::new(Vec::new());
+
+/// A computation blackbox queued through `queue_computation`
+#[derive(Clone)]
+pub struct QueuedComputation {
+    pub circuit: Circuit,
+    pub computation_offset: u64,
+    pub args: Vec<Argument>,
+    pub callback_accounts: Vec<CallbackAccount>,
+}
+
+/// Leading fields of the Arcium `queue_computation` instruction, as serialized by
+/// `arcium_anchor::queue_computation`.
+#[derive(AnchorDeserialize)]
+struct QueueComputationArgs {
+    computation_offset: u64,
+    args: Vec<Argument>,
+    callback_accounts: Vec<CallbackAccount>,
+}
+
+/// Leading fields of the Arcium `init_computation_definition` instruction, as serialized by
+/// `arcium_anchor::init_comp_def`.
+#[derive(AnchorDeserialize)]
+struct InitComputationDefinitionArgs {
+    comp_offset: u32,
+    _mxe_program: Pubkey,
+    _computation_definition: ComputationDefinitionMeta,
+    circuit_source_override: Option<CircuitSource>,
+}
+
+/// Anchor instruction discriminator for `name`
+pub fn sighash(name: &str) -> [u8; 8] {
+    hash(format!("global:{name}").as_bytes()).to_bytes()[..8]
+        .try_into()
+        .unwrap()
+}
+
+pub fn process_instruction<'info>(
+    program_id: &Pubkey,
+    accounts: &'info [AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    if data.len() < 8 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let (tag, rest) = data.split_at(8);
+
+    if tag == EXECUTE_CALLBACK_TAG {
+        execute_callback(accounts, rest)
+    } else if tag == sighash("init_computation_definition") {
+        init_computation_definition(program_id, accounts, rest)
+    } else if tag == sighash("queue_computation") {
+        queue_computation(accounts, rest)
+    } else {
+        // Everything else the real program does (fees, node bookkeeping) is irrelevant here
+        Ok(())
+    }
+}
+
+/// Creates the computation definition account for whichever circuit it belongs to, with the
+/// circuit source blackbox registered it with.
+fn init_computation_definition<'info>(
+    program_id: &Pubkey,
+    accounts: &'info [AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    let (comp_def, circuit) = accounts
+        .iter()
+        .find_map(|account| Circuit::from_comp_def_address(account.key).map(|c| (account, c)))
+        .ok_or(ProgramError::InvalidArgument)?;
+    let payer = accounts
+        .iter()
+        .find(|account| account.is_signer && account.is_writable)
+        .ok_or(ProgramError::MissingRequiredSignature)?;
+    let system = accounts
+        .iter()
+        .find(|account| system_program::check_id(account.key))
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+    let offset = comp_def_offset(circuit.name()).to_le_bytes();
+    let (_, bump) = Pubkey::find_program_address(&[COMP_DEF_PDA_SEED, &offset], program_id);
+
+    let args = InitComputationDefinitionArgs::deserialize(&mut &data[..])
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    if args.comp_offset != comp_def_offset(circuit.name()) {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // Without an override the circuit is uploaded on chain afterwards
+    let circuit_source = args
+        .circuit_source_override
+        .unwrap_or(CircuitSource::OnChain(OnChainCircuitSource {
+            is_completed: false,
+            upload_auth: *payer.key,
+        }));
+    let comp_def_account = ComputationDefinitionAccount {
+        circuit_source,
+        ..Default::default()
+    };
+    let mut data = Vec::new();
+    comp_def_account
+        .try_serialize(&mut data)
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+
+    invoke_signed(
+        &system_instruction::create_account(
+            payer.key,
+            comp_def.key,
+            Rent::get()?.minimum_balance(data.len()),
+            data.len() as u64,
+            program_id,
+        ),
+        &[payer.clone(), comp_def.clone(), system.clone()],
+        &[&[COMP_DEF_PDA_SEED, &offset, &[bump]]],
+    )?;
+    comp_def.try_borrow_mut_data()?.copy_from_slice(&data);
+
+    Ok(())
+}
+
+/// Records a queued computation for the harness.
+fn queue_computation(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let circuit = accounts
+        .iter()
+        .find_map(|account| Circuit::from_comp_def_address(account.key))
+        .ok_or(ProgramError::InvalidArgument)?;
+    let args = QueueComputationArgs::deserialize(&mut &data[..])
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    QUEUE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .push(QueuedComputation {
+            circuit,
+            computation_offset: args.computation_offset,
+            args: args.args,
+            callback_accounts: args.callback_accounts,
+        });
+
+    Ok(())
+}
+
+/// Invokes a callback instruction on the program passed as the first account.
+///
+/// Data is the callback's instruction data; the remaining accounts are its accounts.
+fn execute_callback<'info>(accounts: &'info [AccountInfo<'info>], data: &[u8]) -> ProgramResult {
+    let (program, callback_accounts) = accounts
+        .split_first()
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+    let instruction = Instruction {
+        program_id: *program.key,
+        accounts: callback_accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: *account.key,
+                is_signer: false,
+                is_writable: account.is_writable,
+            })
+            .collect(),
+        data: data.to_vec(),
+    };
+    invoke(&instruction, accounts)
+}
@@ -0,0 +1,258 @@
+use anchor_lang::prelude::*;
+
+use crate::state::{
+    Fees, InitBlackboxParams, Limits, OpKind, OpStatus, ParameterChange, PauseFlags,
+};
+
+/// Circuit a computation was queued for
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ComputationKind {
+    Deposit,
+    Transfer,
+    Withdraw,
+    WithdrawAll,
+    RevealBalance,
+    ProcessBatch,
+    MigrateMapping,
+}
+
+#[event]
+pub struct ProgramConfigInitialized {
+    pub authority: Pubkey,
+}
+
+/// `proposed` may take over the program config by accepting; `None` withdraws a proposal
+#[event]
+pub struct AuthorityProposed {
+    pub authority: Pubkey,
+    pub proposed: Option<Pubkey>,
+}
+
+#[event]
+pub struct AuthorityChanged {
+    pub previous: Pubkey,
+    pub authority: Pubkey,
+}
+
+/// A parameter change to `blackbox`, or to the program config if `None`, that anyone can
+/// execute from `executable_slot`
+#[event]
+pub struct ChangeQueued {
+    pub id: u64,
+    pub blackbox: Option<Pubkey>,
+    pub change: ParameterChange,
+    pub executable_slot: u64,
+}
+
+/// A queued change was applied; the event for the parameter it set is emitted alongside
+#[event]
+pub struct ChangeExecuted {
+    pub id: u64,
+}
+
+#[event]
+pub struct ChangeCancelled {
+    pub id: u64,
+}
+
+#[event]
+pub struct TimelockUpdated {
+    pub timelock_slots: u64,
+}
+
+/// New pause flags of `blackbox`, or of every blackbox if `None`
+#[event]
+pub struct PauseUpdated {
+    pub blackbox: Option<Pubkey>,
+    pub paused: PauseFlags,
+}
+
+#[event]
+pub struct MaxFeeUpdated {
+    pub max_fee_bps: u16,
+}
+
+#[event]
+pub struct MaxShardCapacityUpdated {
+    pub max_shard_capacity: u16,
+}
+
+#[event]
+pub struct FeesUpdated {
+    pub blackbox: Pubkey,
+    pub fees: Fees,
+}
+
+/// A fee on a deposit or withdrawal paid into the blackbox's treasury
+#[event]
+pub struct FeeCharged {
+    pub blackbox: Pubkey,
+    pub kind: OpKind,
+    pub amount: u64,
+}
+
+#[event]
+pub struct FeesCollected {
+    pub blackbox: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct LimitsUpdated {
+    pub blackbox: Pubkey,
+    pub limits: Limits,
+}
+
+#[event]
+pub struct BlackboxInitialized {
+    pub blackbox: Pubkey,
+    pub token_mint: Pubkey,
+    pub vault: Pubkey,
+    pub treasury: Pubkey,
+    pub params: InitBlackboxParams,
+}
+
+#[event]
+pub struct MappingAccountInitialized {
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub index: u8,
+}
+
+#[event]
+pub struct OpQueueInitialized {
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+}
+
+#[event]
+pub struct ComputationQueued {
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub computation_offset: u64,
+    pub kind: ComputationKind,
+    /// User the computation acts for; whoever flushed the queue for batches
+    pub owner: Pubkey,
+    /// Plaintext amount of deposits and withdrawals
+    pub amount: Option<u64>,
+}
+
+/// A callback, or `requeue_stale_op`, moved a computation out of `Pending` or `Stale`.
+///
+/// `Requeued` appends the computation's operation to the back of the mapping account's queue,
+/// except for batches, which go back to the front.
+#[event]
+pub struct ComputationResolved {
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub computation_offset: u64,
+    pub status: OpStatus,
+}
+
+#[event]
+pub struct OperationEnqueued {
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub kind: OpKind,
+    pub owner: Pubkey,
+    /// Plaintext amount of deposits and withdrawals
+    pub amount: Option<u64>,
+}
+
+/// The first `operations` pending operations went in flight as batch `computation_offset`.
+#[event]
+pub struct OpQueueFlushed {
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub computation_offset: u64,
+    pub operations: u8,
+}
+
+/// A solvency proof of `blackbox` over its first `shards` mapping accounts was queued
+#[event]
+pub struct SolvencyRequested {
+    pub blackbox: Pubkey,
+    pub attestation: Pubkey,
+    pub computation_offset: u64,
+    pub vault_amount: u64,
+    pub shards: u8,
+}
+
+#[event]
+pub struct SolvencyAttested {
+    pub blackbox: Pubkey,
+    pub attestation: Pubkey,
+    pub computation_offset: u64,
+    pub solvent: bool,
+    pub attested_slot: u64,
+}
+
+/// `blackbox` stopped every operation for good; owners claim their exported balances instead
+#[event]
+pub struct EmergencyEntered {
+    pub blackbox: Pubkey,
+}
+
+/// Page `page` of `mapping_account` was exported, with `entries` balances
+#[event]
+pub struct BalancesExported {
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub export: Pubkey,
+    pub page: u8,
+    pub entries: u8,
+    /// Whether the mapping has entries past this page
+    pub more: bool,
+}
+
+#[event]
+pub struct EmergencyClaimed {
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+/// A queued deposit was paid back to `destination`, its source: emergency mode left it in the
+/// operation queue, or the mapping account had no room for its owner
+#[event]
+pub struct QueuedDepositRefunded {
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub owner: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+/// `blackbox`'s new mapping accounts start on `circuit_version`, and existing ones are migrated
+/// to it
+#[event]
+pub struct CircuitVersionUpdated {
+    pub blackbox: Pubkey,
+    pub circuit_version: u8,
+}
+
+/// `mapping_account` was re-encrypted for `circuit_version`, whose circuits now run against it
+#[event]
+pub struct MappingMigrated {
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub circuit_version: u8,
+}
+
+#[event]
+pub struct VaultDeposited {
+    pub blackbox: Pubkey,
+    /// Token account the tokens came from
+    pub source: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct VaultPaidOut {
+    pub blackbox: Pubkey,
+    /// Token account the tokens went to
+    pub destination: Pubkey,
+    pub amount: u64,
+}
@@ -1,5 +1,5 @@
-use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_lang::{prelude::*, solana_program::bpf_loader_upgradeable};
+use anchor_spl::token::{self, Mint, Token, TokenAccount};
 use arcium_anchor::{
     comp_def_offset, init_comp_def, queue_computation, CLOCK_PDA_SEED, CLUSTER_PDA_SEED,
     COMP_DEF_PDA_SEED, MEMPOOL_PDA_SEED, MXE_PDA_SEED, POOL_PDA_SEED,
@@ -10,42 +10,366 @@ use arcium_client::idl::arcium::{
         StakingPoolAccount,
     },
     program::Arcium,
-    types::{Argument, CallbackAccount},
+    types::{Argument, CallbackAccount, CircuitSource, OffChainCircuitSource},
     ID_CONST as ARCIUM_PROG_ID,
 };
 use arcium_macros::{
     arcium_callback, arcium_program, callback_accounts, init_computation_definition_accounts,
     queue_computation_accounts,
 };
+use confidential_ixs::{
+    circuit_hashes::{
+        DEPOSIT_CIRCUIT_HASH, DEPOSIT_V2_CIRCUIT_HASH, EXPORT_BALANCES_CIRCUIT_HASH,
+        MIGRATE_MAPPING_V2_CIRCUIT_HASH, PROCESS_BATCH_CIRCUIT_HASH, PROCESS_BATCH_V2_CIRCUIT_HASH,
+        PROVE_SOLVENCY_CIRCUIT_HASH, REVEAL_BALANCE_CIRCUIT_HASH, TRANSFER_CIRCUIT_HASH,
+        TRANSFER_V2_CIRCUIT_HASH, WITHDRAW_ALL_CIRCUIT_HASH, WITHDRAW_ALL_V2_CIRCUIT_HASH,
+        WITHDRAW_CIRCUIT_HASH, WITHDRAW_V2_CIRCUIT_HASH,
+    },
+    MAX_BATCH_SIZE, MAX_EXPORT_ENTRIES, MAX_SOLVENCY_SHARDS, OP_EMPTY,
+};
+
+pub mod events;
+pub mod state;
+
+use crate::events::{
+    AuthorityChanged, AuthorityProposed, BalancesExported, BlackboxInitialized, ChangeCancelled,
+    ChangeExecuted, ChangeQueued, CircuitVersionUpdated, ComputationKind, ComputationQueued,
+    ComputationResolved, EmergencyClaimed, EmergencyEntered, FeeCharged, FeesCollected,
+    FeesUpdated, LimitsUpdated, MappingAccountInitialized, MappingMigrated, MaxFeeUpdated,
+    MaxShardCapacityUpdated, OpQueueFlushed, OpQueueInitialized, OperationEnqueued, PauseUpdated,
+    ProgramConfigInitialized, QueuedDepositRefunded, SolvencyAttested, SolvencyRequested,
+    TimelockUpdated, VaultDeposited, VaultPaidOut,
+};
 // Account/state definitions
-use crate::state::{BlackboxAccount, MappingAccount, ErrorCode};
+use crate::state::{
+    BalanceReveal, BlackboxAccount, ClaimedBalance, ConsumedNonce, EmergencyExport, EpochOutflow, ErrorCode,
+    ExportedBalance, InitBlackboxParams, MappingAccount, OpKind, OpQueueAccount, OpRecord, OpResult, OpStatus,
+    ParameterChange, PauseFlags, PendingChange, ProgramConfig, QueuedOp, SolvencyAttestation,
+    BATCH_TIMEOUT_SLOTS, LATEST_CIRCUIT_VERSION,
+};
 
 const COMP_DEF_OFFSET_DEPOSIT: u32 = comp_def_offset("deposit");
 const COMP_DEF_OFFSET_TRANSFER: u32 = comp_def_offset("transfer");
 const COMP_DEF_OFFSET_WITHDRAW: u32 = comp_def_offset("withdraw");
+const COMP_DEF_OFFSET_PROCESS_BATCH: u32 = comp_def_offset("process_batch");
+const COMP_DEF_OFFSET_WITHDRAW_ALL: u32 = comp_def_offset("withdraw_all");
+const COMP_DEF_OFFSET_REVEAL_BALANCE: u32 = comp_def_offset("reveal_balance");
+const COMP_DEF_OFFSET_PROVE_SOLVENCY: u32 = comp_def_offset("prove_solvency");
+const COMP_DEF_OFFSET_EXPORT_BALANCES: u32 = comp_def_offset("export_balances");
+const COMP_DEF_OFFSET_DEPOSIT_V2: u32 = comp_def_offset("deposit_v2");
+const COMP_DEF_OFFSET_TRANSFER_V2: u32 = comp_def_offset("transfer_v2");
+const COMP_DEF_OFFSET_WITHDRAW_V2: u32 = comp_def_offset("withdraw_v2");
+const COMP_DEF_OFFSET_WITHDRAW_ALL_V2: u32 = comp_def_offset("withdraw_all_v2");
+const COMP_DEF_OFFSET_PROCESS_BATCH_V2: u32 = comp_def_offset("process_batch_v2");
+const COMP_DEF_OFFSET_MIGRATE_MAPPING_V2: u32 = comp_def_offset("migrate_mapping_v2");
 
-// Maximum size for a mapping account (close to 10MB)
-const MAX_MAPPING_ACCOUNT_SIZE: usize = 10 * 1024 * 1024; // 10MB
+// Offsets of every version of the circuits reading a mapping account, version 1 first; a
+// mapping account's `circuit_version` picks among them. The circuits that only read a mapping
+// serve every version sharing its layout, so they're listed once per version
+const DEPOSIT_VERSIONS: [u32; LATEST_CIRCUIT_VERSION as usize] =
+    [COMP_DEF_OFFSET_DEPOSIT, COMP_DEF_OFFSET_DEPOSIT_V2];
+const TRANSFER_VERSIONS: [u32; LATEST_CIRCUIT_VERSION as usize] =
+    [COMP_DEF_OFFSET_TRANSFER, COMP_DEF_OFFSET_TRANSFER_V2];
+const WITHDRAW_VERSIONS: [u32; LATEST_CIRCUIT_VERSION as usize] =
+    [COMP_DEF_OFFSET_WITHDRAW, COMP_DEF_OFFSET_WITHDRAW_V2];
+const WITHDRAW_ALL_VERSIONS: [u32; LATEST_CIRCUIT_VERSION as usize] =
+    [COMP_DEF_OFFSET_WITHDRAW_ALL, COMP_DEF_OFFSET_WITHDRAW_ALL_V2];
+const PROCESS_BATCH_VERSIONS: [u32; LATEST_CIRCUIT_VERSION as usize] =
+    [COMP_DEF_OFFSET_PROCESS_BATCH, COMP_DEF_OFFSET_PROCESS_BATCH_V2];
+const REVEAL_BALANCE_VERSIONS: [u32; LATEST_CIRCUIT_VERSION as usize] =
+    [COMP_DEF_OFFSET_REVEAL_BALANCE, COMP_DEF_OFFSET_REVEAL_BALANCE];
+const PROVE_SOLVENCY_VERSIONS: [u32; LATEST_CIRCUIT_VERSION as usize] =
+    [COMP_DEF_OFFSET_PROVE_SOLVENCY, COMP_DEF_OFFSET_PROVE_SOLVENCY];
+const EXPORT_BALANCES_VERSIONS: [u32; LATEST_CIRCUIT_VERSION as usize] =
+    [COMP_DEF_OFFSET_EXPORT_BALANCES, COMP_DEF_OFFSET_EXPORT_BALANCES];
+// Offsets of the circuits migrating a mapping out of each version but the latest
+const MIGRATE_MAPPING_VERSIONS: [u32; LATEST_CIRCUIT_VERSION as usize - 1] =
+    [COMP_DEF_OFFSET_MIGRATE_MAPPING_V2];
+// Hashes the compiled versions of the versioned circuits are pinned to, in the same order
+const DEPOSIT_HASHES: [[u8; 32]; LATEST_CIRCUIT_VERSION as usize] =
+    [DEPOSIT_CIRCUIT_HASH, DEPOSIT_V2_CIRCUIT_HASH];
+const TRANSFER_HASHES: [[u8; 32]; LATEST_CIRCUIT_VERSION as usize] =
+    [TRANSFER_CIRCUIT_HASH, TRANSFER_V2_CIRCUIT_HASH];
+const WITHDRAW_HASHES: [[u8; 32]; LATEST_CIRCUIT_VERSION as usize] =
+    [WITHDRAW_CIRCUIT_HASH, WITHDRAW_V2_CIRCUIT_HASH];
+const WITHDRAW_ALL_HASHES: [[u8; 32]; LATEST_CIRCUIT_VERSION as usize] =
+    [WITHDRAW_ALL_CIRCUIT_HASH, WITHDRAW_ALL_V2_CIRCUIT_HASH];
+const PROCESS_BATCH_HASHES: [[u8; 32]; LATEST_CIRCUIT_VERSION as usize] =
+    [PROCESS_BATCH_CIRCUIT_HASH, PROCESS_BATCH_V2_CIRCUIT_HASH];
+const REVEAL_BALANCE_HASHES: [[u8; 32]; LATEST_CIRCUIT_VERSION as usize] =
+    [REVEAL_BALANCE_CIRCUIT_HASH, REVEAL_BALANCE_CIRCUIT_HASH];
+const EXPORT_BALANCES_HASHES: [[u8; 32]; LATEST_CIRCUIT_VERSION as usize] =
+    [EXPORT_BALANCES_CIRCUIT_HASH, EXPORT_BALANCES_CIRCUIT_HASH];
+const MIGRATE_MAPPING_HASHES: [[u8; 32]; LATEST_CIRCUIT_VERSION as usize - 1] =
+    [MIGRATE_MAPPING_V2_CIRCUIT_HASH];
+
+// Maximum size for a mapping account (the most `init` can allocate from inside a program)
+const MAX_MAPPING_ACCOUNT_SIZE: usize = 10 * 1024; // 10KB
+// Fixed part of a mapping account: discriminator, index, token_mint, nonce, version,
+// circuit_version, capacity, vec lengths
+const MAPPING_ACCOUNT_HEADER_SIZE: usize = 8 + 1 + 32 + 16 + 8 + 1 + 2 + 4 + 4;
 // Approximate size of a single entry (pubkey + balance)
 const ENTRY_SIZE: usize = 32 * 2; // 64 bytes
-// Maximum number of entries per mapping account
-const MAX_ENTRIES_PER_ACCOUNT: usize = MAX_MAPPING_ACCOUNT_SIZE / ENTRY_SIZE;
+// Maximum number of entries per mapping account, and so of `ProgramConfig::max_shard_capacity`
+const MAX_ENTRIES_PER_ACCOUNT: usize =
+    (MAX_MAPPING_ACCOUNT_SIZE - MAPPING_ACCOUNT_HEADER_SIZE) / ENTRY_SIZE;
 
 
-declare_id!("Blackbox111111111111111111111111111111111");
+declare_id!("AL25N1v4WjSXdTsszwYG7WA2Ztb6Wj5VAQBFGn8Y4pnz");
 
 #[arcium_program]
 pub mod blackbox {
     use super::*;
 
-    /// Initializes a blackbox for a token
-    pub fn init_blackbox(ctx: Context<InitBlackbox>) -> Result<()> {
+    /// Creates the program config, with the program's upgrade authority as its authority.
+    pub fn init_program_config(ctx: Context<InitProgramConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.bump = ctx.bumps.config;
+        config.authority = ctx.accounts.authority.key();
+        config.paused = PauseFlags::default();
+        config.max_fee_bps = 0;
+        config.pending_authority = None;
+        config.timelock_slots = 0;
+        config.next_change_id = 0;
+        config.max_shard_capacity = MAX_ENTRIES_PER_ACCOUNT as u16;
+
+        emit!(ProgramConfigInitialized {
+            authority: config.authority,
+        });
+
+        Ok(())
+    }
+
+    /// Pauses the given operations on `blackbox`, or on every blackbox if it's omitted, on top of
+    /// those already paused.
+    ///
+    /// An operation is paused if either its blackbox or the program config pauses it. Pausing
+    /// takes effect immediately; resuming is a `ParameterChange::Paused` behind the timelock.
+    pub fn pause(ctx: Context<Pause>, paused: PauseFlags) -> Result<()> {
+        let paused = match &mut ctx.accounts.blackbox {
+            Some(blackbox) => {
+                blackbox.paused = blackbox.paused.union(paused);
+                blackbox.paused
+            }
+            None => {
+                let config = &mut ctx.accounts.config;
+                config.paused = config.paused.union(paused);
+                config.paused
+            }
+        };
+
+        emit!(PauseUpdated {
+            blackbox: ctx.accounts.blackbox.as_ref().map(|blackbox| blackbox.key()),
+            paused,
+        });
+
+        Ok(())
+    }
+
+    /// Proposes `new_authority` as the program config's authority, replacing any earlier
+    /// proposal; `None` withdraws it. Nothing changes until the proposed authority accepts.
+    pub fn propose_authority(
+        ctx: Context<ProposeAuthority>,
+        new_authority: Option<Pubkey>,
+    ) -> Result<()> {
+        ctx.accounts.config.pending_authority = new_authority;
+
+        emit!(AuthorityProposed {
+            authority: ctx.accounts.config.authority,
+            proposed: new_authority,
+        });
+
+        Ok(())
+    }
+
+    /// Hands the program config to the proposed authority, who signs.
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let previous = config.authority;
+        config.authority = ctx.accounts.new_authority.key();
+        config.pending_authority = None;
+
+        emit!(AuthorityChanged {
+            previous,
+            authority: config.authority,
+        });
+
+        Ok(())
+    }
+
+    /// Queues `change` to `blackbox`, or to the program config if it's omitted. Anyone can
+    /// execute it once `ProgramConfig::timelock_slots` have passed.
+    pub fn queue_change(ctx: Context<QueueChange>, change: ParameterChange) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        change.validate(config, ctx.accounts.blackbox.as_deref())?;
+
+        let id = config.next_change_id;
+        config.next_change_id += 1;
+        let blackbox = ctx.accounts.blackbox.as_ref().map(|blackbox| blackbox.key());
+        let executable_slot = Clock::get()?.slot.saturating_add(config.timelock_slots);
+        ctx.accounts.change.set_inner(PendingChange {
+            bump: ctx.bumps.change,
+            id,
+            blackbox,
+            change,
+            executable_slot,
+            payer: ctx.accounts.payer.key(),
+        });
+
+        emit!(ChangeQueued {
+            id,
+            blackbox,
+            change,
+            executable_slot,
+        });
+
+        Ok(())
+    }
+
+    /// Applies a queued change whose timelock has passed, emitting the event for the parameter
+    /// it sets. Ranges are checked again against the current config.
+    pub fn execute_change(ctx: Context<ExecuteChange>) -> Result<()> {
+        let pending = &ctx.accounts.change;
+        require!(
+            Clock::get()?.slot >= pending.executable_slot,
+            ErrorCode::ChangeTimelocked
+        );
+        require!(
+            ctx.accounts.blackbox.as_ref().map(|blackbox| blackbox.key()) == pending.blackbox,
+            ErrorCode::InvalidChangeTarget
+        );
+        pending
+            .change
+            .validate(&ctx.accounts.config, ctx.accounts.blackbox.as_deref())?;
+
+        let config = &mut ctx.accounts.config;
+        match (pending.change, &mut ctx.accounts.blackbox) {
+            (ParameterChange::MaxFee(max_fee_bps), _) => {
+                config.max_fee_bps = max_fee_bps;
+                emit!(MaxFeeUpdated { max_fee_bps });
+            }
+            (ParameterChange::MaxShardCapacity(max_shard_capacity), _) => {
+                config.max_shard_capacity = max_shard_capacity;
+                emit!(MaxShardCapacityUpdated { max_shard_capacity });
+            }
+            (ParameterChange::Timelock(timelock_slots), _) => {
+                config.timelock_slots = timelock_slots;
+                emit!(TimelockUpdated { timelock_slots });
+            }
+            (ParameterChange::Paused(paused), Some(blackbox)) => {
+                blackbox.paused = paused;
+                emit!(PauseUpdated {
+                    blackbox: Some(blackbox.key()),
+                    paused,
+                });
+            }
+            (ParameterChange::Paused(paused), None) => {
+                config.paused = paused;
+                emit!(PauseUpdated {
+                    blackbox: None,
+                    paused,
+                });
+            }
+            // The outflow already counted this epoch stays counted against new limits
+            (ParameterChange::Limits(limits), Some(blackbox)) => {
+                blackbox.limits = limits;
+                emit!(LimitsUpdated {
+                    blackbox: blackbox.key(),
+                    limits,
+                });
+            }
+            (ParameterChange::Fees(fees), Some(blackbox)) => {
+                blackbox.fees = fees;
+                emit!(FeesUpdated {
+                    blackbox: blackbox.key(),
+                    fees,
+                });
+            }
+            (ParameterChange::Emergency, Some(blackbox)) => {
+                blackbox.emergency = true;
+                emit!(EmergencyEntered {
+                    blackbox: blackbox.key(),
+                });
+            }
+            // Mapping accounts keep their version until `migrate_mapping` moves them
+            (ParameterChange::CircuitVersion(circuit_version), Some(blackbox)) => {
+                blackbox.circuit_version = circuit_version;
+                emit!(CircuitVersionUpdated {
+                    blackbox: blackbox.key(),
+                    circuit_version,
+                });
+            }
+            (
+                ParameterChange::Limits(_)
+                | ParameterChange::Fees(_)
+                | ParameterChange::Emergency
+                | ParameterChange::CircuitVersion(_),
+                None,
+            ) => return err!(ErrorCode::InvalidChangeTarget),
+        }
+
+        emit!(ChangeExecuted { id: pending.id });
+
+        Ok(())
+    }
+
+    /// Drops a queued change.
+    pub fn cancel_change(ctx: Context<CancelChange>) -> Result<()> {
+        emit!(ChangeCancelled {
+            id: ctx.accounts.change.id,
+        });
+
+        Ok(())
+    }
+
+    /// Moves everything in the blackbox's treasury to `destination`.
+    pub fn collect_fees(ctx: Context<CollectFees>) -> Result<()> {
+        let amount = ctx.accounts.treasury.amount;
+        emit!(FeesCollected {
+            blackbox: ctx.accounts.blackbox.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+        });
+        transfer_from_blackbox(
+            &ctx.accounts.blackbox,
+            ctx.accounts.treasury.to_account_info(),
+            ctx.accounts.destination.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            amount,
+        )
+    }
+
+    /// Initializes a blackbox for a token, configured by the authority with `params`.
+    ///
+    /// Fees, limits and the circuit version can change later through the timelock; the shard
+    /// capacity, policy hooks and mode flags are fixed for the blackbox's lifetime.
+    pub fn init_blackbox(ctx: Context<InitBlackbox>, params: InitBlackboxParams) -> Result<()> {
+        params.validate(&ctx.accounts.config)?;
         let blackbox = &mut ctx.accounts.blackbox;
         
         blackbox.token_mint = ctx.accounts.token_mint.key();
         blackbox.vault = ctx.accounts.vault.key();
+        blackbox.treasury = ctx.accounts.treasury.key();
         blackbox.mapping_account_count = 0;
-        blackbox.bump = *ctx.bumps.blackbox;
+        blackbox.bump = ctx.bumps.blackbox;
+        blackbox.paused = PauseFlags::default();
+        blackbox.fees = params.fees;
+        blackbox.limits = params.limits;
+        blackbox.outflow = EpochOutflow::default();
+        blackbox.emergency = false;
+        blackbox.circuit_version = params.circuit_version;
+        blackbox.shard_capacity = params.shard_capacity;
+        blackbox.policy = params.policy;
+        blackbox.modes = params.modes;
+        blackbox.cluster_offset = params.cluster_offset;
+
+        emit!(BlackboxInitialized {
+            blackbox: blackbox.key(),
+            token_mint: blackbox.token_mint,
+            vault: blackbox.vault,
+            treasury: blackbox.treasury,
+            params,
+        });
         
         Ok(())
     }
@@ -53,7 +377,8 @@ pub mod blackbox {
     /// Initializes a new mapping account for a specific token blackbox.
     ///
     /// This is called when a new mapping account is needed, either for the first account
-    /// or when existing accounts are full.
+    /// or when existing accounts are full. The account has room for the blackbox's shard
+    /// capacity, capped by the program config's current `max_shard_capacity`.
     pub fn initialize_mapping_account(
         ctx: Context<InitializeMappingAccount>,
     ) -> Result<()> {
@@ -62,12 +387,21 @@ pub mod blackbox {
         
         // Set the mapping account index and update total count
         let index = blackbox.mapping_account_count;
-        blackbox.mapping_account_count += 1;
+        blackbox.mapping_account_count = index.checked_add(1).ok_or(ErrorCode::TooManyShards)?;
         
         mapping_account.index = index;
         mapping_account.token_mint = blackbox.token_mint;
+        mapping_account.nonce = 0;
+        mapping_account.circuit_version = blackbox.circuit_version;
+        mapping_account.capacity = blackbox.new_shard_capacity(&ctx.accounts.config);
         mapping_account.encrypted_pubkeys = Vec::new();
         mapping_account.encrypted_balances = Vec::new();
+
+        emit!(MappingAccountInitialized {
+            blackbox: blackbox.key(),
+            mapping_account: mapping_account.key(),
+            index,
+        });
         
         Ok(())
     }
@@ -77,91 +411,255 @@ pub mod blackbox {
         init_comp_def(
             ctx.accounts,
             true,
-            Some("deposit".to_string()),
-            Some("Deposit funds into blackbox".to_string()),
+            off_chain_source("deposit", DEPOSIT_CIRCUIT_HASH),
+            None,
         )?;
         Ok(())
     }
 
     /// Deposits tokens into blackbox
-    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
-        // Transfer tokens to vault
-        let cpi_accounts = token::Transfer {
-            from: ctx.accounts.user_token_account.to_account_info(),
-            to: ctx.accounts.vault.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
-        };
-        
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        
-        token::transfer(cpi_ctx, amount)?;
+    ///
+    /// `computation_offset` identifies this operation; it must be unique per blackbox. The
+    /// deposit fee goes to the treasury and the rest of `amount` is credited, or refunded if
+    /// the depositor has no entry and the mapping account is full. The mapping is re-encrypted
+    /// under a nonce derived from the computation, like every other result.
+    pub fn deposit(ctx: Context<Deposit>, computation_offset: u64, amount: u64) -> Result<()> {
+        ctx.accounts
+            .blackbox
+            .require_not_paused(&ctx.accounts.config, OpKind::Deposit)?;
+        ctx.accounts.blackbox.require_direct()?;
+        ctx.accounts
+            .blackbox
+            .require_deposit_approved(ctx.accounts.deposit_approver.as_ref().map(|a| a.key()))?;
+        ctx.accounts.blackbox.check_deposit(amount)?;
+
+        // Transfer tokens to vault, and the fee to the treasury
+        let amount = take_deposit(
+            &ctx.accounts.blackbox,
+            &ctx.accounts.config,
+            DepositTransfer {
+                source: ctx.accounts.user_token_account.to_account_info(),
+                vault: ctx.accounts.vault.to_account_info(),
+                treasury: ctx.accounts.treasury.to_account_info(),
+                user: ctx.accounts.user.to_account_info(),
+                token_program: ctx.accounts.token_program.to_account_info(),
+            },
+            amount,
+        )?;
+
+        ctx.accounts.op_record.set_inner(OpRecord {
+            bump: ctx.bumps.op_record,
+            blackbox: ctx.accounts.blackbox.key(),
+            mapping_account: ctx.accounts.mapping_account.key(),
+            computation_offset,
+            mapping_version: ctx.accounts.mapping_account.version,
+            owner: ctx.accounts.user.key(),
+            payer: ctx.accounts.payer.key(),
+            amount,
+            epoch: 0,
+            destination: ctx.accounts.user_token_account.key(),
+            status: OpStatus::Pending,
+            requeue: Some(QueuedOp {
+                kind: OpKind::Deposit,
+                owner: ctx.accounts.user.key(),
+                client_key: [0; 32],
+                enc_transfer: [[0; 32]; 3],
+                amount,
+                nonce: 0,
+                destination: ctx.accounts.user_token_account.key(),
+                epoch: 0,
+            }),
+        });
 
-        // Arguments - signer pubkey, amount
-        let args = vec![
+        // Arguments - signer pubkey, amount, mapping, capacity, output nonce
+        let mut args = vec![
             Argument::PlaintextPubkey(ctx.accounts.user.key()),
             Argument::PlaintextU64(amount)
         ];
+        args.extend(growing_mapping_args(&ctx.accounts.mapping_account));
+        args.push(Argument::PlaintextU128(output_nonce(
+            &ctx.accounts.mapping_account.key(),
+            ctx.accounts.mapping_account.version,
+            computation_offset,
+        )));
 
         // Queue computation for deposit operation
         queue_computation(
             ctx.accounts,
             args,
             vec![
-                CallbackAccount::new(ctx.accounts.user.key(), false),
-                CallbackAccount::new_from_accounts(&ctx.accounts.mapping_accounts, true),
-                CallbackAccount::new(ctx.accounts.system_program.key(), false),
+                CallbackAccount::new(ctx.accounts.blackbox.key(), false),
+                CallbackAccount::new(ctx.accounts.mapping_account.key(), true),
+                CallbackAccount::new(ctx.accounts.op_record.key(), true),
+                CallbackAccount::new(ctx.accounts.vault.key(), true),
+                CallbackAccount::new(ctx.accounts.user_token_account.key(), true),
+                CallbackAccount::new(ctx.accounts.token_program.key(), false),
+                op_queue_callback_account(&ctx.accounts.op_queue),
             ],
-            None,
+            Some(computation_offset),
         )?;
+        emit_queued(&ctx.accounts.op_record, ComputationKind::Deposit, Some(amount));
 
         Ok(())
     }
 
     /// Deposit callback
+    ///
+    /// A result landing after emergency mode started is dropped, so the mapping doesn't move
+    /// past its export; `refund_deposit` then returns the tokens. A deposit the mapping
+    /// account had no room for is refunded right away, leaving the mapping as it was.
     #[arcium_callback]
     pub fn deposit_callback(ctx: Context<DepositCallback>, output: Vec<u8>) -> Result<()> {
-        // TODO: Implement deposit callback
-        
+        if drop_in_emergency(&mut ctx.accounts.op_record, &ctx.accounts.blackbox) {
+            return Ok(());
+        }
+
+        // Output - mapping nonce, mapping ciphertexts, credited flag
+        let (nonce, mapping, rest) = parse_mapping_output(&output)?;
+        require!(rest.len() == 1, ErrorCode::InvalidComputationOutput);
+
+        // A stale result may have found the account full when it no longer is, so only a
+        // current one is refunded; a stale one is re-queued as usual
+        let op_record = &mut ctx.accounts.op_record;
+        if rest[0] == 0
+            && op_record.status == OpStatus::Pending
+            && op_record.mapping_version == ctx.accounts.mapping_account.version
+        {
+            msg!("Computation {} found its mapping account full", op_record.computation_offset);
+            op_record.status = OpStatus::Refunded;
+            emit_resolved(op_record);
+            return pay_out_of_vault(
+                &ctx.accounts.blackbox,
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.destination.to_account_info(),
+                ctx.accounts.token_program.to_account_info(),
+                op_record.amount,
+            );
+        }
+        if !accept_result(
+            op_record,
+            &ctx.accounts.mapping_account,
+            ctx.accounts.op_queue.as_mut(),
+        ) {
+            return Ok(());
+        }
+        ctx.accounts.mapping_account.store_mapping(mapping, nonce);
+
+        Ok(())
+    }
+
+    /// Initializes the transfer computation definition.
+    pub fn init_transfer_comp_def(ctx: Context<InitTransferCompDef>) -> Result<()> {
+        init_comp_def(
+            ctx.accounts,
+            true,
+            off_chain_source("transfer", TRANSFER_CIRCUIT_HASH),
+            None,
+        )?;
         Ok(())
     }
 
     /// Transfers funds within the blackbox (internal transfer).
     ///
     /// This moves funds in the encrypted domain from the sender to the recipient.
-    /// The `enc_amount` represents the transfer amount (encrypted).
+    /// `enc_transfer` is the sender's encrypted `TransferArgs`: the recipient, the amount and
+    /// a no-op flag that turns the transfer into cover traffic, where the computation runs
+    /// identically but leaves every balance unchanged.
+    ///
+    /// `client_key` is the sender's x25519 key, which the MXE agrees the cipher of
+    /// `enc_transfer` with. `nonce` is the nonce the ciphertexts were encrypted under. It is
+    /// recorded as consumed, so the same encrypted payload can't be submitted twice.
     pub fn transfer(
         ctx: Context<Transfer>,
-        enc_recipient: [u8; 32],
-        enc_amount: [u8; 32]
+        computation_offset: u64,
+        enc_transfer: [[u8; 32]; 3],
+        client_key: [u8; 32],
+        nonce: u128,
     ) -> Result<()> {
-        // Arguments - sender pubkey, recipient pubkey, encrypted amount
-        let args = vec![
+        ctx.accounts
+            .blackbox
+            .require_not_paused(&ctx.accounts.config, OpKind::Transfer)?;
+        ctx.accounts.blackbox.require_direct()?;
+        ctx.accounts.consumed_nonce.set_inner(ConsumedNonce {
+            bump: ctx.bumps.consumed_nonce,
+            payer: ctx.accounts.payer.key(),
+        });
+        ctx.accounts.op_record.set_inner(OpRecord {
+            bump: ctx.bumps.op_record,
+            blackbox: ctx.accounts.blackbox.key(),
+            mapping_account: ctx.accounts.mapping_account.key(),
+            computation_offset,
+            mapping_version: ctx.accounts.mapping_account.version,
+            owner: ctx.accounts.sender.key(),
+            payer: ctx.accounts.payer.key(),
+            amount: 0,
+            epoch: 0,
+            destination: Pubkey::default(),
+            status: OpStatus::Pending,
+            requeue: Some(QueuedOp {
+                kind: OpKind::Transfer,
+                owner: ctx.accounts.sender.key(),
+                client_key,
+                enc_transfer,
+                amount: 0,
+                nonce,
+                destination: Pubkey::default(),
+                epoch: 0,
+            }),
+        });
+
+        // Arguments - mapping, capacity, sender pubkey, sender's client key, encrypted
+        // recipient, amount and no-op flag, nonce, output nonce
+        let mut args = growing_mapping_args(&ctx.accounts.mapping_account);
+        args.extend([
             Argument::PlaintextPubkey(ctx.accounts.sender.key()),
-            Argument::CipheredPubkey(enc_recipient),
-            Argument::CipheredU64(enc_amount),
-        ];
+            Argument::PlaintextPubkey(Pubkey::new_from_array(client_key)),
+            Argument::CipheredPubkey(enc_transfer[0]),
+            Argument::CipheredU64(enc_transfer[1]),
+            Argument::CipheredBool(enc_transfer[2]),
+            Argument::PlaintextU128(nonce),
+            Argument::PlaintextU128(output_nonce(
+                &ctx.accounts.mapping_account.key(),
+                ctx.accounts.mapping_account.version,
+                computation_offset,
+            )),
+        ]);
 
         // Queue computation for transfer operation
         queue_computation(
             ctx.accounts,
             args,
             vec![
-                CallbackAccount::new(ctx.accounts.sender.key(), false),
-                CallbackAccount::new(ctx.accounts.recipient.key(), false),
-                CallbackAccount::new_from_accounts(&ctx.accounts.mapping_accounts, true),
-                CallbackAccount::new(ctx.accounts.system_program.key(), false),
+                CallbackAccount::new(ctx.accounts.blackbox.key(), false),
+                CallbackAccount::new(ctx.accounts.mapping_account.key(), true),
+                CallbackAccount::new(ctx.accounts.op_record.key(), true),
+                op_queue_callback_account(&ctx.accounts.op_queue),
             ],
-            None,
+            Some(computation_offset),
         )?;
+        emit_queued(&ctx.accounts.op_record, ComputationKind::Transfer, None);
 
         Ok(())
     }
 
     /// Transfer callback
     #[arcium_callback]
-    pub fn transfer_callback(ctx: Context<Transfer>, encrypted_amount: [u8; 32], nonce: u128) -> Result<()> {
-        // TODO: Implement transfer callback
+    pub fn transfer_callback(ctx: Context<TransferCallback>, output: Vec<u8>) -> Result<()> {
+        if drop_in_emergency(&mut ctx.accounts.op_record, &ctx.accounts.blackbox) {
+            return Ok(());
+        }
+        if !accept_result(
+            &mut ctx.accounts.op_record,
+            &ctx.accounts.mapping_account,
+            ctx.accounts.op_queue.as_mut(),
+        ) {
+            return Ok(());
+        }
+
+        // Output - mapping nonce, mapping ciphertexts
+        let (nonce, mapping, _) = parse_mapping_output(&output)?;
+        ctx.accounts.mapping_account.store_mapping(mapping, nonce);
+
         Ok(())
     }
 
@@ -170,8 +668,8 @@ pub mod blackbox {
         init_comp_def(
             ctx.accounts,
             true,
-            Some("withdraw".to_string()),
-            Some("Withdraw funds from blackbox".to_string()),
+            off_chain_source("withdraw", WITHDRAW_CIRCUIT_HASH),
+            None,
         )?;
         Ok(())
     }
@@ -180,116 +678,3536 @@ pub mod blackbox {
     ///
     /// The withdrawal amount is provided as an encrypted value. The function must verify,
     /// within the encrypted domain, that the user has sufficient balance before updating it.
-    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
-        // Arguments - user pubkey, amount
-        let args = vec![
+    /// On success the callback pays `amount` out of the vault, the withdrawal fee to the
+    /// treasury and the rest to `destination`. `amount` counts towards the epoch's outflow cap
+    /// once queued, until the callback finds the balance doesn't cover it.
+    pub fn withdraw(ctx: Context<Withdraw>, computation_offset: u64, amount: u64) -> Result<()> {
+        ctx.accounts
+            .blackbox
+            .require_not_paused(&ctx.accounts.config, OpKind::Withdraw)?;
+        ctx.accounts.blackbox.require_direct()?;
+        let epoch = Clock::get()?.epoch;
+        ctx.accounts.blackbox.record_withdrawal(amount, epoch)?;
+        ctx.accounts.op_record.set_inner(OpRecord {
+            bump: ctx.bumps.op_record,
+            blackbox: ctx.accounts.blackbox.key(),
+            mapping_account: ctx.accounts.mapping_account.key(),
+            computation_offset,
+            mapping_version: ctx.accounts.mapping_account.version,
+            owner: ctx.accounts.user.key(),
+            payer: ctx.accounts.payer.key(),
+            amount,
+            epoch,
+            destination: ctx.accounts.destination.key(),
+            status: OpStatus::Pending,
+            requeue: Some(QueuedOp {
+                kind: OpKind::Withdraw,
+                owner: ctx.accounts.user.key(),
+                client_key: [0; 32],
+                enc_transfer: [[0; 32]; 3],
+                amount,
+                nonce: 0,
+                destination: ctx.accounts.destination.key(),
+                epoch,
+            }),
+        });
+
+        // Arguments - mapping, user pubkey, amount, output nonce
+        let mut args = mapping_args(&ctx.accounts.mapping_account);
+        args.extend([
             Argument::PlaintextPubkey(ctx.accounts.user.key()),
             Argument::PlaintextU64(amount),
-        ];
+            Argument::PlaintextU128(output_nonce(
+                &ctx.accounts.mapping_account.key(),
+                ctx.accounts.mapping_account.version,
+                computation_offset,
+            )),
+        ]);
 
         // Queue computation for withdrawal operation
         queue_computation(
             ctx.accounts,
             args,
             vec![
-                CallbackAccount::new(ctx.accounts.user.key(), false),
-                CallbackAccount::new_from_accounts(&ctx.accounts.mapping_accounts, true),
-                CallbackAccount::new(ctx.accounts.system_program.key(), false),
+                CallbackAccount::new(ctx.accounts.blackbox.key(), true),
+                CallbackAccount::new(ctx.accounts.config.key(), false),
+                CallbackAccount::new(ctx.accounts.mapping_account.key(), true),
+                CallbackAccount::new(ctx.accounts.op_record.key(), true),
+                CallbackAccount::new(ctx.accounts.vault.key(), true),
+                CallbackAccount::new(ctx.accounts.blackbox.treasury, true),
+                CallbackAccount::new(ctx.accounts.destination.key(), true),
+                CallbackAccount::new(ctx.accounts.token_program.key(), false),
+                op_queue_callback_account(&ctx.accounts.op_queue),
             ],
-            None,
+            Some(computation_offset),
         )?;
+        emit_queued(&ctx.accounts.op_record, ComputationKind::Withdraw, Some(amount));
 
         Ok(())
     }
 
     /// Withdraw callback
     #[arcium_callback]
-    pub fn withdraw_callback(ctx: Context<Withdraw>, encrypted_amount: [u8; 32], nonce: u128) -> Result<()> {
-        // TODO: implement
+    pub fn withdraw_callback(ctx: Context<WithdrawCallback>, output: Vec<u8>) -> Result<()> {
+        if drop_in_emergency(&mut ctx.accounts.op_record, &ctx.accounts.blackbox) {
+            return Ok(());
+        }
+        if !accept_result(
+            &mut ctx.accounts.op_record,
+            &ctx.accounts.mapping_account,
+            ctx.accounts.op_queue.as_mut(),
+        ) {
+            return Ok(());
+        }
+
+        // Output - mapping nonce, mapping ciphertexts, success flag
+        let (nonce, mapping, rest) = parse_mapping_output(&output)?;
+        require!(rest.len() == 1, ErrorCode::InvalidComputationOutput);
+        ctx.accounts.mapping_account.store_mapping(mapping, nonce);
+
+        if rest[0] != 0 {
+            pay_withdrawal(
+                &ctx.accounts.blackbox,
+                &ctx.accounts.config,
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.treasury.to_account_info(),
+                ctx.accounts.destination.to_account_info(),
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.op_record.amount,
+            )?;
+        } else if let Some(op) = ctx.accounts.op_record.requeue {
+            ctx.accounts.blackbox.release_withdrawal(op.amount, op.epoch);
+        }
+
         Ok(())
     }
-}
 
+    /// Initializes the version 2 deposit computation definition.
+    pub fn init_deposit_v2_comp_def(ctx: Context<InitDepositV2CompDef>) -> Result<()> {
+        init_comp_def(
+            ctx.accounts,
+            true,
+            off_chain_source("deposit_v2", DEPOSIT_V2_CIRCUIT_HASH),
+            None,
+        )?;
+        Ok(())
+    }
 
+    /// Deposit callback for mapping accounts on circuit version 2, whose output is laid out
+    /// as version 1's
+    #[arcium_callback]
+    pub fn deposit_v2_callback(ctx: Context<DepositCallback>, output: Vec<u8>) -> Result<()> {
+        deposit_callback(ctx, output)
+    }
 
-/// Accounts for initializing a blackbox for a specific token
-#[derive(Accounts)]
-pub struct InitBlackbox<'info> {
-    /// The token mint for which this blackbox is created
-    pub token_mint: Account<'info, Mint>,
+    /// Initializes the version 2 transfer computation definition.
+    pub fn init_transfer_v2_comp_def(ctx: Context<InitTransferV2CompDef>) -> Result<()> {
+        init_comp_def(
+            ctx.accounts,
+            true,
+            off_chain_source("transfer_v2", TRANSFER_V2_CIRCUIT_HASH),
+            None,
+        )?;
+        Ok(())
+    }
 
-    /// The blackbox account for this token
-    #[account(
-        init,
-        payer = payer,
-        space = 8 + 32 + 8, // discriminator + token_mint + mapping_account_count
-        seeds = [b"blackbox", token_mint.key().as_ref()],
-        bump
-    )]
-    pub blackbox: Account<'info, BlackboxAccount>,
+    /// Transfer callback for mapping accounts on circuit version 2, whose output is laid out
+    /// as version 1's
+    #[arcium_callback]
+    pub fn transfer_v2_callback(ctx: Context<TransferCallback>, output: Vec<u8>) -> Result<()> {
+        transfer_callback(ctx, output)
+    }
 
-    /// The vault (account that holds tokens) for this blackbox 
-    #[account(
-        init,
-        payer = authority,
-        seeds = [b"vault".as_ref(), blackbox.key().as_ref()],
-        bump,
-        token::authority = blackbox,
-        token::mint = token_mint,
-    )]
-    pub vault: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+    /// Initializes the version 2 withdraw computation definition.
+    pub fn init_withdraw_v2_comp_def(ctx: Context<InitWithdrawV2CompDef>) -> Result<()> {
+        init_comp_def(
+            ctx.accounts,
+            true,
+            off_chain_source("withdraw_v2", WITHDRAW_V2_CIRCUIT_HASH),
+            None,
+        )?;
+        Ok(())
+    }
 
-/// Accounts for initializing the deposit computation definition
-#[init_computation_definition_accounts("deposit", payer)]
-#[derive(Accounts)]
-pub struct InitDepositCompDef<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [MXE_PDA_SEED, ID_CONST.to_bytes().as_ref()],
-        seeds::program = ARCIUM_PROG_ID,
-        bump = mxe_account.bump
-    )]
-    pub mxe_account: Box<Account<'info, PersistentMXEAccount>>,
-    #[account(mut)]
-    /// CHECK: comp_def_account, checked by arcium program.
-    /// Can't check it here as it's not initialized yet.
-    pub comp_def_account: UncheckedAccount<'info>,
-    pub arcium_program: Program<'info, Arcium>,
-    pub system_program: Program<'info, System>,
-}
+    /// Withdraw callback for mapping accounts on circuit version 2, whose output is laid out
+    /// as version 1's
+    #[arcium_callback]
+    pub fn withdraw_v2_callback(ctx: Context<WithdrawCallback>, output: Vec<u8>) -> Result<()> {
+        withdraw_callback(ctx, output)
+    }
 
-/// Accounts for initializing the transfer computation definition
-#[init_computation_definition_accounts("transfer", payer)]
-#[derive(Accounts)]
-pub struct InitTransferCompDef<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [MXE_PDA_SEED, ID_CONST.to_bytes().as_ref()],
-        seeds::program = ARCIUM_PROG_ID,
-        bump = mxe_account.bump
-    )]
-    pub mxe_account: Box<Account<'info, PersistentMXEAccount>>,
-    #[account(mut)]
-    /// CHECK: comp_def_account, checked by arcium program.
-    /// Can't check it here as it's not initialized yet.
-    pub comp_def_account: UncheckedAccount<'info>,
+    /// Initializes the version 2 withdraw-all computation definition.
+    pub fn init_withdraw_all_v2_comp_def(ctx: Context<InitWithdrawAllV2CompDef>) -> Result<()> {
+        init_comp_def(
+            ctx.accounts,
+            true,
+            off_chain_source("withdraw_all_v2", WITHDRAW_ALL_V2_CIRCUIT_HASH),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Withdraw-all callback for mapping accounts on circuit version 2, whose output is laid
+    /// out as version 1's
+    #[arcium_callback]
+    pub fn withdraw_all_v2_callback(
+        ctx: Context<WithdrawAllCallback>,
+        output: Vec<u8>,
+    ) -> Result<()> {
+        withdraw_all_callback(ctx, output)
+    }
+
+    /// Initializes the version 2 batch processing computation definition.
+    pub fn init_process_batch_v2_comp_def(
+        ctx: Context<InitProcessBatchV2CompDef>,
+    ) -> Result<()> {
+        init_comp_def(
+            ctx.accounts,
+            true,
+            off_chain_source("process_batch_v2", PROCESS_BATCH_V2_CIRCUIT_HASH),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Batch callback for mapping accounts on circuit version 2, whose output is laid out as
+    /// version 1's
+    #[arcium_callback]
+    pub fn process_batch_v2_callback(
+        ctx: Context<ProcessBatchCallback>,
+        output: Vec<u8>,
+    ) -> Result<()> {
+        process_batch_callback(ctx, output)
+    }
+
+    /// Hands a stale operation to its mapping account's operation queue.
+    ///
+    /// Permissionless: used when the callback that found the result stale couldn't re-queue it
+    /// itself, because no queue was passed or the queue was full.
+    pub fn requeue_stale_op(ctx: Context<RequeueStaleOp>) -> Result<()> {
+        let op_record = &mut ctx.accounts.op_record;
+        let op_queue = &mut ctx.accounts.op_queue;
+        require!(op_record.status == OpStatus::Stale, ErrorCode::NotRequeueable);
+        let op = op_record.requeue.ok_or(ErrorCode::NotRequeueable)?;
+        require!(op_queue.has_room(), ErrorCode::QueueFull);
+
+        op_queue.pending.push(op);
+        op_record.status = OpStatus::Requeued;
+        emit_resolved(op_record);
+
+        Ok(())
+    }
+
+    /// Puts a batch whose callback never arrived back in front of its operation queue.
+    ///
+    /// Permissionless once `BATCH_TIMEOUT_SLOTS` have passed since the flush. The batch is
+    /// marked re-queued, so its callback is ignored if it does arrive later.
+    pub fn requeue_stuck_batch(ctx: Context<RequeueStuckBatch>) -> Result<()> {
+        let op_queue = &mut ctx.accounts.op_queue;
+        let op_record = &mut ctx.accounts.op_record;
+        require!(
+            !op_queue.in_flight.is_empty()
+                && op_record.status == OpStatus::Pending
+                && Clock::get()?.slot >= op_queue.in_flight_slot + BATCH_TIMEOUT_SLOTS,
+            ErrorCode::BatchNotStuck
+        );
+
+        msg!(
+            "Computation {} timed out, re-queuing batch",
+            op_record.computation_offset
+        );
+        op_record.status = OpStatus::Requeued;
+        emit_resolved(op_record);
+        op_queue.requeue_in_flight();
+
+        Ok(())
+    }
+
+    /// Closes a resolved operation record, returning its rent to whoever paid it.
+    ///
+    /// Records still pending, or stale and waiting to be re-queued, stay open. A callback
+    /// delivered after the record is closed fails, since it finds no record.
+    pub fn close_op_record(ctx: Context<CloseOpRecord>) -> Result<()> {
+        require!(ctx.accounts.op_record.is_resolved(), ErrorCode::RecordInUse);
+        Ok(())
+    }
+
+    /// Closes one of the sender's consumed-nonce markers, returning its rent to whoever paid it.
+    ///
+    /// Only the sender can close it: once closed, the nonce can be submitted again, and reusing
+    /// a nonce under the same key reveals the relation between the two plaintexts.
+    pub fn close_consumed_nonce(_ctx: Context<CloseConsumedNonce>, _nonce: u128) -> Result<()> {
+        Ok(())
+    }
+
+    /// Initializes the operation queue for a mapping account.
+    pub fn init_op_queue(ctx: Context<InitOpQueue>) -> Result<()> {
+        let op_queue = &mut ctx.accounts.op_queue;
+
+        op_queue.bump = ctx.bumps.op_queue;
+        op_queue.blackbox = ctx.accounts.blackbox.key();
+        op_queue.mapping_account = ctx.accounts.mapping_account.key();
+        op_queue.pending = Vec::new();
+        op_queue.in_flight = Vec::new();
+        op_queue.in_flight_nonce = 0;
+        op_queue.in_flight_offset = 0;
+        op_queue.in_flight_slot = 0;
+        op_queue.results = Vec::new();
+
+        emit!(OpQueueInitialized {
+            blackbox: op_queue.blackbox,
+            mapping_account: op_queue.mapping_account,
+        });
+
+        Ok(())
+    }
+
+    /// Initializes the batch processing computation definition.
+    pub fn init_process_batch_comp_def(ctx: Context<InitProcessBatchCompDef>) -> Result<()> {
+        init_comp_def(
+            ctx.accounts,
+            true,
+            off_chain_source("process_batch", PROCESS_BATCH_CIRCUIT_HASH),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Appends a deposit to the operation queue.
+    ///
+    /// Tokens move into the vault, and the fee into the treasury, immediately; the balance is
+    /// credited when the queue is flushed. The deposit's status is encrypted for the x25519 key
+    /// `client_key`.
+    pub fn enqueue_deposit(
+        ctx: Context<EnqueueDeposit>,
+        amount: u64,
+        client_key: [u8; 32],
+    ) -> Result<()> {
+        ctx.accounts
+            .blackbox
+            .require_not_paused(&ctx.accounts.config, OpKind::Deposit)?;
+        ctx.accounts
+            .blackbox
+            .require_deposit_approved(ctx.accounts.deposit_approver.as_ref().map(|a| a.key()))?;
+        ctx.accounts.blackbox.check_deposit(amount)?;
+        require!(ctx.accounts.op_queue.has_room(), ErrorCode::QueueFull);
+
+        let amount = take_deposit(
+            &ctx.accounts.blackbox,
+            &ctx.accounts.config,
+            DepositTransfer {
+                source: ctx.accounts.user_token_account.to_account_info(),
+                vault: ctx.accounts.vault.to_account_info(),
+                treasury: ctx.accounts.treasury.to_account_info(),
+                user: ctx.accounts.user.to_account_info(),
+                token_program: ctx.accounts.token_program.to_account_info(),
+            },
+            amount,
+        )?;
+
+        let op = QueuedOp {
+            kind: OpKind::Deposit,
+            owner: ctx.accounts.user.key(),
+            client_key,
+            enc_transfer: [[0; 32]; 3],
+            amount,
+            nonce: 0,
+            destination: ctx.accounts.user_token_account.key(),
+            epoch: 0,
+        };
+        emit_enqueued(&ctx.accounts.op_queue, &op);
+        ctx.accounts.op_queue.pending.push(op);
+
+        Ok(())
+    }
+
+    /// Appends an internal transfer to the operation queue.
+    ///
+    /// As with `transfer`, `enc_transfer` is the sender's encrypted `TransferArgs`, decrypted
+    /// with the x25519 key `client_key`, and `nonce` is recorded as consumed. The transfer's
+    /// status is encrypted for `client_key` too.
+    pub fn enqueue_transfer(
+        ctx: Context<EnqueueTransfer>,
+        enc_transfer: [[u8; 32]; 3],
+        client_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        ctx.accounts
+            .blackbox
+            .require_not_paused(&ctx.accounts.config, OpKind::Transfer)?;
+        ctx.accounts.consumed_nonce.set_inner(ConsumedNonce {
+            bump: ctx.bumps.consumed_nonce,
+            payer: ctx.accounts.payer.key(),
+        });
+
+        let op_queue = &mut ctx.accounts.op_queue;
+        require!(op_queue.has_room(), ErrorCode::QueueFull);
+
+        let op = QueuedOp {
+            kind: OpKind::Transfer,
+            owner: ctx.accounts.user.key(),
+            client_key,
+            enc_transfer,
+            amount: 0,
+            nonce,
+            destination: Pubkey::default(),
+            epoch: 0,
+        };
+        emit_enqueued(op_queue, &op);
+        op_queue.pending.push(op);
+
+        Ok(())
+    }
+
+    /// Appends a withdrawal to the operation queue.
+    ///
+    /// `destination` is paid out of the vault by the batch callback if the withdrawal succeeds.
+    /// The withdrawal's status is encrypted for the x25519 key `client_key`.
+    pub fn enqueue_withdraw(
+        ctx: Context<EnqueueWithdraw>,
+        amount: u64,
+        client_key: [u8; 32],
+    ) -> Result<()> {
+        ctx.accounts
+            .blackbox
+            .require_not_paused(&ctx.accounts.config, OpKind::Withdraw)?;
+        let epoch = Clock::get()?.epoch;
+        ctx.accounts.blackbox.record_withdrawal(amount, epoch)?;
+        let op_queue = &mut ctx.accounts.op_queue;
+        require!(op_queue.has_room(), ErrorCode::QueueFull);
+
+        let op = QueuedOp {
+            kind: OpKind::Withdraw,
+            owner: ctx.accounts.user.key(),
+            client_key,
+            enc_transfer: [[0; 32]; 3],
+            amount,
+            nonce: 0,
+            destination: ctx.accounts.destination.key(),
+            epoch,
+        };
+        emit_enqueued(op_queue, &op);
+        op_queue.pending.push(op);
+
+        Ok(())
+    }
+
+    /// Flushes the operation queue into a single `process_batch` computation.
+    ///
+    /// Permissionless: anyone can crank the queue. The nonce the mapping and the per-operation
+    /// statuses are re-encrypted under is derived from the computation, so a cranker can't pick
+    /// one that was already used.
+    pub fn flush_op_queue(ctx: Context<FlushOpQueue>, computation_offset: u64) -> Result<()> {
+        let op_queue = &mut ctx.accounts.op_queue;
+        require!(!op_queue.pending.is_empty(), ErrorCode::QueueEmpty);
+        require!(op_queue.in_flight.is_empty(), ErrorCode::BatchInFlight);
+        // A batch applies every operation in it, so it waits while any of them is paused
+        for op in op_queue.pending.iter().take(MAX_BATCH_SIZE) {
+            ctx.accounts
+                .blackbox
+                .require_not_paused(&ctx.accounts.config, op.kind)?;
+        }
+
+        ctx.accounts.op_record.set_inner(OpRecord {
+            bump: ctx.bumps.op_record,
+            blackbox: ctx.accounts.blackbox.key(),
+            mapping_account: ctx.accounts.mapping_account.key(),
+            computation_offset,
+            mapping_version: ctx.accounts.mapping_account.version,
+            owner: ctx.accounts.payer.key(),
+            payer: ctx.accounts.payer.key(),
+            amount: 0,
+            epoch: 0,
+            destination: Pubkey::default(),
+            status: OpStatus::Pending,
+            requeue: None,
+        });
+
+        let nonce = output_nonce(
+            &ctx.accounts.mapping_account.key(),
+            ctx.accounts.mapping_account.version,
+            computation_offset,
+        );
+        let count = op_queue.pending.len().min(MAX_BATCH_SIZE);
+        op_queue.in_flight = op_queue.pending.drain(..count).collect();
+        op_queue.in_flight_nonce = nonce;
+        op_queue.in_flight_offset = computation_offset;
+        op_queue.in_flight_slot = Clock::get()?.slot;
+
+        // Arguments - mapping, capacity, then every op slot (padded with empty ops), then the
+        // output nonce
+        let mut args = growing_mapping_args(&ctx.accounts.mapping_account);
+        for slot in 0..MAX_BATCH_SIZE {
+            match op_queue.in_flight.get(slot) {
+                Some(op) => args.extend([
+                    Argument::PlaintextU8(op.kind.circuit_kind()),
+                    Argument::PlaintextPubkey(op.owner),
+                    Argument::PlaintextPubkey(Pubkey::new_from_array(op.client_key)),
+                    Argument::CipheredPubkey(op.enc_transfer[0]),
+                    Argument::CipheredU64(op.enc_transfer[1]),
+                    Argument::CipheredBool(op.enc_transfer[2]),
+                    Argument::PlaintextU64(op.amount),
+                    Argument::PlaintextU128(op.nonce),
+                ]),
+                None => args.extend([
+                    Argument::PlaintextU8(OP_EMPTY),
+                    Argument::PlaintextPubkey(Pubkey::default()),
+                    Argument::PlaintextPubkey(Pubkey::default()),
+                    Argument::CipheredPubkey([0; 32]),
+                    Argument::CipheredU64([0; 32]),
+                    Argument::CipheredBool([0; 32]),
+                    Argument::PlaintextU64(0),
+                    Argument::PlaintextU128(0),
+                ]),
+            }
+        }
+        args.push(Argument::PlaintextU128(nonce));
+
+        // Withdrawal destinations and deposit sources are passed along so the callback can pay
+        // out withdrawals and refund deposits the mapping account has no room for
+        let mut callback_accounts = vec![
+            CallbackAccount::new(ctx.accounts.blackbox.key(), true),
+            CallbackAccount::new(ctx.accounts.config.key(), false),
+            CallbackAccount::new(ctx.accounts.mapping_account.key(), true),
+            CallbackAccount::new(op_queue.key(), true),
+            CallbackAccount::new(ctx.accounts.op_record.key(), true),
+            CallbackAccount::new(ctx.accounts.vault.key(), true),
+            CallbackAccount::new(ctx.accounts.blackbox.treasury, true),
+            CallbackAccount::new(ctx.accounts.token_program.key(), false),
+        ];
+        for op in op_queue.in_flight.iter().filter(|op| op.kind != OpKind::Transfer) {
+            callback_accounts.push(CallbackAccount::new(op.destination, true));
+        }
+
+        queue_computation(ctx.accounts, args, callback_accounts, Some(computation_offset))?;
+        emit_queued(&ctx.accounts.op_record, ComputationKind::ProcessBatch, None);
+        emit!(OpQueueFlushed {
+            blackbox: ctx.accounts.blackbox.key(),
+            mapping_account: ctx.accounts.mapping_account.key(),
+            computation_offset,
+            operations: count as u8,
+        });
+
+        Ok(())
+    }
+
+    /// Batch callback
+    ///
+    /// Stores the re-encrypted mapping and per-operation statuses, pays out every withdrawal
+    /// the circuit approved and refunds every deposit the mapping account had no room for. A
+    /// batch computed against an outdated mapping is put back in front of the queue instead.
+    #[arcium_callback]
+    pub fn process_batch_callback(
+        ctx: Context<ProcessBatchCallback>,
+        output: Vec<u8>,
+    ) -> Result<()> {
+        // Operations in a dropped batch stay in flight for good; `refund_queued_deposits`
+        // returns the deposits among them
+        if drop_in_emergency(&mut ctx.accounts.op_record, &ctx.accounts.blackbox) {
+            return Ok(());
+        }
+        let op_record = &mut ctx.accounts.op_record;
+        if op_record.status != OpStatus::Pending {
+            msg!("Computation {} already resolved", op_record.computation_offset);
+            return Ok(());
+        }
+
+        let op_queue = &mut ctx.accounts.op_queue;
+        if op_record.mapping_version != ctx.accounts.mapping_account.version {
+            // Put the whole batch back in front of the queue for the next flush
+            msg!("Computation {} is stale, re-queuing batch", op_record.computation_offset);
+            op_record.status = OpStatus::Requeued;
+            emit_resolved(op_record);
+            op_queue.requeue_in_flight();
+            return Ok(());
+        }
+        // Announced once the refunds are, which take their deposits out of the batch
+        op_record.status = OpStatus::Applied;
+
+        // Output - mapping ciphertexts, one encrypted status per slot, one flag per slot for
+        // tokens leaving the vault
+        require!(
+            output.len() == 64 + 32 * MAX_BATCH_SIZE + MAX_BATCH_SIZE,
+            ErrorCode::InvalidComputationOutput
+        );
+        let (mapping, rest) = output.split_at(64);
+        let (statuses, payouts) = rest.split_at(32 * MAX_BATCH_SIZE);
+
+        let nonce = op_queue.in_flight_nonce;
+        ctx.accounts.mapping_account.store_mapping(
+            [
+                mapping[..32].try_into().unwrap(),
+                mapping[32..].try_into().unwrap(),
+            ],
+            nonce,
+        );
+
+        let in_flight = std::mem::take(&mut op_queue.in_flight);
+        op_queue.results = Vec::with_capacity(in_flight.len());
+        for (slot, op) in in_flight.iter().enumerate() {
+            op_queue.results.push(OpResult {
+                owner: op.owner,
+                enc_status: statuses[slot * 32..(slot + 1) * 32].try_into().unwrap(),
+                nonce: nonce + 1 + slot as u128,
+            });
+
+            if op.kind == OpKind::Transfer {
+                continue;
+            }
+            if payouts[slot] == 0 {
+                if op.kind == OpKind::Withdraw {
+                    ctx.accounts.blackbox.release_withdrawal(op.amount, op.epoch);
+                }
+                continue;
+            }
+
+            let destination = ctx
+                .remaining_accounts
+                .iter()
+                .find(|account| account.key() == op.destination)
+                .ok_or(ErrorCode::InvalidDestination)?;
+
+            if op.kind == OpKind::Deposit {
+                emit!(QueuedDepositRefunded {
+                    blackbox: ctx.accounts.blackbox.key(),
+                    mapping_account: op_queue.mapping_account,
+                    owner: op.owner,
+                    destination: op.destination,
+                    amount: op.amount,
+                });
+                pay_out_of_vault(
+                    &ctx.accounts.blackbox,
+                    ctx.accounts.vault.to_account_info(),
+                    destination.clone(),
+                    ctx.accounts.token_program.to_account_info(),
+                    op.amount,
+                )?;
+                continue;
+            }
+            pay_withdrawal(
+                &ctx.accounts.blackbox,
+                &ctx.accounts.config,
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.treasury.to_account_info(),
+                destination.clone(),
+                ctx.accounts.token_program.to_account_info(),
+                op.amount,
+            )?;
+        }
+        emit_resolved(&ctx.accounts.op_record);
+
+        Ok(())
+    }
+
+    /// Initializes the withdraw-all computation definition.
+    pub fn init_withdraw_all_comp_def(ctx: Context<InitWithdrawAllCompDef>) -> Result<()> {
+        init_comp_def(
+            ctx.accounts,
+            true,
+            off_chain_source("withdraw_all", WITHDRAW_ALL_CIRCUIT_HASH),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Withdraws the caller's entire balance from the blackbox.
+    ///
+    /// The caller doesn't need to know their balance: the circuit debits it and reveals only the
+    /// payout amount, which the callback transfers from the vault to `destination`. While the
+    /// blackbox bounds withdrawals, the payout stops at the withdrawal maximum or what's left of
+    /// the epoch's outflow cap, whichever is lower, and the rest of the balance stays.
+    pub fn withdraw_all(ctx: Context<WithdrawAll>, computation_offset: u64) -> Result<()> {
+        ctx.accounts
+            .blackbox
+            .require_not_paused(&ctx.accounts.config, OpKind::Withdraw)?;
+        ctx.accounts.blackbox.require_direct()?;
+        let epoch = Clock::get()?.epoch;
+        let max_payout = ctx.accounts.blackbox.reserve_withdraw_all(epoch)?;
+        ctx.accounts.op_record.set_inner(OpRecord {
+            bump: ctx.bumps.op_record,
+            blackbox: ctx.accounts.blackbox.key(),
+            mapping_account: ctx.accounts.mapping_account.key(),
+            computation_offset,
+            mapping_version: ctx.accounts.mapping_account.version,
+            owner: ctx.accounts.user.key(),
+            payer: ctx.accounts.payer.key(),
+            amount: max_payout,
+            epoch,
+            destination: ctx.accounts.destination.key(),
+            status: OpStatus::Pending,
+            requeue: None,
+        });
+
+        // Arguments - mapping, user pubkey, payout bound, output nonce
+        let mut args = mapping_args(&ctx.accounts.mapping_account);
+        args.push(Argument::PlaintextPubkey(ctx.accounts.user.key()));
+        args.push(Argument::PlaintextU64(max_payout));
+        args.push(Argument::PlaintextU128(output_nonce(
+            &ctx.accounts.mapping_account.key(),
+            ctx.accounts.mapping_account.version,
+            computation_offset,
+        )));
+
+        // Queue computation for withdraw-all operation
+        queue_computation(
+            ctx.accounts,
+            args,
+            vec![
+                CallbackAccount::new(ctx.accounts.blackbox.key(), true),
+                CallbackAccount::new(ctx.accounts.config.key(), false),
+                CallbackAccount::new(ctx.accounts.mapping_account.key(), true),
+                CallbackAccount::new(ctx.accounts.op_record.key(), true),
+                CallbackAccount::new(ctx.accounts.vault.key(), true),
+                CallbackAccount::new(ctx.accounts.blackbox.treasury, true),
+                CallbackAccount::new(ctx.accounts.destination.key(), true),
+                CallbackAccount::new(ctx.accounts.token_program.key(), false),
+            ],
+            Some(computation_offset),
+        )?;
+        emit_queued(&ctx.accounts.op_record, ComputationKind::WithdrawAll, None);
+
+        Ok(())
+    }
+
+    /// Withdraw-all callback
+    ///
+    /// Resolves the record as `Failed`, leaving the mapping untouched, if the caller has no
+    /// entry, so the record can still be closed and its nonce retried. The outflow reserved
+    /// when it was queued is replaced with the payout, or released if nothing is paid.
+    #[arcium_callback]
+    pub fn withdraw_all_callback(ctx: Context<WithdrawAllCallback>, output: Vec<u8>) -> Result<()> {
+        // Output - mapping nonce, mapping ciphertexts, payout amount, found flag
+        let (nonce, mapping, rest) = parse_mapping_output(&output)?;
+        require!(rest.len() == 8 + 1, ErrorCode::InvalidComputationOutput);
+        let payout = u64::from_le_bytes(rest[..8].try_into().unwrap());
+        if ctx.accounts.op_record.status != OpStatus::Pending {
+            msg!("Computation {} already resolved", ctx.accounts.op_record.computation_offset);
+            return Ok(());
+        }
+        let (max_payout, epoch) = (ctx.accounts.op_record.amount, ctx.accounts.op_record.epoch);
+
+        let applied = if drop_in_emergency(&mut ctx.accounts.op_record, &ctx.accounts.blackbox) {
+            false
+        } else if rest[8] == 0 {
+            let op_record = &mut ctx.accounts.op_record;
+            msg!("Computation {} found no entry for its owner", op_record.computation_offset);
+            op_record.status = OpStatus::Failed;
+            emit_resolved(op_record);
+            false
+        } else {
+            accept_result(&mut ctx.accounts.op_record, &ctx.accounts.mapping_account, None)
+        };
+        if !applied {
+            ctx.accounts.blackbox.settle_withdraw_all(max_payout, 0, epoch);
+            return Ok(());
+        }
+
+        ctx.accounts.blackbox.settle_withdraw_all(max_payout, payout, epoch);
+        ctx.accounts.mapping_account.store_mapping(mapping, nonce);
+
+        if payout > 0 {
+            pay_withdrawal(
+                &ctx.accounts.blackbox,
+                &ctx.accounts.config,
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.treasury.to_account_info(),
+                ctx.accounts.destination.to_account_info(),
+                ctx.accounts.token_program.to_account_info(),
+                payout,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Initializes the reveal-balance computation definition.
+    pub fn init_reveal_balance_comp_def(ctx: Context<InitRevealBalanceCompDef>) -> Result<()> {
+        init_comp_def(
+            ctx.accounts,
+            true,
+            off_chain_source("reveal_balance", REVEAL_BALANCE_CIRCUIT_HASH),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Reveals the caller's balance in `mapping_account` to the holder of `client_key`.
+    ///
+    /// The mapping is encrypted for the MXE alone, so users can't read their balance from it.
+    /// The circuit looks up the signer's entry and encrypts its balance for the x25519 key
+    /// `client_key` under `nonce`; the callback stores the ciphertext in `balance_reveal`.
+    /// `nonce` is recorded as consumed, so no two reveals encrypt under the same key and nonce.
+    ///
+    /// Stopped along with withdrawals by a pause, and by emergency mode.
+    pub fn reveal_balance(
+        ctx: Context<RevealBalance>,
+        computation_offset: u64,
+        client_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        ctx.accounts
+            .blackbox
+            .require_not_paused(&ctx.accounts.config, OpKind::Withdraw)?;
+        ctx.accounts.consumed_nonce.set_inner(ConsumedNonce {
+            bump: ctx.bumps.consumed_nonce,
+            payer: ctx.accounts.payer.key(),
+        });
+        ctx.accounts.balance_reveal.set_inner(BalanceReveal {
+            bump: ctx.bumps.balance_reveal,
+            blackbox: ctx.accounts.blackbox.key(),
+            mapping_account: ctx.accounts.mapping_account.key(),
+            computation_offset,
+            mapping_version: ctx.accounts.mapping_account.version,
+            owner: ctx.accounts.owner.key(),
+            client_key,
+            nonce,
+            enc_balance: None,
+        });
+
+        // Arguments - mapping, owner pubkey, client key, output nonce
+        let mut args = mapping_args(&ctx.accounts.mapping_account);
+        args.push(Argument::PlaintextPubkey(ctx.accounts.owner.key()));
+        args.push(Argument::PlaintextPubkey(Pubkey::new_from_array(client_key)));
+        args.push(Argument::PlaintextU128(nonce));
+
+        // Queue computation for reveal-balance operation
+        queue_computation(
+            ctx.accounts,
+            args,
+            vec![CallbackAccount::new(ctx.accounts.balance_reveal.key(), true)],
+            Some(computation_offset),
+        )?;
+        emit!(ComputationQueued {
+            blackbox: ctx.accounts.blackbox.key(),
+            mapping_account: ctx.accounts.mapping_account.key(),
+            computation_offset,
+            kind: ComputationKind::RevealBalance,
+            owner: ctx.accounts.owner.key(),
+            amount: None,
+        });
+
+        Ok(())
+    }
+
+    /// Reveal-balance callback
+    ///
+    /// The mapping is only read, so the result stays valid for the version it was read from.
+    #[arcium_callback]
+    pub fn reveal_balance_callback(
+        ctx: Context<RevealBalanceCallback>,
+        output: Vec<u8>,
+    ) -> Result<()> {
+        // Output - balance encrypted for the client
+        let enc_balance: [u8; 32] = output
+            .as_slice()
+            .try_into()
+            .map_err(|_| ErrorCode::InvalidComputationOutput)?;
+
+        let balance_reveal = &mut ctx.accounts.balance_reveal;
+        if balance_reveal.enc_balance.is_some() {
+            msg!("Computation {} already resolved", balance_reveal.computation_offset);
+            return Ok(());
+        }
+        balance_reveal.enc_balance = Some(enc_balance);
+        emit!(ComputationResolved {
+            blackbox: balance_reveal.blackbox,
+            mapping_account: balance_reveal.mapping_account,
+            computation_offset: balance_reveal.computation_offset,
+            status: OpStatus::Applied,
+        });
+
+        Ok(())
+    }
+
+    /// Initializes the prove-solvency computation definition.
+    pub fn init_prove_solvency_comp_def(ctx: Context<InitProveSolvencyCompDef>) -> Result<()> {
+        init_comp_def(
+            ctx.accounts,
+            true,
+            off_chain_source("prove_solvency", PROVE_SOLVENCY_CIRCUIT_HASH),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Proves that the vault holds at least the sum of every balance the blackbox credits.
+    ///
+    /// Permissionless. The proof covers every mapping account the blackbox has now, summed by
+    /// the MXE a range of at most `MAX_SOLVENCY_SHARDS` at a time; this queues the first
+    /// range, whose mapping accounts follow as remaining accounts in index order, and
+    /// `continue_solvency_proof` queues each next one once the previous has landed. The
+    /// circuit carries the total between ranges encrypted for the MXE alone, and the last
+    /// range reveals only whether the vault's token amount, read now, covers it; its callback
+    /// records the answer in `attestation`.
+    pub fn prove_solvency(ctx: Context<ProveSolvency>, computation_offset: u64) -> Result<()> {
+        let shards = ctx.accounts.blackbox.mapping_account_count;
+        let vault_amount = ctx.accounts.vault.amount;
+        ctx.accounts.attestation.set_inner(SolvencyAttestation {
+            bump: ctx.bumps.attestation,
+            blackbox: ctx.accounts.blackbox.key(),
+            computation_offset,
+            vault_amount,
+            shards,
+            summed_shards: 0,
+            queued_range: None,
+            enc_total: [0; 32],
+            total_nonce: 0,
+            requested_slot: Clock::get()?.slot,
+            solvent: None,
+            attested_slot: 0,
+        });
+
+        let args = queue_solvency_range(
+            &ctx.accounts.blackbox,
+            &mut ctx.accounts.attestation,
+            ctx.remaining_accounts,
+            computation_offset,
+        )?;
+        queue_computation(
+            ctx.accounts,
+            args,
+            vec![CallbackAccount::new(ctx.accounts.attestation.key(), true)],
+            Some(computation_offset),
+        )?;
+        emit!(SolvencyRequested {
+            blackbox: ctx.accounts.blackbox.key(),
+            attestation: ctx.accounts.attestation.key(),
+            computation_offset,
+            vault_amount,
+            shards,
+        });
+
+        Ok(())
+    }
+
+    /// Queues the next range of a solvency proof once the previous one has landed.
+    ///
+    /// Permissionless. The range's mapping accounts follow as remaining accounts in index
+    /// order, starting at the first shard the proof hasn't summed yet. `computation_offset`
+    /// identifies this range's computation; the attestation stays keyed by the offset the
+    /// proof was requested with.
+    pub fn continue_solvency_proof(
+        ctx: Context<ContinueSolvencyProof>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        let args = queue_solvency_range(
+            &ctx.accounts.blackbox,
+            &mut ctx.accounts.attestation,
+            ctx.remaining_accounts,
+            computation_offset,
+        )?;
+        queue_computation(
+            ctx.accounts,
+            args,
+            vec![CallbackAccount::new(ctx.accounts.attestation.key(), true)],
+            Some(computation_offset),
+        )?;
+
+        Ok(())
+    }
+
+    /// Prove-solvency callback, for every range of the proof
+    #[arcium_callback]
+    pub fn prove_solvency_callback(
+        ctx: Context<ProveSolvencyCallback>,
+        output: Vec<u8>,
+    ) -> Result<()> {
+        // Output - nonce and encrypted total so far, whether the vault covers the total
+        require!(output.len() == 16 + 32 + 1, ErrorCode::InvalidComputationOutput);
+        let total_nonce = u128::from_le_bytes(output[..16].try_into().unwrap());
+        let enc_total: [u8; 32] = output[16..48].try_into().unwrap();
+        let solvent = output[48] != 0;
+
+        let attestation = &mut ctx.accounts.attestation;
+        let Some(range) = attestation.queued_range.take() else {
+            msg!("Computation {} already resolved", attestation.computation_offset);
+            return Ok(());
+        };
+        attestation.summed_shards += range;
+        attestation.enc_total = enc_total;
+        attestation.total_nonce = total_nonce;
+        // Only the last range's answer means anything
+        if attestation.summed_shards < attestation.shards {
+            return Ok(());
+        }
+
+        attestation.solvent = Some(solvent);
+        attestation.attested_slot = Clock::get()?.slot;
+        emit!(SolvencyAttested {
+            blackbox: attestation.blackbox,
+            attestation: attestation.key(),
+            computation_offset: attestation.computation_offset,
+            solvent,
+            attested_slot: attestation.attested_slot,
+        });
+
+        Ok(())
+    }
+
+    /// Initializes the export-balances computation definition.
+    pub fn init_export_balances_comp_def(ctx: Context<InitExportBalancesCompDef>) -> Result<()> {
+        init_comp_def(
+            ctx.accounts,
+            true,
+            off_chain_source("export_balances", EXPORT_BALANCES_CIRCUIT_HASH),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Exports page `page` of a mapping account's balances in emergency mode.
+    ///
+    /// Permissionless. The circuit encrypts each balance on the page, with a random salt, for
+    /// the x25519 form of its owner's key, and commits to the owner, balance and salt; the
+    /// callback stores both in `export`, after which owners claim without the MXE. The
+    /// circuit reveals whether entries remain past the page.
+    pub fn export_balances(
+        ctx: Context<ExportBalances>,
+        computation_offset: u64,
+        page: u8,
+    ) -> Result<()> {
+        require!(ctx.accounts.blackbox.emergency, ErrorCode::NotInEmergency);
+        let nonce = output_nonce(
+            &ctx.accounts.mapping_account.key(),
+            ctx.accounts.mapping_account.version,
+            computation_offset,
+        );
+        ctx.accounts.export.set_inner(EmergencyExport {
+            bump: ctx.bumps.export,
+            blackbox: ctx.accounts.blackbox.key(),
+            mapping_account: ctx.accounts.mapping_account.key(),
+            mapping_version: ctx.accounts.mapping_account.version,
+            page,
+            computation_offset,
+            nonce,
+            exported: false,
+            entries: Vec::new(),
+            more: false,
+        });
+
+        // Arguments - mapping, page, nonce of the first claim
+        let mut args = mapping_args(&ctx.accounts.mapping_account);
+        args.push(Argument::PlaintextU8(page));
+        args.push(Argument::PlaintextU128(nonce));
+
+        queue_computation(
+            ctx.accounts,
+            args,
+            vec![
+                CallbackAccount::new(ctx.accounts.mapping_account.key(), false),
+                CallbackAccount::new(ctx.accounts.export.key(), true),
+            ],
+            Some(computation_offset),
+        )?;
+
+        Ok(())
+    }
+
+    /// Export-balances callback
+    ///
+    /// An export computed against an outdated mapping is dropped; the page is exported again
+    /// at the mapping's new version.
+    #[arcium_callback]
+    pub fn export_balances_callback(
+        ctx: Context<ExportBalancesCallback>,
+        output: Vec<u8>,
+    ) -> Result<()> {
+        let export = &mut ctx.accounts.export;
+        if export.exported {
+            msg!("Computation {} already resolved", export.computation_offset);
+            return Ok(());
+        }
+        if export.mapping_version != ctx.accounts.mapping_account.version {
+            msg!("Computation {} is stale", export.computation_offset);
+            return Ok(());
+        }
+
+        // Output - two claim ciphertexts per slot, one commitment per slot, more flag. Slots
+        // past the end of the mapping are zeroed.
+        require!(
+            output.len() == 64 * MAX_EXPORT_ENTRIES + 32 * MAX_EXPORT_ENTRIES + 1,
+            ErrorCode::InvalidComputationOutput
+        );
+        let (claims, rest) = output.split_at(64 * MAX_EXPORT_ENTRIES);
+        let (commitments, more) = rest.split_at(32 * MAX_EXPORT_ENTRIES);
+
+        for slot in 0..MAX_EXPORT_ENTRIES {
+            let commitment: [u8; 32] = commitments[slot * 32..(slot + 1) * 32].try_into().unwrap();
+            if commitment == [0; 32] {
+                continue;
+            }
+            let claim = &claims[slot * 64..(slot + 1) * 64];
+            export.entries.push(ExportedBalance {
+                enc_claim: [claim[..32].try_into().unwrap(), claim[32..].try_into().unwrap()],
+                commitment,
+            });
+        }
+        export.more = more[0] != 0;
+        export.exported = true;
+
+        emit!(BalancesExported {
+            blackbox: export.blackbox,
+            mapping_account: export.mapping_account,
+            export: export.key(),
+            page: export.page,
+            entries: export.entries.len() as u8,
+            more: export.more,
+        });
+
+        Ok(())
+    }
+
+    /// Pays an owner their exported balance straight out of the vault, without the MXE.
+    ///
+    /// The owner decrypts their entry of `export` and submits its balance and salt, which must
+    /// open the entry's commitment. Each owner claims once per mapping account, without fees
+    /// or limits.
+    pub fn claim_emergency(
+        ctx: Context<ClaimEmergency>,
+        entry: u8,
+        balance: u64,
+        salt: u128,
+    ) -> Result<()> {
+        let export = &ctx.accounts.export;
+        require!(ctx.accounts.blackbox.emergency, ErrorCode::NotInEmergency);
+        require!(
+            export.exported && export.mapping_version == ctx.accounts.mapping_account.version,
+            ErrorCode::ExportOutdated
+        );
+        let exported = export
+            .entries
+            .get(entry as usize)
+            .ok_or(ErrorCode::InvalidClaim)?;
+
+        // Same preimage as `confidential_ixs::claim_commitment`
+        let owner = ctx.accounts.owner.key();
+        let commitment = anchor_lang::solana_program::hash::hashv(&[
+            owner.as_ref(),
+            &balance.to_le_bytes(),
+            &salt.to_le_bytes(),
+        ]);
+        require!(
+            commitment.to_bytes() == exported.commitment,
+            ErrorCode::InvalidClaim
+        );
+        ctx.accounts.claimed.bump = ctx.bumps.claimed;
+
+        emit!(EmergencyClaimed {
+            blackbox: ctx.accounts.blackbox.key(),
+            mapping_account: ctx.accounts.mapping_account.key(),
+            owner,
+            amount: balance,
+        });
+        if balance > 0 {
+            pay_out_of_vault(
+                &ctx.accounts.blackbox,
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.destination.to_account_info(),
+                ctx.accounts.token_program.to_account_info(),
+                balance,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Refunds a direct deposit emergency mode left uncredited to the token account it came
+    /// from.
+    ///
+    /// Permissionless once the blackbox is in emergency mode. The deposit must still be pending,
+    /// dropped, or stale without having been handed to an operation queue; its balance isn't in
+    /// any export, so only the credited amount is returned, not the deposit fee.
+    pub fn refund_deposit(ctx: Context<RefundDeposit>) -> Result<()> {
+        require!(ctx.accounts.blackbox.emergency, ErrorCode::NotInEmergency);
+        let op_record = &mut ctx.accounts.op_record;
+        require!(
+            op_record.requeue.map(|op| op.kind) == Some(OpKind::Deposit)
+                && matches!(
+                    op_record.status,
+                    OpStatus::Pending | OpStatus::Dropped | OpStatus::Stale
+                ),
+            ErrorCode::NotRefundable
+        );
+
+        op_record.status = OpStatus::Refunded;
+        emit_resolved(op_record);
+        pay_out_of_vault(
+            &ctx.accounts.blackbox,
+            ctx.accounts.vault.to_account_info(),
+            ctx.accounts.destination.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            op_record.amount,
+        )
+    }
+
+    /// Refunds the deposits waiting in an operation queue, in flight or not, once emergency
+    /// mode has stopped the queue.
+    ///
+    /// Permissionless once the blackbox is in emergency mode. Each deposit whose source token
+    /// account is passed in the remaining accounts is paid back and taken off the queue; the
+    /// others stay for another call.
+    pub fn refund_queued_deposits(ctx: Context<RefundQueuedDeposits>) -> Result<()> {
+        require!(ctx.accounts.blackbox.emergency, ErrorCode::NotInEmergency);
+        let op_queue: &mut OpQueueAccount = &mut ctx.accounts.op_queue;
+
+        let mut refunded = Vec::new();
+        for queued in [&mut op_queue.in_flight, &mut op_queue.pending] {
+            queued.retain(|op| {
+                let source = ctx
+                    .remaining_accounts
+                    .iter()
+                    .find(|account| account.key() == op.destination);
+                match source {
+                    Some(source) if op.kind == OpKind::Deposit => {
+                        refunded.push((*op, source.clone()));
+                        false
+                    }
+                    _ => true,
+                }
+            });
+        }
+
+        for (op, source) in refunded {
+            emit!(QueuedDepositRefunded {
+                blackbox: ctx.accounts.blackbox.key(),
+                mapping_account: op_queue.mapping_account,
+                owner: op.owner,
+                destination: op.destination,
+                amount: op.amount,
+            });
+            pay_out_of_vault(
+                &ctx.accounts.blackbox,
+                ctx.accounts.vault.to_account_info(),
+                source,
+                ctx.accounts.token_program.to_account_info(),
+                op.amount,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Initializes the computation definition migrating mappings from circuit version 1 to 2.
+    pub fn init_migrate_mapping_v2_comp_def(
+        ctx: Context<InitMigrateMappingV2CompDef>,
+    ) -> Result<()> {
+        init_comp_def(
+            ctx.accounts,
+            true,
+            off_chain_source("migrate_mapping_v2", MIGRATE_MAPPING_V2_CIRCUIT_HASH),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Re-encrypts a mapping account's mapping into the layout of the next circuit version,
+    /// once the blackbox has moved past the mapping account's version.
+    ///
+    /// The callback stores the migrated mapping and moves the mapping account to that version,
+    /// whose circuits its deposits, transfers and withdrawals run from then on. Computations
+    /// queued against the old mapping turn stale, and are re-queued like any other. A mapping
+    /// account more than one version behind is migrated once per version.
+    pub fn migrate_mapping(ctx: Context<MigrateMapping>, computation_offset: u64) -> Result<()> {
+        require!(!ctx.accounts.blackbox.emergency, ErrorCode::EmergencyMode);
+        ctx.accounts.op_record.set_inner(OpRecord {
+            bump: ctx.bumps.op_record,
+            blackbox: ctx.accounts.blackbox.key(),
+            mapping_account: ctx.accounts.mapping_account.key(),
+            computation_offset,
+            mapping_version: ctx.accounts.mapping_account.version,
+            owner: ctx.accounts.authority.key(),
+            payer: ctx.accounts.payer.key(),
+            amount: 0,
+            epoch: 0,
+            destination: Pubkey::default(),
+            status: OpStatus::Pending,
+            requeue: None,
+        });
+
+        // Arguments - mapping, output nonce
+        let mut args = mapping_args(&ctx.accounts.mapping_account);
+        args.push(Argument::PlaintextU128(output_nonce(
+            &ctx.accounts.mapping_account.key(),
+            ctx.accounts.mapping_account.version,
+            computation_offset,
+        )));
+
+        queue_computation(
+            ctx.accounts,
+            args,
+            vec![
+                CallbackAccount::new(ctx.accounts.blackbox.key(), false),
+                CallbackAccount::new(ctx.accounts.mapping_account.key(), true),
+                CallbackAccount::new(ctx.accounts.op_record.key(), true),
+            ],
+            Some(computation_offset),
+        )?;
+        emit_queued(&ctx.accounts.op_record, ComputationKind::MigrateMapping, None);
+
+        Ok(())
+    }
+
+    /// Callback of the migration from circuit version 1 to 2
+    #[arcium_callback]
+    pub fn migrate_mapping_v2_callback(
+        ctx: Context<MigrateMappingCallback>,
+        output: Vec<u8>,
+    ) -> Result<()> {
+        if drop_in_emergency(&mut ctx.accounts.op_record, &ctx.accounts.blackbox) {
+            return Ok(());
+        }
+        if !accept_result(&mut ctx.accounts.op_record, &ctx.accounts.mapping_account, None) {
+            return Ok(());
+        }
+
+        // Output - mapping nonce, mapping ciphertexts
+        let (nonce, mapping, _) = parse_mapping_output(&output)?;
+        let mapping_account = &mut ctx.accounts.mapping_account;
+        mapping_account.store_mapping(mapping, nonce);
+        mapping_account.circuit_version += 1;
+
+        emit!(MappingMigrated {
+            blackbox: ctx.accounts.blackbox.key(),
+            mapping_account: mapping_account.key(),
+            circuit_version: mapping_account.circuit_version,
+        });
+
+        Ok(())
+    }
+}
+
+/// Token accounts a deposit moves tokens between
+struct DepositTransfer<'info> {
+    source: AccountInfo<'info>,
+    vault: AccountInfo<'info>,
+    treasury: AccountInfo<'info>,
+    /// Owner of `source`
+    user: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+}
+
+/// Moves a deposit of `amount` into the vault, less the deposit fee, which goes to the
+/// treasury. Returns the amount left to credit.
+fn take_deposit<'info>(
+    blackbox: &Account<'info, BlackboxAccount>,
+    config: &ProgramConfig,
+    accounts: DepositTransfer<'info>,
+    amount: u64,
+) -> Result<u64> {
+    let fee = blackbox.fee(config, OpKind::Deposit, amount);
+    let credited = amount - fee;
+    let transfer = |to: AccountInfo<'info>, amount| {
+        let cpi_accounts = token::Transfer {
+            from: accounts.source.clone(),
+            to,
+            authority: accounts.user.clone(),
+        };
+        token::transfer(CpiContext::new(accounts.token_program.clone(), cpi_accounts), amount)
+    };
+
+    transfer(accounts.vault.clone(), credited)?;
+    emit!(VaultDeposited {
+        blackbox: blackbox.key(),
+        source: accounts.source.key(),
+        amount: credited,
+    });
+    if fee > 0 {
+        transfer(accounts.treasury.clone(), fee)?;
+        emit!(FeeCharged {
+            blackbox: blackbox.key(),
+            kind: OpKind::Deposit,
+            amount: fee,
+        });
+    }
+    Ok(credited)
+}
+
+/// Pays a withdrawal of `amount` out of the vault: the withdrawal fee to the treasury and the
+/// rest to `destination`.
+fn pay_withdrawal<'info>(
+    blackbox: &Account<'info, BlackboxAccount>,
+    config: &ProgramConfig,
+    vault: AccountInfo<'info>,
+    treasury: AccountInfo<'info>,
+    destination: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let fee = blackbox.fee(config, OpKind::Withdraw, amount);
+    if fee > 0 {
+        pay_out_of_vault(blackbox, vault.clone(), treasury, token_program.clone(), fee)?;
+        emit!(FeeCharged {
+            blackbox: blackbox.key(),
+            kind: OpKind::Withdraw,
+            amount: fee,
+        });
+    }
+    pay_out_of_vault(blackbox, vault, destination, token_program, amount - fee)
+}
+
+/// Transfers `amount` from the vault to `destination`.
+fn pay_out_of_vault<'info>(
+    blackbox: &Account<'info, BlackboxAccount>,
+    vault: AccountInfo<'info>,
+    destination: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    emit!(VaultPaidOut {
+        blackbox: blackbox.key(),
+        destination: destination.key(),
+        amount,
+    });
+    transfer_from_blackbox(blackbox, vault, destination, token_program, amount)
+}
+
+/// Transfers `amount` out of a token account the blackbox PDA owns, signed by the PDA.
+fn transfer_from_blackbox<'info>(
+    blackbox: &Account<'info, BlackboxAccount>,
+    from: AccountInfo<'info>,
+    to: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"blackbox",
+        blackbox.token_mint.as_ref(),
+        &[blackbox.bump],
+    ]];
+    let cpi_accounts = token::Transfer {
+        from,
+        to,
+        authority: blackbox.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program, cpi_accounts, signer_seeds);
+    token::transfer(cpi_ctx, amount)
+}
+
+/// Decides whether a callback may apply its result.
+///
+/// Results for an already resolved computation are ignored. Results computed against an
+/// outdated mapping are rejected as stale and, if an operation queue was passed, re-queued.
+fn accept_result(
+    op_record: &mut OpRecord,
+    mapping_account: &MappingAccount,
+    op_queue: Option<&mut Account<OpQueueAccount>>,
+) -> bool {
+    if op_record.status != OpStatus::Pending {
+        msg!("Computation {} already resolved", op_record.computation_offset);
+        return false;
+    }
+
+    if op_record.mapping_version != mapping_account.version {
+        msg!("Computation {} is stale", op_record.computation_offset);
+        op_record.status = OpStatus::Stale;
+        if let (Some(op), Some(op_queue)) = (op_record.requeue, op_queue) {
+            if op_queue.has_room() {
+                op_queue.pending.push(op);
+                op_record.status = OpStatus::Requeued;
+            }
+        }
+        emit_resolved(op_record);
+        return false;
+    }
+
+    op_record.status = OpStatus::Applied;
+    emit_resolved(op_record);
+    true
+}
+
+/// Drops a result landing after its blackbox entered emergency mode, which would move the
+/// mapping past the export balances may already have been claimed from. Deposits dropped this
+/// way are refunded instead.
+fn drop_in_emergency(op_record: &mut OpRecord, blackbox: &BlackboxAccount) -> bool {
+    if !blackbox.emergency || op_record.status != OpStatus::Pending {
+        return false;
+    }
+    msg!("Computation {} dropped in emergency mode", op_record.computation_offset);
+    op_record.status = OpStatus::Dropped;
+    emit_resolved(op_record);
+    true
+}
+
+/// Announces the computation `op_record` was just created for.
+fn emit_queued(op_record: &OpRecord, kind: ComputationKind, amount: Option<u64>) {
+    emit!(ComputationQueued {
+        blackbox: op_record.blackbox,
+        mapping_account: op_record.mapping_account,
+        computation_offset: op_record.computation_offset,
+        kind,
+        owner: op_record.owner,
+        amount,
+    });
+}
+
+/// Announces the status `op_record` just moved to.
+fn emit_resolved(op_record: &OpRecord) {
+    emit!(ComputationResolved {
+        blackbox: op_record.blackbox,
+        mapping_account: op_record.mapping_account,
+        computation_offset: op_record.computation_offset,
+        status: op_record.status,
+    });
+}
+
+/// Announces `op` being appended to `op_queue`.
+fn emit_enqueued(op_queue: &OpQueueAccount, op: &QueuedOp) {
+    emit!(OperationEnqueued {
+        blackbox: op_queue.blackbox,
+        mapping_account: op_queue.mapping_account,
+        kind: op.kind,
+        owner: op.owner,
+        amount: (op.kind != OpKind::Transfer).then_some(op.amount),
+    });
+}
+
+/// Callback account for an optional operation queue; the program ID stands in for `None`.
+fn op_queue_callback_account(op_queue: &Option<Account<OpQueueAccount>>) -> CallbackAccount {
+    match op_queue {
+        Some(op_queue) => CallbackAccount::new(op_queue.key(), true),
+        None => CallbackAccount::new(crate::ID, false),
+    }
+}
+
+/// Splits a circuit output into the re-encrypted mapping's nonce and ciphertexts, followed by
+/// whatever the circuit returned after the mapping.
+fn parse_mapping_output(output: &[u8]) -> Result<(u128, [[u8; 32]; 2], &[u8])> {
+    require!(output.len() >= 16 + 64, ErrorCode::InvalidComputationOutput);
+    let nonce = u128::from_le_bytes(output[..16].try_into().unwrap());
+    let mapping = [
+        output[16..48].try_into().unwrap(),
+        output[48..80].try_into().unwrap(),
+    ];
+    Ok((nonce, mapping, &output[80..]))
+}
+
+/// Offset or hash, among every version of a circuit, of the one for `circuit_version`.
+fn versioned<T: Copy>(versions: &[T], circuit_version: u8) -> T {
+    versions[circuit_version as usize - 1]
+}
+
+/// Off-chain source of the compiled `circuit`, committing to its pinned `hash`, which every
+/// comp def is registered with so `runs_circuit` accepts it.
+fn off_chain_source(circuit: &str, hash: [u8; 32]) -> Option<CircuitSource> {
+    Some(CircuitSource::OffChain(OffChainCircuitSource {
+        source: format!("{circuit}.arcis"),
+        hash,
+    }))
+}
+
+/// Whether `comp_def` runs the compiled circuit pinned to `hash`. Only off-chain circuit
+/// sources commit to a hash the MXE checks the circuit against, so any other source fails.
+fn runs_circuit(comp_def: &ComputationDefinitionAccount, hash: [u8; 32]) -> bool {
+    matches!(&comp_def.circuit_source, CircuitSource::OffChain(source) if source.hash == hash)
+}
+
+/// Nonce a computation's outputs are encrypted under, derived from the mapping account, its
+/// version and the computation offset. Offsets are never reused, so neither are nonces.
+fn output_nonce(mapping_account: &Pubkey, version: u64, computation_offset: u64) -> u128 {
+    let hash = anchor_lang::solana_program::hash::hashv(&[
+        b"output_nonce",
+        mapping_account.as_ref(),
+        &version.to_le_bytes(),
+        &computation_offset.to_le_bytes(),
+    ]);
+    u128::from_le_bytes(hash.to_bytes()[..16].try_into().unwrap())
+}
+
+/// Marks the next range of `attestation`'s shards queued and returns the arguments summing it
+/// into the proof's total. The range's mapping accounts are `shards`, in index order.
+fn queue_solvency_range(
+    blackbox: &Account<BlackboxAccount>,
+    attestation: &mut Account<SolvencyAttestation>,
+    shards: &[AccountInfo],
+    computation_offset: u64,
+) -> Result<Vec<Argument>> {
+    require!(
+        attestation.solvent.is_none() && attestation.queued_range.is_none(),
+        ErrorCode::SolvencyRangeUnavailable
+    );
+    let first = attestation.summed_shards;
+    let range = (attestation.shards - first).min(MAX_SOLVENCY_SHARDS as u8);
+    require!(shards.len() == range as usize, ErrorCode::InvalidMappingShards);
+
+    // Arguments - every shard slot (padded with empty mappings), shard count, total so far
+    // and its nonce, whether the range is the first and the last, vault amount, nonce
+    let mut args = Vec::new();
+    for (offset, account) in shards.iter().enumerate() {
+        require_keys_eq!(*account.owner, crate::ID, ErrorCode::InvalidMappingShards);
+        let mapping_account = MappingAccount::try_deserialize(&mut &account.try_borrow_data()?[..])?;
+        require!(
+            mapping_account.token_mint == blackbox.token_mint
+                && mapping_account.index as usize == first as usize + offset,
+            ErrorCode::InvalidMappingShards
+        );
+        // One computation sums the whole range, so every shard must be on a version its
+        // circuit reads
+        require!(
+            versioned(&PROVE_SOLVENCY_VERSIONS, mapping_account.circuit_version)
+                == COMP_DEF_OFFSET_PROVE_SOLVENCY,
+            ErrorCode::UnsupportedCircuitVersion
+        );
+        args.extend(mapping_args(&mapping_account));
+    }
+    for _ in range as usize..MAX_SOLVENCY_SHARDS {
+        args.extend([
+            Argument::CipheredPubkey([0; 32]),
+            Argument::CipheredU64([0; 32]),
+            Argument::PlaintextU128(0),
+        ]);
+    }
+    args.push(Argument::PlaintextU8(range));
+    args.push(Argument::CipheredU128(attestation.enc_total));
+    args.push(Argument::PlaintextU128(attestation.total_nonce));
+    args.push(Argument::PlaintextBool(first == 0));
+    args.push(Argument::PlaintextBool(first + range == attestation.shards));
+    args.push(Argument::PlaintextU64(attestation.vault_amount));
+    args.push(Argument::PlaintextU128(output_nonce(
+        &attestation.key(),
+        first.into(),
+        computation_offset,
+    )));
+
+    attestation.queued_range = Some(range);
+    Ok(args)
+}
+
+/// `mapping_args`, followed by the account's capacity, for the circuits that add entries.
+fn growing_mapping_args(mapping_account: &MappingAccount) -> Vec<Argument> {
+    let mut args = mapping_args(mapping_account);
+    args.push(Argument::PlaintextU16(mapping_account.capacity));
+    args
+}
+
+/// Arguments passing a mapping account's encrypted `Mapping` and its nonce to a circuit.
+fn mapping_args(mapping_account: &MappingAccount) -> Vec<Argument> {
+    vec![
+        Argument::CipheredPubkey(
+            mapping_account.encrypted_pubkeys.first().copied().unwrap_or_default(),
+        ),
+        Argument::CipheredU64(
+            mapping_account.encrypted_balances.first().copied().unwrap_or_default(),
+        ),
+        Argument::PlaintextU128(mapping_account.nonce),
+    ]
+}
+
+
+
+/// Accounts for creating the program config
+#[derive(Accounts)]
+pub struct InitProgramConfig<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ProgramConfig::INIT_SPACE,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// The program's data account, naming its upgrade authority
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        seeds::program = bpf_loader_upgradeable::ID,
+        bump,
+        constraint = program_data.upgrade_authority_address == Some(authority.key())
+            @ ErrorCode::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for pausing operations
+#[derive(Accounts)]
+pub struct Pause<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// Blackbox to pause operations on; they're paused on every blackbox if omitted
+    #[account(
+        mut,
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump = blackbox.bump
+    )]
+    pub blackbox: Option<Account<'info, BlackboxAccount>>,
+
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for proposing a new authority
+#[derive(Accounts)]
+pub struct ProposeAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for accepting the authority
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.pending_authority == Some(new_authority.key())
+            @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    pub new_authority: Signer<'info>,
+}
+
+/// Accounts for queuing a parameter change
+#[derive(Accounts)]
+pub struct QueueChange<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// Blackbox the change applies to; it applies to the program config if omitted
+    #[account(
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump = blackbox.bump
+    )]
+    pub blackbox: Option<Account<'info, BlackboxAccount>>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + PendingChange::INIT_SPACE,
+        seeds = [b"change", config.next_change_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub change: Account<'info, PendingChange>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for executing a queued parameter change
+#[derive(Accounts)]
+pub struct ExecuteChange<'info> {
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"change", change.id.to_le_bytes().as_ref()],
+        bump = change.bump,
+        has_one = payer,
+        close = payer
+    )]
+    pub change: Account<'info, PendingChange>,
+
+    /// The change's blackbox, if it has one
+    #[account(
+        mut,
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump = blackbox.bump
+    )]
+    pub blackbox: Option<Account<'info, BlackboxAccount>>,
+
+    /// CHECK: receives the change's rent; checked by `has_one`
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+}
+
+/// Accounts for cancelling a queued parameter change
+#[derive(Accounts)]
+pub struct CancelChange<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"change", change.id.to_le_bytes().as_ref()],
+        bump = change.bump,
+        has_one = payer,
+        close = payer
+    )]
+    pub change: Account<'info, PendingChange>,
+
+    /// CHECK: receives the change's rent; checked by `has_one`
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for collecting a blackbox's fees
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump = blackbox.bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(
+        mut,
+        constraint = treasury.key() == blackbox.treasury @ ErrorCode::InvalidTreasury
+    )]
+    pub treasury: Account<'info, TokenAccount>,
+
+    /// Token account receiving the fees
+    #[account(
+        mut,
+        constraint = destination.mint == blackbox.token_mint @ ErrorCode::InvalidDestination
+    )]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts for initializing a blackbox for a specific token
+#[derive(Accounts)]
+#[instruction(params: InitBlackboxParams)]
+pub struct InitBlackbox<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    /// The token mint for which this blackbox is created
+    pub token_mint: Account<'info, Mint>,
+
+    /// The blackbox account for this token
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + BlackboxAccount::INIT_SPACE,
+        seeds = [b"blackbox", token_mint.key().as_ref()],
+        bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    /// The vault (account that holds tokens) for this blackbox 
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"vault".as_ref(), blackbox.key().as_ref()],
+        bump,
+        token::authority = blackbox,
+        token::mint = token_mint,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    /// The treasury fees are paid into
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"treasury".as_ref(), blackbox.key().as_ref()],
+        bump,
+        token::authority = blackbox,
+        token::mint = token_mint,
+    )]
+    pub treasury: Account<'info, TokenAccount>,
+
+    /// The Arcium cluster the blackbox's computations go to, which has to exist
+    #[account(
+        seeds = [CLUSTER_PDA_SEED, &params.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub cluster: Account<'info, Cluster>,
+    
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts for initializing the deposit computation definition
+#[init_computation_definition_accounts("deposit", payer)]
+#[derive(Accounts)]
+pub struct InitDepositCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED, ID_CONST.to_bytes().as_ref()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump = mxe_account.bump
+    )]
+    pub mxe_account: Box<Account<'info, PersistentMXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for initializing the transfer computation definition
+#[init_computation_definition_accounts("transfer", payer)]
+#[derive(Accounts)]
+pub struct InitTransferCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED, ID_CONST.to_bytes().as_ref()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump = mxe_account.bump
+    )]
+    pub mxe_account: Box<Account<'info, PersistentMXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for initializing the withdraw computation definition
+#[init_computation_definition_accounts("withdraw", payer)]
+#[derive(Accounts)]
+pub struct InitWithdrawCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED, ID_CONST.to_bytes().as_ref()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump = mxe_account.bump
+    )]
+    pub mxe_account: Box<Account<'info, PersistentMXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for initializing the version 2 deposit computation definition
+#[init_computation_definition_accounts("deposit_v2", payer)]
+#[derive(Accounts)]
+pub struct InitDepositV2CompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED, ID_CONST.to_bytes().as_ref()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump = mxe_account.bump
+    )]
+    pub mxe_account: Box<Account<'info, PersistentMXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for initializing the version 2 transfer computation definition
+#[init_computation_definition_accounts("transfer_v2", payer)]
+#[derive(Accounts)]
+pub struct InitTransferV2CompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED, ID_CONST.to_bytes().as_ref()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump = mxe_account.bump
+    )]
+    pub mxe_account: Box<Account<'info, PersistentMXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for initializing the version 2 withdraw computation definition
+#[init_computation_definition_accounts("withdraw_v2", payer)]
+#[derive(Accounts)]
+pub struct InitWithdrawV2CompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED, ID_CONST.to_bytes().as_ref()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump = mxe_account.bump
+    )]
+    pub mxe_account: Box<Account<'info, PersistentMXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for initializing the version 2 withdraw-all computation definition
+#[init_computation_definition_accounts("withdraw_all_v2", payer)]
+#[derive(Accounts)]
+pub struct InitWithdrawAllV2CompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED, ID_CONST.to_bytes().as_ref()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump = mxe_account.bump
+    )]
+    pub mxe_account: Box<Account<'info, PersistentMXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for initializing the version 2 batch processing computation definition
+#[init_computation_definition_accounts("process_batch_v2", payer)]
+#[derive(Accounts)]
+pub struct InitProcessBatchV2CompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED, ID_CONST.to_bytes().as_ref()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump = mxe_account.bump
+    )]
+    pub mxe_account: Box<Account<'info, PersistentMXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for initializing a new mapping account
+#[derive(Accounts)]
+pub struct InitializeMappingAccount<'info> {
+    #[account(
+        mut,
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+    
+    #[account(
+        init,
+        payer = payer,
+        space = MAPPING_ACCOUNT_HEADER_SIZE
+            + usize::from(blackbox.new_shard_capacity(&config)) * ENTRY_SIZE,
+        seeds = [
+            b"mapping",
+            blackbox.token_mint.as_ref(),
+            &[blackbox.mapping_account_count]
+        ],
+        bump
+    )]
+    pub mapping_account: Account<'info, MappingAccount>,
+    
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for the deposit instruction.
+///
+/// Uses the mapping account to store encrypted pubkeys and balances.
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+#[callback_accounts]
+pub struct Deposit<'info> {
+    #[account(
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+    
+    #[account(
+        mut,
+        constraint = mapping_account.token_mint == blackbox.token_mint
+    )]
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + OpRecord::INIT_SPACE,
+        seeds = [b"op", blackbox.key().as_ref(), &computation_offset.to_le_bytes()],
+        bump
+    )]
+    pub op_record: Account<'info, OpRecord>,
+
+    /// Operation queue that takes over the operation if its result turns out stale
+    #[account(
+        mut,
+        seeds = [b"op_queue", mapping_account.key().as_ref()],
+        bump = op_queue.bump,
+        has_one = mapping_account
+    )]
+    pub op_queue: Option<Account<'info, OpQueueAccount>>,
+    
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = vault.key() == blackbox.vault @ ErrorCode::InvalidVault
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = treasury.key() == blackbox.treasury @ ErrorCode::InvalidTreasury
+    )]
+    pub treasury: Account<'info, TokenAccount>,
+    
+    pub user: Signer<'info>,
+    /// Co-signs the deposit if the blackbox's policy names a deposit approver
+    pub deposit_approver: Option<Signer<'info>>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    
+    // Arcium accounts for computation
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+    
+    #[account(
+        seeds = [CLOCK_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub clock: Account<'info, ClockAccount>,
+    
+    #[account(
+        seeds = [CLUSTER_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub cluster: Account<'info, Cluster>,
+    
+    #[account(
+        seeds = [
+            COMP_DEF_PDA_SEED,
+            &versioned(&DEPOSIT_VERSIONS, mapping_account.circuit_version).to_le_bytes()
+        ],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+        constraint = runs_circuit(
+            &comp_def,
+            versioned(&DEPOSIT_HASHES, mapping_account.circuit_version)
+        ) @ ErrorCode::CircuitMismatch,
+    )]
+    pub comp_def: Account<'info, ComputationDefinitionAccount>,
+    
+    #[account(
+        mut,
+        seeds = [MEMPOOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mempool: Account<'info, Mempool>,
+    
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mxe: Account<'info, PersistentMXEAccount>,
+    
+    #[account(
+        seeds = [POOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub pool: Account<'info, StakingPoolAccount>,
+    
+    #[account(mut)]
+    pub payer: Signer<'info>,
+}
+
+/// Accounts for the internal transfer instruction.
+///
+/// Uses the mapping account to store encrypted pubkeys and balances.
+#[derive(Accounts)]
+#[instruction(
+    computation_offset: u64,
+    enc_transfer: [[u8; 32]; 3],
+    client_key: [u8; 32],
+    nonce: u128
+)]
+#[callback_accounts]
+pub struct Transfer<'info> {
+    #[account(
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+    
+    #[account(
+        mut,
+        constraint = mapping_account.token_mint == blackbox.token_mint
+    )]
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + OpRecord::INIT_SPACE,
+        seeds = [b"op", blackbox.key().as_ref(), &computation_offset.to_le_bytes()],
+        bump
+    )]
+    pub op_record: Account<'info, OpRecord>,
+
+    /// Operation queue that takes over the operation if its result turns out stale
+    #[account(
+        mut,
+        seeds = [b"op_queue", mapping_account.key().as_ref()],
+        bump = op_queue.bump,
+        has_one = mapping_account
+    )]
+    pub op_queue: Option<Account<'info, OpQueueAccount>>,
+
+    /// Marks the sender's ciphertext nonce as consumed; fails if it was already used
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ConsumedNonce::INIT_SPACE,
+        seeds = [b"nonce", sender.key().as_ref(), &nonce.to_le_bytes()],
+        bump
+    )]
+    pub consumed_nonce: Account<'info, ConsumedNonce>,
+    
+    pub sender: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    
+    // Arcium accounts for computation
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+    
+    #[account(
+        seeds = [CLOCK_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub clock: Account<'info, ClockAccount>,
+    
+    #[account(
+        seeds = [CLUSTER_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub cluster: Account<'info, Cluster>,
+    
+    #[account(
+        seeds = [
+            COMP_DEF_PDA_SEED,
+            &versioned(&TRANSFER_VERSIONS, mapping_account.circuit_version).to_le_bytes()
+        ],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+        constraint = runs_circuit(
+            &comp_def,
+            versioned(&TRANSFER_HASHES, mapping_account.circuit_version)
+        ) @ ErrorCode::CircuitMismatch,
+    )]
+    pub comp_def: Account<'info, ComputationDefinitionAccount>,
+    
+    #[account(
+        mut,
+        seeds = [MEMPOOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mempool: Account<'info, Mempool>,
+    
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mxe: Account<'info, PersistentMXEAccount>,
+    
+    #[account(
+        seeds = [POOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub pool: Account<'info, StakingPoolAccount>,
+    
+    #[account(mut)]
+    pub payer: Signer<'info>,
+}
+
+/// Accounts for the withdrawal instruction.
+///
+/// Uses the mapping account to store encrypted pubkeys and balances.
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+#[callback_accounts]
+pub struct Withdraw<'info> {
+    #[account(
+        mut,
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+    
+    #[account(
+        mut,
+        constraint = mapping_account.token_mint == blackbox.token_mint
+    )]
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + OpRecord::INIT_SPACE,
+        seeds = [b"op", blackbox.key().as_ref(), &computation_offset.to_le_bytes()],
+        bump
+    )]
+    pub op_record: Account<'info, OpRecord>,
+
+    /// Operation queue that takes over the operation if its result turns out stale
+    #[account(
+        mut,
+        seeds = [b"op_queue", mapping_account.key().as_ref()],
+        bump = op_queue.bump,
+        has_one = mapping_account
+    )]
+    pub op_queue: Option<Account<'info, OpQueueAccount>>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == blackbox.vault @ ErrorCode::InvalidVault
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    /// Token account receiving the payout
+    #[account(
+        mut,
+        constraint = destination.mint == blackbox.token_mint @ ErrorCode::InvalidDestination
+    )]
+    pub destination: Account<'info, TokenAccount>,
+    
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    
+    // Arcium accounts for computation
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+    
+    #[account(
+        seeds = [CLOCK_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub clock: Account<'info, ClockAccount>,
+    
+    #[account(
+        seeds = [CLUSTER_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub cluster: Account<'info, Cluster>,
+    
+    #[account(
+        seeds = [
+            COMP_DEF_PDA_SEED,
+            &versioned(&WITHDRAW_VERSIONS, mapping_account.circuit_version).to_le_bytes()
+        ],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+        constraint = runs_circuit(
+            &comp_def,
+            versioned(&WITHDRAW_HASHES, mapping_account.circuit_version)
+        ) @ ErrorCode::CircuitMismatch,
+    )]
+    pub comp_def: Account<'info, ComputationDefinitionAccount>,
+    
+    #[account(
+        mut,
+        seeds = [MEMPOOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mempool: Account<'info, Mempool>,
+    
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mxe: Account<'info, PersistentMXEAccount>,
+    
+    #[account(
+        seeds = [POOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub pool: Account<'info, StakingPoolAccount>,
+    
+    #[account(mut)]
+    pub payer: Signer<'info>,
+}
+
+/// Accounts for the deposit callback.
+#[derive(Accounts)]
+pub struct DepositCallback<'info> {
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+
+    /// CHECK: instructions sysvar, used by the arcium callback check.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    #[account(
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump = blackbox.bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(
+        mut,
+        constraint = mapping_account.token_mint == blackbox.token_mint
+    )]
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"op", blackbox.key().as_ref(), &op_record.computation_offset.to_le_bytes()],
+        bump = op_record.bump,
+        has_one = blackbox,
+        has_one = mapping_account
+    )]
+    pub op_record: Account<'info, OpRecord>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == blackbox.vault @ ErrorCode::InvalidVault
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    /// Token account the deposit came from, refunded if the mapping account had no room
+    #[account(
+        mut,
+        address = op_record.destination @ ErrorCode::InvalidDestination
+    )]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+
+    /// Operation queue that takes over the operation if its result turns out stale
+    #[account(
+        mut,
+        seeds = [b"op_queue", mapping_account.key().as_ref()],
+        bump = op_queue.bump,
+        has_one = mapping_account
+    )]
+    pub op_queue: Option<Account<'info, OpQueueAccount>>,
+}
+
+/// Accounts for the transfer callback.
+#[derive(Accounts)]
+pub struct TransferCallback<'info> {
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+
+    /// CHECK: instructions sysvar, used by the arcium callback check.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    #[account(
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump = blackbox.bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(
+        mut,
+        constraint = mapping_account.token_mint == blackbox.token_mint
+    )]
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"op", blackbox.key().as_ref(), &op_record.computation_offset.to_le_bytes()],
+        bump = op_record.bump,
+        has_one = blackbox,
+        has_one = mapping_account
+    )]
+    pub op_record: Account<'info, OpRecord>,
+
+    /// Operation queue that takes over the operation if its result turns out stale
+    #[account(
+        mut,
+        seeds = [b"op_queue", mapping_account.key().as_ref()],
+        bump = op_queue.bump,
+        has_one = mapping_account
+    )]
+    pub op_queue: Option<Account<'info, OpQueueAccount>>,
+}
+
+/// Accounts for the withdrawal callback.
+#[derive(Accounts)]
+pub struct WithdrawCallback<'info> {
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+
+    /// CHECK: instructions sysvar, used by the arcium callback check.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump = blackbox.bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(
+        mut,
+        constraint = mapping_account.token_mint == blackbox.token_mint
+    )]
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"op", blackbox.key().as_ref(), &op_record.computation_offset.to_le_bytes()],
+        bump = op_record.bump,
+        has_one = blackbox,
+        has_one = mapping_account
+    )]
+    pub op_record: Account<'info, OpRecord>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == blackbox.vault @ ErrorCode::InvalidVault
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = treasury.key() == blackbox.treasury @ ErrorCode::InvalidTreasury
+    )]
+    pub treasury: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        address = op_record.destination @ ErrorCode::InvalidDestination
+    )]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+
+    /// Operation queue that takes over the operation if its result turns out stale
+    #[account(
+        mut,
+        seeds = [b"op_queue", mapping_account.key().as_ref()],
+        bump = op_queue.bump,
+        has_one = mapping_account
+    )]
+    pub op_queue: Option<Account<'info, OpQueueAccount>>,
+}
+
+/// Accounts for re-queuing a stale operation
+#[derive(Accounts)]
+pub struct RequeueStaleOp<'info> {
+    #[account(
+        mut,
+        seeds = [b"op", op_record.blackbox.as_ref(), &op_record.computation_offset.to_le_bytes()],
+        bump = op_record.bump
+    )]
+    pub op_record: Account<'info, OpRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"op_queue", op_record.mapping_account.as_ref()],
+        bump = op_queue.bump
+    )]
+    pub op_queue: Account<'info, OpQueueAccount>,
+}
+
+/// Accounts for re-queuing a batch whose callback never arrived
+#[derive(Accounts)]
+pub struct RequeueStuckBatch<'info> {
+    #[account(
+        mut,
+        seeds = [b"op_queue", op_queue.mapping_account.as_ref()],
+        bump = op_queue.bump
+    )]
+    pub op_queue: Account<'info, OpQueueAccount>,
+
+    /// Record of the in-flight batch
+    #[account(
+        mut,
+        seeds = [b"op", op_queue.blackbox.as_ref(), &op_queue.in_flight_offset.to_le_bytes()],
+        bump = op_record.bump
+    )]
+    pub op_record: Account<'info, OpRecord>,
+}
+
+/// Accounts for closing a resolved operation record
+#[derive(Accounts)]
+pub struct CloseOpRecord<'info> {
+    #[account(
+        mut,
+        seeds = [b"op", op_record.blackbox.as_ref(), &op_record.computation_offset.to_le_bytes()],
+        bump = op_record.bump,
+        has_one = payer,
+        close = payer
+    )]
+    pub op_record: Account<'info, OpRecord>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+}
+
+/// Accounts for closing a consumed-nonce marker
+#[derive(Accounts)]
+#[instruction(nonce: u128)]
+pub struct CloseConsumedNonce<'info> {
+    #[account(
+        mut,
+        seeds = [b"nonce", sender.key().as_ref(), &nonce.to_le_bytes()],
+        bump = consumed_nonce.bump,
+        has_one = payer,
+        close = payer
+    )]
+    pub consumed_nonce: Account<'info, ConsumedNonce>,
+
+    pub sender: Signer<'info>,
+
+    /// CHECK: receives the marker's rent; checked by `has_one`
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+}
+
+/// Accounts for initializing the operation queue of a mapping account
+#[derive(Accounts)]
+pub struct InitOpQueue<'info> {
+    #[account(
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(constraint = mapping_account.token_mint == blackbox.token_mint)]
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + OpQueueAccount::INIT_SPACE,
+        seeds = [b"op_queue", mapping_account.key().as_ref()],
+        bump
+    )]
+    pub op_queue: Account<'info, OpQueueAccount>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for initializing the batch processing computation definition
+#[init_computation_definition_accounts("process_batch", payer)]
+#[derive(Accounts)]
+pub struct InitProcessBatchCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED, ID_CONST.to_bytes().as_ref()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump = mxe_account.bump
+    )]
+    pub mxe_account: Box<Account<'info, PersistentMXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for appending a deposit to an operation queue
+#[derive(Accounts)]
+pub struct EnqueueDeposit<'info> {
+    #[account(
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"op_queue", op_queue.mapping_account.as_ref()],
+        bump = op_queue.bump,
+        has_one = blackbox
+    )]
+    pub op_queue: Account<'info, OpQueueAccount>,
+
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == blackbox.vault @ ErrorCode::InvalidVault
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = treasury.key() == blackbox.treasury @ ErrorCode::InvalidTreasury
+    )]
+    pub treasury: Account<'info, TokenAccount>,
+
+    pub user: Signer<'info>,
+    /// Co-signs the deposit if the blackbox's policy names a deposit approver
+    pub deposit_approver: Option<Signer<'info>>,
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts for appending an internal transfer to an operation queue
+#[derive(Accounts)]
+#[instruction(
+    enc_transfer: [[u8; 32]; 3],
+    client_key: [u8; 32],
+    nonce: u128
+)]
+pub struct EnqueueTransfer<'info> {
+    #[account(
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"op_queue", op_queue.mapping_account.as_ref()],
+        bump = op_queue.bump,
+        has_one = blackbox
+    )]
+    pub op_queue: Account<'info, OpQueueAccount>,
+
+    /// Marks the sender's ciphertext nonce as consumed; fails if it was already used
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ConsumedNonce::INIT_SPACE,
+        seeds = [b"nonce", user.key().as_ref(), &nonce.to_le_bytes()],
+        bump
+    )]
+    pub consumed_nonce: Account<'info, ConsumedNonce>,
+
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for appending a withdrawal to an operation queue
+#[derive(Accounts)]
+pub struct EnqueueWithdraw<'info> {
+    #[account(
+        mut,
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"op_queue", op_queue.mapping_account.as_ref()],
+        bump = op_queue.bump,
+        has_one = blackbox
+    )]
+    pub op_queue: Account<'info, OpQueueAccount>,
+
+    /// Token account receiving the payout
+    #[account(constraint = destination.mint == blackbox.token_mint @ ErrorCode::InvalidDestination)]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub user: Signer<'info>,
+}
+
+/// Accounts for flushing an operation queue into a batch computation.
+///
+/// Anyone can flush a queue; the payer only covers the computation fees.
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+#[callback_accounts]
+pub struct FlushOpQueue<'info> {
+    #[account(
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(
+        mut,
+        constraint = mapping_account.token_mint == blackbox.token_mint
+    )]
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"op_queue", mapping_account.key().as_ref()],
+        bump = op_queue.bump,
+        has_one = blackbox,
+        has_one = mapping_account
+    )]
+    pub op_queue: Account<'info, OpQueueAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + OpRecord::INIT_SPACE,
+        seeds = [b"op", blackbox.key().as_ref(), &computation_offset.to_le_bytes()],
+        bump
+    )]
+    pub op_record: Account<'info, OpRecord>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == blackbox.vault @ ErrorCode::InvalidVault
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+
+    // Arcium accounts for computation
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+
+    #[account(
+        seeds = [CLOCK_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub clock: Account<'info, ClockAccount>,
+
+    #[account(
+        seeds = [CLUSTER_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub cluster: Account<'info, Cluster>,
+
+    #[account(
+        seeds = [
+            COMP_DEF_PDA_SEED,
+            &versioned(&PROCESS_BATCH_VERSIONS, mapping_account.circuit_version).to_le_bytes()
+        ],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+        constraint = runs_circuit(
+            &comp_def,
+            versioned(&PROCESS_BATCH_HASHES, mapping_account.circuit_version)
+        ) @ ErrorCode::CircuitMismatch,
+    )]
+    pub comp_def: Account<'info, ComputationDefinitionAccount>,
+
+    #[account(
+        mut,
+        seeds = [MEMPOOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mempool: Account<'info, Mempool>,
+
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mxe: Account<'info, PersistentMXEAccount>,
+
+    #[account(
+        seeds = [POOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub pool: Account<'info, StakingPoolAccount>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+}
+
+/// Accounts for the batch callback.
+///
+/// Withdrawal destinations follow as remaining accounts, in queue order.
+#[derive(Accounts)]
+pub struct ProcessBatchCallback<'info> {
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+
+    /// CHECK: instructions sysvar, used by the arcium callback check.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump = blackbox.bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(mut)]
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"op_queue", mapping_account.key().as_ref()],
+        bump = op_queue.bump,
+        has_one = blackbox,
+        has_one = mapping_account
+    )]
+    pub op_queue: Account<'info, OpQueueAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"op", blackbox.key().as_ref(), &op_record.computation_offset.to_le_bytes()],
+        bump = op_record.bump,
+        has_one = blackbox,
+        has_one = mapping_account
+    )]
+    pub op_record: Account<'info, OpRecord>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == blackbox.vault @ ErrorCode::InvalidVault
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = treasury.key() == blackbox.treasury @ ErrorCode::InvalidTreasury
+    )]
+    pub treasury: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts for initializing the withdraw-all computation definition
+#[init_computation_definition_accounts("withdraw_all", payer)]
+#[derive(Accounts)]
+pub struct InitWithdrawAllCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED, ID_CONST.to_bytes().as_ref()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump = mxe_account.bump
+    )]
+    pub mxe_account: Box<Account<'info, PersistentMXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for the withdraw-all instruction.
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+#[callback_accounts]
+pub struct WithdrawAll<'info> {
+    #[account(
+        mut,
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(
+        mut,
+        constraint = mapping_account.token_mint == blackbox.token_mint
+    )]
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + OpRecord::INIT_SPACE,
+        seeds = [b"op", blackbox.key().as_ref(), &computation_offset.to_le_bytes()],
+        bump
+    )]
+    pub op_record: Account<'info, OpRecord>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == blackbox.vault @ ErrorCode::InvalidVault
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    /// Token account receiving the payout
+    #[account(
+        mut,
+        constraint = destination.mint == blackbox.token_mint @ ErrorCode::InvalidDestination
+    )]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+
+    // Arcium accounts for computation
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+
+    #[account(
+        seeds = [CLOCK_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub clock: Account<'info, ClockAccount>,
+
+    #[account(
+        seeds = [CLUSTER_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub cluster: Account<'info, Cluster>,
+
+    #[account(
+        seeds = [
+            COMP_DEF_PDA_SEED,
+            &versioned(&WITHDRAW_ALL_VERSIONS, mapping_account.circuit_version).to_le_bytes()
+        ],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+        constraint = runs_circuit(
+            &comp_def,
+            versioned(&WITHDRAW_ALL_HASHES, mapping_account.circuit_version)
+        ) @ ErrorCode::CircuitMismatch,
+    )]
+    pub comp_def: Account<'info, ComputationDefinitionAccount>,
+
+    #[account(
+        mut,
+        seeds = [MEMPOOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mempool: Account<'info, Mempool>,
+
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mxe: Account<'info, PersistentMXEAccount>,
+
+    #[account(
+        seeds = [POOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub pool: Account<'info, StakingPoolAccount>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+}
+
+/// Accounts for the withdraw-all callback.
+#[derive(Accounts)]
+pub struct WithdrawAllCallback<'info> {
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+
+    /// CHECK: instructions sysvar, used by the arcium callback check.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump = blackbox.bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(
+        mut,
+        constraint = mapping_account.token_mint == blackbox.token_mint
+    )]
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"op", blackbox.key().as_ref(), &op_record.computation_offset.to_le_bytes()],
+        bump = op_record.bump,
+        has_one = blackbox,
+        has_one = mapping_account
+    )]
+    pub op_record: Account<'info, OpRecord>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == blackbox.vault @ ErrorCode::InvalidVault
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = treasury.key() == blackbox.treasury @ ErrorCode::InvalidTreasury
+    )]
+    pub treasury: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        address = op_record.destination @ ErrorCode::InvalidDestination
+    )]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts for initializing the reveal-balance computation definition
+#[init_computation_definition_accounts("reveal_balance", payer)]
+#[derive(Accounts)]
+pub struct InitRevealBalanceCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED, ID_CONST.to_bytes().as_ref()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump = mxe_account.bump
+    )]
+    pub mxe_account: Box<Account<'info, PersistentMXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for the reveal-balance instruction.
+#[derive(Accounts)]
+#[instruction(
+    computation_offset: u64,
+    client_key: [u8; 32],
+    nonce: u128
+)]
+#[callback_accounts]
+pub struct RevealBalance<'info> {
+    #[account(
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(
+        constraint = mapping_account.token_mint == blackbox.token_mint
+    )]
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + BalanceReveal::INIT_SPACE,
+        seeds = [b"reveal", blackbox.key().as_ref(), &computation_offset.to_le_bytes()],
+        bump
+    )]
+    pub balance_reveal: Account<'info, BalanceReveal>,
+
+    /// Marks the owner's reveal nonce as consumed; fails if it was already used
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ConsumedNonce::INIT_SPACE,
+        seeds = [b"nonce", owner.key().as_ref(), &nonce.to_le_bytes()],
+        bump
+    )]
+    pub consumed_nonce: Account<'info, ConsumedNonce>,
+
+    /// Owner of the balance being revealed
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+
+    // Arcium accounts for computation
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+
+    #[account(
+        seeds = [CLOCK_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub clock: Account<'info, ClockAccount>,
+
+    #[account(
+        seeds = [CLUSTER_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub cluster: Account<'info, Cluster>,
+
+    #[account(
+        seeds = [
+            COMP_DEF_PDA_SEED,
+            &versioned(&REVEAL_BALANCE_VERSIONS, mapping_account.circuit_version).to_le_bytes()
+        ],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+        constraint = runs_circuit(
+            &comp_def,
+            versioned(&REVEAL_BALANCE_HASHES, mapping_account.circuit_version)
+        ) @ ErrorCode::CircuitMismatch,
+    )]
+    pub comp_def: Account<'info, ComputationDefinitionAccount>,
+
+    #[account(
+        mut,
+        seeds = [MEMPOOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mempool: Account<'info, Mempool>,
+
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mxe: Account<'info, PersistentMXEAccount>,
+
+    #[account(
+        seeds = [POOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub pool: Account<'info, StakingPoolAccount>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+}
+
+/// Accounts for the reveal-balance callback.
+#[derive(Accounts)]
+pub struct RevealBalanceCallback<'info> {
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+
+    /// CHECK: instructions sysvar, used by the arcium callback check.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"reveal",
+            balance_reveal.blackbox.as_ref(),
+            &balance_reveal.computation_offset.to_le_bytes()
+        ],
+        bump = balance_reveal.bump
+    )]
+    pub balance_reveal: Account<'info, BalanceReveal>,
+}
+
+/// Accounts for initializing the prove-solvency computation definition
+#[init_computation_definition_accounts("prove_solvency", payer)]
+#[derive(Accounts)]
+pub struct InitProveSolvencyCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED, ID_CONST.to_bytes().as_ref()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump = mxe_account.bump
+    )]
+    pub mxe_account: Box<Account<'info, PersistentMXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for the prove-solvency instruction.
+///
+/// The blackbox's mapping accounts follow as remaining accounts, in index order.
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+#[callback_accounts]
+pub struct ProveSolvency<'info> {
+    #[account(
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump = blackbox.bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(
+        constraint = vault.key() == blackbox.vault @ ErrorCode::InvalidVault
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + SolvencyAttestation::INIT_SPACE,
+        seeds = [b"solvency", blackbox.key().as_ref(), &computation_offset.to_le_bytes()],
+        bump
+    )]
+    pub attestation: Account<'info, SolvencyAttestation>,
+
+    pub system_program: Program<'info, System>,
+
+    // Arcium accounts for computation
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+
+    #[account(
+        seeds = [CLOCK_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub clock: Account<'info, ClockAccount>,
+
+    #[account(
+        seeds = [CLUSTER_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub cluster: Account<'info, Cluster>,
+
+    #[account(
+        seeds = [COMP_DEF_PDA_SEED, &COMP_DEF_OFFSET_PROVE_SOLVENCY.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+        constraint = runs_circuit(&comp_def, PROVE_SOLVENCY_CIRCUIT_HASH)
+            @ ErrorCode::CircuitMismatch,
+    )]
+    pub comp_def: Account<'info, ComputationDefinitionAccount>,
+
+    #[account(
+        mut,
+        seeds = [MEMPOOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mempool: Account<'info, Mempool>,
+
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mxe: Account<'info, PersistentMXEAccount>,
+
+    #[account(
+        seeds = [POOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub pool: Account<'info, StakingPoolAccount>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+}
+
+/// Accounts for queuing the next range of a solvency proof.
+///
+/// The range's mapping accounts follow as remaining accounts, in index order.
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+#[callback_accounts]
+pub struct ContinueSolvencyProof<'info> {
+    #[account(
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump = blackbox.bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"solvency",
+            blackbox.key().as_ref(),
+            &attestation.computation_offset.to_le_bytes()
+        ],
+        bump = attestation.bump,
+        has_one = blackbox
+    )]
+    pub attestation: Account<'info, SolvencyAttestation>,
+
+    pub system_program: Program<'info, System>,
+
+    // Arcium accounts for computation
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+
+    #[account(
+        seeds = [CLOCK_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub clock: Account<'info, ClockAccount>,
+
+    #[account(
+        seeds = [CLUSTER_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub cluster: Account<'info, Cluster>,
+
+    #[account(
+        seeds = [COMP_DEF_PDA_SEED, &COMP_DEF_OFFSET_PROVE_SOLVENCY.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+        constraint = runs_circuit(&comp_def, PROVE_SOLVENCY_CIRCUIT_HASH)
+            @ ErrorCode::CircuitMismatch,
+    )]
+    pub comp_def: Account<'info, ComputationDefinitionAccount>,
+
+    #[account(
+        mut,
+        seeds = [MEMPOOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mempool: Account<'info, Mempool>,
+
+    #[account(
+        mut,
+        seeds = [MXE_PDA_SEED],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub mxe: Account<'info, PersistentMXEAccount>,
+
+    #[account(
+        seeds = [POOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
+        seeds::program = ARCIUM_PROG_ID,
+        bump,
+    )]
+    pub pool: Account<'info, StakingPoolAccount>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+}
+
+/// Accounts for the prove-solvency callback.
+#[derive(Accounts)]
+pub struct ProveSolvencyCallback<'info> {
+    #[account(address = ARCIUM_PROG_ID)]
     pub arcium_program: Program<'info, Arcium>,
-    pub system_program: Program<'info, System>,
+
+    /// CHECK: instructions sysvar, used by the arcium callback check.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"solvency",
+            attestation.blackbox.as_ref(),
+            &attestation.computation_offset.to_le_bytes()
+        ],
+        bump = attestation.bump
+    )]
+    pub attestation: Account<'info, SolvencyAttestation>,
 }
 
-/// Accounts for initializing the withdraw computation definition
-#[init_computation_definition_accounts("withdraw", payer)]
+/// Accounts for initializing the export-balances computation definition
+#[init_computation_definition_accounts("export_balances", payer)]
 #[derive(Accounts)]
-pub struct InitWithdrawCompDef<'info> {
+pub struct InitExportBalancesCompDef<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(
@@ -307,100 +4225,78 @@ pub struct InitWithdrawCompDef<'info> {
     pub system_program: Program<'info, System>,
 }
 
-/// Accounts for initializing a new mapping account
+/// Accounts for the export-balances instruction
 #[derive(Accounts)]
-pub struct InitializeMappingAccount<'info> {
+#[instruction(computation_offset: u64, page: u8)]
+#[callback_accounts]
+pub struct ExportBalances<'info> {
     #[account(
-        mut,
         seeds = [b"blackbox", blackbox.token_mint.as_ref()],
-        bump
+        bump = blackbox.bump
     )]
     pub blackbox: Account<'info, BlackboxAccount>,
-    
+
+    #[account(
+        constraint = mapping_account.token_mint == blackbox.token_mint
+    )]
+    pub mapping_account: Account<'info, MappingAccount>,
+
     #[account(
         init,
         payer = payer,
-        space = 8 + 8 + 32 + 4 + (MAX_ENTRIES_PER_ACCOUNT * 32) + 4 + (MAX_ENTRIES_PER_ACCOUNT * 32),
+        space = 8 + EmergencyExport::INIT_SPACE,
         seeds = [
-            b"mapping",
-            blackbox.token_mint.as_ref(),
-            &[blackbox.mapping_account_count]
+            b"emergency",
+            mapping_account.key().as_ref(),
+            &mapping_account.version.to_le_bytes(),
+            &[page]
         ],
         bump
     )]
-    pub mapping_account: Account<'info, MappingAccount>,
-    
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+    pub export: Account<'info, EmergencyExport>,
 
-/// Accounts for the deposit instruction.
-///
-/// Uses mapping accounts to store encrypted pubkeys and balances.
-#[derive(Accounts)]
-#[callback_accounts]
-pub struct Deposit<'info> {
-    #[account(
-        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
-        bump
-    )]
-    pub blackbox: Account<'info, BlackboxAccount>,
-    
-    #[account(
-        mut,
-        constraint = mapping_accounts.iter().all(|account| account.token_mint == blackbox.token_mint)
-    )]
-    pub mapping_accounts: Vec<Account<'info, MappingAccount>>,
-    
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
-    
-    #[account(
-        mut,
-        seeds = [b"vault".as_ref(), blackbox.key().as_ref()],
-        bump,
-        constraint = vault.key() == blackbox.vault @ ErrorCode::InvalidVault
-    )]
-    pub vault: Account<'info, TokenAccount>,
-    
-    pub user: Signer<'info>,
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-    
+
     // Arcium accounts for computation
     #[account(address = ARCIUM_PROG_ID)]
     pub arcium_program: Program<'info, Arcium>,
-    
+
     #[account(
         seeds = [CLOCK_PDA_SEED],
         seeds::program = ARCIUM_PROG_ID,
         bump,
     )]
     pub clock: Account<'info, ClockAccount>,
-    
+
     #[account(
-        seeds = [CLUSTER_PDA_SEED],
+        seeds = [CLUSTER_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
         seeds::program = ARCIUM_PROG_ID,
         bump,
     )]
     pub cluster: Account<'info, Cluster>,
-    
+
     #[account(
-        seeds = [COMP_DEF_PDA_SEED, &COMP_DEF_OFFSET_DEPOSIT.to_le_bytes()],
+        seeds = [
+            COMP_DEF_PDA_SEED,
+            &versioned(&EXPORT_BALANCES_VERSIONS, mapping_account.circuit_version).to_le_bytes()
+        ],
         seeds::program = ARCIUM_PROG_ID,
         bump,
+        constraint = runs_circuit(
+            &comp_def,
+            versioned(&EXPORT_BALANCES_HASHES, mapping_account.circuit_version)
+        ) @ ErrorCode::CircuitMismatch,
     )]
     pub comp_def: Account<'info, ComputationDefinitionAccount>,
-    
+
     #[account(
         mut,
-        seeds = [MEMPOOL_PDA_SEED],
+        seeds = [MEMPOOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
         seeds::program = ARCIUM_PROG_ID,
         bump,
     )]
     pub mempool: Account<'info, Mempool>,
-    
+
     #[account(
         mut,
         seeds = [MXE_PDA_SEED],
@@ -408,145 +4304,260 @@ pub struct Deposit<'info> {
         bump,
     )]
     pub mxe: Account<'info, PersistentMXEAccount>,
-    
+
     #[account(
-        seeds = [POOL_PDA_SEED],
+        seeds = [POOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
         seeds::program = ARCIUM_PROG_ID,
         bump,
     )]
     pub pool: Account<'info, StakingPoolAccount>,
-    
+
     #[account(mut)]
     pub payer: Signer<'info>,
 }
 
-/// Accounts for the internal transfer instruction.
-///
-/// Uses mapping accounts to store encrypted pubkeys and balances.
+/// Accounts for the export-balances callback.
 #[derive(Accounts)]
-#[callback_accounts]
-pub struct Transfer<'info> {
+pub struct ExportBalancesCallback<'info> {
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+
+    /// CHECK: instructions sysvar, used by the arcium callback check.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    #[account(address = export.mapping_account)]
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"emergency",
+            export.mapping_account.as_ref(),
+            &export.mapping_version.to_le_bytes(),
+            &[export.page]
+        ],
+        bump = export.bump
+    )]
+    pub export: Account<'info, EmergencyExport>,
+}
+
+/// Accounts for claiming an exported balance
+#[derive(Accounts)]
+pub struct ClaimEmergency<'info> {
     #[account(
         seeds = [b"blackbox", blackbox.token_mint.as_ref()],
-        bump
+        bump = blackbox.bump
     )]
     pub blackbox: Account<'info, BlackboxAccount>,
-    
+
+    #[account(
+        constraint = mapping_account.token_mint == blackbox.token_mint
+    )]
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        seeds = [
+            b"emergency",
+            mapping_account.key().as_ref(),
+            &export.mapping_version.to_le_bytes(),
+            &[export.page]
+        ],
+        bump = export.bump
+    )]
+    pub export: Account<'info, EmergencyExport>,
+
+    /// Marks the owner's balance in `mapping_account` as claimed
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ClaimedBalance::INIT_SPACE,
+        seeds = [b"claimed", mapping_account.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub claimed: Account<'info, ClaimedBalance>,
+
     #[account(
         mut,
-        constraint = mapping_accounts.iter().all(|account| account.token_mint == blackbox.token_mint)
+        constraint = vault.key() == blackbox.vault @ ErrorCode::InvalidVault
     )]
-    pub mapping_accounts: Vec<Account<'info, MappingAccount>>,
-    
-    pub sender: Signer<'info>,
+    pub vault: Account<'info, TokenAccount>,
+
+    /// Token account receiving the payout
+    #[account(
+        mut,
+        constraint = destination.mint == blackbox.token_mint @ ErrorCode::InvalidDestination
+    )]
+    pub destination: Account<'info, TokenAccount>,
+
+    /// Owner of the exported balance
+    pub owner: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
     pub system_program: Program<'info, System>,
-    
-    // Arcium accounts for computation
-    #[account(address = ARCIUM_PROG_ID)]
-    pub arcium_program: Program<'info, Arcium>,
-    
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts for refunding a direct deposit left uncredited by emergency mode
+#[derive(Accounts)]
+pub struct RefundDeposit<'info> {
     #[account(
-        seeds = [CLOCK_PDA_SEED],
-        seeds::program = ARCIUM_PROG_ID,
-        bump,
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump = blackbox.bump
     )]
-    pub clock: Account<'info, ClockAccount>,
-    
+    pub blackbox: Account<'info, BlackboxAccount>,
+
     #[account(
-        seeds = [CLUSTER_PDA_SEED],
-        seeds::program = ARCIUM_PROG_ID,
-        bump,
+        mut,
+        seeds = [b"op", blackbox.key().as_ref(), &op_record.computation_offset.to_le_bytes()],
+        bump = op_record.bump,
+        has_one = blackbox,
+        has_one = destination @ ErrorCode::InvalidDestination
     )]
-    pub cluster: Account<'info, Cluster>,
-    
+    pub op_record: Account<'info, OpRecord>,
+
     #[account(
-        seeds = [COMP_DEF_PDA_SEED, &COMP_DEF_OFFSET_TRANSFER.to_le_bytes()],
-        seeds::program = ARCIUM_PROG_ID,
-        bump,
+        mut,
+        constraint = vault.key() == blackbox.vault @ ErrorCode::InvalidVault
     )]
-    pub comp_def: Account<'info, ComputationDefinitionAccount>,
-    
+    pub vault: Account<'info, TokenAccount>,
+
+    /// Token account the deposit came from
+    #[account(mut)]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts for refunding the deposits waiting in an operation queue in emergency mode. The
+/// deposits' source token accounts follow as remaining accounts.
+#[derive(Accounts)]
+pub struct RefundQueuedDeposits<'info> {
+    #[account(
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump = blackbox.bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
     #[account(
         mut,
-        seeds = [MEMPOOL_PDA_SEED],
-        seeds::program = ARCIUM_PROG_ID,
-        bump,
+        seeds = [b"op_queue", op_queue.mapping_account.as_ref()],
+        bump = op_queue.bump,
+        has_one = blackbox
     )]
-    pub mempool: Account<'info, Mempool>,
-    
+    pub op_queue: Account<'info, OpQueueAccount>,
+
     #[account(
         mut,
-        seeds = [MXE_PDA_SEED],
-        seeds::program = ARCIUM_PROG_ID,
-        bump,
+        constraint = vault.key() == blackbox.vault @ ErrorCode::InvalidVault
     )]
-    pub mxe: Account<'info, PersistentMXEAccount>,
-    
+    pub vault: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts for initializing the version 2 migrate-mapping computation definition
+#[init_computation_definition_accounts("migrate_mapping_v2", payer)]
+#[derive(Accounts)]
+pub struct InitMigrateMappingV2CompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
     #[account(
-        seeds = [POOL_PDA_SEED],
+        mut,
+        seeds = [MXE_PDA_SEED, ID_CONST.to_bytes().as_ref()],
         seeds::program = ARCIUM_PROG_ID,
-        bump,
+        bump = mxe_account.bump
     )]
-    pub pool: Account<'info, StakingPoolAccount>,
-    
+    pub mxe_account: Box<Account<'info, PersistentMXEAccount>>,
     #[account(mut)]
-    pub payer: Signer<'info>,
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
 }
 
-/// Accounts for the withdrawal instruction.
-///
-/// Uses mapping accounts to store encrypted pubkeys and balances.
+/// Accounts for the migrate-mapping instruction.
 #[derive(Accounts)]
+#[instruction(computation_offset: u64)]
 #[callback_accounts]
-pub struct Withdraw<'info> {
+pub struct MigrateMapping<'info> {
     #[account(
         seeds = [b"blackbox", blackbox.token_mint.as_ref()],
         bump
     )]
     pub blackbox: Account<'info, BlackboxAccount>,
-    
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
     #[account(
         mut,
-        constraint = mapping_accounts.iter().all(|account| account.token_mint == blackbox.token_mint)
+        constraint = mapping_account.token_mint == blackbox.token_mint,
+        constraint = mapping_account.circuit_version < blackbox.circuit_version
+            @ ErrorCode::AlreadyMigrated
     )]
-    pub mapping_accounts: Vec<Account<'info, MappingAccount>>,
-    
-    pub user: Signer<'info>,
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + OpRecord::INIT_SPACE,
+        seeds = [b"op", blackbox.key().as_ref(), &computation_offset.to_le_bytes()],
+        bump
+    )]
+    pub op_record: Account<'info, OpRecord>,
+
+    pub authority: Signer<'info>,
     pub system_program: Program<'info, System>,
-    
+
     // Arcium accounts for computation
     #[account(address = ARCIUM_PROG_ID)]
     pub arcium_program: Program<'info, Arcium>,
-    
+
     #[account(
         seeds = [CLOCK_PDA_SEED],
         seeds::program = ARCIUM_PROG_ID,
         bump,
     )]
     pub clock: Account<'info, ClockAccount>,
-    
+
     #[account(
-        seeds = [CLUSTER_PDA_SEED],
+        seeds = [CLUSTER_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
         seeds::program = ARCIUM_PROG_ID,
         bump,
     )]
     pub cluster: Account<'info, Cluster>,
-    
+
+    /// Migration out of the mapping account's version, which the constraint above keeps
+    /// below the latest
     #[account(
-        seeds = [COMP_DEF_PDA_SEED, &COMP_DEF_OFFSET_WITHDRAW.to_le_bytes()],
+        seeds = [
+            COMP_DEF_PDA_SEED,
+            &versioned(&MIGRATE_MAPPING_VERSIONS, mapping_account.circuit_version)
+                .to_le_bytes()
+        ],
         seeds::program = ARCIUM_PROG_ID,
         bump,
+        constraint = runs_circuit(
+            &comp_def,
+            versioned(&MIGRATE_MAPPING_HASHES, mapping_account.circuit_version)
+        ) @ ErrorCode::CircuitMismatch,
     )]
     pub comp_def: Account<'info, ComputationDefinitionAccount>,
-    
+
     #[account(
         mut,
-        seeds = [MEMPOOL_PDA_SEED],
+        seeds = [MEMPOOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
         seeds::program = ARCIUM_PROG_ID,
         bump,
     )]
     pub mempool: Account<'info, Mempool>,
-    
+
     #[account(
         mut,
         seeds = [MXE_PDA_SEED],
@@ -554,14 +4565,46 @@ pub struct Withdraw<'info> {
         bump,
     )]
     pub mxe: Account<'info, PersistentMXEAccount>,
-    
+
     #[account(
-        seeds = [POOL_PDA_SEED],
+        seeds = [POOL_PDA_SEED, &blackbox.cluster_offset.to_le_bytes()],
         seeds::program = ARCIUM_PROG_ID,
         bump,
     )]
     pub pool: Account<'info, StakingPoolAccount>,
-    
+
     #[account(mut)]
     pub payer: Signer<'info>,
 }
+
+/// Accounts for the migrate-mapping callback.
+#[derive(Accounts)]
+pub struct MigrateMappingCallback<'info> {
+    #[account(address = ARCIUM_PROG_ID)]
+    pub arcium_program: Program<'info, Arcium>,
+
+    /// CHECK: instructions sysvar, used by the arcium callback check.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    #[account(
+        seeds = [b"blackbox", blackbox.token_mint.as_ref()],
+        bump = blackbox.bump
+    )]
+    pub blackbox: Account<'info, BlackboxAccount>,
+
+    #[account(
+        mut,
+        constraint = mapping_account.token_mint == blackbox.token_mint
+    )]
+    pub mapping_account: Account<'info, MappingAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"op", blackbox.key().as_ref(), &op_record.computation_offset.to_le_bytes()],
+        bump = op_record.bump,
+        has_one = blackbox,
+        has_one = mapping_account
+    )]
+    pub op_record: Account<'info, OpRecord>,
+}
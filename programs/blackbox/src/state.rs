@@ -1,4 +1,158 @@
 use anchor_lang::prelude::*;
+use confidential_ixs::{MAX_BATCH_SIZE, MAX_EXPORT_ENTRIES};
+
+/// Maximum number of operations an `OpQueueAccount` holds, pending and in flight combined.
+/// Twice the batch size, so a stale batch always fits back into the queue.
+pub const MAX_QUEUED_OPS: usize = 2 * MAX_BATCH_SIZE;
+
+/// Slots after which a batch whose callback hasn't arrived can be put back in the queue
+pub const BATCH_TIMEOUT_SLOTS: u64 = 1_500;
+
+/// Basis points in a whole
+pub const MAX_BPS: u16 = 10_000;
+
+/// Newest version of the deposit, transfer and withdraw circuits. Version 1 is registered under
+/// the circuits' bare names, later versions under `<name>_v<version>`.
+pub const LATEST_CIRCUIT_VERSION: u8 = 2;
+
+/// Program-wide settings, and the authority allowed to change them
+#[account]
+#[derive(InitSpace)]
+pub struct ProgramConfig {
+    pub bump: u8,
+    pub authority: Pubkey,
+    /// Operations paused on every blackbox
+    pub paused: PauseFlags,
+    /// Cap on every blackbox's fees, in basis points
+    pub max_fee_bps: u16,
+    /// Successor proposed by the authority, who takes over by accepting
+    pub pending_authority: Option<Pubkey>,
+    /// Slots a queued parameter change waits before it can be executed
+    pub timelock_slots: u64,
+    /// Id of the next queued change
+    pub next_change_id: u64,
+    /// Cap on the balance entries a new blackbox, and any new mapping account, has room for
+    pub max_shard_capacity: u16,
+}
+
+/// Operations stopped by an emergency pause
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+pub struct PauseFlags {
+    pub deposit: bool,
+    pub transfer: bool,
+    /// Also stops `withdraw_all` and `reveal_balance`
+    pub withdraw: bool,
+}
+
+impl PauseFlags {
+    pub fn is_paused(&self, kind: OpKind) -> bool {
+        match kind {
+            OpKind::Deposit => self.deposit,
+            OpKind::Transfer => self.transfer,
+            OpKind::Withdraw => self.withdraw,
+        }
+    }
+
+    /// Operations paused by either `self` or `other`
+    pub fn union(self, other: PauseFlags) -> PauseFlags {
+        PauseFlags {
+            deposit: self.deposit || other.deposit,
+            transfer: self.transfer || other.transfer,
+            withdraw: self.withdraw || other.withdraw,
+        }
+    }
+}
+
+/// Fees a blackbox charges, in basis points of the amount moved
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+pub struct Fees {
+    pub deposit_bps: u16,
+    /// Also charged on `withdraw_all` payouts
+    pub withdraw_bps: u16,
+}
+
+impl Fees {
+    /// Every fee is within `ProgramConfig::max_fee_bps`
+    pub fn is_within(&self, config: &ProgramConfig) -> bool {
+        self.deposit_bps <= config.max_fee_bps && self.withdraw_bps <= config.max_fee_bps
+    }
+}
+
+/// Bounds on single deposits and withdrawals, and on what may leave the vault in one epoch.
+/// A maximum or cap of 0 means no limit.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+pub struct Limits {
+    pub min_deposit: u64,
+    pub max_deposit: u64,
+    pub min_withdraw: u64,
+    pub max_withdraw: u64,
+    /// Total withdrawals, fees included, queued per epoch
+    pub epoch_outflow_cap: u64,
+}
+
+impl Limits {
+    /// Every minimum is within its maximum
+    pub fn is_valid(&self) -> bool {
+        let within = |min: u64, max: u64| max == 0 || min <= max;
+        within(self.min_deposit, self.max_deposit) && within(self.min_withdraw, self.max_withdraw)
+    }
+}
+
+/// Checks operations go through on a blackbox besides its limits
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+pub struct PolicyHooks {
+    /// Key that must co-sign every deposit, direct or queued, such as a screening service's;
+    /// anyone can deposit if `None`
+    pub deposit_approver: Option<Pubkey>,
+}
+
+/// How a blackbox takes operations
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+pub struct ModeFlags {
+    /// Deposits, transfers and withdrawals only go through operation queues, batched with
+    /// others, rather than as computations of their own. `withdraw_all` is disabled.
+    pub queued_only: bool,
+}
+
+/// Configuration a blackbox is created with
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct InitBlackboxParams {
+    /// Balance entries each of the blackbox's mapping accounts has room for
+    pub shard_capacity: u16,
+    pub fees: Fees,
+    pub limits: Limits,
+    /// Circuit version the blackbox starts on; older ones serve blackboxes that have to stay
+    /// compatible with them
+    pub circuit_version: u8,
+    pub policy: PolicyHooks,
+    pub modes: ModeFlags,
+    /// Offset of the Arcium cluster the blackbox's computations are queued to
+    pub cluster_offset: u32,
+}
+
+impl InitBlackboxParams {
+    /// Fails if a parameter is outside the bounds `config` and the program set.
+    pub fn validate(&self, config: &ProgramConfig) -> Result<()> {
+        require!(
+            self.shard_capacity > 0 && self.shard_capacity <= config.max_shard_capacity,
+            ErrorCode::InvalidShardCapacity
+        );
+        require!(self.fees.is_within(config), ErrorCode::FeeTooHigh);
+        require!(self.limits.is_valid(), ErrorCode::InvalidLimits);
+        require!(
+            self.circuit_version >= 1 && self.circuit_version <= LATEST_CIRCUIT_VERSION,
+            ErrorCode::InvalidCircuitVersion
+        );
+        Ok(())
+    }
+}
+
+/// Withdrawals counted against `Limits::epoch_outflow_cap` in `epoch`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+pub struct EpochOutflow {
+    pub epoch: u64,
+    pub amount: u64,
+}
 
 #[account]
 #[derive(InitSpace)]
@@ -6,7 +160,233 @@ pub struct BlackboxAccount {
     pub bump: u8,
     pub token_mint: Pubkey,
     pub vault: Pubkey,
+    /// Token account fees are paid into
+    pub treasury: Pubkey,
     pub mapping_account_count: u8,
+    /// Operations paused on this blackbox only, on top of `ProgramConfig::paused`
+    pub paused: PauseFlags,
+    pub fees: Fees,
+    pub limits: Limits,
+    pub outflow: EpochOutflow,
+    /// Set for good by `ParameterChange::Emergency`: every operation stops, and owners claim
+    /// their exported balances instead
+    pub emergency: bool,
+    /// Circuit version new mapping accounts start on, which `migrate_mapping` moves existing
+    /// ones to
+    pub circuit_version: u8,
+    /// Balance entries each mapping account has room for, unless the config's cap was lowered
+    /// below it since
+    pub shard_capacity: u16,
+    pub policy: PolicyHooks,
+    pub modes: ModeFlags,
+    /// Offset of the Arcium cluster, with its mempool and staking pool, every computation of
+    /// the blackbox is queued to
+    pub cluster_offset: u32,
+}
+
+impl BlackboxAccount {
+    /// Fails if operations of `kind` are paused on this blackbox or everywhere, or if the
+    /// blackbox is in emergency mode.
+    pub fn require_not_paused(&self, config: &ProgramConfig, kind: OpKind) -> Result<()> {
+        require!(!self.emergency, ErrorCode::EmergencyMode);
+        require!(
+            !config.paused.is_paused(kind) && !self.paused.is_paused(kind),
+            ErrorCode::Paused
+        );
+        Ok(())
+    }
+
+    /// Fails if the blackbox only takes operations through its operation queues.
+    pub fn require_direct(&self) -> Result<()> {
+        require!(!self.modes.queued_only, ErrorCode::QueuedOnly);
+        Ok(())
+    }
+
+    /// Fails unless the deposit is co-signed by `approver`, if the blackbox's policy names one.
+    pub fn require_deposit_approved(&self, approver: Option<Pubkey>) -> Result<()> {
+        if let Some(deposit_approver) = self.policy.deposit_approver {
+            require!(
+                approver == Some(deposit_approver),
+                ErrorCode::DepositNotApproved
+            );
+        }
+        Ok(())
+    }
+
+    /// Fails if a deposit of `amount` is outside the blackbox's limits.
+    pub fn check_deposit(&self, amount: u64) -> Result<()> {
+        require!(
+            amount >= self.limits.min_deposit,
+            ErrorCode::DepositTooSmall
+        );
+        require!(
+            self.limits.max_deposit == 0 || amount <= self.limits.max_deposit,
+            ErrorCode::DepositTooLarge
+        );
+        Ok(())
+    }
+
+    /// Checks a withdrawal of `amount` against the blackbox's limits and counts it towards
+    /// `epoch`'s outflow. It's counted when queued, and released if the balance doesn't cover
+    /// it.
+    pub fn record_withdrawal(&mut self, amount: u64, epoch: u64) -> Result<()> {
+        require!(
+            amount >= self.limits.min_withdraw,
+            ErrorCode::WithdrawalTooSmall
+        );
+        require!(
+            self.limits.max_withdraw == 0 || amount <= self.limits.max_withdraw,
+            ErrorCode::WithdrawalTooLarge
+        );
+
+        if self.outflow.epoch != epoch {
+            self.outflow = EpochOutflow { epoch, amount: 0 };
+        }
+        let outflow = self.outflow.amount.saturating_add(amount);
+        require!(
+            self.limits.epoch_outflow_cap == 0 || outflow <= self.limits.epoch_outflow_cap,
+            ErrorCode::EpochOutflowCapReached
+        );
+        self.outflow.amount = outflow;
+        Ok(())
+    }
+
+    /// Stops counting a rejected withdrawal of `amount` counted in `epoch`. Outflow counted in
+    /// an earlier epoch has already been reset.
+    pub fn release_withdrawal(&mut self, amount: u64, epoch: u64) {
+        if self.outflow.epoch == epoch {
+            self.outflow.amount = self.outflow.amount.saturating_sub(amount);
+        }
+    }
+
+    /// Most a `withdraw_all` queued in `epoch` may pay out: the withdrawal maximum or what's
+    /// left of the epoch's outflow cap, `u64::MAX` if neither is set. The minimum doesn't
+    /// apply, so balances below it can still get out. A finite bound is counted towards the
+    /// outflow until `settle_withdraw_all` replaces it with the payout.
+    pub fn reserve_withdraw_all(&mut self, epoch: u64) -> Result<u64> {
+        if self.outflow.epoch != epoch {
+            self.outflow = EpochOutflow { epoch, amount: 0 };
+        }
+        let mut bound = match self.limits.max_withdraw {
+            0 => u64::MAX,
+            max => max,
+        };
+        if self.limits.epoch_outflow_cap != 0 {
+            let left = self
+                .limits
+                .epoch_outflow_cap
+                .saturating_sub(self.outflow.amount);
+            require!(left > 0, ErrorCode::EpochOutflowCapReached);
+            bound = bound.min(left);
+        }
+        if bound != u64::MAX {
+            self.outflow.amount = self.outflow.amount.saturating_add(bound);
+        }
+        Ok(bound)
+    }
+
+    /// Counts the `payout` of a `withdraw_all` reserved by `reserve_withdraw_all` in `epoch`
+    /// instead of its `bound`.
+    pub fn settle_withdraw_all(&mut self, bound: u64, payout: u64, epoch: u64) {
+        if self.outflow.epoch == epoch {
+            let reserved = if bound == u64::MAX { 0 } else { bound };
+            self.outflow.amount = self
+                .outflow
+                .amount
+                .saturating_sub(reserved)
+                .saturating_add(payout);
+        }
+    }
+
+    /// Balance entries a mapping account created now has room for: the blackbox's capacity,
+    /// capped by `ProgramConfig::max_shard_capacity` like its fees.
+    pub fn new_shard_capacity(&self, config: &ProgramConfig) -> u16 {
+        self.shard_capacity.min(config.max_shard_capacity)
+    }
+
+    /// Fee on moving `amount` in or out for an operation of `kind`, rounded down. Transfers
+    /// are free, and fees above `ProgramConfig::max_fee_bps` are charged at the cap.
+    pub fn fee(&self, config: &ProgramConfig, kind: OpKind, amount: u64) -> u64 {
+        let bps = match kind {
+            OpKind::Deposit => self.fees.deposit_bps,
+            OpKind::Transfer => 0,
+            OpKind::Withdraw => self.fees.withdraw_bps,
+        };
+        let bps = bps.min(config.max_fee_bps);
+        (u128::from(amount) * u128::from(bps) / u128::from(MAX_BPS)) as u64
+    }
+}
+
+/// A parameter update, applied by `execute_change` once the timelock has passed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum ParameterChange {
+    /// `ProgramConfig::max_fee_bps`
+    MaxFee(u16),
+    /// A blackbox's fees
+    Fees(Fees),
+    /// A blackbox's limits
+    Limits(Limits),
+    /// The pause flags of a blackbox, or the program-wide ones. Only resuming needs the
+    /// timelock; `pause` stops operations immediately.
+    Paused(PauseFlags),
+    /// `ProgramConfig::timelock_slots`; the change itself waits out the current delay
+    Timelock(u64),
+    /// Puts a blackbox in emergency mode, which can't be left
+    Emergency,
+    /// Moves a blackbox to a newer circuit version; its mapping accounts follow as they're
+    /// migrated
+    CircuitVersion(u8),
+    /// `ProgramConfig::max_shard_capacity`; mapping accounts already created keep their
+    /// capacity
+    MaxShardCapacity(u16),
+}
+
+impl ParameterChange {
+    /// Fails if the change is out of range, or doesn't apply to `blackbox`, or to the program
+    /// config if that's `None`.
+    pub fn validate(
+        &self,
+        config: &ProgramConfig,
+        blackbox: Option<&BlackboxAccount>,
+    ) -> Result<()> {
+        match (self, blackbox.is_some()) {
+            (Self::MaxFee(max_fee_bps), false) => {
+                require!(*max_fee_bps <= MAX_BPS, ErrorCode::FeeTooHigh)
+            }
+            (Self::Fees(fees), true) => require!(fees.is_within(config), ErrorCode::FeeTooHigh),
+            (Self::Limits(limits), true) => require!(limits.is_valid(), ErrorCode::InvalidLimits),
+            (Self::CircuitVersion(version), true) => {
+                let current = blackbox.map_or(0, |blackbox| blackbox.circuit_version);
+                require!(
+                    *version > current && *version <= LATEST_CIRCUIT_VERSION,
+                    ErrorCode::InvalidCircuitVersion
+                )
+            }
+            (Self::MaxShardCapacity(max_shard_capacity), false) => require!(
+                *max_shard_capacity > 0
+                    && usize::from(*max_shard_capacity) <= crate::MAX_ENTRIES_PER_ACCOUNT,
+                ErrorCode::InvalidShardCapacity
+            ),
+            (Self::Paused(_), _) | (Self::Timelock(_), false) | (Self::Emergency, true) => {}
+            _ => return err!(ErrorCode::InvalidChangeTarget),
+        }
+        Ok(())
+    }
+}
+
+/// A parameter change waiting out the timelock
+#[account]
+#[derive(InitSpace)]
+pub struct PendingChange {
+    pub bump: u8,
+    pub id: u64,
+    /// Blackbox the change applies to; the program config if `None`
+    pub blackbox: Option<Pubkey>,
+    pub change: ParameterChange,
+    /// First slot the change can be executed in
+    pub executable_slot: u64,
+    /// Receives the account's rent back when the change is executed or cancelled
+    pub payer: Pubkey,
 }
 
 #[account]
@@ -14,10 +394,290 @@ pub struct BlackboxAccount {
 pub struct MappingAccount {
     pub index: u8,
     pub token_mint: Pubkey,
+    /// Nonce the stored `Mapping` ciphertexts were encrypted under
+    pub nonce: u128,
+    /// Incremented every time a callback stores a new mapping. Computations capture it when
+    /// queued, and their results are only applied if it hasn't moved since.
+    pub version: u64,
+    /// Circuit version the stored mapping was last written by, which picks the deposit,
+    /// transfer and withdraw circuits run against it
+    pub circuit_version: u8,
+    /// Balance entries the account has room for; circuits add none past it
+    pub capacity: u16,
     pub encrypted_pubkeys: Vec<[u8; 32]>,
     pub encrypted_balances: Vec<[u8; 32]>,
 }
 
+impl MappingAccount {
+    /// Replaces the stored mapping with the re-encrypted `Mapping` returned by a circuit.
+    pub fn store_mapping(&mut self, mapping: [[u8; 32]; 2], nonce: u128) {
+        self.encrypted_pubkeys = vec![mapping[0]];
+        self.encrypted_balances = vec![mapping[1]];
+        self.nonce = nonce;
+        self.version += 1;
+    }
+}
+
+/// Kind of an operation waiting in an `OpQueueAccount`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum OpKind {
+    Deposit,
+    Transfer,
+    Withdraw,
+}
+
+impl OpKind {
+    /// Discriminant understood by the `process_batch` circuit
+    pub fn circuit_kind(&self) -> u8 {
+        match self {
+            OpKind::Deposit => confidential_ixs::OP_DEPOSIT,
+            OpKind::Transfer => confidential_ixs::OP_TRANSFER,
+            OpKind::Withdraw => confidential_ixs::OP_WITHDRAW,
+        }
+    }
+}
+
+/// An operation appended by a user, applied on the next flush of the queue
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct QueuedOp {
+    pub kind: OpKind,
+    pub owner: Pubkey,
+    /// x25519 key the operation's status is encrypted for, and a transfer's `enc_transfer` was
+    /// encrypted with. Zero for deposits and withdrawals re-queued from a direct computation,
+    /// whose owner learns the outcome from its payout or balance instead.
+    pub client_key: [u8; 32],
+    /// Encrypted `TransferArgs`: recipient, amount and cover-traffic flag, transfers only
+    pub enc_transfer: [[u8; 32]; 3],
+    /// Plaintext amount, deposits and withdrawals only
+    pub amount: u64,
+    /// Nonce `enc_transfer` was encrypted under
+    pub nonce: u128,
+    /// Token account receiving a withdrawal's payout, or the deposit's source, which a deposit
+    /// left uncredited by emergency mode is refunded to
+    pub destination: Pubkey,
+    /// Epoch the amount was counted towards the outflow cap in, withdrawals only
+    pub epoch: u64,
+}
+
+/// Encrypted status of an operation applied by the last flushed batch
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct OpResult {
+    pub owner: Pubkey,
+    /// Status encrypted for the operation's `QueuedOp::client_key`
+    pub enc_status: [u8; 32],
+    pub nonce: u128,
+}
+
+/// Operations waiting to be applied to a mapping account in a single batch computation
+#[account]
+#[derive(InitSpace)]
+pub struct OpQueueAccount {
+    pub bump: u8,
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    /// Operations appended since the last flush
+    #[max_len(MAX_QUEUED_OPS)]
+    pub pending: Vec<QueuedOp>,
+    /// Operations handed to the MXE by the last flush, cleared by the callback
+    #[max_len(MAX_BATCH_SIZE)]
+    pub in_flight: Vec<QueuedOp>,
+    /// Nonce the in-flight batch re-encrypts the mapping under
+    pub in_flight_nonce: u128,
+    /// Computation offset of the in-flight batch
+    pub in_flight_offset: u64,
+    /// Slot the in-flight batch was flushed at
+    pub in_flight_slot: u64,
+    /// Results of the last completed batch
+    #[max_len(MAX_BATCH_SIZE)]
+    pub results: Vec<OpResult>,
+}
+
+impl OpQueueAccount {
+    /// Whether another operation can be appended
+    pub fn has_room(&self) -> bool {
+        self.pending.len() + self.in_flight.len() < MAX_QUEUED_OPS
+    }
+
+    /// Puts the in-flight batch back in front of the pending operations, for the next flush.
+    pub fn requeue_in_flight(&mut self) {
+        let mut ops = std::mem::take(&mut self.in_flight);
+        ops.append(&mut self.pending);
+        self.pending = ops;
+    }
+}
+
+/// Lifecycle of a queued computation
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum OpStatus {
+    /// Queued, callback not received yet
+    Pending,
+    /// Callback applied the result
+    Applied,
+    /// Callback rejected the result because the mapping changed while it was computed
+    Stale,
+    /// Stale, and handed to the mapping account's operation queue
+    Requeued,
+    /// Callback landed after the blackbox entered emergency mode, so the result was discarded:
+    /// balances are claimed from the export instead, and deposits refunded
+    Dropped,
+    /// Callback found nothing to apply, such as a `withdraw_all` by someone without a balance,
+    /// and left the mapping as it was
+    Failed,
+    /// Deposit left uncredited by emergency mode, or by a mapping account without room for its
+    /// owner, whose tokens went back to its source
+    Refunded,
+}
+
+/// Record of a queued computation, keyed by its computation offset.
+///
+/// Created when the computation is queued, so offsets can't be reused, and resolved by the
+/// callback, so a callback delivered twice has no effect.
+#[account]
+#[derive(InitSpace)]
+pub struct OpRecord {
+    pub bump: u8,
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub computation_offset: u64,
+    /// Mapping account version the computation started from
+    pub mapping_version: u64,
+    pub owner: Pubkey,
+    /// Paid the record's rent, which `close_op_record` returns once it's resolved
+    pub payer: Pubkey,
+    /// Plaintext amount paid out by the callback, withdrawals only. For `withdraw_all`, the
+    /// most it may pay out.
+    pub amount: u64,
+    /// Epoch `amount` was counted towards the outflow in, withdrawals only
+    pub epoch: u64,
+    /// Token account receiving a withdrawal's payout, or the deposit's source, which a deposit
+    /// left uncredited by emergency mode is refunded to
+    pub destination: Pubkey,
+    pub status: OpStatus,
+    /// Equivalent queued operation, appended to the operation queue if the result is stale.
+    /// `None` for computations that can't be expressed as a batch operation.
+    pub requeue: Option<QueuedOp>,
+}
+
+impl OpRecord {
+    /// Whether the record is done with: its callback resolved it, and a stale operation was
+    /// handed to the operation queue or can't be
+    pub fn is_resolved(&self) -> bool {
+        match self.status {
+            OpStatus::Pending => false,
+            OpStatus::Stale => self.requeue.is_none(),
+            OpStatus::Applied
+            | OpStatus::Requeued
+            | OpStatus::Dropped
+            | OpStatus::Failed
+            | OpStatus::Refunded => true,
+        }
+    }
+}
+
+/// Marks a user's ciphertext nonce as consumed, so an encrypted payload can't be replayed
+#[account]
+#[derive(InitSpace)]
+pub struct ConsumedNonce {
+    pub bump: u8,
+    /// Paid the account's rent, which `close_consumed_nonce` returns
+    pub payer: Pubkey,
+}
+
+/// A user's balance re-encrypted for their x25519 key, keyed by computation offset.
+///
+/// Created when the reveal is queued and filled in by its callback.
+#[account]
+#[derive(InitSpace)]
+pub struct BalanceReveal {
+    pub bump: u8,
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub computation_offset: u64,
+    /// Mapping account version the balance was read from
+    pub mapping_version: u64,
+    pub owner: Pubkey,
+    /// x25519 public key the balance is encrypted for
+    pub client_key: [u8; 32],
+    /// Nonce the balance is encrypted under
+    pub nonce: u128,
+    /// Encrypted balance, `None` until the callback arrives
+    pub enc_balance: Option<[u8; 32]>,
+}
+
+/// Whether a blackbox's vault covered every credited balance, keyed by the computation offset
+/// the proof was requested with.
+///
+/// Created when the proof is requested. The MXE sums the shards a range of at most
+/// `MAX_SOLVENCY_SHARDS` at a time, each range's callback carrying the total on encrypted in
+/// `enc_total`, and the last one records whether the vault covers it. The vault amount is the
+/// one at `requested_slot`; each range's balances are those when the range was queued.
+#[account]
+#[derive(InitSpace)]
+pub struct SolvencyAttestation {
+    pub bump: u8,
+    pub blackbox: Pubkey,
+    pub computation_offset: u64,
+    /// Vault token amount the credited total was compared against
+    pub vault_amount: u64,
+    /// Number of mapping accounts summed: every one the blackbox had when the proof was
+    /// requested
+    pub shards: u8,
+    /// Shards whose balances `enc_total` already counts
+    pub summed_shards: u8,
+    /// Number of shards in the range whose computation is queued, `None` when none is
+    pub queued_range: Option<u8>,
+    /// Total of the summed shards' balances, encrypted for the MXE alone
+    pub enc_total: [u8; 32],
+    /// Nonce `enc_total` is encrypted under
+    pub total_nonce: u128,
+    /// Slot the proof was requested at
+    pub requested_slot: u64,
+    /// Whether the vault covered the total, `None` until the last range's callback arrives
+    pub solvent: Option<bool>,
+    /// Slot the callback recorded the result at, 0 until it arrives
+    pub attested_slot: u64,
+}
+
+/// A balance exported from a mapping account in emergency mode
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct ExportedBalance {
+    /// Balance and salt, encrypted for the x25519 form of the owner's ed25519 key
+    pub enc_claim: [[u8; 32]; 2],
+    /// `claim_commitment` of the owner, balance and salt
+    pub commitment: [u8; 32],
+}
+
+/// One page of a mapping account's balances, exported for their owners in emergency mode.
+///
+/// Keyed by the mapping account, its version and the page, so each page is exported once per
+/// mapping. Created when the export is queued and filled in by its callback; claims are only
+/// accepted while the mapping account is still at `mapping_version`.
+#[account]
+#[derive(InitSpace)]
+pub struct EmergencyExport {
+    pub bump: u8,
+    pub blackbox: Pubkey,
+    pub mapping_account: Pubkey,
+    pub mapping_version: u64,
+    pub page: u8,
+    pub computation_offset: u64,
+    /// Nonce of the first entry's claim; entry `k` is encrypted under `nonce + k`
+    pub nonce: u128,
+    /// Whether the callback has stored the entries
+    pub exported: bool,
+    #[max_len(MAX_EXPORT_ENTRIES)]
+    pub entries: Vec<ExportedBalance>,
+    /// Whether the mapping has entries past this page
+    pub more: bool,
+}
+
+/// Marks an owner's exported balance in a mapping account as claimed
+#[account]
+#[derive(InitSpace)]
+pub struct ClaimedBalance {
+    pub bump: u8,
+}
+
 /// Error codes for the blackbox program
 #[error_code]
 pub enum ErrorCode {
@@ -27,5 +687,74 @@ pub enum ErrorCode {
     NoSpaceAvailable,
     #[msg("Invalid vault")]
     InvalidVault,
+    #[msg("Operation queue is full")]
+    QueueFull,
+    #[msg("Operation queue is empty")]
+    QueueEmpty,
+    #[msg("A batch for this queue is already being computed")]
+    BatchInFlight,
+    #[msg("Computation output is malformed")]
+    InvalidComputationOutput,
+    #[msg("Invalid payout destination")]
+    InvalidDestination,
+    #[msg("Operation is not stale or can't be re-queued")]
+    NotRequeueable,
+    #[msg("Operation is paused")]
+    Paused,
+    #[msg("Signer is not the program authority")]
+    Unauthorized,
+    #[msg("Fee exceeds the program's cap")]
+    FeeTooHigh,
+    #[msg("Invalid treasury")]
+    InvalidTreasury,
+    #[msg("Deposit is below the blackbox's minimum")]
+    DepositTooSmall,
+    #[msg("Deposit is above the blackbox's maximum")]
+    DepositTooLarge,
+    #[msg("Withdrawal is below the blackbox's minimum")]
+    WithdrawalTooSmall,
+    #[msg("Withdrawal is above the blackbox's maximum")]
+    WithdrawalTooLarge,
+    #[msg("Withdrawal would exceed the blackbox's outflow cap for this epoch")]
+    EpochOutflowCapReached,
+    #[msg("A minimum exceeds its maximum")]
+    InvalidLimits,
+    #[msg("Change is still timelocked")]
+    ChangeTimelocked,
+    #[msg("Change doesn't apply to this account")]
+    InvalidChangeTarget,
+    #[msg("Blackbox already has as many mapping accounts as it can index")]
+    TooManyShards,
+    #[msg("The shards of the proof's next range must be passed, in index order")]
+    InvalidMappingShards,
+    #[msg("Blackbox is in emergency mode")]
+    EmergencyMode,
+    #[msg("Blackbox is not in emergency mode")]
+    NotInEmergency,
+    #[msg("Export was computed against an outdated mapping")]
+    ExportOutdated,
+    #[msg("Claim doesn't open the exported commitment")]
+    InvalidClaim,
+    #[msg("Circuit version must be newer than the current one and at most the latest")]
+    InvalidCircuitVersion,
+    #[msg("Mapping account is already on the blackbox's circuit version")]
+    AlreadyMigrated,
+    #[msg("Computation definition doesn't run the circuit the program was built with")]
+    CircuitMismatch,
+    #[msg("Shard capacity must be positive and within the program's maximum")]
+    InvalidShardCapacity,
+    #[msg("Blackbox only takes operations through its operation queues")]
+    QueuedOnly,
+    #[msg("Deposit must be co-signed by the blackbox's deposit approver")]
+    DepositNotApproved,
+    #[msg("No batch is in flight, or it hasn't timed out yet")]
+    BatchNotStuck,
+    #[msg("Operation is still pending or waiting to be re-queued")]
+    RecordInUse,
+    #[msg("Only deposits left uncredited by emergency mode are refunded")]
+    NotRefundable,
+    #[msg("Solvency proof already has a range queued, or has summed every shard")]
+    SolvencyRangeUnavailable,
+    #[msg("Solvency proof's circuit doesn't read the shard's circuit version")]
+    UnsupportedCircuitVersion,
 }
-
@@ -0,0 +1,129 @@
+//! Solvency proofs over every mapping account of a blackbox.
+
+mod common;
+
+use blackbox::state::ErrorCode;
+use common::{assert_custom_error, TestEnv};
+use confidential_ixs::MAX_SOLVENCY_SHARDS;
+
+#[tokio::test]
+async fn solvency_covers_every_shard() {
+    let mut env = TestEnv::new().await;
+    let (first, vault) = (env.mapping_account, env.vault);
+    let second = env.initialize_mapping_account().await.unwrap();
+    let alice = env.user(1_000).await;
+    let bob = env.user(1_000).await;
+    env.deposit_into(&alice, first, vault, 300).await.unwrap();
+    env.deposit_into(&bob, second, vault, 200).await.unwrap();
+    env.settle_ok().await;
+
+    let slot = env.slot().await;
+    let attestation = env.prove_solvency().await.unwrap();
+    let pending = env.solvency_attestation(&attestation).await;
+    assert_eq!(pending.blackbox, env.blackbox);
+    assert_eq!(pending.vault_amount, 500);
+    assert_eq!(pending.shards, 2);
+    assert_eq!(pending.requested_slot, slot);
+    assert_eq!(pending.solvent, None);
+
+    env.advance_slots(5).await;
+    env.settle_ok().await;
+    let attested = env.solvency_attestation(&attestation).await;
+    assert_eq!(attested.solvent, Some(true));
+    assert_eq!(attested.attested_slot, slot + 5);
+}
+
+#[tokio::test]
+async fn vault_short_of_the_balances_is_insolvent() {
+    let mut env = TestEnv::new().await;
+    let second = env.initialize_mapping_account().await.unwrap();
+    let vault = env.vault;
+    let alice = env.user(1_000).await;
+    env.deposit(&alice, 300).await.unwrap();
+    env.deposit_into(&alice, second, vault, 200).await.unwrap();
+    env.settle_ok().await;
+
+    // One token short of what the second shard credits on top of the first
+    env.set_token_balance(&vault, 499).await;
+    let attestation = env.prove_solvency().await.unwrap();
+    env.settle_ok().await;
+    let attestation = env.solvency_attestation(&attestation).await;
+    assert_eq!(attestation.vault_amount, 499);
+    assert_eq!(attestation.solvent, Some(false));
+
+    env.set_token_balance(&vault, 500).await;
+    let attestation = env.prove_solvency().await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(
+        env.solvency_attestation(&attestation).await.solvent,
+        Some(true)
+    );
+}
+
+#[tokio::test]
+async fn every_shard_must_be_passed() {
+    let mut env = TestEnv::new().await;
+    env.initialize_mapping_account().await.unwrap();
+
+    assert_custom_error(
+        env.prove_solvency_over(1).await.map(drop),
+        ErrorCode::InvalidMappingShards,
+    );
+    env.prove_solvency_over(2).await.unwrap();
+    env.settle_ok().await;
+}
+
+#[tokio::test]
+async fn proofs_sum_the_shards_a_range_at_a_time() {
+    assert_eq!(
+        blackbox_client::MAX_SOLVENCY_SHARDS as usize,
+        MAX_SOLVENCY_SHARDS
+    );
+    let mut env = TestEnv::new().await;
+    let (first, vault) = (env.mapping_account, env.vault);
+    let mut last = first;
+    for _ in 0..2 * MAX_SOLVENCY_SHARDS {
+        last = env.initialize_mapping_account().await.unwrap();
+    }
+    let shards = 2 * MAX_SOLVENCY_SHARDS as u8 + 1;
+    let alice = env.user(1_000).await;
+    env.deposit_into(&alice, first, vault, 300).await.unwrap();
+    env.deposit_into(&alice, last, vault, 200).await.unwrap();
+    env.settle_ok().await;
+
+    let attestation = env.prove_solvency().await.unwrap();
+    assert_custom_error(
+        env.continue_solvency_proof(attestation).await,
+        ErrorCode::SolvencyRangeUnavailable,
+    );
+    env.settle_ok().await;
+    let proof = env.solvency_attestation(&attestation).await;
+    assert_eq!(proof.shards, shards);
+    assert_eq!(proof.summed_shards, MAX_SOLVENCY_SHARDS as u8);
+    assert_eq!(proof.queued_range, None);
+    assert_eq!(proof.solvent, None);
+
+    // Ranges pick up where the last one stopped
+    assert_custom_error(
+        env.continue_solvency_proof_over(attestation, 0..MAX_SOLVENCY_SHARDS as u8)
+            .await,
+        ErrorCode::InvalidMappingShards,
+    );
+    env.finish_solvency_proof(attestation).await;
+    let proof = env.solvency_attestation(&attestation).await;
+    assert_eq!(proof.summed_shards, shards);
+    assert_eq!(proof.solvent, Some(true));
+    assert_custom_error(
+        env.continue_solvency_proof(attestation).await,
+        ErrorCode::SolvencyRangeUnavailable,
+    );
+
+    // The last shard's balance counts as much as the first's
+    env.set_token_balance(&vault, 499).await;
+    let attestation = env.prove_solvency().await.unwrap();
+    env.finish_solvency_proof(attestation).await;
+    assert_eq!(
+        env.solvency_attestation(&attestation).await.solvent,
+        Some(false)
+    );
+}
@@ -0,0 +1,216 @@
+//! Per-blackbox configuration: the parameters `init_blackbox` takes, the bounds the program
+//! config sets on them, and the policy hooks and modes they turn on.
+
+mod common;
+
+use blackbox::state::{ErrorCode, Fees, Limits, OpStatus, LATEST_CIRCUIT_VERSION};
+use common::{assert_custom_error, TestEnv};
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+// Discriminator, index, token mint, nonce, version, circuit version, capacity and vec lengths
+const MAPPING_ACCOUNT_HEADER_SIZE: usize = 8 + 1 + 32 + 16 + 8 + 1 + 2 + 4 + 4;
+
+#[tokio::test]
+async fn blackboxes_start_with_their_params() {
+    let mut env = TestEnv::before_blackbox().await;
+    let admin = env.admin.insecure_clone();
+    env.set_max_fee(&admin, 100).await.unwrap();
+    let mut params = env.default_params().await;
+    params.shard_capacity = 16;
+    params.fees = Fees {
+        deposit_bps: 10,
+        withdraw_bps: 30,
+    };
+    params.limits = Limits {
+        min_deposit: 100,
+        ..Limits::default()
+    };
+    params.circuit_version = 1;
+    env.init_blackbox(&admin, &params).await.unwrap();
+
+    let blackbox = env.blackbox_account().await;
+    assert_eq!(blackbox.shard_capacity, 16);
+    assert!(blackbox.fees == params.fees && blackbox.limits == params.limits);
+    assert_eq!(blackbox.circuit_version, 1);
+    assert_eq!(blackbox.cluster_offset, mock_mxe::CLUSTER_OFFSET);
+
+    // Shards are sized for the blackbox's capacity and start on its circuit version
+    let mapping_account = env.initialize_mapping_account().await.unwrap();
+    let account = env
+        .banks
+        .get_account(mapping_account)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.data.len(), MAPPING_ACCOUNT_HEADER_SIZE + 16 * 64);
+    let shard = env.mapping_account_data(&mapping_account).await;
+    assert_eq!((shard.circuit_version, shard.capacity), (1, 16));
+}
+
+#[tokio::test]
+async fn params_stay_within_the_config_bounds() {
+    let mut env = TestEnv::before_blackbox().await;
+    let admin = env.admin.insecure_clone();
+    let defaults = env.default_params().await;
+    let max_shard_capacity = env.program_config().await.max_shard_capacity;
+
+    let mut cases = Vec::new();
+    for shard_capacity in [0, max_shard_capacity + 1] {
+        let mut params = defaults;
+        params.shard_capacity = shard_capacity;
+        cases.push((params, ErrorCode::InvalidShardCapacity));
+    }
+    let mut params = defaults;
+    params.fees.deposit_bps = 1;
+    cases.push((params, ErrorCode::FeeTooHigh));
+    let mut params = defaults;
+    params.limits = Limits {
+        min_withdraw: 500,
+        max_withdraw: 100,
+        ..Limits::default()
+    };
+    cases.push((params, ErrorCode::InvalidLimits));
+    for circuit_version in [0, LATEST_CIRCUIT_VERSION + 1] {
+        let mut params = defaults;
+        params.circuit_version = circuit_version;
+        cases.push((params, ErrorCode::InvalidCircuitVersion));
+    }
+    for (params, error) in cases {
+        assert_custom_error(env.init_blackbox(&admin, &params).await, error);
+    }
+    // The cluster has to exist
+    let mut params = defaults;
+    params.cluster_offset = mock_mxe::CLUSTER_OFFSET + 1;
+    assert!(env.init_blackbox(&admin, &params).await.is_err());
+    assert_custom_error(
+        env.init_blackbox(&Keypair::new(), &defaults).await,
+        ErrorCode::Unauthorized,
+    );
+
+    // The cap moves through the timelock like any other parameter
+    for max_shard_capacity in [0, max_shard_capacity + 1] {
+        assert_custom_error(
+            env.set_max_shard_capacity(&admin, max_shard_capacity).await,
+            ErrorCode::InvalidShardCapacity,
+        );
+    }
+    env.set_max_shard_capacity(&admin, 8).await.unwrap();
+    let mut params = defaults;
+    params.shard_capacity = 9;
+    assert_custom_error(
+        env.init_blackbox(&admin, &params).await,
+        ErrorCode::InvalidShardCapacity,
+    );
+    params.shard_capacity = 8;
+    env.init_blackbox(&admin, &params).await.unwrap();
+}
+
+#[tokio::test]
+async fn new_shards_take_a_lowered_cap() {
+    let mut env = TestEnv::with_params(|params| params.shard_capacity = 16).await;
+    let (admin, first) = (env.admin.insecure_clone(), env.mapping_account);
+    env.set_max_shard_capacity(&admin, 8).await.unwrap();
+
+    let second = env.initialize_mapping_account().await.unwrap();
+    let account = env.banks.get_account(second).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), MAPPING_ACCOUNT_HEADER_SIZE + 8 * 64);
+    assert_eq!(env.mapping_account_data(&second).await.capacity, 8);
+    // The blackbox and its existing shards keep theirs
+    assert_eq!(env.blackbox_account().await.shard_capacity, 16);
+    assert_eq!(env.mapping_account_data(&first).await.capacity, 16);
+}
+
+#[tokio::test]
+async fn full_shards_refund_new_owners() {
+    let mut env = TestEnv::with_params(|params| params.shard_capacity = 1).await;
+    let (mapping_account, vault) = (env.mapping_account, env.vault);
+    let alice = env.user(1_000).await;
+    let bob = env.user(1_000).await;
+    env.deposit(&alice, 300).await.unwrap();
+    env.settle_ok().await;
+
+    // Bob's deposit finds no room and is paid back, and Alice can't transfer to him either
+    env.deposit(&bob, 200).await.unwrap();
+    let refused = env.last_computation_offset();
+    env.settle_ok().await;
+    assert!(env.op_record(refused).await.status == OpStatus::Refunded);
+    assert_eq!(env.token_balance(&bob.token_account).await, 1_000);
+    env.transfer(&alice, &bob.pubkey(), 100).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(300));
+    assert_eq!(env.balance_of(&bob.pubkey()).await, None);
+
+    // Queued deposits are refunded out of their batch, while owners in the shard keep
+    // depositing
+    let op_queue = env.init_op_queue(mapping_account).await.unwrap();
+    env.enqueue_deposit(&bob, op_queue, 200).await.unwrap();
+    env.enqueue_deposit(&alice, op_queue, 100).await.unwrap();
+    env.flush_op_queue(mapping_account).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.token_balance(&bob.token_account).await, 1_000);
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(400));
+    assert_eq!(env.token_balance(&vault).await, 400);
+    let queue = env.op_queue(&op_queue).await;
+    assert!(queue.in_flight.is_empty() && queue.pending.is_empty());
+}
+
+#[tokio::test]
+async fn queued_only_blackboxes_batch_every_operation() {
+    let mut env = TestEnv::with_params(|params| params.modes.queued_only = true).await;
+    let mapping_account = env.mapping_account;
+    let alice = env.user(1_000).await;
+    let bob = env.user(0).await;
+
+    assert_custom_error(env.deposit(&alice, 300).await, ErrorCode::QueuedOnly);
+    assert_custom_error(
+        env.transfer(&alice, &bob.pubkey(), 100).await,
+        ErrorCode::QueuedOnly,
+    );
+    assert_custom_error(env.withdraw(&alice, 100).await, ErrorCode::QueuedOnly);
+
+    let op_queue = env.init_op_queue(mapping_account).await.unwrap();
+    env.enqueue_deposit(&alice, op_queue, 300).await.unwrap();
+    env.flush_op_queue(mapping_account).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(300));
+}
+
+#[tokio::test]
+async fn deposits_need_the_approver() {
+    let approver = Keypair::new();
+    let mut env = TestEnv::with_params(|params| {
+        params.policy.deposit_approver = Some(approver.pubkey());
+    })
+    .await;
+    let mapping_account = env.mapping_account;
+    let op_queue = env.init_op_queue(mapping_account).await.unwrap();
+    let alice = env.user(1_000).await;
+
+    assert_custom_error(
+        env.deposit(&alice, 300).await,
+        ErrorCode::DepositNotApproved,
+    );
+    assert_custom_error(
+        env.enqueue_deposit(&alice, op_queue, 300).await,
+        ErrorCode::DepositNotApproved,
+    );
+    env.deposit_approver = Some(Keypair::new());
+    assert_custom_error(
+        env.deposit(&alice, 300).await,
+        ErrorCode::DepositNotApproved,
+    );
+
+    env.deposit_approver = Some(approver.insecure_clone());
+    env.deposit(&alice, 300).await.unwrap();
+    env.enqueue_deposit(&alice, op_queue, 200).await.unwrap();
+    env.settle_ok().await;
+    env.flush_op_queue(mapping_account).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(500));
+
+    // Only deposits are gated
+    env.deposit_approver = None;
+    env.withdraw(&alice, 500).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.token_balance(&alice.token_account).await, 1_000);
+}
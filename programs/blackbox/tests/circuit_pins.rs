@@ -0,0 +1,93 @@
+//! Circuit pinning: computations are only queued against comp defs running the circuits
+//! `confidential-ixs` was built with.
+
+mod common;
+
+use arcium_client::idl::arcium::types::{
+    CircuitSource, OffChainCircuitSource, OnChainCircuitSource,
+};
+use blackbox::state::{ErrorCode, LATEST_CIRCUIT_VERSION};
+use common::{assert_custom_error, TestEnv};
+use mock_mxe::Circuit;
+use solana_sdk::signer::Signer;
+
+/// Off-chain source for `circuit`, committing to its pinned hash unless `tampered`
+fn off_chain(circuit: Circuit, tampered: bool) -> CircuitSource {
+    let mut hash = circuit.pinned_hash();
+    hash[0] ^= tampered as u8;
+    CircuitSource::OffChain(OffChainCircuitSource {
+        source: format!("{}.arcis", circuit.name()),
+        hash,
+    })
+}
+
+#[tokio::test]
+async fn comp_defs_are_registered_with_the_pinned_circuits() {
+    let mut env = TestEnv::new().await;
+    for circuit in Circuit::ALL {
+        let comp_def = env.comp_def(circuit).await;
+        assert!(
+            matches!(
+                &comp_def.circuit_source,
+                CircuitSource::OffChain(source)
+                    if source.hash == circuit.pinned_hash()
+                        && source.source == format!("{}.arcis", circuit.name())
+            ),
+            "{circuit:?} isn't registered with its pinned circuit"
+        );
+    }
+}
+
+#[tokio::test]
+async fn swapped_circuits_are_refused() {
+    let mut env = TestEnv::new().await;
+    let deposit = Circuit::Deposit.for_version(LATEST_CIRCUIT_VERSION);
+    let alice = env.user(1_000).await;
+
+    env.swap_circuit(deposit, off_chain(deposit, true)).await;
+    assert_custom_error(env.deposit(&alice, 300).await, ErrorCode::CircuitMismatch);
+
+    // Only the swapped circuit is refused
+    env.swap_circuit(deposit, off_chain(deposit, false)).await;
+    env.deposit(&alice, 300).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(300));
+}
+
+#[tokio::test]
+async fn circuits_without_a_hash_are_refused() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let withdraw = Circuit::Withdraw.for_version(LATEST_CIRCUIT_VERSION);
+    let alice = env.user(1_000).await;
+    env.deposit(&alice, 300).await.unwrap();
+    env.settle_ok().await;
+
+    env.swap_circuit(
+        withdraw,
+        CircuitSource::OnChain(OnChainCircuitSource {
+            is_completed: true,
+            upload_auth: admin.pubkey(),
+        }),
+    )
+    .await;
+    assert_custom_error(env.withdraw(&alice, 300).await, ErrorCode::CircuitMismatch);
+}
+
+#[tokio::test]
+async fn each_version_is_pinned_on_its_own() {
+    let mut env = TestEnv::new().await;
+    let alice = env.user(1_000).await;
+    env.rewind_to_circuit_version_1().await;
+
+    // A blackbox still on version 1 doesn't run the version 2 circuit
+    let deposit_v2 = Circuit::Deposit.for_version(2);
+    env.swap_circuit(deposit_v2, off_chain(deposit_v2, true))
+        .await;
+    env.deposit(&alice, 300).await.unwrap();
+    env.settle_ok().await;
+
+    env.swap_circuit(Circuit::Deposit, off_chain(Circuit::Deposit, true))
+        .await;
+    assert_custom_error(env.deposit(&alice, 300).await, ErrorCode::CircuitMismatch);
+}
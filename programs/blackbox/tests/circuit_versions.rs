@@ -0,0 +1,148 @@
+//! Circuit versions: blackboxes move to newer versions of the circuits writing a mapping back,
+//! and their mapping accounts follow through `migrate_mapping`.
+
+mod common;
+
+use blackbox::state::{ErrorCode, LATEST_CIRCUIT_VERSION};
+use common::{assert_custom_error, TestEnv};
+use solana_sdk::signature::Keypair;
+
+#[tokio::test]
+async fn new_blackboxes_drop_emptied_entries() {
+    let mut env = TestEnv::new().await;
+    let mapping_account = env.mapping_account;
+    assert_eq!(
+        env.blackbox_account().await.circuit_version,
+        LATEST_CIRCUIT_VERSION
+    );
+    assert_eq!(
+        env.mapping_account_data(&mapping_account)
+            .await
+            .circuit_version,
+        LATEST_CIRCUIT_VERSION
+    );
+
+    let alice = env.user(1_000).await;
+    env.deposit(&alice, 400).await.unwrap();
+    env.settle_ok().await;
+    env.withdraw(&alice, 400).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, None);
+    assert_eq!(env.token_balance(&alice.token_account).await, 1_000);
+
+    // Withdrawing everything and batched withdrawals drop the entry too
+    let bob = env.user(1_000).await;
+    env.deposit(&bob, 300).await.unwrap();
+    env.settle_ok().await;
+    env.withdraw_all_from(&bob, mapping_account).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&bob.pubkey()).await, None);
+    assert_eq!(env.token_balance(&bob.token_account).await, 1_000);
+
+    let op_queue = env.init_op_queue(mapping_account).await.unwrap();
+    env.enqueue_deposit(&alice, op_queue, 200).await.unwrap();
+    env.enqueue_withdraw(&alice, op_queue, 200).await.unwrap();
+    env.flush_op_queue(mapping_account).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, None);
+    assert_eq!(env.token_balance(&alice.token_account).await, 1_000);
+}
+
+#[tokio::test]
+async fn migration_follows_the_blackbox_version() {
+    let mut env = TestEnv::new().await;
+    let (admin, mapping_account) = (env.admin.insecure_clone(), env.mapping_account);
+    env.rewind_to_circuit_version_1().await;
+    let alice = env.user(1_000).await;
+    let bob = env.user(1_000).await;
+    env.deposit(&alice, 300).await.unwrap();
+    env.deposit(&bob, 500).await.unwrap();
+    env.settle_ok().await;
+
+    // Version 1 keeps the entry a withdrawal empties
+    env.withdraw(&alice, 300).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(0));
+
+    // Nothing to migrate to until the blackbox moves on
+    assert_custom_error(
+        env.migrate_mapping(&admin, mapping_account).await,
+        ErrorCode::AlreadyMigrated,
+    );
+    env.set_circuit_version(&admin, 2).await.unwrap();
+    assert_eq!(env.blackbox_account().await.circuit_version, 2);
+    assert_eq!(
+        env.mapping_account_data(&mapping_account)
+            .await
+            .circuit_version,
+        1
+    );
+    assert_custom_error(
+        env.migrate_mapping(&Keypair::new(), mapping_account).await,
+        ErrorCode::Unauthorized,
+    );
+
+    env.migrate_mapping(&admin, mapping_account).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(
+        env.mapping_account_data(&mapping_account)
+            .await
+            .circuit_version,
+        2
+    );
+    assert_eq!(env.balance_of(&alice.pubkey()).await, None);
+    assert_eq!(env.balance_of(&bob.pubkey()).await, Some(500));
+    assert_custom_error(
+        env.migrate_mapping(&admin, mapping_account).await,
+        ErrorCode::AlreadyMigrated,
+    );
+
+    // Operations on the migrated shard run the version 2 circuits
+    env.withdraw(&bob, 500).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&bob.pubkey()).await, None);
+    assert_eq!(env.token_balance(&bob.token_account).await, 1_000);
+}
+
+#[tokio::test]
+async fn migration_outdates_queued_operations() {
+    let mut env = TestEnv::new().await;
+    let (admin, mapping_account) = (env.admin.insecure_clone(), env.mapping_account);
+    env.rewind_to_circuit_version_1().await;
+    env.set_circuit_version(&admin, 2).await.unwrap();
+    let alice = env.user(1_000).await;
+
+    // The deposit reads the mapping the migration replaces
+    env.migrate_mapping(&admin, mapping_account).await.unwrap();
+    env.deposit(&alice, 300).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(
+        env.mapping_account_data(&mapping_account)
+            .await
+            .circuit_version,
+        2
+    );
+    assert_eq!(env.balance_of(&alice.pubkey()).await, None);
+}
+
+#[tokio::test]
+async fn circuit_version_only_moves_forward() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    env.rewind_to_circuit_version_1().await;
+
+    for circuit_version in [0, 1, LATEST_CIRCUIT_VERSION + 1] {
+        assert_custom_error(
+            env.set_circuit_version(&admin, circuit_version).await,
+            ErrorCode::InvalidCircuitVersion,
+        );
+    }
+    env.set_circuit_version(&admin, LATEST_CIRCUIT_VERSION)
+        .await
+        .unwrap();
+    assert_custom_error(
+        env.set_circuit_version(&admin, LATEST_CIRCUIT_VERSION)
+            .await,
+        ErrorCode::InvalidCircuitVersion,
+    );
+}
@@ -0,0 +1,1750 @@
+//! Shared fixture for the blackbox integration tests: a `ProgramTest` bank with the mock Arcium
+//! program, the program config, a token mint, an initialized blackbox with one mapping account,
+//! and every computation definition registered.
+
+#![allow(dead_code)]
+
+use std::ops::Range;
+
+use anchor_lang::{
+    solana_program::{
+        bpf_loader_upgradeable,
+        instruction::{AccountMeta, Instruction},
+        program_pack::Pack,
+        system_program,
+    },
+    AccountDeserialize, AccountSerialize, InstructionData, ToAccountMetas,
+};
+use arcis::prelude::PublicKey;
+use arcium_anchor::{
+    CLOCK_PDA_SEED, CLUSTER_PDA_SEED, MEMPOOL_PDA_SEED, MXE_PDA_SEED, POOL_PDA_SEED,
+};
+use arcium_client::idl::arcium::{
+    accounts::ComputationDefinitionAccount, types::CircuitSource, ID_CONST as ARCIUM_PROG_ID,
+};
+use blackbox::state::{
+    BalanceReveal, BlackboxAccount, EmergencyExport, ErrorCode, Fees, InitBlackboxParams, Limits,
+    MappingAccount, ModeFlags, OpQueueAccount, OpRecord, ParameterChange, PauseFlags,
+    PendingChange, PolicyHooks, ProgramConfig, SolvencyAttestation, LATEST_CIRCUIT_VERSION,
+};
+use confidential_ixs::{
+    claim_commitment, EmergencyClaim, Mapping, TransferArgs, MAX_SOLVENCY_SHARDS,
+};
+use mock_mxe::{processor::sighash, Circuit, Executed, MockMxe, MxeCipher};
+use solana_program_test::{BanksClient, BanksClientError, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    epoch_schedule::EpochSchedule,
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+};
+
+pub const DECIMALS: u8 = 6;
+
+/// Instructions of the blackbox program, other than callbacks
+pub const INSTRUCTIONS: [&str; 45] = [
+    "init_program_config",
+    "pause",
+    "propose_authority",
+    "accept_authority",
+    "queue_change",
+    "execute_change",
+    "cancel_change",
+    "collect_fees",
+    "init_blackbox",
+    "initialize_mapping_account",
+    "init_deposit_comp_def",
+    "deposit",
+    "init_transfer_comp_def",
+    "transfer",
+    "init_withdraw_comp_def",
+    "withdraw",
+    "init_deposit_v2_comp_def",
+    "init_transfer_v2_comp_def",
+    "init_withdraw_v2_comp_def",
+    "init_withdraw_all_v2_comp_def",
+    "init_process_batch_v2_comp_def",
+    "requeue_stale_op",
+    "requeue_stuck_batch",
+    "close_op_record",
+    "close_consumed_nonce",
+    "init_op_queue",
+    "init_process_batch_comp_def",
+    "enqueue_deposit",
+    "enqueue_transfer",
+    "enqueue_withdraw",
+    "flush_op_queue",
+    "init_withdraw_all_comp_def",
+    "withdraw_all",
+    "init_reveal_balance_comp_def",
+    "reveal_balance",
+    "init_prove_solvency_comp_def",
+    "prove_solvency",
+    "continue_solvency_proof",
+    "init_export_balances_comp_def",
+    "export_balances",
+    "claim_emergency",
+    "refund_deposit",
+    "refund_queued_deposits",
+    "init_migrate_mapping_v2_comp_def",
+    "migrate_mapping",
+];
+
+/// Arcium accounts every queue instruction passes
+pub struct ArciumAccounts {
+    pub clock: Pubkey,
+    pub cluster: Pubkey,
+    pub mempool: Pubkey,
+    pub mxe: Pubkey,
+    pub pool: Pubkey,
+}
+
+impl ArciumAccounts {
+    fn new() -> Self {
+        let pda = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &ARCIUM_PROG_ID).0;
+        let offset = mock_mxe::CLUSTER_OFFSET.to_le_bytes();
+        Self {
+            clock: pda(&[CLOCK_PDA_SEED]),
+            cluster: pda(&[CLUSTER_PDA_SEED, &offset]),
+            mempool: pda(&[MEMPOOL_PDA_SEED, &offset]),
+            mxe: pda(&[MXE_PDA_SEED]),
+            pool: pda(&[POOL_PDA_SEED, &offset]),
+        }
+    }
+}
+
+/// A blackbox user with a funded token account
+pub struct User {
+    pub keypair: Keypair,
+    pub token_account: Pubkey,
+}
+
+impl User {
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+}
+
+pub struct TestEnv {
+    pub banks: BanksClient,
+    pub payer: Keypair,
+    /// Upgrade authority of the program, and the config's initial authority
+    pub admin: Keypair,
+    pub mock: MockMxe,
+    pub mint: Keypair,
+    pub blackbox: Pubkey,
+    pub vault: Pubkey,
+    pub treasury: Pubkey,
+    pub mapping_account: Pubkey,
+    /// Co-signs deposits, for blackboxes whose policy names a deposit approver
+    pub deposit_approver: Option<Keypair>,
+    pub arcium: ArciumAccounts,
+    /// Compute units consumed by each successful blackbox instruction and callback, in order
+    pub compute_units: Vec<(String, u64)>,
+    context: ProgramTestContext,
+    next_offset: u64,
+    next_nonce: u128,
+}
+
+impl TestEnv {
+    /// Starts a bank and sets up a blackbox with one mapping account and all computation
+    /// definitions registered.
+    pub async fn new() -> Self {
+        Self::with_params(|_| {}).await
+    }
+
+    /// Like `new`, with the blackbox created from `default_params` adjusted by `configure`.
+    pub async fn with_params(configure: impl FnOnce(&mut InitBlackboxParams)) -> Self {
+        let mut env = Self::before_blackbox().await;
+        let mut params = env.default_params().await;
+        configure(&mut params);
+        let admin = env.admin.insecure_clone();
+        env.init_blackbox(&admin, &params).await.unwrap();
+        env.initialize_mapping_account().await.unwrap();
+        for circuit in Circuit::ALL {
+            env.init_comp_def(circuit).await.unwrap();
+        }
+
+        env
+    }
+
+    /// Starts a bank with the program config and the token mint, but no blackbox yet.
+    pub async fn before_blackbox() -> Self {
+        let mock = MockMxe::new();
+        let admin = Keypair::new();
+        let mut program_test = mock_mxe::program_test();
+        program_test.add_account(program_data_address(), program_data(&admin.pubkey()));
+        let context = program_test.start_with_context().await;
+        let (banks, payer) = (context.banks_client.clone(), context.payer.insecure_clone());
+
+        let mint = Keypair::new();
+        let blackbox =
+            Pubkey::find_program_address(&[b"blackbox", mint.pubkey().as_ref()], &blackbox::ID).0;
+        let vault = Pubkey::find_program_address(&[b"vault", blackbox.as_ref()], &blackbox::ID).0;
+        let treasury = treasury_address(&blackbox);
+
+        let mut env = Self {
+            banks,
+            payer,
+            admin,
+            mock,
+            mapping_account: mapping_account_address(&mint.pubkey(), 0),
+            deposit_approver: None,
+            mint,
+            blackbox,
+            vault,
+            treasury,
+            arcium: ArciumAccounts::new(),
+            compute_units: Vec::new(),
+            context,
+            next_offset: 1,
+            next_nonce: 1,
+        };
+
+        env.init_program_config().await.unwrap();
+        env.create_mint().await;
+
+        env
+    }
+
+    /// Full-size shards on the latest circuit version and the mock's cluster, with no fees,
+    /// limits, hooks or modes
+    pub async fn default_params(&mut self) -> InitBlackboxParams {
+        InitBlackboxParams {
+            shard_capacity: self.program_config().await.max_shard_capacity,
+            fees: Fees::default(),
+            limits: Limits::default(),
+            circuit_version: LATEST_CIRCUIT_VERSION,
+            policy: PolicyHooks::default(),
+            modes: ModeFlags::default(),
+            cluster_offset: mock_mxe::CLUSTER_OFFSET,
+        }
+    }
+
+    /// Warps the bank to the first slot of the next epoch.
+    pub async fn next_epoch(&mut self) {
+        let schedule: EpochSchedule = self.banks.get_sysvar().await.unwrap();
+        let clock: Clock = self.banks.get_sysvar().await.unwrap();
+        let slot = schedule.get_first_slot_in_epoch(clock.epoch + 1);
+        self.context.warp_to_slot(slot).unwrap();
+    }
+
+    /// Warps the bank `slots` slots ahead.
+    pub async fn advance_slots(&mut self, slots: u64) {
+        let clock: Clock = self.banks.get_sysvar().await.unwrap();
+        self.context.warp_to_slot(clock.slot + slots).unwrap();
+    }
+
+    pub async fn slot(&mut self) -> u64 {
+        let clock: Clock = self.banks.get_sysvar().await.unwrap();
+        clock.slot
+    }
+
+    pub async fn send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), BanksClientError> {
+        let blockhash = self.banks.get_latest_blockhash().await?;
+        let mut all_signers = vec![&self.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        let processed = self
+            .banks
+            .process_transaction_with_metadata(transaction)
+            .await?;
+        processed.result?;
+
+        // Attribute the units to the instruction when it's the only one in the transaction
+        if let ([instruction], Some(metadata)) = (instructions, processed.metadata) {
+            if let Some(name) = instruction_name(instruction) {
+                self.compute_units
+                    .push((name.to_string(), metadata.compute_units_consumed));
+            }
+        }
+        Ok(())
+    }
+
+    async fn create_mint(&mut self) {
+        let rent = self.banks.get_rent().await.unwrap();
+        let mint = self.mint.pubkey();
+        let instructions = [
+            system_instruction::create_account(
+                &self.payer.pubkey(),
+                &mint,
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint(
+                &spl_token::ID,
+                &mint,
+                &self.payer.pubkey(),
+                None,
+                DECIMALS,
+            )
+            .unwrap(),
+        ];
+        let mint_keypair = self.mint.insecure_clone();
+        self.send(&instructions, &[&mint_keypair]).await.unwrap();
+    }
+
+    /// Creates a token account owned by `owner` holding `amount` freshly minted tokens.
+    pub async fn token_account(&mut self, owner: &Pubkey, amount: u64) -> Pubkey {
+        let rent = self.banks.get_rent().await.unwrap();
+        let account = Keypair::new();
+        let mut instructions = vec![
+            system_instruction::create_account(
+                &self.payer.pubkey(),
+                &account.pubkey(),
+                rent.minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_account3(
+                &spl_token::ID,
+                &account.pubkey(),
+                &self.mint.pubkey(),
+                owner,
+            )
+            .unwrap(),
+        ];
+        if amount > 0 {
+            instructions.push(
+                spl_token::instruction::mint_to(
+                    &spl_token::ID,
+                    &self.mint.pubkey(),
+                    &account.pubkey(),
+                    &self.payer.pubkey(),
+                    &[],
+                    amount,
+                )
+                .unwrap(),
+            );
+        }
+        self.send(&instructions, &[&account]).await.unwrap();
+        account.pubkey()
+    }
+
+    /// Creates a user holding `amount` tokens.
+    pub async fn user(&mut self, amount: u64) -> User {
+        // Recipients are encrypted as one field element, so keys must be below p
+        let keypair = std::iter::repeat_with(Keypair::new)
+            .find(|keypair| keypair.pubkey().to_bytes()[31] < 0x80)
+            .unwrap();
+        let token_account = self.token_account(&keypair.pubkey(), amount).await;
+        User {
+            keypair,
+            token_account,
+        }
+    }
+
+    /// x25519 key `user`'s client encrypts with, which their statuses are encrypted for
+    pub fn client_key(&self, user: &User) -> [u8; 32] {
+        let owner = PublicKey::from(user.pubkey().to_bytes());
+        self.mock.cipher.client_key(owner)
+    }
+
+    pub async fn token_balance(&mut self, account: &Pubkey) -> u64 {
+        let account = self.banks.get_account(*account).await.unwrap().unwrap();
+        spl_token::state::Account::unpack(&account.data)
+            .unwrap()
+            .amount
+    }
+
+    /// Overwrites the amount `account` holds, as if tokens had left or entered it outside the
+    /// program.
+    pub async fn set_token_balance(&mut self, account: &Pubkey, amount: u64) {
+        let mut account_data = self.banks.get_account(*account).await.unwrap().unwrap();
+        let mut token_account = spl_token::state::Account::unpack(&account_data.data).unwrap();
+        token_account.amount = amount;
+        spl_token::state::Account::pack(token_account, &mut account_data.data).unwrap();
+        self.context.set_account(account, &account_data.into());
+    }
+
+    pub async fn mapping_account_data(&mut self, address: &Pubkey) -> MappingAccount {
+        let account = self.banks.get_account(*address).await.unwrap().unwrap();
+        MappingAccount::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    /// Decrypted contents of the mapping account at `address`
+    pub async fn mapping_of(&mut self, address: &Pubkey) -> Mapping {
+        let mapping_account = self.mapping_account_data(address).await;
+        self.mock.mapping(&mapping_account)
+    }
+
+    /// Decrypted contents of the blackbox's first mapping account
+    pub async fn mapping(&mut self) -> Mapping {
+        let address = self.mapping_account;
+        self.mapping_of(&address).await
+    }
+
+    /// Balance the first mapping account holds for `owner`, or `None` if it has no entry
+    pub async fn balance_of(&mut self, owner: &Pubkey) -> Option<u64> {
+        let mapping = self.mapping().await;
+        let key = PublicKey::from(owner.to_bytes());
+        (0..mapping.pubkeys.len())
+            .find(|&i| mapping.pubkeys[i] == key)
+            .map(|i| mapping.balances[i])
+    }
+
+    /// Executes every queued computation and delivers its output.
+    pub async fn settle(&mut self) -> Vec<Executed> {
+        let executed = self
+            .mock
+            .process_pending(&mut self.banks, &self.payer)
+            .await
+            .unwrap();
+        for executed in &executed {
+            if let (Ok(()), Some(units)) = (&executed.result, executed.compute_units) {
+                self.compute_units
+                    .push((executed.computation.circuit.callback_name(), units));
+            }
+        }
+        executed
+    }
+
+    /// Executes every queued computation and asserts all callbacks succeeded.
+    pub async fn settle_ok(&mut self) {
+        for executed in self.settle().await {
+            if let Err(err) = executed.result {
+                panic!("{:?} callback failed: {err}", executed.computation.circuit);
+            }
+        }
+    }
+
+    fn computation_offset(&mut self) -> u64 {
+        self.next_offset += 1;
+        self.next_offset
+    }
+
+    /// Offset of the computation queued last
+    pub fn last_computation_offset(&self) -> u64 {
+        self.next_offset
+    }
+
+    /// A transfer nonce not used before in this environment
+    pub fn nonce(&mut self) -> u128 {
+        self.next_nonce += 1;
+        self.next_nonce
+    }
+
+    pub async fn init_program_config(&mut self) -> Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::InitProgramConfig {
+                config: program_config_address(),
+                program_data: program_data_address(),
+                authority: self.admin.pubkey(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::InitProgramConfig {}.data(),
+        };
+        let admin = self.admin.insecure_clone();
+        self.send(&[instruction], &[&admin]).await
+    }
+
+    pub async fn program_config(&mut self) -> ProgramConfig {
+        let address = program_config_address();
+        let account = self.banks.get_account(address).await.unwrap().unwrap();
+        ProgramConfig::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    /// Pauses operations on `blackbox`, or on every blackbox if it's `None`, signed by
+    /// `authority`.
+    pub async fn pause(
+        &mut self,
+        authority: &Keypair,
+        blackbox: Option<Pubkey>,
+        paused: PauseFlags,
+    ) -> Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::Pause {
+                config: program_config_address(),
+                blackbox,
+                authority: authority.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::Pause { paused }.data(),
+        };
+        self.send(&[instruction], &[authority]).await
+    }
+
+    pub async fn propose_authority(
+        &mut self,
+        authority: &Keypair,
+        new_authority: Option<Pubkey>,
+    ) -> Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::ProposeAuthority {
+                config: program_config_address(),
+                authority: authority.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::ProposeAuthority { new_authority }.data(),
+        };
+        self.send(&[instruction], &[authority]).await
+    }
+
+    pub async fn accept_authority(
+        &mut self,
+        new_authority: &Keypair,
+    ) -> Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::AcceptAuthority {
+                config: program_config_address(),
+                new_authority: new_authority.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::AcceptAuthority {}.data(),
+        };
+        self.send(&[instruction], &[new_authority]).await
+    }
+
+    /// Queues `change` to `blackbox`, or to the program config if it's `None`, returning the
+    /// change's id. The environment's payer pays the rent.
+    pub async fn queue_change(
+        &mut self,
+        authority: &Keypair,
+        blackbox: Option<Pubkey>,
+        change: ParameterChange,
+    ) -> Result<u64, BanksClientError> {
+        let id = self.program_config().await.next_change_id;
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::QueueChange {
+                config: program_config_address(),
+                blackbox,
+                change: pending_change_address(id),
+                payer: self.payer.pubkey(),
+                authority: authority.pubkey(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::QueueChange { change }.data(),
+        };
+        self.send(&[instruction], &[authority]).await?;
+        Ok(id)
+    }
+
+    pub async fn pending_change(&mut self, id: u64) -> Option<PendingChange> {
+        let account = self
+            .banks
+            .get_account(pending_change_address(id))
+            .await
+            .unwrap()?;
+        Some(PendingChange::try_deserialize(&mut &account.data[..]).unwrap())
+    }
+
+    pub async fn execute_change(&mut self, id: u64) -> Result<(), BanksClientError> {
+        let change = self.pending_change(id).await.expect("no such change");
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::ExecuteChange {
+                config: program_config_address(),
+                change: pending_change_address(id),
+                blackbox: change.blackbox,
+                payer: change.payer,
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::ExecuteChange {}.data(),
+        };
+        self.send(&[instruction], &[]).await
+    }
+
+    pub async fn cancel_change(
+        &mut self,
+        authority: &Keypair,
+        id: u64,
+    ) -> Result<(), BanksClientError> {
+        let change = self.pending_change(id).await.expect("no such change");
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::CancelChange {
+                config: program_config_address(),
+                change: pending_change_address(id),
+                payer: change.payer,
+                authority: authority.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::CancelChange {}.data(),
+        };
+        self.send(&[instruction], &[authority]).await
+    }
+
+    /// Queues `change` and executes it straight away, which needs the timelock to be 0.
+    async fn change_now(
+        &mut self,
+        authority: &Keypair,
+        blackbox: Option<Pubkey>,
+        change: ParameterChange,
+    ) -> Result<(), BanksClientError> {
+        let id = self.queue_change(authority, blackbox, change).await?;
+        self.execute_change(id).await
+    }
+
+    /// Sets the pause flags of `blackbox`, or the program-wide ones if it's `None`, through an
+    /// immediate change.
+    pub async fn set_paused(
+        &mut self,
+        authority: &Keypair,
+        blackbox: Option<Pubkey>,
+        paused: PauseFlags,
+    ) -> Result<(), BanksClientError> {
+        self.change_now(authority, blackbox, ParameterChange::Paused(paused))
+            .await
+    }
+
+    pub async fn set_max_fee(
+        &mut self,
+        authority: &Keypair,
+        max_fee_bps: u16,
+    ) -> Result<(), BanksClientError> {
+        self.change_now(authority, None, ParameterChange::MaxFee(max_fee_bps))
+            .await
+    }
+
+    pub async fn set_max_shard_capacity(
+        &mut self,
+        authority: &Keypair,
+        max_shard_capacity: u16,
+    ) -> Result<(), BanksClientError> {
+        self.change_now(
+            authority,
+            None,
+            ParameterChange::MaxShardCapacity(max_shard_capacity),
+        )
+        .await
+    }
+
+    pub async fn set_fees(
+        &mut self,
+        authority: &Keypair,
+        fees: Fees,
+    ) -> Result<(), BanksClientError> {
+        let blackbox = self.blackbox;
+        self.change_now(authority, Some(blackbox), ParameterChange::Fees(fees))
+            .await
+    }
+
+    pub async fn set_limits(
+        &mut self,
+        authority: &Keypair,
+        limits: Limits,
+    ) -> Result<(), BanksClientError> {
+        let blackbox = self.blackbox;
+        self.change_now(authority, Some(blackbox), ParameterChange::Limits(limits))
+            .await
+    }
+
+    /// Moves the blackbox to `circuit_version` through an immediate change.
+    pub async fn set_circuit_version(
+        &mut self,
+        authority: &Keypair,
+        circuit_version: u8,
+    ) -> Result<(), BanksClientError> {
+        let blackbox = self.blackbox;
+        self.change_now(
+            authority,
+            Some(blackbox),
+            ParameterChange::CircuitVersion(circuit_version),
+        )
+        .await
+    }
+
+    /// Puts the blackbox and its mapping accounts back on circuit version 1, as if they had
+    /// been created before version 2 existed.
+    pub async fn rewind_to_circuit_version_1(&mut self) {
+        let mut blackbox = self.blackbox_account().await;
+        blackbox.circuit_version = 1;
+        let address = self.blackbox;
+        self.overwrite_account(&address, &blackbox).await;
+        for index in 0..blackbox.mapping_account_count {
+            let address = mapping_account_address(&self.mint.pubkey(), index);
+            let mut mapping_account = self.mapping_account_data(&address).await;
+            mapping_account.circuit_version = 1;
+            self.overwrite_account(&address, &mapping_account).await;
+        }
+    }
+
+    pub async fn comp_def(&mut self, circuit: Circuit) -> ComputationDefinitionAccount {
+        let account = self
+            .banks
+            .get_account(circuit.comp_def_address())
+            .await
+            .unwrap()
+            .unwrap();
+        ComputationDefinitionAccount::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    /// Re-registers `circuit`'s comp def with `circuit_source`, as a swapped-out circuit would be.
+    pub async fn swap_circuit(&mut self, circuit: Circuit, circuit_source: CircuitSource) {
+        let mut comp_def = self.comp_def(circuit).await;
+        comp_def.circuit_source = circuit_source;
+        self.overwrite_account(&circuit.comp_def_address(), &comp_def)
+            .await;
+    }
+
+    /// Overwrites the start of the account at `address` with `state`.
+    async fn overwrite_account<T: AccountSerialize>(&mut self, address: &Pubkey, state: &T) {
+        let mut account = self.banks.get_account(*address).await.unwrap().unwrap();
+        let mut data = Vec::new();
+        state.try_serialize(&mut data).unwrap();
+        account.data[..data.len()].copy_from_slice(&data);
+        self.context.set_account(address, &account.into());
+    }
+
+    /// Comp def of the version of `circuit` that `mapping_account` runs
+    async fn comp_def_for(&mut self, circuit: Circuit, mapping_account: &Pubkey) -> Pubkey {
+        let mapping_account = self.mapping_account_data(mapping_account).await;
+        circuit
+            .for_version(mapping_account.circuit_version)
+            .comp_def_address()
+    }
+
+    /// Puts the blackbox in emergency mode through an immediate change.
+    pub async fn enter_emergency(&mut self, authority: &Keypair) -> Result<(), BanksClientError> {
+        let blackbox = self.blackbox;
+        self.change_now(authority, Some(blackbox), ParameterChange::Emergency)
+            .await
+    }
+
+    /// Moves the blackbox's collected fees to the token account `destination`.
+    pub async fn collect_fees(
+        &mut self,
+        authority: &Keypair,
+        destination: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::CollectFees {
+                config: program_config_address(),
+                blackbox: self.blackbox,
+                treasury: self.treasury,
+                destination,
+                authority: authority.pubkey(),
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::CollectFees {}.data(),
+        };
+        self.send(&[instruction], &[authority]).await
+    }
+
+    pub async fn blackbox_account(&mut self) -> BlackboxAccount {
+        let account = self
+            .banks
+            .get_account(self.blackbox)
+            .await
+            .unwrap()
+            .unwrap();
+        BlackboxAccount::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    pub async fn init_blackbox(
+        &mut self,
+        authority: &Keypair,
+        params: &InitBlackboxParams,
+    ) -> Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::InitBlackbox {
+                config: program_config_address(),
+                token_mint: self.mint.pubkey(),
+                blackbox: self.blackbox,
+                vault: self.vault,
+                treasury: self.treasury,
+                cluster: Pubkey::find_program_address(
+                    &[CLUSTER_PDA_SEED, &params.cluster_offset.to_le_bytes()],
+                    &ARCIUM_PROG_ID,
+                )
+                .0,
+                authority: authority.pubkey(),
+                payer: self.payer.pubkey(),
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::InitBlackbox { params: *params }.data(),
+        };
+        self.send(&[instruction], &[authority]).await
+    }
+
+    /// Initializes the blackbox's next mapping account, returning its address.
+    pub async fn initialize_mapping_account(&mut self) -> Result<Pubkey, BanksClientError> {
+        let blackbox = self.banks.get_account(self.blackbox).await?.unwrap();
+        let blackbox =
+            blackbox::state::BlackboxAccount::try_deserialize(&mut &blackbox.data[..]).unwrap();
+        let mapping_account =
+            mapping_account_address(&self.mint.pubkey(), blackbox.mapping_account_count);
+
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::InitializeMappingAccount {
+                blackbox: self.blackbox,
+                config: program_config_address(),
+                mapping_account,
+                payer: self.payer.pubkey(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::InitializeMappingAccount {}.data(),
+        };
+        self.send(&[instruction], &[]).await?;
+        Ok(mapping_account)
+    }
+
+    pub async fn init_comp_def(&mut self, circuit: Circuit) -> Result<(), BanksClientError> {
+        let payer = self.payer.pubkey();
+        let mxe_account =
+            Pubkey::find_program_address(&[MXE_PDA_SEED, blackbox::ID.as_ref()], &ARCIUM_PROG_ID).0;
+        let comp_def_account = circuit.comp_def_address();
+
+        // Every comp def init instruction takes the same accounts
+        macro_rules! comp_def_ix {
+            ($accounts:ident) => {
+                Instruction {
+                    program_id: blackbox::ID,
+                    accounts: blackbox::accounts::$accounts {
+                        payer,
+                        mxe_account,
+                        comp_def_account,
+                        arcium_program: ARCIUM_PROG_ID,
+                        system_program: system_program::ID,
+                    }
+                    .to_account_metas(None),
+                    data: blackbox::instruction::$accounts {}.data(),
+                }
+            };
+        }
+        let instruction = match circuit {
+            Circuit::Deposit => comp_def_ix!(InitDepositCompDef),
+            Circuit::Transfer => comp_def_ix!(InitTransferCompDef),
+            Circuit::Withdraw => comp_def_ix!(InitWithdrawCompDef),
+            Circuit::WithdrawAll => comp_def_ix!(InitWithdrawAllCompDef),
+            Circuit::RevealBalance => comp_def_ix!(InitRevealBalanceCompDef),
+            Circuit::ProcessBatch => comp_def_ix!(InitProcessBatchCompDef),
+            Circuit::ProveSolvency => comp_def_ix!(InitProveSolvencyCompDef),
+            Circuit::ExportBalances => comp_def_ix!(InitExportBalancesCompDef),
+            Circuit::DepositV2 => comp_def_ix!(InitDepositV2CompDef),
+            Circuit::TransferV2 => comp_def_ix!(InitTransferV2CompDef),
+            Circuit::WithdrawV2 => comp_def_ix!(InitWithdrawV2CompDef),
+            Circuit::WithdrawAllV2 => comp_def_ix!(InitWithdrawAllV2CompDef),
+            Circuit::ProcessBatchV2 => comp_def_ix!(InitProcessBatchV2CompDef),
+            Circuit::MigrateMappingV2 => comp_def_ix!(InitMigrateMappingV2CompDef),
+        };
+        self.send(&[instruction], &[]).await
+    }
+
+    /// Queues a deposit of `amount` from `user` into `mapping_account`, paid into `vault`.
+    pub async fn deposit_into(
+        &mut self,
+        user: &User,
+        mapping_account: Pubkey,
+        vault: Pubkey,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let computation_offset = self.computation_offset();
+        let comp_def = self.comp_def_for(Circuit::Deposit, &mapping_account).await;
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::Deposit {
+                blackbox: self.blackbox,
+                config: program_config_address(),
+                mapping_account,
+                op_record: op_record_address(&self.blackbox, computation_offset),
+                op_queue: None,
+                user_token_account: user.token_account,
+                vault,
+                treasury: self.treasury,
+                user: user.pubkey(),
+                deposit_approver: self.deposit_approver.as_ref().map(Signer::pubkey),
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                arcium_program: ARCIUM_PROG_ID,
+                clock: self.arcium.clock,
+                cluster: self.arcium.cluster,
+                comp_def,
+                mempool: self.arcium.mempool,
+                mxe: self.arcium.mxe,
+                pool: self.arcium.pool,
+                payer: self.payer.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::Deposit {
+                computation_offset,
+                amount,
+            }
+            .data(),
+        };
+        let signers = self.deposit_signers(user);
+        self.send(&[instruction], &signers.iter().collect::<Vec<_>>())
+            .await
+    }
+
+    /// `user` and, if the blackbox has one, the deposit approver
+    fn deposit_signers(&self, user: &User) -> Vec<Keypair> {
+        std::iter::once(&user.keypair)
+            .chain(&self.deposit_approver)
+            .map(Keypair::insecure_clone)
+            .collect()
+    }
+
+    pub async fn deposit(&mut self, user: &User, amount: u64) -> Result<(), BanksClientError> {
+        let (mapping_account, vault) = (self.mapping_account, self.vault);
+        self.deposit_into(user, mapping_account, vault, amount)
+            .await
+    }
+
+    /// Queues a transfer within `mapping_account`, encrypted client-side under `nonce`.
+    pub async fn transfer_in(
+        &mut self,
+        mapping_account: Pubkey,
+        from: &User,
+        to: &Pubkey,
+        amount: u64,
+        noop: bool,
+        nonce: u128,
+    ) -> Result<(), BanksClientError> {
+        let computation_offset = self.computation_offset();
+        let comp_def = self.comp_def_for(Circuit::Transfer, &mapping_account).await;
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::Transfer {
+                blackbox: self.blackbox,
+                config: program_config_address(),
+                mapping_account,
+                op_record: op_record_address(&self.blackbox, computation_offset),
+                op_queue: None,
+                consumed_nonce: consumed_nonce_address(&from.pubkey(), nonce),
+                sender: from.pubkey(),
+                system_program: system_program::ID,
+                arcium_program: ARCIUM_PROG_ID,
+                clock: self.arcium.clock,
+                cluster: self.arcium.cluster,
+                comp_def,
+                mempool: self.arcium.mempool,
+                mxe: self.arcium.mxe,
+                pool: self.arcium.pool,
+                payer: self.payer.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::Transfer {
+                computation_offset,
+                enc_transfer: self.mock.cipher.seal_transfer(
+                    TransferArgs {
+                        to: PublicKey::from(to.to_bytes()),
+                        amount,
+                        noop,
+                    },
+                    nonce,
+                ),
+                client_key: self.client_key(from),
+                nonce,
+            }
+            .data(),
+        };
+        self.send(&[instruction], &[&from.keypair]).await
+    }
+
+    /// Queues a transfer within the first mapping account, encrypted under `nonce`.
+    pub async fn transfer_with_nonce(
+        &mut self,
+        from: &User,
+        to: &Pubkey,
+        amount: u64,
+        noop: bool,
+        nonce: u128,
+    ) -> Result<(), BanksClientError> {
+        let mapping_account = self.mapping_account;
+        self.transfer_in(mapping_account, from, to, amount, noop, nonce)
+            .await
+    }
+
+    pub async fn transfer(
+        &mut self,
+        from: &User,
+        to: &Pubkey,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let nonce = self.nonce();
+        self.transfer_with_nonce(from, to, amount, false, nonce)
+            .await
+    }
+
+    /// Queues a withdrawal of `amount` from `mapping_account`, paid from `vault` to
+    /// `destination`.
+    pub async fn withdraw_from(
+        &mut self,
+        user: &User,
+        mapping_account: Pubkey,
+        vault: Pubkey,
+        destination: Pubkey,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let computation_offset = self.computation_offset();
+        let comp_def = self.comp_def_for(Circuit::Withdraw, &mapping_account).await;
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::Withdraw {
+                blackbox: self.blackbox,
+                config: program_config_address(),
+                mapping_account,
+                op_record: op_record_address(&self.blackbox, computation_offset),
+                op_queue: None,
+                vault,
+                destination,
+                user: user.pubkey(),
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                arcium_program: ARCIUM_PROG_ID,
+                clock: self.arcium.clock,
+                cluster: self.arcium.cluster,
+                comp_def,
+                mempool: self.arcium.mempool,
+                mxe: self.arcium.mxe,
+                pool: self.arcium.pool,
+                payer: self.payer.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::Withdraw {
+                computation_offset,
+                amount,
+            }
+            .data(),
+        };
+        self.send(&[instruction], &[&user.keypair]).await
+    }
+
+    pub async fn withdraw(&mut self, user: &User, amount: u64) -> Result<(), BanksClientError> {
+        let (mapping_account, vault) = (self.mapping_account, self.vault);
+        self.withdraw_from(user, mapping_account, vault, user.token_account, amount)
+            .await
+    }
+    /// Queues a withdrawal of `user`'s whole balance in `mapping_account`.
+    pub async fn withdraw_all_from(
+        &mut self,
+        user: &User,
+        mapping_account: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let computation_offset = self.computation_offset();
+        let comp_def = self.comp_def_for(Circuit::WithdrawAll, &mapping_account).await;
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::WithdrawAll {
+                blackbox: self.blackbox,
+                config: program_config_address(),
+                mapping_account,
+                op_record: op_record_address(&self.blackbox, computation_offset),
+                vault: self.vault,
+                destination: user.token_account,
+                user: user.pubkey(),
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                arcium_program: ARCIUM_PROG_ID,
+                clock: self.arcium.clock,
+                cluster: self.arcium.cluster,
+                comp_def,
+                mempool: self.arcium.mempool,
+                mxe: self.arcium.mxe,
+                pool: self.arcium.pool,
+                payer: self.payer.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::WithdrawAll { computation_offset }.data(),
+        };
+        self.send(&[instruction], &[&user.keypair]).await
+    }
+
+    /// Queues a reveal of `user`'s balance in `mapping_account` for `client_key`, returning the
+    /// address of the `BalanceReveal` the callback fills in.
+    pub async fn reveal_balance_in(
+        &mut self,
+        user: &User,
+        mapping_account: Pubkey,
+        client_key: [u8; 32],
+    ) -> Result<Pubkey, BanksClientError> {
+        let computation_offset = self.computation_offset();
+        let nonce = self.nonce();
+        let balance_reveal = balance_reveal_address(&self.blackbox, computation_offset);
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::RevealBalance {
+                blackbox: self.blackbox,
+                config: program_config_address(),
+                mapping_account,
+                balance_reveal,
+                consumed_nonce: consumed_nonce_address(&user.pubkey(), nonce),
+                owner: user.pubkey(),
+                system_program: system_program::ID,
+                arcium_program: ARCIUM_PROG_ID,
+                clock: self.arcium.clock,
+                cluster: self.arcium.cluster,
+                comp_def: Circuit::RevealBalance.comp_def_address(),
+                mempool: self.arcium.mempool,
+                mxe: self.arcium.mxe,
+                pool: self.arcium.pool,
+                payer: self.payer.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::RevealBalance {
+                computation_offset,
+                client_key,
+                nonce,
+            }
+            .data(),
+        };
+        self.send(&[instruction], &[&user.keypair]).await?;
+        Ok(balance_reveal)
+    }
+
+    pub async fn balance_reveal(&mut self, address: &Pubkey) -> BalanceReveal {
+        let account = self.banks.get_account(*address).await.unwrap().unwrap();
+        BalanceReveal::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    /// Requests a solvency proof, passing the first `shards` mapping accounts as its first
+    /// range, and returns the address of the `SolvencyAttestation` its callbacks fill in.
+    pub async fn prove_solvency_over(&mut self, shards: u8) -> Result<Pubkey, BanksClientError> {
+        let computation_offset = self.computation_offset();
+        let attestation = solvency_attestation_address(&self.blackbox, computation_offset);
+        let mut instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::ProveSolvency {
+                blackbox: self.blackbox,
+                vault: self.vault,
+                attestation,
+                system_program: system_program::ID,
+                arcium_program: ARCIUM_PROG_ID,
+                clock: self.arcium.clock,
+                cluster: self.arcium.cluster,
+                comp_def: Circuit::ProveSolvency.comp_def_address(),
+                mempool: self.arcium.mempool,
+                mxe: self.arcium.mxe,
+                pool: self.arcium.pool,
+                payer: self.payer.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::ProveSolvency { computation_offset }.data(),
+        };
+        for index in 0..shards {
+            let mapping_account = mapping_account_address(&self.mint.pubkey(), index);
+            instruction
+                .accounts
+                .push(AccountMeta::new_readonly(mapping_account, false));
+        }
+        self.send(&[instruction], &[]).await?;
+        Ok(attestation)
+    }
+
+    /// Requests a solvency proof over every mapping account of the blackbox and queues its
+    /// first range.
+    pub async fn prove_solvency(&mut self) -> Result<Pubkey, BanksClientError> {
+        let shards = self.blackbox_account().await.mapping_account_count;
+        self.prove_solvency_over(shards.min(MAX_SOLVENCY_SHARDS as u8))
+            .await
+    }
+
+    /// Queues the range of the proof at `attestation` made of the mapping accounts `shards`.
+    pub async fn continue_solvency_proof_over(
+        &mut self,
+        attestation: Pubkey,
+        shards: Range<u8>,
+    ) -> Result<(), BanksClientError> {
+        let computation_offset = self.computation_offset();
+        let mut instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::ContinueSolvencyProof {
+                blackbox: self.blackbox,
+                attestation,
+                system_program: system_program::ID,
+                arcium_program: ARCIUM_PROG_ID,
+                clock: self.arcium.clock,
+                cluster: self.arcium.cluster,
+                comp_def: Circuit::ProveSolvency.comp_def_address(),
+                mempool: self.arcium.mempool,
+                mxe: self.arcium.mxe,
+                pool: self.arcium.pool,
+                payer: self.payer.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::ContinueSolvencyProof { computation_offset }.data(),
+        };
+        for index in shards {
+            let mapping_account = mapping_account_address(&self.mint.pubkey(), index);
+            instruction
+                .accounts
+                .push(AccountMeta::new_readonly(mapping_account, false));
+        }
+        self.send(&[instruction], &[]).await
+    }
+
+    /// Queues the next range of the proof at `attestation`.
+    pub async fn continue_solvency_proof(
+        &mut self,
+        attestation: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let proof = self.solvency_attestation(&attestation).await;
+        let end = proof
+            .shards
+            .min(proof.summed_shards + MAX_SOLVENCY_SHARDS as u8);
+        self.continue_solvency_proof_over(attestation, proof.summed_shards..end)
+            .await
+    }
+
+    /// Settles each range of the proof at `attestation` and queues the next, until the last
+    /// one has landed.
+    pub async fn finish_solvency_proof(&mut self, attestation: Pubkey) {
+        loop {
+            self.settle_ok().await;
+            let proof = self.solvency_attestation(&attestation).await;
+            if proof.solvent.is_some() {
+                return;
+            }
+            self.continue_solvency_proof(attestation).await.unwrap();
+        }
+    }
+
+    pub async fn solvency_attestation(&mut self, address: &Pubkey) -> SolvencyAttestation {
+        let account = self.banks.get_account(*address).await.unwrap().unwrap();
+        SolvencyAttestation::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    /// Queues the export of page `page` of `mapping_account`, returning the address of the
+    /// `EmergencyExport` the callback fills in.
+    pub async fn export_balances(
+        &mut self,
+        mapping_account: Pubkey,
+        page: u8,
+    ) -> Result<Pubkey, BanksClientError> {
+        let computation_offset = self.computation_offset();
+        let version = self.mapping_account_data(&mapping_account).await.version;
+        let export = emergency_export_address(&mapping_account, version, page);
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::ExportBalances {
+                blackbox: self.blackbox,
+                mapping_account,
+                export,
+                system_program: system_program::ID,
+                arcium_program: ARCIUM_PROG_ID,
+                clock: self.arcium.clock,
+                cluster: self.arcium.cluster,
+                comp_def: Circuit::ExportBalances.comp_def_address(),
+                mempool: self.arcium.mempool,
+                mxe: self.arcium.mxe,
+                pool: self.arcium.pool,
+                payer: self.payer.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::ExportBalances {
+                computation_offset,
+                page,
+            }
+            .data(),
+        };
+        self.send(&[instruction], &[]).await?;
+        Ok(export)
+    }
+
+    pub async fn emergency_export(&mut self, address: &Pubkey) -> EmergencyExport {
+        let account = self.banks.get_account(*address).await.unwrap().unwrap();
+        EmergencyExport::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    /// Opens `owner`'s entry of the export at `address` the way their client would, returning
+    /// the entry's index, balance and salt, or `None` if the page has no entry for them.
+    pub async fn exported_claim(
+        &mut self,
+        address: &Pubkey,
+        owner: &User,
+    ) -> Option<(u8, u64, u128)> {
+        let export = self.emergency_export(address).await;
+        let key = PublicKey::from(owner.pubkey().to_bytes());
+        export
+            .entries
+            .iter()
+            .enumerate()
+            .find_map(|(entry, exported)| {
+                let nonce = export.nonce.wrapping_add(entry as u128);
+                let EmergencyClaim { balance, salt } =
+                    self.mock
+                        .cipher
+                        .open_claim(exported.enc_claim, &owner.keypair, nonce)?;
+                (claim_commitment(key, balance, salt) == exported.commitment).then_some((
+                    entry as u8,
+                    balance,
+                    salt,
+                ))
+            })
+    }
+
+    /// Claims `balance` for `user` against entry `entry` of the export at `export`, paid to
+    /// their token account.
+    pub async fn claim_emergency(
+        &mut self,
+        user: &User,
+        export: Pubkey,
+        entry: u8,
+        balance: u64,
+        salt: u128,
+    ) -> Result<(), BanksClientError> {
+        let mapping_account = self.emergency_export(&export).await.mapping_account;
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::ClaimEmergency {
+                blackbox: self.blackbox,
+                mapping_account,
+                export,
+                claimed: claimed_balance_address(&mapping_account, &user.pubkey()),
+                vault: self.vault,
+                destination: user.token_account,
+                owner: user.pubkey(),
+                payer: self.payer.pubkey(),
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::ClaimEmergency {
+                entry,
+                balance,
+                salt,
+            }
+            .data(),
+        };
+        self.send(&[instruction], &[&user.keypair]).await
+    }
+
+    /// Refunds `user`'s deposit at `computation_offset`, left uncredited by emergency mode.
+    pub async fn refund_deposit(
+        &mut self,
+        user: &User,
+        computation_offset: u64,
+    ) -> Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::RefundDeposit {
+                blackbox: self.blackbox,
+                op_record: op_record_address(&self.blackbox, computation_offset),
+                vault: self.vault,
+                destination: user.token_account,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::RefundDeposit {}.data(),
+        };
+        self.send(&[instruction], &[]).await
+    }
+
+    /// Refunds the deposits of `users` left in `mapping_account`'s operation queue by
+    /// emergency mode.
+    pub async fn refund_queued_deposits(
+        &mut self,
+        mapping_account: Pubkey,
+        users: &[&User],
+    ) -> Result<(), BanksClientError> {
+        let mut accounts = blackbox::accounts::RefundQueuedDeposits {
+            blackbox: self.blackbox,
+            op_queue: op_queue_address(&mapping_account),
+            vault: self.vault,
+            token_program: spl_token::ID,
+        }
+        .to_account_metas(None);
+        accounts.extend(
+            users
+                .iter()
+                .map(|user| AccountMeta::new(user.token_account, false)),
+        );
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts,
+            data: blackbox::instruction::RefundQueuedDeposits {}.data(),
+        };
+        self.send(&[instruction], &[]).await
+    }
+
+    /// Queues the migration of `mapping_account` to the next circuit version, signed by
+    /// `authority`.
+    pub async fn migrate_mapping(
+        &mut self,
+        authority: &Keypair,
+        mapping_account: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let computation_offset = self.computation_offset();
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::MigrateMapping {
+                blackbox: self.blackbox,
+                config: program_config_address(),
+                mapping_account,
+                op_record: op_record_address(&self.blackbox, computation_offset),
+                authority: authority.pubkey(),
+                system_program: system_program::ID,
+                arcium_program: ARCIUM_PROG_ID,
+                clock: self.arcium.clock,
+                cluster: self.arcium.cluster,
+                comp_def: Circuit::MigrateMappingV2.comp_def_address(),
+                mempool: self.arcium.mempool,
+                mxe: self.arcium.mxe,
+                pool: self.arcium.pool,
+                payer: self.payer.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::MigrateMapping { computation_offset }.data(),
+        };
+        self.send(&[instruction], &[authority]).await
+    }
+
+    /// Initializes the operation queue of `mapping_account`, returning its address.
+    pub async fn init_op_queue(
+        &mut self,
+        mapping_account: Pubkey,
+    ) -> Result<Pubkey, BanksClientError> {
+        let op_queue = op_queue_address(&mapping_account);
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::InitOpQueue {
+                blackbox: self.blackbox,
+                mapping_account,
+                op_queue,
+                payer: self.payer.pubkey(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::InitOpQueue {}.data(),
+        };
+        self.send(&[instruction], &[]).await?;
+        Ok(op_queue)
+    }
+
+    pub async fn enqueue_deposit(
+        &mut self,
+        user: &User,
+        op_queue: Pubkey,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::EnqueueDeposit {
+                blackbox: self.blackbox,
+                config: program_config_address(),
+                op_queue,
+                user_token_account: user.token_account,
+                vault: self.vault,
+                treasury: self.treasury,
+                user: user.pubkey(),
+                deposit_approver: self.deposit_approver.as_ref().map(Signer::pubkey),
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::EnqueueDeposit {
+                amount,
+                client_key: self.client_key(user),
+            }
+            .data(),
+        };
+        let signers = self.deposit_signers(user);
+        self.send(&[instruction], &signers.iter().collect::<Vec<_>>())
+            .await
+    }
+
+    pub async fn enqueue_transfer(
+        &mut self,
+        from: &User,
+        op_queue: Pubkey,
+        to: &Pubkey,
+        amount: u64,
+        noop: bool,
+    ) -> Result<(), BanksClientError> {
+        let nonce = self.nonce();
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::EnqueueTransfer {
+                blackbox: self.blackbox,
+                config: program_config_address(),
+                op_queue,
+                consumed_nonce: consumed_nonce_address(&from.pubkey(), nonce),
+                user: from.pubkey(),
+                payer: self.payer.pubkey(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::EnqueueTransfer {
+                enc_transfer: self.mock.cipher.seal_transfer(
+                    TransferArgs {
+                        to: PublicKey::from(to.to_bytes()),
+                        amount,
+                        noop,
+                    },
+                    nonce,
+                ),
+                client_key: self.client_key(from),
+                nonce,
+            }
+            .data(),
+        };
+        self.send(&[instruction], &[&from.keypair]).await
+    }
+
+    pub async fn enqueue_withdraw(
+        &mut self,
+        user: &User,
+        op_queue: Pubkey,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::EnqueueWithdraw {
+                blackbox: self.blackbox,
+                config: program_config_address(),
+                op_queue,
+                destination: user.token_account,
+                user: user.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::EnqueueWithdraw {
+                amount,
+                client_key: self.client_key(user),
+            }
+            .data(),
+        };
+        self.send(&[instruction], &[&user.keypair]).await
+    }
+
+    /// Flushes the operation queue of `mapping_account` into a batch computation.
+    pub async fn flush_op_queue(
+        &mut self,
+        mapping_account: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let computation_offset = self.computation_offset();
+        let comp_def = self.comp_def_for(Circuit::ProcessBatch, &mapping_account).await;
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::FlushOpQueue {
+                blackbox: self.blackbox,
+                config: program_config_address(),
+                mapping_account,
+                op_queue: op_queue_address(&mapping_account),
+                op_record: op_record_address(&self.blackbox, computation_offset),
+                vault: self.vault,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                arcium_program: ARCIUM_PROG_ID,
+                clock: self.arcium.clock,
+                cluster: self.arcium.cluster,
+                comp_def,
+                mempool: self.arcium.mempool,
+                mxe: self.arcium.mxe,
+                pool: self.arcium.pool,
+                payer: self.payer.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::FlushOpQueue { computation_offset }.data(),
+        };
+        self.send(&[instruction], &[]).await
+    }
+
+    /// Puts the in-flight batch of `mapping_account`'s operation queue back in front of it.
+    pub async fn requeue_stuck_batch(
+        &mut self,
+        mapping_account: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let op_queue = op_queue_address(&mapping_account);
+        let computation_offset = self.op_queue(&op_queue).await.in_flight_offset;
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::RequeueStuckBatch {
+                op_queue,
+                op_record: op_record_address(&self.blackbox, computation_offset),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::RequeueStuckBatch {}.data(),
+        };
+        self.send(&[instruction], &[]).await
+    }
+
+    /// Closes the record of the computation at `computation_offset`, signed by the payer.
+    pub async fn close_op_record(
+        &mut self,
+        computation_offset: u64,
+    ) -> Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::CloseOpRecord {
+                op_record: op_record_address(&self.blackbox, computation_offset),
+                payer: self.payer.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::CloseOpRecord {}.data(),
+        };
+        self.send(&[instruction], &[]).await
+    }
+
+    /// Closes `sender`'s marker of the consumed `nonce`.
+    pub async fn close_consumed_nonce(
+        &mut self,
+        sender: &User,
+        nonce: u128,
+    ) -> Result<(), BanksClientError> {
+        let instruction = Instruction {
+            program_id: blackbox::ID,
+            accounts: blackbox::accounts::CloseConsumedNonce {
+                consumed_nonce: consumed_nonce_address(&sender.pubkey(), nonce),
+                sender: sender.pubkey(),
+                payer: self.payer.pubkey(),
+            }
+            .to_account_metas(None),
+            data: blackbox::instruction::CloseConsumedNonce { nonce }.data(),
+        };
+        self.send(&[instruction], &[&sender.keypair]).await
+    }
+
+    pub async fn op_queue(&mut self, address: &Pubkey) -> OpQueueAccount {
+        let account = self.banks.get_account(*address).await.unwrap().unwrap();
+        OpQueueAccount::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    pub async fn op_record(&mut self, computation_offset: u64) -> OpRecord {
+        let address = op_record_address(&self.blackbox, computation_offset);
+        let account = self.banks.get_account(address).await.unwrap().unwrap();
+        OpRecord::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+}
+
+/// Asserts `result` failed with the program error `expected`.
+pub fn assert_custom_error(result: Result<(), BanksClientError>, expected: ErrorCode) {
+    let code = u32::from(expected);
+    match result {
+        Err(BanksClientError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(actual),
+        ))) => assert_eq!(actual, code, "expected {expected:?}"),
+        other => panic!("expected {expected:?}, got {other:?}"),
+    }
+}
+
+/// Name of the blackbox instruction `instruction` invokes
+fn instruction_name(instruction: &Instruction) -> Option<&'static str> {
+    if instruction.program_id != blackbox::ID || instruction.data.len() < 8 {
+        return None;
+    }
+    INSTRUCTIONS
+        .into_iter()
+        .find(|name| sighash(name) == instruction.data[..8])
+}
+
+pub fn program_config_address() -> Pubkey {
+    Pubkey::find_program_address(&[b"config"], &blackbox::ID).0
+}
+
+/// Address of the program's `ProgramData`, which `processor!` programs don't get
+fn program_data_address() -> Pubkey {
+    Pubkey::find_program_address(&[blackbox::ID.as_ref()], &bpf_loader_upgradeable::ID).0
+}
+
+/// A `ProgramData` account naming `upgrade_authority`: the loader's `ProgramData` variant tag,
+/// the deployment slot and the optional authority, bincode-encoded.
+fn program_data(upgrade_authority: &Pubkey) -> Account {
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.push(1);
+    data.extend_from_slice(upgrade_authority.as_ref());
+    Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: bpf_loader_upgradeable::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+pub fn pending_change_address(id: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"change", &id.to_le_bytes()], &blackbox::ID).0
+}
+
+pub fn treasury_address(blackbox: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"treasury", blackbox.as_ref()], &blackbox::ID).0
+}
+
+pub fn mapping_account_address(mint: &Pubkey, index: u8) -> Pubkey {
+    Pubkey::find_program_address(&[b"mapping", mint.as_ref(), &[index]], &blackbox::ID).0
+}
+
+pub fn op_record_address(blackbox: &Pubkey, computation_offset: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"op", blackbox.as_ref(), &computation_offset.to_le_bytes()],
+        &blackbox::ID,
+    )
+    .0
+}
+
+pub fn op_queue_address(mapping_account: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"op_queue", mapping_account.as_ref()], &blackbox::ID).0
+}
+
+pub fn consumed_nonce_address(sender: &Pubkey, nonce: u128) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"nonce", sender.as_ref(), &nonce.to_le_bytes()],
+        &blackbox::ID,
+    )
+    .0
+}
+
+pub fn balance_reveal_address(blackbox: &Pubkey, computation_offset: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"reveal",
+            blackbox.as_ref(),
+            &computation_offset.to_le_bytes(),
+        ],
+        &blackbox::ID,
+    )
+    .0
+}
+
+pub fn solvency_attestation_address(blackbox: &Pubkey, computation_offset: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"solvency",
+            blackbox.as_ref(),
+            &computation_offset.to_le_bytes(),
+        ],
+        &blackbox::ID,
+    )
+    .0
+}
+
+pub fn emergency_export_address(mapping_account: &Pubkey, version: u64, page: u8) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"emergency",
+            mapping_account.as_ref(),
+            &version.to_le_bytes(),
+            &[page],
+        ],
+        &blackbox::ID,
+    )
+    .0
+}
+
+pub fn claimed_balance_address(mapping_account: &Pubkey, owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"claimed", mapping_account.as_ref(), owner.as_ref()],
+        &blackbox::ID,
+    )
+    .0
+}
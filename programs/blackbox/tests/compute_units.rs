@@ -0,0 +1,125 @@
+//! Profiles the compute units of every instruction and callback, and fails when one exceeds
+//! its budget in `blackbox-client/compute-budgets.tsv`.
+//!
+//! Every instruction but the solvency proof's touches one mapping account, whose size is fixed,
+//! and each range of a solvency proof reads at most `MAX_SOLVENCY_SHARDS`; the profile runs
+//! with one shard more, so the proof takes a full range and then another.
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use blackbox::state::{Fees, LATEST_CIRCUIT_VERSION};
+use blackbox_client::compute_budget::compute_unit_limit;
+use common::{TestEnv, INSTRUCTIONS};
+use confidential_ixs::MAX_SOLVENCY_SHARDS;
+use mock_mxe::Circuit;
+
+/// Runs every instruction once against the newest of `MAX_SOLVENCY_SHARDS + 1` mapping
+/// accounts.
+async fn profile() -> Vec<(String, u64)> {
+    let mut env = TestEnv::new().await;
+    // Charge fees so deposits and payouts take the paths that move them
+    let admin = env.admin.insecure_clone();
+    env.set_max_fee(&admin, 100).await.unwrap();
+    let fees = Fees {
+        deposit_bps: 100,
+        withdraw_bps: 100,
+    };
+    env.set_fees(&admin, fees).await.unwrap();
+    let mut mapping_account = env.mapping_account;
+    for _ in 0..MAX_SOLVENCY_SHARDS {
+        mapping_account = env.initialize_mapping_account().await.unwrap();
+    }
+    let vault = env.vault;
+    let alice = env.user(1_000_000).await;
+    let bob = env.user(0).await;
+
+    // Run the circuits writing the mapping back in every version, migrating the shard in between
+    env.rewind_to_circuit_version_1().await;
+    let op_queue = env.init_op_queue(mapping_account).await.unwrap();
+    for circuit_version in 1..=LATEST_CIRCUIT_VERSION {
+        if circuit_version > 1 {
+            env.set_circuit_version(&admin, circuit_version).await.unwrap();
+            env.migrate_mapping(&admin, mapping_account).await.unwrap();
+            env.settle_ok().await;
+        }
+        env.deposit_into(&alice, mapping_account, vault, 1_000).await.unwrap();
+        env.settle_ok().await;
+        let nonce = env.nonce();
+        env.transfer_in(mapping_account, &alice, &bob.pubkey(), 100, false, nonce)
+            .await
+            .unwrap();
+        env.settle_ok().await;
+        env.close_consumed_nonce(&alice, nonce).await.unwrap();
+        env.withdraw_from(&bob, mapping_account, vault, bob.token_account, 50)
+            .await
+            .unwrap();
+        env.settle_ok().await;
+
+        env.enqueue_deposit(&alice, op_queue, 1_000).await.unwrap();
+        env.enqueue_transfer(&alice, op_queue, &bob.pubkey(), 100, false)
+            .await
+            .unwrap();
+        env.enqueue_withdraw(&bob, op_queue, 50).await.unwrap();
+        env.flush_op_queue(mapping_account).await.unwrap();
+        env.settle_ok().await;
+        env.withdraw_all_from(&alice, mapping_account).await.unwrap();
+        env.settle_ok().await;
+    }
+    let offset = env.last_computation_offset();
+    env.close_op_record(offset).await.unwrap();
+    env.reveal_balance_in(&bob, mapping_account, [1; 32])
+        .await
+        .unwrap();
+    env.settle_ok().await;
+    let attestation = env.prove_solvency().await.unwrap();
+    env.finish_solvency_proof(attestation).await;
+    env.collect_fees(&admin, bob.token_account).await.unwrap();
+
+    env.enter_emergency(&admin).await.unwrap();
+    let export = env.export_balances(mapping_account, 0).await.unwrap();
+    env.settle_ok().await;
+    let (entry, balance, salt) = env.exported_claim(&export, &bob).await.unwrap();
+    env.claim_emergency(&bob, export, entry, balance, salt)
+        .await
+        .unwrap();
+
+    std::mem::take(&mut env.compute_units)
+}
+
+#[tokio::test]
+async fn instructions_stay_within_compute_budgets() {
+    // Per instruction, the units measured each time it ran
+    let mut measured: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+    for (name, units) in profile().await {
+        measured.entry(name).or_default().push(units);
+    }
+
+    let names = INSTRUCTIONS
+        .into_iter()
+        .map(str::to_string)
+        .chain(Circuit::ALL.into_iter().map(|circuit| circuit.callback_name()));
+
+    let mut failures = Vec::new();
+    println!("{:<32} {:>8} {:>8}", "instruction", "max", "budget");
+    for name in names {
+        let Some(budget) = compute_unit_limit(&name) else {
+            failures.push(format!("{name} has no budget"));
+            continue;
+        };
+        let Some(runs) = measured.get(&name) else {
+            println!("{name:<32} {:>8} {budget:>8}  not exercised", "-");
+            continue;
+        };
+
+        let max = *runs.iter().max().unwrap();
+        println!("{name:<32} {max:>8} {budget:>8}");
+
+        if max > u64::from(budget) {
+            failures.push(format!("{name} used {max} compute units, budget is {budget}"));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,244 @@
+//! Emergency mode: balances exported to their owners and claimed without the MXE.
+
+mod common;
+
+use blackbox::state::{ErrorCode, OpStatus};
+use common::{assert_custom_error, TestEnv};
+use confidential_ixs::MAX_EXPORT_ENTRIES;
+
+#[tokio::test]
+async fn emergency_stops_every_operation() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let alice = env.user(1_000).await;
+    let bob = env.user(0).await;
+    env.deposit(&alice, 500).await.unwrap();
+    env.settle_ok().await;
+
+    env.enter_emergency(&admin).await.unwrap();
+    assert!(env.blackbox_account().await.emergency);
+    assert_custom_error(env.deposit(&alice, 100).await, ErrorCode::EmergencyMode);
+    assert_custom_error(
+        env.transfer(&alice, &bob.pubkey(), 100).await,
+        ErrorCode::EmergencyMode,
+    );
+    assert_custom_error(env.withdraw(&alice, 100).await, ErrorCode::EmergencyMode);
+    let mapping_account = env.mapping_account;
+    assert_custom_error(
+        env.reveal_balance_in(&alice, mapping_account, [1; 32])
+            .await,
+        ErrorCode::EmergencyMode,
+    );
+}
+
+#[tokio::test]
+async fn owners_claim_their_exported_balances() {
+    let mut env = TestEnv::new().await;
+    let (admin, mapping_account, vault) =
+        (env.admin.insecure_clone(), env.mapping_account, env.vault);
+    let alice = env.user(1_000).await;
+    let bob = env.user(0).await;
+    env.deposit(&alice, 700).await.unwrap();
+    env.settle_ok().await;
+    env.transfer(&alice, &bob.pubkey(), 250).await.unwrap();
+    env.settle_ok().await;
+
+    assert_custom_error(
+        env.export_balances(mapping_account, 0).await.map(drop),
+        ErrorCode::NotInEmergency,
+    );
+    env.enter_emergency(&admin).await.unwrap();
+    let export = env.export_balances(mapping_account, 0).await.unwrap();
+    env.settle_ok().await;
+    let exported = env.emergency_export(&export).await;
+    assert!(exported.exported);
+    assert_eq!(exported.entries.len(), 2);
+    assert!(!exported.more);
+
+    for (user, expected) in [(&alice, 450), (&bob, 250)] {
+        let (entry, balance, salt) = env.exported_claim(&export, &user).await.unwrap();
+        assert_eq!(balance, expected);
+        let before = env.token_balance(&user.token_account).await;
+        env.claim_emergency(user, export, entry, balance, salt)
+            .await
+            .unwrap();
+        assert_eq!(
+            env.token_balance(&user.token_account).await,
+            before + balance
+        );
+
+        // A balance is claimed once
+        assert!(env
+            .claim_emergency(user, export, entry, balance, salt)
+            .await
+            .is_err());
+    }
+    assert_eq!(env.token_balance(&vault).await, 0);
+}
+
+#[tokio::test]
+async fn results_landing_after_emergency_are_dropped() {
+    let mut env = TestEnv::new().await;
+    let (admin, mapping_account, vault) =
+        (env.admin.insecure_clone(), env.mapping_account, env.vault);
+    let alice = env.user(1_000).await;
+    env.deposit(&alice, 500).await.unwrap();
+    env.settle_ok().await;
+    env.withdraw(&alice, 200).await.unwrap();
+    let withdrawal = env.last_computation_offset();
+    let in_flight = env.mock.hold();
+
+    env.enter_emergency(&admin).await.unwrap();
+    let export = env.export_balances(mapping_account, 0).await.unwrap();
+    env.settle_ok().await;
+    let (entry, balance, salt) = env.exported_claim(&export, &alice).await.unwrap();
+    assert_eq!(balance, 500);
+    env.claim_emergency(&alice, export, entry, balance, salt)
+        .await
+        .unwrap();
+    assert_eq!(env.token_balance(&alice.token_account).await, 1_000);
+
+    // The withdrawal would pay out of a vault its balance was already claimed from
+    env.mock.release(in_flight);
+    env.settle_ok().await;
+    assert!(env.op_record(withdrawal).await.status == OpStatus::Dropped);
+    assert_eq!(env.token_balance(&alice.token_account).await, 1_000);
+    assert_eq!(env.token_balance(&vault).await, 0);
+}
+
+#[tokio::test]
+async fn deposits_left_uncredited_are_refunded() {
+    let mut env = TestEnv::new().await;
+    let (admin, mapping_account, vault) =
+        (env.admin.insecure_clone(), env.mapping_account, env.vault);
+    let alice = env.user(1_000).await;
+    env.deposit(&alice, 100).await.unwrap();
+    env.settle_ok().await;
+    let applied = env.last_computation_offset();
+    env.deposit(&alice, 400).await.unwrap();
+    let deposit = env.last_computation_offset();
+    let in_flight = env.mock.hold();
+
+    env.enter_emergency(&admin).await.unwrap();
+    let version = env.mapping_account_data(&mapping_account).await.version;
+    // The late deposit is dropped rather than outdating the shard's export
+    env.mock.release(in_flight);
+    env.settle_ok().await;
+    assert!(env.op_record(deposit).await.status == OpStatus::Dropped);
+    assert_eq!(
+        env.mapping_account_data(&mapping_account).await.version,
+        version
+    );
+
+    assert_custom_error(
+        env.refund_deposit(&alice, applied).await,
+        ErrorCode::NotRefundable,
+    );
+    env.refund_deposit(&alice, deposit).await.unwrap();
+    assert!(env.op_record(deposit).await.status == OpStatus::Refunded);
+    assert_eq!(env.token_balance(&alice.token_account).await, 900);
+    assert_eq!(env.token_balance(&vault).await, 100);
+    assert_custom_error(
+        env.refund_deposit(&alice, deposit).await,
+        ErrorCode::NotRefundable,
+    );
+}
+
+#[tokio::test]
+async fn queued_deposits_are_refunded() {
+    let mut env = TestEnv::new().await;
+    let (admin, mapping_account, vault) =
+        (env.admin.insecure_clone(), env.mapping_account, env.vault);
+    let alice = env.user(1_000).await;
+    let bob = env.user(1_000).await;
+    let op_queue = env.init_op_queue(mapping_account).await.unwrap();
+    env.enqueue_deposit(&alice, op_queue, 300).await.unwrap();
+    env.flush_op_queue(mapping_account).await.unwrap();
+    let batch = env.last_computation_offset();
+    let in_flight = env.mock.hold();
+    env.enqueue_deposit(&bob, op_queue, 200).await.unwrap();
+
+    env.enter_emergency(&admin).await.unwrap();
+    assert_custom_error(
+        env.refund_deposit(&alice, batch).await,
+        ErrorCode::NotRefundable,
+    );
+    env.refund_queued_deposits(mapping_account, &[&alice, &bob])
+        .await
+        .unwrap();
+    for user in [&alice, &bob] {
+        assert_eq!(env.token_balance(&user.token_account).await, 1_000);
+    }
+    assert_eq!(env.token_balance(&vault).await, 0);
+    let queue = env.op_queue(&op_queue).await;
+    assert!(queue.in_flight.is_empty() && queue.pending.is_empty());
+
+    // The batch's result lands on nothing
+    env.mock.release(in_flight);
+    env.settle_ok().await;
+    assert!(env.op_record(batch).await.status == OpStatus::Dropped);
+    assert_eq!(env.balance_of(&alice.pubkey()).await, None);
+}
+
+#[tokio::test]
+async fn claims_must_open_the_commitment() {
+    let mut env = TestEnv::new().await;
+    let (admin, mapping_account) = (env.admin.insecure_clone(), env.mapping_account);
+    let alice = env.user(1_000).await;
+    let mallory = env.user(1_000).await;
+    env.deposit(&alice, 300).await.unwrap();
+    env.deposit(&mallory, 300).await.unwrap();
+    env.settle_ok().await;
+
+    env.enter_emergency(&admin).await.unwrap();
+    let export = env.export_balances(mapping_account, 0).await.unwrap();
+    env.settle_ok().await;
+    let (entry, balance, salt) = env.exported_claim(&export, &alice).await.unwrap();
+
+    assert_custom_error(
+        env.claim_emergency(&alice, export, entry, balance + 1, salt)
+            .await,
+        ErrorCode::InvalidClaim,
+    );
+    assert_custom_error(
+        env.claim_emergency(&mallory, export, entry, balance, salt)
+            .await,
+        ErrorCode::InvalidClaim,
+    );
+    env.claim_emergency(&alice, export, entry, balance, salt)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn exports_page_through_the_mapping() {
+    let mut env = TestEnv::new().await;
+    let (admin, mapping_account) = (env.admin.insecure_clone(), env.mapping_account);
+    let mut users = Vec::new();
+    for _ in 0..MAX_EXPORT_ENTRIES + 1 {
+        let user = env.user(100).await;
+        env.deposit(&user, 100).await.unwrap();
+        env.settle_ok().await;
+        users.push(user);
+    }
+
+    env.enter_emergency(&admin).await.unwrap();
+    let first = env.export_balances(mapping_account, 0).await.unwrap();
+    let second = env.export_balances(mapping_account, 1).await.unwrap();
+    env.settle_ok().await;
+    let (first_page, second_page) = (
+        env.emergency_export(&first).await,
+        env.emergency_export(&second).await,
+    );
+    assert_eq!(first_page.entries.len(), MAX_EXPORT_ENTRIES);
+    assert!(first_page.more);
+    assert_eq!(second_page.entries.len(), 1);
+    assert!(!second_page.more);
+
+    let last = users.last().unwrap();
+    assert!(env.exported_claim(&first, &last).await.is_none());
+    let (entry, balance, salt) = env.exported_claim(&second, &last).await.unwrap();
+    env.claim_emergency(last, second, entry, balance, salt)
+        .await
+        .unwrap();
+}
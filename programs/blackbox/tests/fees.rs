@@ -0,0 +1,128 @@
+//! Deposit and withdrawal fees, the program-wide cap on them, and collecting the treasury.
+
+mod common;
+
+use blackbox::state::{ErrorCode, Fees};
+use common::{assert_custom_error, TestEnv};
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+/// Caps fees at 5% and sets 1% on deposits and 2% on withdrawals.
+async fn charge_fees(env: &mut TestEnv) {
+    let admin = env.admin.insecure_clone();
+    env.set_max_fee(&admin, 500).await.unwrap();
+    let fees = Fees {
+        deposit_bps: 100,
+        withdraw_bps: 200,
+    };
+    env.set_fees(&admin, fees).await.unwrap();
+}
+
+#[tokio::test]
+async fn deposit_and_withdraw_fees_go_to_the_treasury() {
+    let mut env = TestEnv::new().await;
+    charge_fees(&mut env).await;
+    let (vault, treasury) = (env.vault, env.treasury);
+    let alice = env.user(10_000).await;
+
+    env.deposit(&alice, 1_000).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(990));
+    assert_eq!(env.token_balance(&vault).await, 990);
+    assert_eq!(env.token_balance(&treasury).await, 10);
+
+    // The balance is debited the full amount, the fee comes out of the payout
+    env.withdraw(&alice, 500).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(490));
+    assert_eq!(env.token_balance(&alice.token_account).await, 9_490);
+    assert_eq!(env.token_balance(&vault).await, 490);
+    assert_eq!(env.token_balance(&treasury).await, 20);
+
+    let mapping_account = env.mapping_account;
+    env.withdraw_all_from(&alice, mapping_account)
+        .await
+        .unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(0));
+    assert_eq!(env.token_balance(&alice.token_account).await, 9_971);
+    assert_eq!(env.token_balance(&vault).await, 0);
+    assert_eq!(env.token_balance(&treasury).await, 29);
+}
+
+#[tokio::test]
+async fn queued_operations_pay_fees() {
+    let mut env = TestEnv::new().await;
+    charge_fees(&mut env).await;
+    let treasury = env.treasury;
+    let alice = env.user(10_000).await;
+    let mapping_account = env.mapping_account;
+    let op_queue = env.init_op_queue(mapping_account).await.unwrap();
+
+    env.enqueue_deposit(&alice, op_queue, 1_000).await.unwrap();
+    assert_eq!(env.token_balance(&treasury).await, 10);
+    env.enqueue_withdraw(&alice, op_queue, 400).await.unwrap();
+    env.flush_op_queue(mapping_account).await.unwrap();
+    env.settle_ok().await;
+
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(590));
+    assert_eq!(env.token_balance(&alice.token_account).await, 9_392);
+    assert_eq!(env.token_balance(&treasury).await, 18);
+}
+
+#[tokio::test]
+async fn fees_stay_within_the_cap() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let alice = env.user(10_000).await;
+    let fees = Fees {
+        deposit_bps: 100,
+        withdraw_bps: 100,
+    };
+
+    // No fees can be set until the cap is raised
+    assert_custom_error(env.set_fees(&admin, fees).await, ErrorCode::FeeTooHigh);
+    assert_custom_error(env.set_max_fee(&admin, 10_001).await, ErrorCode::FeeTooHigh);
+    env.set_max_fee(&admin, 100).await.unwrap();
+    env.set_fees(&admin, fees).await.unwrap();
+    assert_eq!(env.blackbox_account().await.fees.withdraw_bps, 100);
+
+    // Lowering the cap lowers the fees charged
+    env.set_max_fee(&admin, 50).await.unwrap();
+    env.deposit(&alice, 1_000).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(995));
+    let treasury = env.treasury;
+    assert_eq!(env.token_balance(&treasury).await, 5);
+}
+
+#[tokio::test]
+async fn only_the_authority_collects_fees() {
+    let mut env = TestEnv::new().await;
+    charge_fees(&mut env).await;
+    let admin = env.admin.insecure_clone();
+    let attacker = Keypair::new();
+    let alice = env.user(10_000).await;
+    env.deposit(&alice, 5_000).await.unwrap();
+    env.settle_ok().await;
+    let treasury = env.treasury;
+    assert_eq!(env.token_balance(&treasury).await, 50);
+
+    let loot = env.token_account(&attacker.pubkey(), 0).await;
+    assert_custom_error(
+        env.collect_fees(&attacker, loot).await,
+        ErrorCode::Unauthorized,
+    );
+    assert_custom_error(
+        env.set_fees(&attacker, Fees::default()).await,
+        ErrorCode::Unauthorized,
+    );
+
+    let destination = env.token_account(&admin.pubkey(), 0).await;
+    env.collect_fees(&admin, destination).await.unwrap();
+    assert_eq!(env.token_balance(&destination).await, 50);
+    assert_eq!(env.token_balance(&treasury).await, 0);
+    // Collected fees never came out of the vault
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(4_950));
+    let vault = env.vault;
+    assert_eq!(env.token_balance(&vault).await, 4_950);
+}
@@ -0,0 +1,251 @@
+mod common;
+
+use blackbox::state::{ErrorCode, OpStatus};
+use common::{assert_custom_error, TestEnv};
+use mock_mxe::MxeCipher;
+use solana_sdk::signer::Signer;
+
+#[tokio::test]
+async fn setup_registers_blackbox_and_mapping_account() {
+    let mut env = TestEnv::new().await;
+
+    let mapping_account = env.mapping_account;
+    let mapping_account = env.mapping_account_data(&mapping_account).await;
+    assert_eq!(mapping_account.index, 0);
+    assert_eq!(mapping_account.token_mint, env.mint.pubkey());
+    assert_eq!(mapping_account.version, 0);
+    assert!(env.mapping().await.pubkeys.is_empty());
+
+    let vault = env.vault;
+    assert_eq!(env.token_balance(&vault).await, 0);
+
+    let second = env.initialize_mapping_account().await.unwrap();
+    assert_eq!(env.mapping_account_data(&second).await.index, 1);
+}
+
+#[tokio::test]
+async fn deposit_transfer_withdraw() {
+    let mut env = TestEnv::new().await;
+    let alice = env.user(1_000).await;
+    let bob = env.user(0).await;
+    let vault = env.vault;
+
+    env.deposit(&alice, 600).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.token_balance(&alice.token_account).await, 400);
+    assert_eq!(env.token_balance(&vault).await, 600);
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(600));
+
+    env.transfer(&alice, &bob.pubkey(), 250).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(350));
+    assert_eq!(env.balance_of(&bob.pubkey()).await, Some(250));
+    // Internal transfers never move tokens
+    assert_eq!(env.token_balance(&vault).await, 600);
+
+    env.withdraw(&bob, 100).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.token_balance(&bob.token_account).await, 100);
+    assert_eq!(env.token_balance(&vault).await, 500);
+    assert_eq!(env.balance_of(&bob.pubkey()).await, Some(150));
+
+    let mapping_account = env.mapping_account;
+    assert_eq!(env.mapping_account_data(&mapping_account).await.version, 3);
+}
+
+#[tokio::test]
+async fn reveal_balance_encrypts_only_the_owners_balance() {
+    let mut env = TestEnv::new().await;
+    let alice = env.user(1_000).await;
+    let bob = env.user(0).await;
+    let mapping_account = env.mapping_account;
+    let client_key = env.client_key(&alice);
+
+    env.deposit(&alice, 600).await.unwrap();
+    env.settle_ok().await;
+    env.transfer(&alice, &bob.pubkey(), 250).await.unwrap();
+    env.settle_ok().await;
+
+    let reveal = env
+        .reveal_balance_in(&alice, mapping_account, client_key)
+        .await
+        .unwrap();
+    assert_eq!(env.balance_reveal(&reveal).await.enc_balance, None);
+    env.settle_ok().await;
+
+    let reveal = env.balance_reveal(&reveal).await;
+    assert_eq!(reveal.owner, alice.pubkey());
+    assert_eq!(reveal.client_key, client_key);
+    assert_eq!(reveal.mapping_version, 2);
+    let expected = env.mock.cipher.seal_amount(350, reveal.nonce);
+    assert_eq!(reveal.enc_balance, Some(expected));
+    // Reading the mapping doesn't change it
+    assert_eq!(env.mapping_account_data(&mapping_account).await.version, 2);
+}
+
+#[tokio::test]
+async fn withdraw_more_than_balance_pays_nothing() {
+    let mut env = TestEnv::new().await;
+    let alice = env.user(500).await;
+    let vault = env.vault;
+
+    env.deposit(&alice, 500).await.unwrap();
+    env.settle_ok().await;
+
+    env.withdraw(&alice, 501).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.token_balance(&alice.token_account).await, 0);
+    assert_eq!(env.token_balance(&vault).await, 500);
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(500));
+}
+
+#[tokio::test]
+async fn withdraw_without_entry_pays_nothing() {
+    let mut env = TestEnv::new().await;
+    let alice = env.user(500).await;
+    let mallory = env.user(0).await;
+    let vault = env.vault;
+
+    env.deposit(&alice, 500).await.unwrap();
+    env.settle_ok().await;
+
+    env.withdraw(&mallory, 1).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.token_balance(&mallory.token_account).await, 0);
+    assert_eq!(env.token_balance(&vault).await, 500);
+    assert_eq!(env.balance_of(&mallory.pubkey()).await, None);
+}
+
+#[tokio::test]
+async fn withdraw_all_without_entry_fails_and_can_be_closed() {
+    let mut env = TestEnv::new().await;
+    let alice = env.user(500).await;
+    let mallory = env.user(0).await;
+    let mapping_account = env.mapping_account;
+
+    env.deposit(&alice, 500).await.unwrap();
+    env.settle_ok().await;
+
+    env.withdraw_all_from(&mallory, mapping_account)
+        .await
+        .unwrap();
+    let offset = env.last_computation_offset();
+    env.settle_ok().await;
+    assert!(env.op_record(offset).await.status == OpStatus::Failed);
+    assert_eq!(env.token_balance(&mallory.token_account).await, 0);
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(500));
+    env.close_op_record(offset).await.unwrap();
+}
+
+#[tokio::test]
+async fn transfer_more_than_balance_moves_nothing() {
+    let mut env = TestEnv::new().await;
+    let alice = env.user(300).await;
+    let bob = env.user(0).await;
+
+    env.deposit(&alice, 300).await.unwrap();
+    env.settle_ok().await;
+
+    env.transfer(&alice, &bob.pubkey(), 301).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(300));
+    assert_eq!(env.balance_of(&bob.pubkey()).await, None);
+}
+
+#[tokio::test]
+async fn noop_transfer_moves_nothing() {
+    let mut env = TestEnv::new().await;
+    let alice = env.user(300).await;
+    let bob = env.user(0).await;
+
+    env.deposit(&alice, 300).await.unwrap();
+    env.settle_ok().await;
+
+    env.transfer_with_nonce(&alice, &bob.pubkey(), 100, true, 7)
+        .await
+        .unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(300));
+    assert_eq!(env.balance_of(&bob.pubkey()).await, None);
+}
+
+#[tokio::test]
+async fn transfer_nonce_cannot_be_replayed() {
+    let mut env = TestEnv::new().await;
+    let alice = env.user(300).await;
+    let bob = env.user(0).await;
+
+    env.deposit(&alice, 300).await.unwrap();
+    env.settle_ok().await;
+
+    env.transfer_with_nonce(&alice, &bob.pubkey(), 100, false, 42)
+        .await
+        .unwrap();
+    assert!(env
+        .transfer_with_nonce(&alice, &bob.pubkey(), 100, false, 42)
+        .await
+        .is_err());
+    assert_eq!(env.mock.pending(), 1);
+}
+
+#[tokio::test]
+async fn resolved_records_and_consumed_nonces_are_closed() {
+    let mut env = TestEnv::new().await;
+    let alice = env.user(300).await;
+    let bob = env.user(0).await;
+
+    env.deposit(&alice, 300).await.unwrap();
+    let deposit = env.last_computation_offset();
+    assert_custom_error(env.close_op_record(deposit).await, ErrorCode::RecordInUse);
+    env.settle_ok().await;
+    let payer = env.payer.pubkey();
+    let before = env.banks.get_balance(payer).await.unwrap();
+    env.close_op_record(deposit).await.unwrap();
+    assert!(env.banks.get_balance(payer).await.unwrap() > before);
+    let record = common::op_record_address(&env.blackbox, deposit);
+    assert!(env.banks.get_account(record).await.unwrap().is_none());
+
+    env.transfer_with_nonce(&alice, &bob.pubkey(), 100, false, 42)
+        .await
+        .unwrap();
+    env.settle_ok().await;
+    let nonce = common::consumed_nonce_address(&alice.pubkey(), 42);
+    assert!(env.close_consumed_nonce(&bob, 42).await.is_err());
+    env.close_consumed_nonce(&alice, 42).await.unwrap();
+    assert!(env.banks.get_account(nonce).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn deposit_into_wrong_vault_is_rejected() {
+    let mut env = TestEnv::new().await;
+    let alice = env.user(500).await;
+    let attacker = env.user(0).await;
+    let mapping_account = env.mapping_account;
+
+    let result = env
+        .deposit_into(&alice, mapping_account, attacker.token_account, 500)
+        .await;
+    assert_custom_error(result, ErrorCode::InvalidVault);
+    assert_eq!(env.token_balance(&alice.token_account).await, 500);
+    assert_eq!(env.mock.pending(), 0);
+}
+
+#[tokio::test]
+async fn withdraw_from_wrong_vault_is_rejected() {
+    let mut env = TestEnv::new().await;
+    let alice = env.user(500).await;
+    env.deposit(&alice, 500).await.unwrap();
+    env.settle_ok().await;
+
+    // A token account of the right mint that isn't the blackbox's vault
+    let payer = env.payer.pubkey();
+    let decoy = env.token_account(&payer, 1_000).await;
+
+    let mapping_account = env.mapping_account;
+    let result = env
+        .withdraw_from(&alice, mapping_account, decoy, alice.token_account, 100)
+        .await;
+    assert_custom_error(result, ErrorCode::InvalidVault);
+    assert_eq!(env.mock.pending(), 0);
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(500));
+}
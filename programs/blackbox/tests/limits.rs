@@ -0,0 +1,219 @@
+//! Bounds on single deposits and withdrawals, and the per-epoch cap on vault outflow.
+
+mod common;
+
+use blackbox::state::{ErrorCode, Limits};
+use common::{assert_custom_error, TestEnv};
+use solana_sdk::signature::Keypair;
+
+#[tokio::test]
+async fn deposits_stay_within_bounds() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let alice = env.user(10_000).await;
+    let limits = Limits {
+        min_deposit: 100,
+        max_deposit: 1_000,
+        ..Limits::default()
+    };
+    env.set_limits(&admin, limits).await.unwrap();
+
+    assert_custom_error(env.deposit(&alice, 99).await, ErrorCode::DepositTooSmall);
+    assert_custom_error(env.deposit(&alice, 1_001).await, ErrorCode::DepositTooLarge);
+    let mapping_account = env.mapping_account;
+    let op_queue = env.init_op_queue(mapping_account).await.unwrap();
+    assert_custom_error(
+        env.enqueue_deposit(&alice, op_queue, 1_001).await,
+        ErrorCode::DepositTooLarge,
+    );
+    assert_eq!(env.token_balance(&alice.token_account).await, 10_000);
+    assert_eq!(env.mock.pending(), 0);
+
+    env.deposit(&alice, 100).await.unwrap();
+    env.enqueue_deposit(&alice, op_queue, 1_000).await.unwrap();
+    env.settle_ok().await;
+    env.flush_op_queue(mapping_account).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(1_100));
+}
+
+#[tokio::test]
+async fn withdrawals_stay_within_bounds_and_the_epoch_cap() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let alice = env.user(10_000).await;
+    env.deposit(&alice, 5_000).await.unwrap();
+    env.settle_ok().await;
+    let limits = Limits {
+        min_withdraw: 10,
+        max_withdraw: 500,
+        epoch_outflow_cap: 800,
+        ..Limits::default()
+    };
+    env.set_limits(&admin, limits).await.unwrap();
+
+    assert_custom_error(env.withdraw(&alice, 9).await, ErrorCode::WithdrawalTooSmall);
+    assert_custom_error(
+        env.withdraw(&alice, 501).await,
+        ErrorCode::WithdrawalTooLarge,
+    );
+    env.withdraw(&alice, 500).await.unwrap();
+    let mapping_account = env.mapping_account;
+    let op_queue = env.init_op_queue(mapping_account).await.unwrap();
+    env.enqueue_withdraw(&alice, op_queue, 300).await.unwrap();
+    assert_eq!(env.blackbox_account().await.outflow.amount, 800);
+
+    // Both count towards the cap before they're paid out
+    assert_custom_error(
+        env.withdraw(&alice, 10).await,
+        ErrorCode::EpochOutflowCapReached,
+    );
+    assert_custom_error(
+        env.enqueue_withdraw(&alice, op_queue, 10).await,
+        ErrorCode::EpochOutflowCapReached,
+    );
+    env.settle_ok().await;
+    env.flush_op_queue(mapping_account).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.token_balance(&alice.token_account).await, 5_800);
+
+    env.next_epoch().await;
+    env.withdraw(&alice, 500).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.blackbox_account().await.outflow.amount, 500);
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(3_700));
+}
+
+#[tokio::test]
+async fn rejected_withdrawals_release_the_epoch_cap() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let alice = env.user(1_000).await;
+    env.deposit(&alice, 100).await.unwrap();
+    env.settle_ok().await;
+    let limits = Limits {
+        epoch_outflow_cap: 800,
+        ..Limits::default()
+    };
+    env.set_limits(&admin, limits).await.unwrap();
+
+    // Neither is covered by the balance, so neither is paid out
+    env.withdraw(&alice, 500).await.unwrap();
+    let mapping_account = env.mapping_account;
+    let op_queue = env.init_op_queue(mapping_account).await.unwrap();
+    env.enqueue_withdraw(&alice, op_queue, 300).await.unwrap();
+    assert_eq!(env.blackbox_account().await.outflow.amount, 800);
+    env.settle_ok().await;
+    env.flush_op_queue(mapping_account).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.token_balance(&alice.token_account).await, 900);
+    assert_eq!(env.blackbox_account().await.outflow.amount, 0);
+
+    env.withdraw(&alice, 100).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.token_balance(&alice.token_account).await, 1_000);
+    assert_eq!(env.blackbox_account().await.outflow.amount, 100);
+}
+
+#[tokio::test]
+async fn withdraw_all_pays_up_to_the_limits() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let alice = env.user(1_000).await;
+    env.deposit(&alice, 1_000).await.unwrap();
+    env.settle_ok().await;
+    let mapping_account = env.mapping_account;
+
+    // The bound counts towards the cap until the callback replaces it with the payout
+    let limits = Limits {
+        max_withdraw: 400,
+        epoch_outflow_cap: 600,
+        ..Limits::default()
+    };
+    env.set_limits(&admin, limits).await.unwrap();
+    env.withdraw_all_from(&alice, mapping_account)
+        .await
+        .unwrap();
+    assert_eq!(env.blackbox_account().await.outflow.amount, 400);
+    env.settle_ok().await;
+    assert_eq!(env.token_balance(&alice.token_account).await, 400);
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(600));
+
+    env.withdraw_all_from(&alice, mapping_account)
+        .await
+        .unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.token_balance(&alice.token_account).await, 600);
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(400));
+    assert_eq!(env.blackbox_account().await.outflow.amount, 600);
+    assert_custom_error(
+        env.withdraw_all_from(&alice, mapping_account).await,
+        ErrorCode::EpochOutflowCapReached,
+    );
+
+    // A minimum doesn't stop it; it's how a balance below the minimum gets out
+    let limits = Limits {
+        min_withdraw: 1_000,
+        ..Limits::default()
+    };
+    env.set_limits(&admin, limits).await.unwrap();
+    env.withdraw_all_from(&alice, mapping_account)
+        .await
+        .unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.token_balance(&alice.token_account).await, 1_000);
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(0));
+}
+
+#[tokio::test]
+async fn withdraw_all_without_entry_releases_its_bound() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let mallory = env.user(0).await;
+    let mapping_account = env.mapping_account;
+    let limits = Limits {
+        epoch_outflow_cap: 600,
+        ..Limits::default()
+    };
+    env.set_limits(&admin, limits).await.unwrap();
+
+    env.withdraw_all_from(&mallory, mapping_account)
+        .await
+        .unwrap();
+    assert_eq!(env.blackbox_account().await.outflow.amount, 600);
+    env.settle_ok().await;
+    assert_eq!(env.blackbox_account().await.outflow.amount, 0);
+}
+
+#[tokio::test]
+async fn only_the_authority_sets_consistent_limits() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let attacker = Keypair::new();
+    let limits = Limits {
+        max_deposit: 1,
+        ..Limits::default()
+    };
+
+    assert_custom_error(
+        env.set_limits(&attacker, limits).await,
+        ErrorCode::Unauthorized,
+    );
+    let inverted = Limits {
+        min_withdraw: 2,
+        max_withdraw: 1,
+        ..Limits::default()
+    };
+    assert_custom_error(
+        env.set_limits(&admin, inverted).await,
+        ErrorCode::InvalidLimits,
+    );
+    // A minimum with no maximum is unbounded above
+    let open = Limits {
+        min_withdraw: 2,
+        ..Limits::default()
+    };
+    env.set_limits(&admin, open).await.unwrap();
+    env.set_limits(&admin, limits).await.unwrap();
+    assert_eq!(env.blackbox_account().await.limits.max_deposit, 1);
+}
@@ -0,0 +1,50 @@
+//! Operation queue batches whose callback doesn't arrive.
+
+mod common;
+
+use blackbox::state::{ErrorCode, OpStatus, BATCH_TIMEOUT_SLOTS};
+use common::{assert_custom_error, TestEnv};
+use confidential_ixs::STATUS_APPLIED;
+use mock_mxe::MxeCipher;
+
+#[tokio::test]
+async fn stuck_batch_is_requeued_and_its_late_callback_ignored() {
+    let mut env = TestEnv::new().await;
+    let alice = env.user(1_000).await;
+    let mapping_account = env.mapping_account;
+    let op_queue = env.init_op_queue(mapping_account).await.unwrap();
+    env.enqueue_deposit(&alice, op_queue, 600).await.unwrap();
+    env.flush_op_queue(mapping_account).await.unwrap();
+    let stuck = env.op_queue(&op_queue).await.in_flight_offset;
+
+    assert_custom_error(
+        env.requeue_stuck_batch(mapping_account).await,
+        ErrorCode::BatchNotStuck,
+    );
+    env.advance_slots(BATCH_TIMEOUT_SLOTS).await;
+    env.requeue_stuck_batch(mapping_account).await.unwrap();
+    let queue = env.op_queue(&op_queue).await;
+    assert!(queue.in_flight.is_empty());
+    assert_eq!(queue.pending.len(), 1);
+    assert!(env.op_record(stuck).await.status == OpStatus::Requeued);
+    assert_custom_error(
+        env.requeue_stuck_batch(mapping_account).await,
+        ErrorCode::BatchNotStuck,
+    );
+
+    // The callback arriving late doesn't apply the batch
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, None);
+
+    env.flush_op_queue(mapping_account).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(600));
+    let queue = env.op_queue(&op_queue).await;
+    assert!(queue.pending.is_empty() && queue.in_flight.is_empty());
+    let result = queue.results[0];
+    assert_eq!(result.owner, alice.pubkey());
+    assert_eq!(
+        env.mock.cipher.open_u64(result.enc_status, result.nonce),
+        STATUS_APPLIED
+    );
+}
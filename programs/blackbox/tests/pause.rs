@@ -0,0 +1,167 @@
+//! Emergency pauses and the authority allowed to set them.
+
+mod common;
+
+use blackbox::state::{ErrorCode, PauseFlags};
+use common::{assert_custom_error, TestEnv};
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+const DEPOSIT: PauseFlags = PauseFlags {
+    deposit: true,
+    transfer: false,
+    withdraw: false,
+};
+const TRANSFER: PauseFlags = PauseFlags {
+    deposit: false,
+    transfer: true,
+    withdraw: false,
+};
+const WITHDRAW: PauseFlags = PauseFlags {
+    deposit: false,
+    transfer: false,
+    withdraw: true,
+};
+const NONE: PauseFlags = PauseFlags {
+    deposit: false,
+    transfer: false,
+    withdraw: false,
+};
+
+#[tokio::test]
+async fn global_pause_stops_only_paused_operations() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let alice = env.user(1_000).await;
+    let bob = env.user(0).await;
+    env.deposit(&alice, 500).await.unwrap();
+    env.settle_ok().await;
+
+    env.set_paused(&admin, None, DEPOSIT).await.unwrap();
+    assert!(env.program_config().await.paused.deposit);
+    assert_custom_error(env.deposit(&alice, 500).await, ErrorCode::Paused);
+    assert_eq!(env.token_balance(&alice.token_account).await, 500);
+    assert_eq!(env.mock.pending(), 0);
+
+    env.transfer(&alice, &bob.pubkey(), 100).await.unwrap();
+    env.withdraw(&alice, 100).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&bob.pubkey()).await, Some(100));
+    assert_eq!(env.token_balance(&alice.token_account).await, 600);
+
+    env.set_paused(&admin, None, NONE).await.unwrap();
+    env.deposit(&alice, 500).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(800));
+}
+
+#[tokio::test]
+async fn blackbox_pause_adds_to_global_flags() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let alice = env.user(1_000).await;
+    env.deposit(&alice, 500).await.unwrap();
+    env.settle_ok().await;
+    let blackbox = env.blackbox;
+
+    env.set_paused(&admin, Some(blackbox), WITHDRAW)
+        .await
+        .unwrap();
+    assert!(!env.program_config().await.paused.withdraw);
+    assert_custom_error(env.withdraw(&alice, 100).await, ErrorCode::Paused);
+    let mapping_account = env.mapping_account;
+    assert_custom_error(
+        env.withdraw_all_from(&alice, mapping_account).await,
+        ErrorCode::Paused,
+    );
+    assert_custom_error(
+        env.reveal_balance_in(&alice, mapping_account, [1; 32]).await,
+        ErrorCode::Paused,
+    );
+    let op_queue = env.init_op_queue(mapping_account).await.unwrap();
+    assert_custom_error(
+        env.enqueue_withdraw(&alice, op_queue, 100).await,
+        ErrorCode::Paused,
+    );
+
+    // Lifting the global pause doesn't lift the blackbox's
+    env.set_paused(&admin, None, DEPOSIT).await.unwrap();
+    env.set_paused(&admin, None, NONE).await.unwrap();
+    assert_custom_error(env.withdraw(&alice, 100).await, ErrorCode::Paused);
+
+    env.set_paused(&admin, Some(blackbox), NONE).await.unwrap();
+    env.withdraw(&alice, 100).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.token_balance(&alice.token_account).await, 600);
+}
+
+#[tokio::test]
+async fn flush_waits_while_a_queued_operation_is_paused() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let alice = env.user(1_000).await;
+    let bob = env.user(0).await;
+    let mapping_account = env.mapping_account;
+    let op_queue = env.init_op_queue(mapping_account).await.unwrap();
+    env.enqueue_deposit(&alice, op_queue, 500).await.unwrap();
+    env.enqueue_transfer(&alice, op_queue, &bob.pubkey(), 100, false)
+        .await
+        .unwrap();
+
+    env.set_paused(&admin, None, TRANSFER).await.unwrap();
+    assert_custom_error(env.flush_op_queue(mapping_account).await, ErrorCode::Paused);
+    assert_eq!(env.mock.pending(), 0);
+
+    env.set_paused(&admin, None, NONE).await.unwrap();
+    env.flush_op_queue(mapping_account).await.unwrap();
+    env.settle_ok().await;
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(400));
+    assert_eq!(env.balance_of(&bob.pubkey()).await, Some(100));
+}
+
+#[tokio::test]
+async fn only_the_authority_administers() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let attacker = Keypair::new();
+    let blackbox = env.blackbox;
+    assert_eq!(env.program_config().await.authority, admin.pubkey());
+
+    assert_custom_error(
+        env.set_paused(&attacker, None, DEPOSIT).await,
+        ErrorCode::Unauthorized,
+    );
+    assert_custom_error(
+        env.set_paused(&attacker, Some(blackbox), DEPOSIT).await,
+        ErrorCode::Unauthorized,
+    );
+    assert_custom_error(
+        env.pause(&attacker, None, DEPOSIT).await,
+        ErrorCode::Unauthorized,
+    );
+    assert_custom_error(
+        env.propose_authority(&attacker, Some(attacker.pubkey()))
+            .await,
+        ErrorCode::Unauthorized,
+    );
+
+    // The authority only changes once the successor accepts
+    let successor = Keypair::new();
+    env.propose_authority(&admin, Some(successor.pubkey()))
+        .await
+        .unwrap();
+    assert_eq!(env.program_config().await.authority, admin.pubkey());
+    assert_custom_error(
+        env.accept_authority(&attacker).await,
+        ErrorCode::Unauthorized,
+    );
+    env.accept_authority(&successor).await.unwrap();
+    let config = env.program_config().await;
+    assert_eq!(config.authority, successor.pubkey());
+    assert_eq!(config.pending_authority, None);
+    assert_custom_error(
+        env.set_paused(&admin, None, DEPOSIT).await,
+        ErrorCode::Unauthorized,
+    );
+    env.set_paused(&successor, None, DEPOSIT).await.unwrap();
+    assert!(env.program_config().await.paused.deposit);
+}
@@ -0,0 +1,143 @@
+//! Runs random sequences of deposits, transfers, withdrawals and shard initializations through
+//! the program and the mock MXE, checking after every step that the vault holds exactly what
+//! the mapping accounts credit and that no withdrawal pays out more than its owner had.
+
+mod common;
+
+use std::collections::HashMap;
+
+use arcis::prelude::PublicKey;
+use common::{TestEnv, User};
+use proptest::prelude::*;
+use solana_sdk::pubkey::Pubkey;
+
+const USERS: usize = 6;
+const MAX_SHARDS: usize = 3;
+const FUNDING: u64 = 1_000_000_000;
+
+#[derive(Clone, Debug)]
+enum Step {
+    Deposit { user: usize, shard: usize, amount: u64 },
+    Transfer { from: usize, to: usize, shard: usize, amount: u64, noop: bool },
+    Withdraw { user: usize, shard: usize, amount: u64 },
+    InitShard,
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    let user = 0..USERS;
+    // Taken modulo the number of shards initialized when the step runs
+    let shard = 0..MAX_SHARDS;
+    let amount = 0..100_000u64;
+    prop_oneof![
+        4 => (user.clone(), shard.clone(), amount.clone())
+            .prop_map(|(user, shard, amount)| Step::Deposit { user, shard, amount }),
+        4 => (user.clone(), user.clone(), shard.clone(), amount.clone(), any::<bool>())
+            .prop_map(|(from, to, shard, amount, noop)| Step::Transfer { from, to, shard, amount, noop }),
+        3 => (user, shard, amount)
+            .prop_map(|(user, shard, amount)| Step::Withdraw { user, shard, amount }),
+        1 => Just(Step::InitShard),
+    ]
+}
+
+/// Sum of all balances in every shard, as decrypted by the mock MXE
+async fn credited(env: &mut TestEnv, shards: &[Pubkey]) -> u64 {
+    let mut total = 0;
+    for shard in shards {
+        total += env.mapping_of(shard).await.balances.iter().sum::<u64>();
+    }
+    total
+}
+
+async fn balance_in(env: &mut TestEnv, shard: &Pubkey, user: &User) -> u64 {
+    let mapping = env.mapping_of(shard).await;
+    let key = PublicKey::from(user.pubkey().to_bytes());
+    (0..mapping.pubkeys.len())
+        .filter(|&i| mapping.pubkeys[i] == key)
+        .map(|i| mapping.balances[i])
+        .sum()
+}
+
+async fn run(steps: Vec<Step>) -> Result<(), TestCaseError> {
+    let mut env = TestEnv::new().await;
+    let mut users = Vec::with_capacity(USERS);
+    for _ in 0..USERS {
+        users.push(env.user(FUNDING).await);
+    }
+    let mut shards = vec![env.mapping_account];
+    // Plaintext oracle: what each user owns in each shard
+    let mut owned: HashMap<(usize, usize), u64> = HashMap::new();
+    let vault = env.vault;
+
+    for step in steps {
+        match step {
+            Step::Deposit { user, shard, amount } => {
+                let shard = shard % shards.len();
+                env.deposit_into(&users[user], shards[shard], vault, amount)
+                    .await
+                    .unwrap();
+                *owned.entry((user, shard)).or_default() += amount;
+            }
+            Step::Transfer { from, to, shard, amount, noop } => {
+                let shard = shard % shards.len();
+                let nonce = env.nonce();
+                let recipient = users[to].pubkey();
+                env.transfer_in(shards[shard], &users[from], &recipient, amount, noop, nonce)
+                    .await
+                    .unwrap();
+                let has = owned.get(&(from, shard)).copied();
+                if !noop && has.is_some_and(|has| has >= amount) {
+                    *owned.get_mut(&(from, shard)).unwrap() -= amount;
+                    *owned.entry((to, shard)).or_default() += amount;
+                }
+            }
+            Step::Withdraw { user, shard, amount } => {
+                let shard = shard % shards.len();
+                let had = balance_in(&mut env, &shards[shard], &users[user]).await;
+                let destination = users[user].token_account;
+                let before = env.token_balance(&destination).await;
+
+                env.withdraw_from(&users[user], shards[shard], vault, destination, amount)
+                    .await
+                    .unwrap();
+                env.settle_ok().await;
+
+                let paid = env.token_balance(&destination).await - before;
+                prop_assert!(paid <= had, "user {} withdrew {} owning {}", user, paid, had);
+                if paid > 0 {
+                    *owned.get_mut(&(user, shard)).unwrap() -= paid;
+                }
+            }
+            Step::InitShard => {
+                if shards.len() < MAX_SHARDS {
+                    shards.push(env.initialize_mapping_account().await.unwrap());
+                }
+            }
+        }
+        env.settle_ok().await;
+
+        prop_assert_eq!(env.token_balance(&vault).await, credited(&mut env, &shards).await);
+        for (shard_index, shard) in shards.clone().iter().enumerate() {
+            for (user_index, user) in users.iter().enumerate() {
+                prop_assert_eq!(
+                    balance_in(&mut env, shard, user).await,
+                    owned.get(&(user_index, shard_index)).copied().unwrap_or(0),
+                    "user {} in shard {}",
+                    user_index,
+                    shard_index
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+proptest! {
+    // Every case boots a fresh bank, so keep the case count modest
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn vault_matches_credited_balances(steps in prop::collection::vec(step(), 1..32)) {
+        tokio::runtime::Runtime::new().unwrap().block_on(run(steps))?;
+    }
+}
@@ -0,0 +1,176 @@
+//! Parameter changes queued behind the timelock, and pauses that skip it.
+
+mod common;
+
+use blackbox::state::{ErrorCode, Fees, Limits, ParameterChange, PauseFlags};
+use common::{assert_custom_error, TestEnv};
+use solana_sdk::signature::Keypair;
+
+const DEPOSIT: PauseFlags = PauseFlags {
+    deposit: true,
+    transfer: false,
+    withdraw: false,
+};
+const WITHDRAW: PauseFlags = PauseFlags {
+    deposit: false,
+    transfer: false,
+    withdraw: true,
+};
+
+/// Sets the timelock to `slots` while it's still 0.
+async fn set_timelock(env: &mut TestEnv, slots: u64) {
+    let admin = env.admin.insecure_clone();
+    let id = env
+        .queue_change(&admin, None, ParameterChange::Timelock(slots))
+        .await
+        .unwrap();
+    env.execute_change(id).await.unwrap();
+    assert_eq!(env.program_config().await.timelock_slots, slots);
+}
+
+#[tokio::test]
+async fn changes_wait_out_the_timelock() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    set_timelock(&mut env, 100).await;
+
+    let slot = env.slot().await;
+    let id = env
+        .queue_change(&admin, None, ParameterChange::MaxFee(500))
+        .await
+        .unwrap();
+    let pending = env.pending_change(id).await.unwrap();
+    assert_eq!(pending.executable_slot, slot + 100);
+    assert_custom_error(env.execute_change(id).await, ErrorCode::ChangeTimelocked);
+
+    env.advance_slots(50).await;
+    assert_custom_error(env.execute_change(id).await, ErrorCode::ChangeTimelocked);
+    env.advance_slots(50).await;
+    env.execute_change(id).await.unwrap();
+    assert_eq!(env.program_config().await.max_fee_bps, 500);
+    assert!(env.pending_change(id).await.is_none());
+
+    // A new delay only applies to changes queued after it
+    let id = env
+        .queue_change(&admin, None, ParameterChange::Timelock(0))
+        .await
+        .unwrap();
+    assert_custom_error(env.execute_change(id).await, ErrorCode::ChangeTimelocked);
+    env.advance_slots(100).await;
+    env.execute_change(id).await.unwrap();
+    assert_eq!(env.program_config().await.timelock_slots, 0);
+}
+
+#[tokio::test]
+async fn changes_are_checked_again_when_executed() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    env.set_max_fee(&admin, 100).await.unwrap();
+    set_timelock(&mut env, 10).await;
+    let blackbox = env.blackbox;
+
+    let fees = Fees {
+        deposit_bps: 100,
+        withdraw_bps: 100,
+    };
+    let raise = env
+        .queue_change(&admin, Some(blackbox), ParameterChange::Fees(fees))
+        .await
+        .unwrap();
+    let cap = env
+        .queue_change(&admin, None, ParameterChange::MaxFee(50))
+        .await
+        .unwrap();
+    env.advance_slots(10).await;
+    env.execute_change(cap).await.unwrap();
+    assert_custom_error(env.execute_change(raise).await, ErrorCode::FeeTooHigh);
+    assert_eq!(env.blackbox_account().await.fees.deposit_bps, 0);
+}
+
+#[tokio::test]
+async fn changes_apply_only_to_their_target() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let blackbox = env.blackbox;
+
+    assert_custom_error(
+        env.queue_change(&admin, Some(blackbox), ParameterChange::MaxFee(1))
+            .await
+            .map(drop),
+        ErrorCode::InvalidChangeTarget,
+    );
+    assert_custom_error(
+        env.queue_change(&admin, Some(blackbox), ParameterChange::Timelock(1))
+            .await
+            .map(drop),
+        ErrorCode::InvalidChangeTarget,
+    );
+    assert_custom_error(
+        env.queue_change(&admin, None, ParameterChange::Limits(Limits::default()))
+            .await
+            .map(drop),
+        ErrorCode::InvalidChangeTarget,
+    );
+    assert_eq!(env.program_config().await.next_change_id, 0);
+}
+
+#[tokio::test]
+async fn pausing_is_immediate_and_resuming_waits() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let alice = env.user(1_000).await;
+    env.deposit(&alice, 500).await.unwrap();
+    env.settle_ok().await;
+    set_timelock(&mut env, 100).await;
+
+    // Pausing only ever adds to what's paused
+    env.pause(&admin, None, DEPOSIT).await.unwrap();
+    env.pause(&admin, None, WITHDRAW).await.unwrap();
+    let paused = env.program_config().await.paused;
+    assert!(paused.deposit && paused.withdraw && !paused.transfer);
+    assert_custom_error(env.deposit(&alice, 100).await, ErrorCode::Paused);
+    assert_custom_error(env.withdraw(&alice, 100).await, ErrorCode::Paused);
+
+    let id = env
+        .queue_change(&admin, None, ParameterChange::Paused(WITHDRAW))
+        .await
+        .unwrap();
+    assert_custom_error(env.execute_change(id).await, ErrorCode::ChangeTimelocked);
+    assert_custom_error(env.deposit(&alice, 100).await, ErrorCode::Paused);
+
+    env.advance_slots(100).await;
+    env.execute_change(id).await.unwrap();
+    env.deposit(&alice, 100).await.unwrap();
+    env.settle_ok().await;
+    assert_custom_error(env.withdraw(&alice, 100).await, ErrorCode::Paused);
+    assert_eq!(env.balance_of(&alice.pubkey()).await, Some(600));
+}
+
+#[tokio::test]
+async fn only_the_authority_cancels_changes() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let attacker = Keypair::new();
+    set_timelock(&mut env, 100).await;
+
+    assert_custom_error(
+        env.queue_change(&attacker, None, ParameterChange::MaxFee(1))
+            .await
+            .map(drop),
+        ErrorCode::Unauthorized,
+    );
+    let id = env
+        .queue_change(&admin, None, ParameterChange::MaxFee(1))
+        .await
+        .unwrap();
+    assert_custom_error(
+        env.cancel_change(&attacker, id).await,
+        ErrorCode::Unauthorized,
+    );
+
+    env.cancel_change(&admin, id).await.unwrap();
+    assert!(env.pending_change(id).await.is_none());
+    env.advance_slots(100).await;
+    assert_eq!(env.program_config().await.max_fee_bps, 0);
+    assert_eq!(env.program_config().await.next_change_id, 2);
+}